@@ -1,29 +1,40 @@
 #!/bin/bash
 
+# Networks and assets come from the network registry the services load at startup
+NETWORKS_FILE=${PISTELLAR_NETWORKS_FILE:-config/networks.json}
+
+# Function to get the network named by the argument, or the registry's default
+network_name() {
+  local network=${1:-$(jq -r ".default" "$NETWORKS_FILE")}
+  echo "$network"
+}
+
 # Function to get Stellar asset information
 get_stellar_asset() {
   local asset_code=$1
-  local stellar_data=$(jq -r ".stellar_assets[] | select(.code == \"$asset_code\")" data/stellar_data.json)
+  local network=$(network_name "$2")
+  local stellar_data=$(jq -r --arg network "$network" --arg code "$asset_code" '.networks[$network].stellar_assets[] | select(.code == $code)' "$NETWORKS_FILE")
   echo "$stellar_data"
 }
 
 # Function to get Pi asset information
 get_pi_asset() {
   local asset_symbol=$1
-  local pi_data=$(jq -r ".pi_assets[] | select(.symbol == \"$asset_symbol\")" data/pi_data.json)
+  local network=$(network_name "$2")
+  local pi_data=$(jq -r --arg network "$network" --arg symbol "$asset_symbol" '.networks[$network].pi_assets[] | select(.symbol == $symbol)' "$NETWORKS_FILE")
   echo "$pi_data"
 }
 
 # Function to get Stellar network information
 get_stellar_network() {
-  local network=$1
-  local stellar_data=$(jq -r ".stellar_network.$network" data/stellar_data.json)
+  local network=$(network_name "$1")
+  local stellar_data=$(jq -r --arg network "$network" '.networks[$network].stellar' "$NETWORKS_FILE")
   echo "$stellar_data"
 }
 
 # Function to get Pi network information
 get_pi_network() {
-  local network=$1
-  local pi_data=$(jq -r ".pi_network.$network" data/pi_data.json)
+  local network=$(network_name "$1")
+  local pi_data=$(jq -r --arg network "$network" '.networks[$network].pi' "$NETWORKS_FILE")
   echo "$pi_data"
 }
@@ -0,0 +1,245 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use log::{info, warn, error};
+use crate::cross_chain::chain::{Asset, Chain, TransactionStatus};
+use crate::cross_chain::error::ChainError;
+use super::bridge::{convert_amount, Bridge, BridgeError, BridgeTransfer, Direction, TransferState};
+
+/// Settings of the reconciliation auditor. Amounts are in the smallest Pi unit.
+#[derive(Debug, Clone)]
+pub struct AuditorConfig {
+    /// How often the background job audits.
+    pub interval: Duration,
+    /// Largest discrepancy still treated as balanced, e.g. to absorb fees paid from custody.
+    pub tolerance: u64,
+    /// Discrepancy above which bridge payouts are paused; `None` only reports.
+    pub pause_threshold: Option<u64>,
+}
+
+impl AuditorConfig {
+    /// Audits every minute, tolerates no discrepancy and pauses above `pause_threshold`.
+    pub fn new(pause_threshold: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            tolerance: 0,
+            pause_threshold: Some(pause_threshold),
+        }
+    }
+}
+
+/// A paid-out transfer whose payout does not check out on the destination chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspectTransfer {
+    pub transfer_id: String,
+    pub reason: String,
+}
+
+/// Reconciliation of one bridged asset. Amounts are in the smallest Pi unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetAudit {
+    /// The wrapped asset on Stellar.
+    pub asset: Asset,
+    /// PI held in the Pi custody account.
+    pub locked: u64,
+    /// Wrapped PI in circulation on Stellar.
+    pub wrapped_supply: u64,
+    /// Locked PI that legitimately backs no wrapped PI: deposits not minted yet, burns not
+    /// released yet, and rejected deposits not refunded yet.
+    pub unmatched: u64,
+    /// The transfers making up `unmatched`.
+    pub unmatched_transfers: Vec<String>,
+    /// `locked - wrapped_supply - unmatched`. Negative means wrapped PI is not fully backed.
+    pub discrepancy: i128,
+    /// Whether the discrepancy is within the tolerance.
+    pub balanced: bool,
+    /// Transfers paid out since the last balanced audit whose payouts look wrong. Only
+    /// checked when the asset is not balanced.
+    pub suspects: Vec<SuspectTransfer>,
+}
+
+/// Result of one audit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditReport {
+    pub audited_at: DateTime<Utc>,
+    pub assets: Vec<AssetAudit>,
+    /// Why the bridge was paused, if this audit found a discrepancy above the threshold.
+    pub paused: Option<String>,
+}
+
+impl AuditReport {
+    pub fn is_balanced(&self) -> bool {
+        self.assets.iter().all(|asset| asset.balanced)
+    }
+}
+
+/// Checks that the bridge has created no value.
+///
+/// Every PI in the Pi custody account should either back wrapped PI on Stellar or belong to a
+/// transfer that has not been paid out. Each audit compares the custody balance with the
+/// wrapped supply through the chain clients and reports any difference, together with the
+/// transfers that may be responsible. When the difference exceeds the pause threshold, the
+/// bridge is paused until an operator resumes it.
+///
+/// Deposits that reach custody before the bridge observes them show up as a surplus until the
+/// next poll, so the pause threshold should leave room for the deposits of one poll interval.
+pub struct Auditor {
+    bridge: Arc<Bridge>,
+    config: AuditorConfig,
+    // Start of the last balanced audit; only transfers paid out since are suspected
+    last_balanced: Mutex<Option<DateTime<Utc>>>,
+    last_report: Mutex<Option<AuditReport>>,
+}
+
+/// Handle to a running auditor job.
+pub struct AuditorHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Auditor {
+    pub fn new(bridge: Arc<Bridge>, config: AuditorConfig) -> Self {
+        Self {
+            bridge,
+            config,
+            last_balanced: Mutex::new(None),
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// The report of the most recent successful audit.
+    pub fn last_report(&self) -> Option<AuditReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+
+    /// Reconciles every bridged asset, pausing the bridge if a discrepancy exceeds the threshold.
+    pub async fn audit(&self) -> Result<AuditReport, BridgeError> {
+        let audited_at = Utc::now();
+        let asset = {
+            let _polls = self.bridge.hold_polls().await;
+            self.audit_wrapped_pi().await?
+        };
+
+        let paused = match self.config.pause_threshold {
+            Some(threshold) if asset.discrepancy.unsigned_abs() > threshold as u128 => {
+                let reason = format!(
+                    "Audit found a discrepancy of {} in {}: {} locked, {} wrapped, {} unmatched",
+                    asset.discrepancy, self.bridge.config().wrapped_asset_code, asset.locked, asset.wrapped_supply, asset.unmatched
+                );
+                self.bridge.pause(&reason)?;
+                Some(reason)
+            }
+            _ => None,
+        };
+        if asset.balanced {
+            *self.last_balanced.lock().unwrap() = Some(audited_at);
+        }
+
+        let report = AuditReport { audited_at, assets: vec![asset], paused };
+        *self.last_report.lock().unwrap() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Spawns a job auditing every `interval` onto the current runtime.
+    pub fn start(self: Arc<Self>) -> AuditorHandle {
+        let (shutdown, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            info!("Bridge auditor started, auditing every {:?}", self.config.interval);
+            let mut ticker = tokio::time::interval(self.config.interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        match self.audit().await {
+                            Ok(report) if report.is_balanced() => info!("Bridge audit balanced"),
+                            Ok(_) => {}
+                            Err(e) => error!("Bridge audit failed: {}", e),
+                        }
+                    }
+                    _ = stopped.changed() => break,
+                }
+            }
+            info!("Bridge auditor stopped");
+        });
+        AuditorHandle { shutdown, task }
+    }
+
+    // The bridge moves a single asset: PI, locked on Pi and wrapped on Stellar
+    async fn audit_wrapped_pi(&self) -> Result<AssetAudit, BridgeError> {
+        let config = self.bridge.config();
+        let asset = config.wrapped_asset();
+        let locked = self.bridge.pi().balance(&config.pi_custody_account, &Asset::Native).await?;
+        let supply = self.bridge.stellar().issued_supply(&asset).await?;
+        let wrapped_supply = convert_amount(supply, config.stellar_decimals, config.pi_decimals)
+            .ok_or_else(|| ChainError::InvalidResponse(format!("Wrapped supply {} cannot be converted to Pi units", supply)))?;
+
+        let transfers = self.bridge.transfers();
+        let mut unmatched = 0u64;
+        let mut unmatched_transfers = Vec::new();
+        for transfer in &transfers {
+            let held = match (transfer.direction, transfer.state) {
+                (_, TransferState::Minted | TransferState::Released | TransferState::Refunded) => continue,
+                (Direction::PiToStellar, _) => transfer.amount,
+                (Direction::StellarToPi, _) => transfer.payout_amount,
+            };
+            unmatched += held;
+            unmatched_transfers.push(transfer.id.clone());
+        }
+
+        let discrepancy = locked as i128 - wrapped_supply as i128 - unmatched as i128;
+        let balanced = discrepancy.unsigned_abs() <= self.config.tolerance as u128;
+        let suspects = if balanced { Vec::new() } else { self.suspects(&transfers).await };
+        if !balanced {
+            warn!(
+                "Bridge audit of {} is off by {}: {} locked, {} wrapped, {} unmatched; suspects: {:?}",
+                config.wrapped_asset_code, discrepancy, locked, wrapped_supply, unmatched, suspects
+            );
+        }
+
+        Ok(AssetAudit { asset, locked, wrapped_supply, unmatched, unmatched_transfers, discrepancy, balanced, suspects })
+    }
+
+    // Checks the payouts of transfers paid out since the last balanced audit
+    async fn suspects(&self, transfers: &[BridgeTransfer]) -> Vec<SuspectTransfer> {
+        let since = *self.last_balanced.lock().unwrap();
+        let mut suspects = Vec::new();
+        let paid = transfers
+            .iter()
+            .filter(|t| matches!(t.state, TransferState::Minted | TransferState::Released))
+            .filter(|t| !since.is_some_and(|since| t.updated_at < since));
+
+        for transfer in paid {
+            let destination = match transfer.direction {
+                Direction::PiToStellar => self.bridge.stellar(),
+                Direction::StellarToPi => self.bridge.pi(),
+            };
+            let reason = match &transfer.payout_tx_id {
+                None => Some("Paid out without a transaction ID".to_string()),
+                Some(tx_id) => payout_problem(destination.as_ref(), tx_id).await,
+            };
+            if let Some(reason) = reason {
+                suspects.push(SuspectTransfer { transfer_id: transfer.id.clone(), reason });
+            }
+        }
+        suspects
+    }
+}
+
+impl AuditorHandle {
+    /// Stops the auditor after its current audit and waits for it to exit.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+// Why a payout transaction does not account for its transfer, if it does not
+async fn payout_problem(chain: &dyn Chain, tx_id: &str) -> Option<String> {
+    match chain.transaction_status(tx_id).await {
+        Ok(TransactionStatus::Failed { reason }) => Some(format!("Payout {} failed: {}", tx_id, reason)),
+        Ok(TransactionStatus::Pending) => Some(format!("Payout {} is not on {}", tx_id, chain.chain_name())),
+        Ok(_) => None,
+        Err(e) => Some(format!("Payout {} could not be checked: {}", tx_id, e)),
+    }
+}
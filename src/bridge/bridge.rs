@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use log::{info, warn, error};
+use crate::cross_chain::chain::{Asset, Chain, Payment, SignedTransfer, Transfer, TransactionStatus};
+use crate::cross_chain::error::ChainError;
+use crate::models::stellar::strkey;
+use crate::utils::json::JsonFileStore;
+
+// How long after a saved transaction expires before a replacement is signed, so that a chain
+// whose clock runs behind ours can no longer apply it
+const EXPIRY_GRACE_SECS: i64 = 60;
+
+/// Errors returned by the bridge.
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+
+    #[error("Bridge store error: {0}")]
+    Store(String),
+
+    #[error("Bridge transfer {transfer_id} cannot be refunded: {reason}")]
+    NotRefundable { transfer_id: String, reason: String },
+}
+
+/// Which way a transfer crosses the bridge.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// PI locked in the Pi custody account, wrapped PI minted on Stellar.
+    PiToStellar,
+    /// Wrapped PI returned to its Stellar issuer (burning it), PI released from custody.
+    StellarToPi,
+}
+
+/// Progress of a transfer across the bridge.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferState {
+    /// The deposit was seen but does not have enough confirmations yet.
+    Observed,
+    /// The deposit is final; the payout has not been signed yet.
+    Confirmed,
+    /// The payout was signed and saved and is being submitted. Retries resubmit the saved
+    /// payout rather than signing a new one, until the chain can no longer apply it.
+    Paying,
+    /// Wrapped PI was minted on Stellar. Terminal.
+    Minted,
+    /// PI was released from custody on Pi. Terminal.
+    Released,
+    /// The deposit cannot be bridged, e.g. it has no destination. Terminal unless an operator
+    /// calls `Bridge::refund`.
+    Rejected,
+    /// A refund of a rejected deposit was signed and saved and is being submitted.
+    Refunding,
+    /// The rejected deposit was returned to its sender. Terminal.
+    Refunded,
+}
+
+impl TransferState {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TransferState::Minted | TransferState::Released | TransferState::Rejected | TransferState::Refunded
+        )
+    }
+}
+
+/// A single deposit and its payout on the other chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BridgeTransfer {
+    /// `{source chain}:{deposit ID}`.
+    pub id: String,
+    pub direction: Direction,
+    pub source_tx_id: String,
+    pub sender: String,
+    /// Destination address on the other chain, taken from the deposit memo; see `Bridge`.
+    pub recipient: String,
+    /// Deposited amount in the source chain's smallest unit.
+    pub amount: u64,
+    /// Amount paid out in the destination chain's smallest unit.
+    pub payout_amount: u64,
+    pub state: TransferState,
+    pub confirmations: u64,
+    /// Transaction of the payout, or of the refund of a rejected deposit.
+    pub payout_tx_id: Option<String>,
+    /// The signed payout or refund while it is being submitted.
+    #[serde(default)]
+    pub pending: Option<SignedTransfer>,
+    /// Why the transfer was rejected.
+    pub reason: Option<String>,
+    pub observed_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Accounts, asset and finality settings of the bridge.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BridgeConfig {
+    /// Pi account holding locked PI.
+    pub pi_custody_account: String,
+    /// Stellar account issuing wrapped PI; payments to it burn wrapped PI.
+    pub stellar_issuer: String,
+    /// Asset code of wrapped PI on Stellar.
+    pub wrapped_asset_code: String,
+    pub pi_decimals: u32,
+    pub stellar_decimals: u32,
+    /// Confirmations required before a Pi deposit is bridged.
+    pub pi_confirmations: u64,
+    /// Confirmations required before a Stellar deposit is bridged.
+    pub stellar_confirmations: u64,
+}
+
+impl BridgeConfig {
+    /// Creates a configuration for wrapped "PI" with default decimals and confirmation counts.
+    pub fn new(pi_custody_account: &str, stellar_issuer: &str) -> Self {
+        Self {
+            pi_custody_account: pi_custody_account.to_string(),
+            stellar_issuer: stellar_issuer.to_string(),
+            wrapped_asset_code: "PI".to_string(),
+            pi_decimals: 8,
+            stellar_decimals: 7,
+            pi_confirmations: 3,
+            stellar_confirmations: 1,
+        }
+    }
+
+    /// The wrapped PI asset on Stellar.
+    pub fn wrapped_asset(&self) -> Asset {
+        Asset::Issued {
+            code: self.wrapped_asset_code.clone(),
+            issuer: self.stellar_issuer.clone(),
+        }
+    }
+}
+
+/// Converts an amount between chains with different decimals.
+///
+/// Returns `None` if the amount would lose precision or overflow.
+pub fn convert_amount(amount: u64, from_decimals: u32, to_decimals: u32) -> Option<u64> {
+    if to_decimals >= from_decimals {
+        10u64.checked_pow(to_decimals - from_decimals).and_then(|scale| amount.checked_mul(scale))
+    } else {
+        let scale = 10u64.checked_pow(from_decimals - to_decimals)?;
+        (amount % scale == 0).then(|| amount / scale)
+    }
+}
+
+/// Everything the bridge persists: transfers and how far each chain has been scanned.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BridgeSnapshot {
+    /// Payment cursor per chain name.
+    pub cursors: HashMap<String, String>,
+    pub transfers: Vec<BridgeTransfer>,
+    /// Why payouts are paused, if they are.
+    #[serde(default)]
+    pub paused: Option<String>,
+}
+
+/// Trait defining where bridge state is saved between restarts
+pub trait BridgeStore: Send + Sync {
+    fn load(&self) -> Result<BridgeSnapshot, String>;
+    fn save(&self, snapshot: &BridgeSnapshot) -> Result<(), String>;
+}
+
+/// Stores the bridge state as a JSON document on disk
+pub struct JsonFileBridgeStore {
+    file: JsonFileStore<BridgeSnapshot>,
+}
+
+impl JsonFileBridgeStore {
+    /// Creates a new store backed by the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self { file: JsonFileStore::new("bridge store", path) }
+    }
+}
+
+impl BridgeStore for JsonFileBridgeStore {
+    fn load(&self) -> Result<BridgeSnapshot, String> {
+        Ok(self.file.load()?.unwrap_or_default())
+    }
+
+    fn save(&self, snapshot: &BridgeSnapshot) -> Result<(), String> {
+        self.file.save(snapshot)
+    }
+}
+
+#[derive(Default)]
+struct BridgeState {
+    cursors: HashMap<String, String>,
+    transfers: HashMap<String, BridgeTransfer>,
+    paused: Option<String>,
+}
+
+/// Lock-and-mint bridge moving PI between Pi and Stellar.
+///
+/// PI deposited into the Pi custody account is minted as wrapped PI on Stellar, and wrapped PI
+/// paid back to its issuer is released from custody on Pi. Depositors name their destination
+/// address in the memo. A Pi address does not fit in a Stellar text memo, so wrapped PI is
+/// returned with a hash memo holding the 32-byte public key of the Pi account, which Stellar
+/// payments report in hex. Each deposit becomes a transfer that moves through
+/// Observed → Confirmed → Paying → Minted/Released, and every change is saved so the bridge
+/// can resume after a restart without rescanning.
+///
+/// A payout is signed once and saved before it is submitted. After a failed or interrupted
+/// submission, the bridge looks the saved transaction up by its ID where the chain knows it in
+/// advance (Stellar) and resubmits the same signed payout if it has not been applied. Pi payouts
+/// carry the transfer ID as their idempotency key instead. A payout that failed on-chain is
+/// signed again.
+///
+/// Rejected deposits stay in custody (PI) or burned (wrapped PI) until an operator calls
+/// `refund`, which returns them to their sender in the same way.
+///
+/// While the bridge is paused, deposits are still observed and confirmed but nothing is paid
+/// out. The pause is saved with the transfers, so it survives restarts until `resume` is called.
+pub struct Bridge {
+    config: BridgeConfig,
+    pi: Arc<dyn Chain>,
+    stellar: Arc<dyn Chain>,
+    state: Mutex<BridgeState>,
+    store: Arc<dyn BridgeStore>,
+    // Serializes polls so a transfer is never paid out by two polls at once, and audits so
+    // they never see a payout sent but not yet recorded
+    polling: tokio::sync::Mutex<()>,
+}
+
+impl Bridge {
+    /// Creates a bridge between the two chains, reloading any saved state.
+    pub fn new(
+        config: BridgeConfig,
+        pi: Arc<dyn Chain>,
+        stellar: Arc<dyn Chain>,
+        store: Arc<dyn BridgeStore>,
+    ) -> Result<Self, BridgeError> {
+        let saved = store.load().map_err(BridgeError::Store)?;
+        info!("Loaded {} bridge transfers from store", saved.transfers.len());
+        let state = BridgeState {
+            cursors: saved.cursors,
+            transfers: saved.transfers.into_iter().map(|t| (t.id.clone(), t)).collect(),
+            paused: saved.paused,
+        };
+        if let Some(reason) = &state.paused {
+            warn!("Bridge payouts are paused: {}", reason);
+        }
+        Ok(Self {
+            config,
+            pi,
+            stellar,
+            state: Mutex::new(state),
+            store,
+            polling: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn config(&self) -> &BridgeConfig {
+        &self.config
+    }
+
+    pub(crate) fn pi(&self) -> &Arc<dyn Chain> {
+        &self.pi
+    }
+
+    pub(crate) fn stellar(&self) -> &Arc<dyn Chain> {
+        &self.stellar
+    }
+
+    /// Waits for any running poll to finish and holds off new ones until the guard is dropped.
+    pub(crate) async fn hold_polls(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.polling.lock().await
+    }
+
+    /// Stops paying out transfers until `resume` is called. Pausing an already paused bridge
+    /// keeps the original reason.
+    pub fn pause(&self, reason: &str) -> Result<(), BridgeError> {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            if state.paused.is_some() {
+                return Ok(());
+            }
+            warn!("Pausing bridge payouts: {}", reason);
+            state.paused = Some(reason.to_string());
+            snapshot(&state)
+        };
+        self.store.save(&snapshot).map_err(BridgeError::Store)
+    }
+
+    /// Lets confirmed transfers be paid out again.
+    pub fn resume(&self) -> Result<(), BridgeError> {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            if state.paused.take().is_none() {
+                return Ok(());
+            }
+            info!("Resuming bridge payouts");
+            snapshot(&state)
+        };
+        self.store.save(&snapshot).map_err(BridgeError::Store)
+    }
+
+    /// Why payouts are paused, or `None` if they are not.
+    pub fn paused(&self) -> Option<String> {
+        self.state.lock().unwrap().paused.clone()
+    }
+
+    /// Returns a transfer by ID.
+    pub fn get_transfer(&self, transfer_id: &str) -> Option<BridgeTransfer> {
+        self.state.lock().unwrap().transfers.get(transfer_id).cloned()
+    }
+
+    /// Returns all transfers, oldest first.
+    pub fn transfers(&self) -> Vec<BridgeTransfer> {
+        snapshot(&self.state.lock().unwrap()).transfers
+    }
+
+    /// Scans both custody accounts for new deposits, then advances every unfinished transfer
+    /// by at most one state. Returns the transfers whose state changed.
+    ///
+    /// A chain that cannot be reached only holds up its own transfers.
+    pub async fn poll(&self) -> Result<Vec<(String, TransferState)>, BridgeError> {
+        let _guard = self.polling.lock().await;
+        let mut changed = Vec::new();
+
+        for direction in [Direction::PiToStellar, Direction::StellarToPi] {
+            match self.observe(direction).await {
+                Ok(observed) => changed.extend(observed),
+                Err(BridgeError::Chain(e)) => error!("Failed to scan deposits for {:?}: {}", direction, e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        for transfer in self.transfers().into_iter().filter(|t| !t.state.is_terminal()) {
+            let transfer_id = transfer.id.clone();
+            match self.advance(transfer).await {
+                Ok(Some(update)) => changed.push(update),
+                Ok(None) => {}
+                Err(BridgeError::Chain(e)) => error!("Failed to advance bridge transfer {}: {}", transfer_id, e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Records new deposits into the custody account of the direction's source chain.
+    async fn observe(&self, direction: Direction) -> Result<Vec<(String, TransferState)>, BridgeError> {
+        let (chain, account, asset) = self.source(direction);
+        let chain_name = chain.chain_name().to_string();
+        let cursor = self.state.lock().unwrap().cursors.get(&chain_name).cloned();
+        let payments = chain.payments(account, cursor.as_deref()).await?;
+        let Some(last) = payments.last() else {
+            return Ok(Vec::new());
+        };
+        let next_cursor = last.cursor.clone();
+
+        let now = Utc::now();
+        let mut observed = Vec::new();
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            for deposit in payments {
+                // Payouts from the custody account show up in the same listing
+                if deposit.to != account {
+                    continue;
+                }
+                if deposit.asset != asset {
+                    warn!("Ignoring {} deposit {} of unsupported asset {:?}", chain_name, deposit.id, deposit.asset);
+                    continue;
+                }
+                let id = format!("{}:{}", chain_name, deposit.id);
+                if state.transfers.contains_key(&id) {
+                    continue;
+                }
+                let transfer = self.new_transfer(id, direction, deposit, now);
+                info!("Observed bridge transfer {} ({:?})", transfer.id, transfer.state);
+                observed.push((transfer.id.clone(), transfer.state));
+                state.transfers.insert(transfer.id.clone(), transfer);
+            }
+            state.cursors.insert(chain_name, next_cursor);
+            snapshot(&state)
+        };
+        self.store.save(&snapshot).map_err(BridgeError::Store)?;
+        Ok(observed)
+    }
+
+    fn new_transfer(&self, id: String, direction: Direction, deposit: Payment, now: DateTime<Utc>) -> BridgeTransfer {
+        let (from_decimals, to_decimals, destination) = match direction {
+            Direction::PiToStellar => (self.config.pi_decimals, self.config.stellar_decimals, &self.stellar),
+            Direction::StellarToPi => (self.config.stellar_decimals, self.config.pi_decimals, &self.pi),
+        };
+        let memo = deposit.memo.as_deref().map(str::trim).unwrap_or_default();
+        let recipient = match direction {
+            Direction::StellarToPi => pi_address_from_memo(memo),
+            Direction::PiToStellar => memo.to_string(),
+        };
+        let payout_amount = convert_amount(deposit.amount, from_decimals, to_decimals);
+
+        let reason = if recipient.is_empty() {
+            Some("Deposit memo does not name a destination address.".to_string())
+        } else if !destination.is_valid_address(&recipient) {
+            Some(format!("Deposit memo is not a valid {} address.", destination.chain_name()))
+        } else if deposit.amount == 0 {
+            Some("Deposit amount is zero.".to_string())
+        } else if payout_amount.is_none() {
+            Some(format!("Amount {} cannot be represented with {} decimals.", deposit.amount, to_decimals))
+        } else {
+            None
+        };
+
+        BridgeTransfer {
+            id,
+            direction,
+            source_tx_id: deposit.tx_id,
+            sender: deposit.from,
+            recipient,
+            amount: deposit.amount,
+            payout_amount: payout_amount.unwrap_or(0),
+            state: if reason.is_some() { TransferState::Rejected } else { TransferState::Observed },
+            confirmations: 0,
+            payout_tx_id: None,
+            pending: None,
+            reason,
+            observed_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns a rejected deposit to its sender on the chain it came from, once the deposit has
+    /// the confirmations a bridged one would need.
+    ///
+    /// The refund is signed and saved before it is submitted; if the submission fails, later
+    /// polls resubmit it.
+    pub async fn refund(&self, transfer_id: &str) -> Result<TransferState, BridgeError> {
+        let _guard = self.polling.lock().await;
+        let not_refundable = |reason: &str| BridgeError::NotRefundable {
+            transfer_id: transfer_id.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut transfer = self.get_transfer(transfer_id).ok_or_else(|| not_refundable("unknown transfer"))?;
+        if transfer.state != TransferState::Rejected {
+            return Err(not_refundable(&format!("it is {:?}, not rejected", transfer.state)));
+        }
+        let (chain, account, asset) = self.source(transfer.direction);
+        let required = self.required_confirmations(transfer.direction);
+        transfer.confirmations = chain.confirmations(&transfer.source_tx_id).await?;
+        if transfer.confirmations < required {
+            return Err(not_refundable(&format!("the deposit has {} of {} confirmations", transfer.confirmations, required)));
+        }
+
+        let refund = chain
+            .build_transfer(&Transfer {
+                from: account.to_string(),
+                to: transfer.sender.clone(),
+                asset,
+                amount: transfer.amount,
+                memo: None,
+                reference: Some(format!("refund:{}", transfer.id)),
+            })
+            .await?;
+        info!("Refunding rejected bridge transfer {} to {}", transfer.id, transfer.sender);
+        transfer.pending = Some(refund);
+        transfer.state = TransferState::Refunding;
+        transfer.updated_at = Utc::now();
+        self.save_transfer(transfer.clone())?;
+
+        self.submit_pending(&mut transfer, chain.as_ref()).await?;
+        let state = transfer.state;
+        self.save_transfer(transfer)?;
+        Ok(state)
+    }
+
+    // The chain, bridge account and asset that deposits in `direction` are paid into
+    fn source(&self, direction: Direction) -> (&Arc<dyn Chain>, &str, Asset) {
+        match direction {
+            Direction::PiToStellar => (&self.pi, &self.config.pi_custody_account, Asset::Native),
+            Direction::StellarToPi => (&self.stellar, &self.config.stellar_issuer, self.config.wrapped_asset()),
+        }
+    }
+
+    // The chain, bridge account and asset that transfers in `direction` are paid out from
+    fn destination(&self, direction: Direction) -> (&Arc<dyn Chain>, &str, Asset) {
+        match direction {
+            Direction::PiToStellar => self.source(Direction::StellarToPi),
+            Direction::StellarToPi => self.source(Direction::PiToStellar),
+        }
+    }
+
+    fn required_confirmations(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::PiToStellar => self.config.pi_confirmations,
+            Direction::StellarToPi => self.config.stellar_confirmations,
+        }
+    }
+
+    /// Moves a transfer on by one state, returning the new state if it changed.
+    async fn advance(&self, mut transfer: BridgeTransfer) -> Result<Option<(String, TransferState)>, BridgeError> {
+        let previous = transfer.state;
+
+        match transfer.state {
+            TransferState::Observed => {
+                let (source, _, _) = self.source(transfer.direction);
+                let confirmations = source.confirmations(&transfer.source_tx_id).await?;
+                if confirmations == transfer.confirmations {
+                    return Ok(None);
+                }
+                transfer.confirmations = confirmations;
+                if confirmations >= self.required_confirmations(transfer.direction) {
+                    transfer.state = TransferState::Confirmed;
+                }
+            }
+            TransferState::Confirmed => {
+                if self.paused().is_some() {
+                    return Ok(None);
+                }
+                let (destination, from, asset) = self.destination(transfer.direction);
+                // The transfer ID is the Pi node's idempotency key; Stellar payouts are recognised
+                // by the hash they are signed with instead
+                let payout = destination
+                    .build_transfer(&Transfer {
+                        from: from.to_string(),
+                        to: transfer.recipient.clone(),
+                        asset,
+                        amount: transfer.payout_amount,
+                        memo: None,
+                        reference: Some(transfer.id.clone()),
+                    })
+                    .await?;
+                // Saved before it is submitted, so a retry can never sign a second payout
+                transfer.pending = Some(payout);
+                transfer.state = TransferState::Paying;
+                transfer.updated_at = Utc::now();
+                self.save_transfer(transfer.clone())?;
+                self.submit_pending(&mut transfer, destination.as_ref()).await?;
+            }
+            TransferState::Paying => {
+                let (destination, _, _) = self.destination(transfer.direction);
+                self.send_pending(&mut transfer, destination.as_ref()).await?;
+            }
+            TransferState::Refunding => {
+                let (source, _, _) = self.source(transfer.direction);
+                self.send_pending(&mut transfer, source.as_ref()).await?;
+            }
+            TransferState::Minted | TransferState::Released | TransferState::Rejected | TransferState::Refunded => {
+                return Ok(None)
+            }
+        }
+
+        if transfer.state == previous && matches!(previous, TransferState::Paying | TransferState::Refunding) {
+            return Ok(None);
+        }
+        transfer.updated_at = Utc::now();
+        let update = (transfer.id.clone(), transfer.state);
+        self.save_transfer(transfer)?;
+        Ok((update.1 != previous).then_some(update))
+    }
+
+    // Settles a Paying or Refunding transfer's saved transaction after an earlier attempt. If the
+    // chain already applied it, the transfer is finished; if it failed on-chain or expired
+    // unapplied, the transfer goes back to be signed again; otherwise the same signed transaction
+    // is resubmitted. While the bridge is paused, payouts are only looked up.
+    async fn send_pending(&self, transfer: &mut BridgeTransfer, chain: &dyn Chain) -> Result<(), BridgeError> {
+        let refund = transfer.state == TransferState::Refunding;
+        let Some(pending) = &transfer.pending else {
+            // Nothing can have been submitted without a saved transaction
+            discard(transfer);
+            return Ok(());
+        };
+
+        if let Some(tx_id) = pending.tx_id.clone() {
+            let expired = pending
+                .expires_at
+                .is_some_and(|expires_at| Utc::now() > expires_at + chrono::Duration::seconds(EXPIRY_GRACE_SECS));
+            match chain.transaction_status(&tx_id).await? {
+                TransactionStatus::Pending if !expired => {}
+                TransactionStatus::Pending | TransactionStatus::Expired => {
+                    warn!("Transaction {} of bridge transfer {} expired without being applied", tx_id, transfer.id);
+                    discard(transfer);
+                    return Ok(());
+                }
+                TransactionStatus::Failed { reason } => {
+                    error!("Transaction {} of bridge transfer {} failed: {}", tx_id, transfer.id, reason);
+                    discard(transfer);
+                    return Ok(());
+                }
+                TransactionStatus::Included | TransactionStatus::Confirmed { .. } => {
+                    info!("Bridge transfer {} was already settled in {}", transfer.id, tx_id);
+                    finish(transfer, tx_id);
+                    return Ok(());
+                }
+            }
+        }
+        if !refund && self.paused().is_some() {
+            return Ok(());
+        }
+        self.submit_pending(transfer, chain).await
+    }
+
+    // Submits the saved transaction and finishes the transfer once the chain accepts it. A
+    // transaction the chain refuses as stale is discarded unless it was applied meanwhile.
+    async fn submit_pending(&self, transfer: &mut BridgeTransfer, chain: &dyn Chain) -> Result<(), BridgeError> {
+        let Some(pending) = &transfer.pending else {
+            return Ok(());
+        };
+        // Recorded by the ID it was signed with, which lookups use, not that of a fee bump
+        let signed_id = pending.tx_id.clone();
+        match chain.submit_transfer(pending).await {
+            Ok(tx_id) => {
+                let tx_id = signed_id.unwrap_or(tx_id);
+                info!("Bridge transfer {} settled in {}", transfer.id, tx_id);
+                finish(transfer, tx_id);
+            }
+            Err(e) if e.is_stale() => {
+                if let Some(tx_id) = signed_id {
+                    if chain.transaction_status(&tx_id).await?.confirmations() > 0 {
+                        info!("Bridge transfer {} was already settled in {}", transfer.id, tx_id);
+                        finish(transfer, tx_id);
+                        return Ok(());
+                    }
+                }
+                warn!("Transaction of bridge transfer {} can no longer be applied: {}", transfer.id, e);
+                discard(transfer);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    fn save_transfer(&self, transfer: BridgeTransfer) -> Result<(), BridgeError> {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            state.transfers.insert(transfer.id.clone(), transfer);
+            snapshot(&state)
+        };
+        self.store.save(&snapshot).map_err(BridgeError::Store)
+    }
+}
+
+// The Pi account whose public key a hash memo holds, or a text memo as it is
+fn pi_address_from_memo(memo: &str) -> String {
+    match hex::decode(memo).ok().and_then(|key| <[u8; 32]>::try_from(key).ok()) {
+        Some(key) => strkey::encode_account_id(&key),
+        None => memo.to_string(),
+    }
+}
+
+// Drops a saved transaction that was never applied, so that payouts are signed again and
+// refunds wait for an operator to retry them
+fn discard(transfer: &mut BridgeTransfer) {
+    transfer.pending = None;
+    transfer.state = match transfer.state {
+        TransferState::Refunding => TransferState::Rejected,
+        _ => TransferState::Confirmed,
+    };
+}
+
+// Records the transaction that paid out or refunded the transfer
+fn finish(transfer: &mut BridgeTransfer, tx_id: String) {
+    transfer.payout_tx_id = Some(tx_id);
+    transfer.pending = None;
+    transfer.state = match (transfer.state, transfer.direction) {
+        (TransferState::Refunding, _) => TransferState::Refunded,
+        (_, Direction::PiToStellar) => TransferState::Minted,
+        (_, Direction::StellarToPi) => TransferState::Released,
+    };
+}
+
+fn snapshot(state: &BridgeState) -> BridgeSnapshot {
+    let mut transfers: Vec<BridgeTransfer> = state.transfers.values().cloned().collect();
+    transfers.sort_by(|a, b| a.observed_at.cmp(&b.observed_at).then_with(|| a.id.cmp(&b.id)));
+    BridgeSnapshot {
+        cursors: state.cursors.clone(),
+        transfers,
+        paused: state.paused.clone(),
+    }
+}
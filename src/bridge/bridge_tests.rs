@@ -0,0 +1,697 @@
+#[cfg(test)]
+mod tests {
+    use crate::bridge::auditor::{Auditor, AuditorConfig};
+    use crate::bridge::bridge::{
+        convert_amount, Bridge, BridgeConfig, BridgeError, BridgeSnapshot, BridgeStore, Direction, JsonFileBridgeStore,
+        TransferState,
+    };
+    use crate::bridge::relayer::Relayer;
+    use crate::cross_chain::chain::{Asset, Chain, Transfer};
+    use crate::cross_chain::devnet::{Devnet, DevnetCall};
+    use crate::cross_chain::mock_server::{MockResponse, MockServer};
+    use crate::cross_chain::pi_integration::PiIntegration;
+    use crate::cross_chain::stellar_integration::{EnvelopeBuilder, StellarIntegration, StellarPaymentRequest};
+    use crate::cross_chain::stellar_signer::KeypairEnvelopeBuilder;
+    use crate::cross_chain::transport::TransportConfig;
+    use crate::fixtures::keypair;
+    use crate::models::stellar::strkey;
+    use crate::models::stellar::transaction::Network;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const CUSTODY: &str = "pi-custody";
+    const ISSUER: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+    const ALICE: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+    const BOB: &str = "GABQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQHGPC";
+    const PI_BOB: &str = "GACAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAJJHP";
+
+    #[derive(Default)]
+    struct MemoryStore {
+        snapshot: Mutex<BridgeSnapshot>,
+        // The next save of a snapshot with a transfer in this state fails
+        fail_save_of: Mutex<Option<TransferState>>,
+    }
+
+    impl BridgeStore for MemoryStore {
+        fn load(&self) -> Result<BridgeSnapshot, String> {
+            Ok(self.snapshot.lock().unwrap().clone())
+        }
+
+        fn save(&self, snapshot: &BridgeSnapshot) -> Result<(), String> {
+            let mut fail_save_of = self.fail_save_of.lock().unwrap();
+            if let Some(state) = *fail_save_of {
+                if snapshot.transfers.iter().any(|transfer| transfer.state == state) {
+                    *fail_save_of = None;
+                    return Err(format!("Injected failure saving a {:?} transfer", state));
+                }
+            }
+            *self.snapshot.lock().unwrap() = snapshot.clone();
+            Ok(())
+        }
+    }
+
+    // Stands in for XDR signing; the envelope only records what it was built from
+    struct FakeEnvelopeBuilder;
+
+    impl EnvelopeBuilder for FakeEnvelopeBuilder {
+        fn build_payment(&self, payment: &StellarPaymentRequest, sequence: i64, base_fee: u32) -> Result<String, String> {
+            Ok(format!("{}-{}-{}-{}-{}", payment.source_account, payment.destination_account, payment.amount, sequence, base_fee))
+        }
+    }
+
+    struct Servers {
+        pi: MockServer,
+        stellar: MockServer,
+    }
+
+    // Starts mock Pi and Stellar APIs with empty payment listings
+    async fn servers() -> Servers {
+        let pi = MockServer::start().await;
+        let stellar = MockServer::start().await;
+        stellar.respond("GET", &format!("/accounts/{}/payments", ISSUER), horizon_page(vec![]));
+        stellar.respond("GET", &format!("/accounts/{}", ISSUER), MockResponse::json(200, json!({ "id": ISSUER, "sequence": "100" })));
+        stellar.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+        pi.respond("GET", &format!("/accounts/{}/payments", CUSTODY), MockResponse::json(200, json!({ "payments": [] })));
+        Servers { pi, stellar }
+    }
+
+    fn bridge(servers: &Servers, store: Arc<dyn BridgeStore>) -> Bridge {
+        Bridge::new(
+            BridgeConfig::new(CUSTODY, ISSUER),
+            Arc::new(PiIntegration::new(&servers.pi.url())),
+            Arc::new(StellarIntegration::new(&servers.stellar.url()).with_envelope_builder(Arc::new(FakeEnvelopeBuilder))),
+            store,
+        )
+        .unwrap()
+    }
+
+    fn pi_deposits(deposits: Vec<Value>) -> MockResponse {
+        MockResponse::json(200, json!({ "payments": deposits }))
+    }
+
+    fn pi_deposit(id: &str, amount: u64, memo: Option<&str>) -> Value {
+        json!({ "id": id, "from": "pi-alice", "to": CUSTODY, "amount": amount, "memo": memo, "paging_token": id })
+    }
+
+    fn horizon_page(records: Vec<Value>) -> MockResponse {
+        MockResponse::json(200, json!({ "_embedded": { "records": records } }))
+    }
+
+    fn horizon_payment(id: &str, from: &str, to: &str, code: &str, amount: &str, memo: &str) -> Value {
+        json!({
+            "id": id,
+            "paging_token": id,
+            "transaction_hash": format!("hash-{}", id),
+            "type": "payment",
+            "from": from,
+            "to": to,
+            "asset_type": "credit_alphanum4",
+            "asset_code": code,
+            "asset_issuer": ISSUER,
+            "amount": amount,
+            "transaction": { "hash": format!("hash-{}", id), "ledger": 100, "successful": true, "memo": memo, "memo_type": "text" }
+        })
+    }
+
+    // Names PI_BOB as the Pi recipient by the public key behind the address
+    fn returned_with_hash_memo(mut payment: Value) -> Value {
+        let key = strkey::decode_account_id(PI_BOB).unwrap();
+        payment["transaction"]["memo"] = json!(base64::encode(key));
+        payment["transaction"]["memo_type"] = json!("hash");
+        payment
+    }
+
+    fn fee_stats() -> Value {
+        let distribution = json!({ "min": "100", "mode": "100", "p50": "100", "p90": "150", "p99": "200", "max": "500" });
+        json!({
+            "last_ledger": "100",
+            "last_ledger_base_fee": "100",
+            "ledger_capacity_usage": "0.5",
+            "fee_charged": distribution,
+            "max_fee": distribution
+        })
+    }
+
+    fn submitted(hash: &str) -> MockResponse {
+        MockResponse::json(200, json!({ "hash": hash, "ledger": 101, "successful": true }))
+    }
+
+    fn confirmations(count: u64) -> MockResponse {
+        MockResponse::json(200, json!({ "id": "pi-tx-1", "confirmations": count }))
+    }
+
+    #[tokio::test]
+    async fn test_pi_deposit_is_minted_on_stellar() {
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 250_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(1));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(3));
+        servers.stellar.respond("POST", "/transactions", submitted("stellar-tx-1"));
+
+        // The original empty listing queued by servers() is served first
+        let bridge = bridge(&servers, Arc::new(MemoryStore::default()));
+        assert!(bridge.poll().await.unwrap().is_empty());
+
+        let id = "pi:pi-tx-1".to_string();
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed)]);
+        assert_eq!(bridge.get_transfer(&id).unwrap().confirmations, 1);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Confirmed)]);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        assert!(bridge.poll().await.unwrap().is_empty());
+
+        let transfer = bridge.get_transfer(&id).unwrap();
+        assert_eq!(transfer.direction, Direction::PiToStellar);
+        assert_eq!(transfer.recipient, ALICE);
+        assert_eq!(transfer.payout_amount, 25_000_000);
+        assert_eq!(transfer.payout_tx_id.as_deref(), Some("stellar-tx-1"));
+
+        // Minted from the issuer with the next sequence number and the current base fee
+        let mints = servers.stellar.requests_to("POST", "/transactions");
+        assert_eq!(mints.len(), 1);
+        assert_eq!(mints[0].body, format!("tx={}-{}-25000000-101-100", ISSUER, ALICE));
+
+        // Later scans resume after the last deposit seen
+        let scans = servers.pi.requests_to("GET", &payments_path);
+        assert!(scans.last().unwrap().path.contains("cursor=pi-tx-1"));
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_pi_returned_to_issuer_is_released_on_pi() {
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", ISSUER);
+        servers.stellar.respond("GET", &payments_path, horizon_page(vec![
+            // A mint paid out by the issuer, not a deposit
+            horizon_payment("101", ISSUER, ALICE, "PI", "2.0000000", ""),
+            // Some other asset sent to the issuer
+            returned_with_hash_memo(horizon_payment("102", BOB, ISSUER, "USD", "5.0000000", "")),
+            returned_with_hash_memo(horizon_payment("103", BOB, ISSUER, "PI", "1.5000000", "")),
+            // A Pi address is too long for a text memo
+            horizon_payment("104", BOB, ISSUER, "PI", "1.0000000", "GABQGAYDAMBQGAYDAMBQGAYDAMBQGAYD"),
+        ]));
+        servers.stellar.respond("GET", &payments_path, horizon_page(vec![]));
+        servers.stellar.respond("GET", "/transactions/hash-103", MockResponse::json(200, json!({ "hash": "hash-103", "ledger": 100, "successful": true })));
+        servers.stellar.respond("GET", "/", MockResponse::json(200, json!({ "history_latest_ledger": 100 })));
+        servers.pi.respond("POST", "/payments", MockResponse::json(200, json!({ "tx_id": "pi-tx-9" })));
+
+        let bridge = bridge(&servers, Arc::new(MemoryStore::default()));
+        bridge.poll().await.unwrap();
+        let changed = bridge.poll().await.unwrap();
+        assert_eq!(changed, vec![
+            ("stellar:103".to_string(), TransferState::Observed),
+            ("stellar:104".to_string(), TransferState::Rejected),
+            ("stellar:103".to_string(), TransferState::Confirmed),
+        ]);
+        assert_eq!(bridge.poll().await.unwrap(), vec![("stellar:103".to_string(), TransferState::Released)]);
+        assert_eq!(bridge.transfers().len(), 2);
+
+        let releases = servers.pi.requests_to("POST", "/payments");
+        assert_eq!(releases.len(), 1);
+        let body: Value = serde_json::from_str(&releases[0].body).unwrap();
+        assert_eq!(body["from"], CUSTODY);
+        assert_eq!(body["to"], PI_BOB);
+        assert_eq!(body["amount"], 150_000_000);
+        assert_eq!(releases[0].headers["idempotency-key"], "stellar:103");
+    }
+
+    #[tokio::test]
+    async fn test_unbridgeable_deposits_are_rejected() {
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![
+            pi_deposit("pi-tx-1", 100_000_000, None),
+            // 8-decimal dust that cannot be represented with Stellar's 7 decimals
+            pi_deposit("pi-tx-2", 100_000_001, Some(ALICE)),
+            // Not a Stellar address: the checksum of ALICE with its last character changed
+            pi_deposit("pi-tx-3", 100_000_000, Some("GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXB")),
+        ]));
+
+        let bridge = bridge(&servers, Arc::new(MemoryStore::default()));
+        bridge.poll().await.unwrap();
+        bridge.poll().await.unwrap();
+
+        let transfers = bridge.transfers();
+        assert_eq!(transfers.len(), 3);
+        assert!(transfers.iter().all(|t| t.state == TransferState::Rejected));
+        assert_eq!(transfers[0].reason.as_deref(), Some("Deposit memo does not name a destination address."));
+        assert_eq!(transfers[1].reason.as_deref(), Some("Amount 100000001 cannot be represented with 7 decimals."));
+        assert_eq!(transfers[2].reason.as_deref(), Some("Deposit memo is not a valid stellar address."));
+        assert!(servers.stellar.requests_to("POST", "/transactions").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_resumes_after_restart_without_paying_twice() {
+        let path = std::env::temp_dir().join(format!("bridge_store_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 300_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(5));
+        servers.stellar.respond("POST", "/transactions", submitted("stellar-tx-1"));
+
+        {
+            let bridge = bridge(&servers, Arc::new(JsonFileBridgeStore::new(&path)));
+            bridge.poll().await.unwrap();
+            bridge.poll().await.unwrap();
+            assert_eq!(bridge.get_transfer("pi:pi-tx-1").unwrap().state, TransferState::Confirmed);
+        }
+
+        let bridge = bridge(&servers, Arc::new(JsonFileBridgeStore::new(&path)));
+        assert_eq!(bridge.poll().await.unwrap(), vec![("pi:pi-tx-1".to_string(), TransferState::Minted)]);
+        assert!(bridge.poll().await.unwrap().is_empty());
+        assert_eq!(servers.stellar.requests_to("POST", "/transactions").len(), 1);
+
+        let scans = servers.pi.requests_to("GET", &payments_path);
+        assert!(scans.last().unwrap().path.contains("cursor=pi-tx-1"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_applied_stellar_payout_is_not_signed_again() {
+        let signer = KeypairEnvelopeBuilder::new(Network::testnet(), keypair(7));
+        let issuer = signer.account_id().to_string();
+
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 300_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(3));
+        servers.stellar.respond("GET", &format!("/accounts/{}/payments", issuer), horizon_page(vec![]));
+        servers.stellar.respond("GET", &format!("/accounts/{}", issuer), MockResponse::json(200, json!({ "id": issuer, "sequence": "100" })));
+        servers.stellar.respond("GET", "/", MockResponse::json(200, json!({ "history_latest_ledger": 102 })));
+        // The mint is applied, but every response to it, retries included, is lost
+        servers.stellar.respond("POST", "/transactions", MockResponse::json(500, json!({ "title": "Internal Server Error" })));
+        let attempts = 1 + TransportConfig::default().max_retries as usize;
+
+        let store = Arc::new(MemoryStore::default());
+        let signer = Arc::new(signer);
+        let new_bridge = || {
+            let stellar = StellarIntegration::new(&servers.stellar.url()).with_envelope_builder(signer.clone());
+            Bridge::new(BridgeConfig::new(CUSTODY, &issuer), Arc::new(PiIntegration::new(&servers.pi.url())), Arc::new(stellar), store.clone()).unwrap()
+        };
+        let bridge = new_bridge();
+        bridge.poll().await.unwrap();
+        let id = "pi:pi-tx-1".to_string();
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed), (id.clone(), TransferState::Confirmed)]);
+        assert!(bridge.poll().await.unwrap().is_empty());
+
+        // The signed mint and its hash were saved before it was submitted
+        let transfer = bridge.get_transfer(&id).unwrap();
+        assert_eq!(transfer.state, TransferState::Paying);
+        let pending = transfer.pending.unwrap();
+        let hash = pending.tx_id.clone().unwrap();
+        assert_eq!(servers.stellar.requests_to("POST", "/transactions").len(), attempts);
+
+        // After a restart the hash is looked up instead of signing or submitting again
+        servers.stellar.respond("GET", &format!("/transactions/{}", hash), MockResponse::json(200, json!({ "hash": hash, "ledger": 101, "successful": true })));
+        let bridge = new_bridge();
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        let transfer = bridge.get_transfer(&id).unwrap();
+        assert_eq!(transfer.payout_tx_id, Some(hash));
+        assert_eq!(transfer.pending, None);
+        assert_eq!(servers.stellar.requests_to("POST", "/transactions").len(), attempts);
+        assert_eq!(servers.stellar.requests_to("GET", &format!("/accounts/{}", issuer)).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_stellar_payout_is_signed_again() {
+        let signer = KeypairEnvelopeBuilder::new(Network::testnet(), keypair(7));
+        let issuer = signer.account_id().to_string();
+
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 300_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(3));
+        servers.stellar.respond("GET", &format!("/accounts/{}/payments", issuer), horizon_page(vec![]));
+        // Another transaction from the issuer used the sequence number first
+        let account_path = format!("/accounts/{}", issuer);
+        servers.stellar.respond("GET", &account_path, MockResponse::json(200, json!({ "id": issuer, "sequence": "100" })));
+        servers.stellar.respond("GET", &account_path, MockResponse::json(200, json!({ "id": issuer, "sequence": "101" })));
+        servers.stellar.respond("POST", "/transactions", MockResponse::json(400, json!({
+            "title": "Transaction Failed",
+            "extras": { "result_codes": { "transaction": "tx_bad_seq" } }
+        })));
+        // The replacement is wrapped in a fee bump, whose hash Horizon returns
+        servers.stellar.respond("POST", "/transactions", submitted("fee-bump-hash"));
+
+        let stellar = StellarIntegration::new(&servers.stellar.url()).with_envelope_builder(Arc::new(signer));
+        let bridge = Bridge::new(
+            BridgeConfig::new(CUSTODY, &issuer),
+            Arc::new(PiIntegration::new(&servers.pi.url())),
+            Arc::new(stellar),
+            Arc::new(MemoryStore::default()),
+        ).unwrap();
+        bridge.poll().await.unwrap();
+        let id = "pi:pi-tx-1".to_string();
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed), (id.clone(), TransferState::Confirmed)]);
+
+        // The refused mint is dropped, not resubmitted
+        assert!(bridge.poll().await.unwrap().is_empty());
+        let transfer = bridge.get_transfer(&id).unwrap();
+        assert_eq!(transfer.state, TransferState::Confirmed);
+        assert_eq!(transfer.pending, None);
+
+        // The next poll signs it with a fresh sequence number
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        let posts = servers.stellar.requests_to("POST", "/transactions");
+        assert_eq!(posts.len(), 2);
+        assert_ne!(posts[0].body, posts[1].body);
+
+        // Recorded by the hash of the signed mint, which can be looked up later
+        let payout_tx_id = bridge.get_transfer(&id).unwrap().payout_tx_id.unwrap();
+        assert_eq!(payout_tx_id.len(), 64);
+        assert_ne!(payout_tx_id, "fee-bump-hash");
+    }
+
+    #[tokio::test]
+    async fn test_expired_stellar_payout_is_signed_again() {
+        let signer = KeypairEnvelopeBuilder::new(Network::testnet(), keypair(7));
+        let issuer = signer.account_id().to_string();
+
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 300_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(3));
+        servers.stellar.respond("GET", &format!("/accounts/{}/payments", issuer), horizon_page(vec![]));
+        servers.stellar.respond("GET", &format!("/accounts/{}", issuer), MockResponse::json(200, json!({ "id": issuer, "sequence": "100" })));
+        servers.stellar.respond("POST", "/transactions", MockResponse::json(400, json!({ "title": "Bad Request" })));
+        servers.stellar.respond("POST", "/transactions", submitted("stellar-tx-2"));
+
+        let store = Arc::new(MemoryStore::default());
+        let signer = Arc::new(signer);
+        let new_bridge = || {
+            let stellar = StellarIntegration::new(&servers.stellar.url()).with_envelope_builder(signer.clone());
+            Bridge::new(BridgeConfig::new(CUSTODY, &issuer), Arc::new(PiIntegration::new(&servers.pi.url())), Arc::new(stellar), store.clone()).unwrap()
+        };
+        let bridge = new_bridge();
+        bridge.poll().await.unwrap();
+        let id = "pi:pi-tx-1".to_string();
+        bridge.poll().await.unwrap();
+        assert!(bridge.poll().await.unwrap().is_empty());
+        let expires_at = bridge.get_transfer(&id).unwrap().pending.unwrap().expires_at.unwrap();
+        assert!(expires_at > chrono::Utc::now());
+
+        // Still unknown to Horizon long after its time bounds closed
+        for transfer in store.snapshot.lock().unwrap().transfers.iter_mut() {
+            transfer.pending.as_mut().unwrap().expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(5));
+        }
+        let bridge = new_bridge();
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Confirmed)]);
+        assert_eq!(bridge.get_transfer(&id).unwrap().pending, None);
+
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        // Signed again from the account's current sequence number
+        assert_eq!(servers.stellar.requests_to("GET", &format!("/accounts/{}", issuer)).len(), 2);
+        assert_eq!(servers.stellar.requests_to("POST", "/transactions").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_deposit_is_refunded() {
+        let (devnets, bridge) = devnets();
+        // 8-decimal dust that cannot be minted with 7 decimals
+        let tx_id = devnets.pi.transfer(&Transfer {
+            from: "pi-alice".to_string(),
+            to: CUSTODY.to_string(),
+            asset: Asset::Native,
+            amount: 100_000_001,
+            memo: Some(ALICE.to_string()),
+            reference: None,
+        }).await.unwrap();
+        devnets.pi.close_block();
+        let id = format!("pi:{}", tx_id);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Rejected)]);
+
+        // Not before the deposit is final
+        assert!(matches!(bridge.refund(&id).await, Err(BridgeError::NotRefundable { .. })));
+        devnets.pi.close_blocks(2);
+        devnets.pi.fail_next(DevnetCall::Submit, 1);
+        assert!(bridge.refund(&id).await.is_err());
+        assert_eq!(bridge.get_transfer(&id).unwrap().state, TransferState::Refunding);
+
+        // The saved refund is submitted by the next poll
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Refunded)]);
+        devnets.pi.close_block();
+        assert_eq!(devnets.pi.balance("pi-alice", &Asset::Native).await.unwrap(), 1_000_000_000);
+        assert_eq!(devnets.pi.balance(CUSTODY, &Asset::Native).await.unwrap(), 0);
+        assert!(matches!(bridge.refund(&id).await, Err(BridgeError::NotRefundable { .. })));
+
+        let report = Auditor::new(bridge.clone(), AuditorConfig::new(0)).audit().await.unwrap();
+        assert!(report.is_balanced());
+        assert!(report.assets[0].unmatched_transfers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_relayer_drives_transfers_until_stopped() {
+        let servers = servers().await;
+        let payments_path = format!("/accounts/{}/payments", CUSTODY);
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![pi_deposit("pi-tx-1", 100_000_000, Some(ALICE))]));
+        servers.pi.respond("GET", &payments_path, pi_deposits(vec![]));
+        servers.pi.respond("GET", "/transactions/pi-tx-1", confirmations(3));
+        servers.stellar.respond("POST", "/transactions", submitted("stellar-tx-1"));
+
+        let bridge = Arc::new(bridge(&servers, Arc::new(MemoryStore::default())));
+        let handle = Relayer::new(bridge.clone(), Duration::from_millis(20)).start();
+
+        let mut minted = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if bridge.get_transfer("pi:pi-tx-1").map(|t| t.state) == Some(TransferState::Minted) {
+                minted = true;
+                break;
+            }
+        }
+        handle.stop().await;
+
+        assert!(minted, "Relayer did not mint the deposit");
+        let polls = servers.pi.requests_to("GET", &payments_path).len();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(servers.pi.requests_to("GET", &payments_path).len(), polls);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_on_devnets() {
+        let pi = Arc::new(Devnet::pi());
+        let stellar = Arc::new(Devnet::stellar());
+        let wrapped = BridgeConfig::new(CUSTODY, ISSUER).wrapped_asset();
+        pi.fund("pi-alice", &Asset::Native, 300_000_000);
+        let bridge = Bridge::new(BridgeConfig::new(CUSTODY, ISSUER), pi.clone(), stellar.clone(), Arc::new(MemoryStore::default())).unwrap();
+
+        let deposit = Transfer {
+            from: "pi-alice".to_string(),
+            to: CUSTODY.to_string(),
+            asset: Asset::Native,
+            amount: 250_000_000,
+            memo: Some(ALICE.to_string()),
+            reference: None,
+        };
+        let deposit_tx = pi.transfer(&deposit).await.unwrap();
+        assert!(bridge.poll().await.unwrap().is_empty());
+
+        pi.close_block();
+        let id = format!("pi:{}", deposit_tx);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed)]);
+        pi.close_blocks(2);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Confirmed)]);
+
+        // An unavailable Horizon holds the mint until the next poll
+        stellar.fail_next(DevnetCall::Submit, 1);
+        assert!(bridge.poll().await.unwrap().is_empty());
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        stellar.close_block();
+        assert_eq!(stellar.balance(ALICE, &wrapped).await.unwrap(), 25_000_000);
+
+        // Returning wrapped PI to the issuer burns it and releases PI from custody
+        let burn_tx = stellar.transfer(&Transfer {
+            from: ALICE.to_string(),
+            to: ISSUER.to_string(),
+            asset: wrapped.clone(),
+            amount: 10_000_000,
+            memo: Some("pi-alice".to_string()),
+            reference: None,
+        }).await.unwrap();
+        stellar.close_block();
+        let id = format!("stellar:{}", burn_tx);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed), (id.clone(), TransferState::Confirmed)]);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id, TransferState::Released)]);
+        pi.close_block();
+
+        assert_eq!(stellar.balance(ALICE, &wrapped).await.unwrap(), 15_000_000);
+        assert_eq!(pi.balance("pi-alice", &Asset::Native).await.unwrap(), 150_000_000);
+        assert_eq!(pi.balance(CUSTODY, &Asset::Native).await.unwrap(), 150_000_000);
+    }
+
+    struct Devnets {
+        pi: Arc<Devnet>,
+        stellar: Arc<Devnet>,
+        store: Arc<MemoryStore>,
+    }
+
+    // Devnets with a funded Pi depositor, and a bridge between them
+    fn devnets() -> (Devnets, Arc<Bridge>) {
+        let devnets = Devnets { pi: Arc::new(Devnet::pi()), stellar: Arc::new(Devnet::stellar()), store: Arc::new(MemoryStore::default()) };
+        devnets.pi.fund("pi-alice", &Asset::Native, 1_000_000_000);
+        let bridge = devnet_bridge(&devnets);
+        (devnets, bridge)
+    }
+
+    fn devnet_bridge(devnets: &Devnets) -> Arc<Bridge> {
+        Arc::new(Bridge::new(BridgeConfig::new(CUSTODY, ISSUER), devnets.pi.clone(), devnets.stellar.clone(), devnets.store.clone()).unwrap())
+    }
+
+    // Deposits PI into custody and polls until the bridge has confirmed it
+    async fn confirmed_deposit(devnets: &Devnets, bridge: &Bridge, amount: u64) -> String {
+        let tx_id = devnets.pi.transfer(&Transfer {
+            from: "pi-alice".to_string(),
+            to: CUSTODY.to_string(),
+            asset: Asset::Native,
+            amount,
+            memo: Some(ALICE.to_string()),
+            reference: None,
+        }).await.unwrap();
+        devnets.pi.close_block();
+        let id = format!("pi:{}", tx_id);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Observed)]);
+        devnets.pi.close_blocks(2);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Confirmed)]);
+        id
+    }
+
+    #[tokio::test]
+    async fn test_payout_is_minted_once_when_saving_it_fails() {
+        let (devnets, bridge) = devnets();
+        let wrapped = BridgeConfig::new(CUSTODY, ISSUER).wrapped_asset();
+        let id = confirmed_deposit(&devnets, &bridge, 250_000_000).await;
+
+        // The payout is submitted, but the process cannot record that it was
+        *devnets.store.fail_save_of.lock().unwrap() = Some(TransferState::Minted);
+        assert!(matches!(bridge.poll().await, Err(BridgeError::Store(_))));
+        assert_eq!(devnets.stellar.pending_transactions(), 1);
+
+        // After a restart the saved payout is recognised by its transaction ID, not paid again
+        let bridge = devnet_bridge(&devnets);
+        assert_eq!(bridge.get_transfer(&id).unwrap().state, TransferState::Paying);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        assert_eq!(devnets.stellar.pending_transactions(), 1);
+        devnets.stellar.close_block();
+        assert!(bridge.poll().await.unwrap().is_empty());
+        assert_eq!(devnets.stellar.issued_supply(&wrapped).await.unwrap(), 25_000_000);
+
+        let report = Auditor::new(bridge.clone(), AuditorConfig::new(0)).audit().await.unwrap();
+        assert!(report.is_balanced());
+    }
+
+    #[tokio::test]
+    async fn test_audit_balances_while_transfers_settle() {
+        let (devnets, bridge) = devnets();
+        let auditor = Auditor::new(bridge.clone(), AuditorConfig::new(0));
+
+        let id = confirmed_deposit(&devnets, &bridge, 250_000_000).await;
+        let report = auditor.audit().await.unwrap();
+        assert!(report.is_balanced());
+        let audit = &report.assets[0];
+        assert_eq!(audit.asset, BridgeConfig::new(CUSTODY, ISSUER).wrapped_asset());
+        assert_eq!((audit.locked, audit.wrapped_supply, audit.unmatched, audit.discrepancy), (250_000_000, 0, 250_000_000, 0));
+        assert_eq!(audit.unmatched_transfers, vec![id.clone()]);
+
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id, TransferState::Minted)]);
+        devnets.stellar.close_block();
+        let report = auditor.audit().await.unwrap();
+        let audit = &report.assets[0];
+        assert_eq!((audit.locked, audit.wrapped_supply, audit.unmatched, audit.discrepancy), (250_000_000, 250_000_000, 0, 0));
+        assert!(audit.unmatched_transfers.is_empty());
+        assert_eq!(report.paused, None);
+        assert_eq!(auditor.last_report(), Some(report));
+    }
+
+    #[tokio::test]
+    async fn test_audit_pauses_bridge_on_unbacked_supply() {
+        let (devnets, bridge) = devnets();
+        let auditor = Auditor::new(bridge.clone(), AuditorConfig::new(10_000_000));
+        let id = confirmed_deposit(&devnets, &bridge, 250_000_000).await;
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id, TransferState::Minted)]);
+        devnets.stellar.close_block();
+        assert!(auditor.audit().await.unwrap().is_balanced());
+
+        // The issuer mints 0.5 wrapped PI outside the bridge
+        let wrapped = bridge.config().wrapped_asset();
+        devnets.stellar.transfer(&Transfer {
+            from: ISSUER.to_string(),
+            to: BOB.to_string(),
+            asset: wrapped,
+            amount: 5_000_000,
+            memo: None,
+            reference: None,
+        }).await.unwrap();
+        devnets.stellar.close_block();
+
+        let report = auditor.audit().await.unwrap();
+        assert!(!report.is_balanced());
+        assert_eq!(report.assets[0].discrepancy, -50_000_000);
+        // The bridge's own mint still checks out
+        assert!(report.assets[0].suspects.is_empty());
+        assert!(report.paused.is_some());
+        assert_eq!(bridge.paused(), report.paused);
+
+        // Deposits are still confirmed, but nothing is paid out, even after a restart
+        let id = confirmed_deposit(&devnets, &bridge, 100_000_000).await;
+        assert!(bridge.poll().await.unwrap().is_empty());
+        let bridge = devnet_bridge(&devnets);
+        assert_eq!(bridge.paused(), report.paused);
+        assert!(bridge.poll().await.unwrap().is_empty());
+
+        bridge.resume().unwrap();
+        assert_eq!(bridge.paused(), None);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id, TransferState::Minted)]);
+    }
+
+    #[tokio::test]
+    async fn test_audit_names_failed_payouts() {
+        let (devnets, bridge) = devnets();
+        let auditor = Auditor::new(bridge.clone(), AuditorConfig { pause_threshold: None, ..AuditorConfig::new(0) });
+        let id = confirmed_deposit(&devnets, &bridge, 250_000_000).await;
+        devnets.stellar.fail_next_transactions(1);
+        assert_eq!(bridge.poll().await.unwrap(), vec![(id.clone(), TransferState::Minted)]);
+        devnets.stellar.close_block();
+
+        let report = auditor.audit().await.unwrap();
+        let audit = &report.assets[0];
+        assert_eq!(audit.discrepancy, 250_000_000);
+        assert_eq!(audit.suspects.len(), 1);
+        assert_eq!(audit.suspects[0].transfer_id, id);
+        assert!(audit.suspects[0].reason.contains("failed"), "{}", audit.suspects[0].reason);
+        // Reporting only
+        assert_eq!(report.paused, None);
+        assert_eq!(bridge.paused(), None);
+    }
+
+    #[tokio::test]
+    async fn test_auditor_job_audits_until_stopped() {
+        let (devnets, bridge) = devnets();
+        confirmed_deposit(&devnets, &bridge, 250_000_000).await;
+        let auditor = Arc::new(Auditor::new(bridge, AuditorConfig { interval: Duration::from_millis(20), ..AuditorConfig::new(0) }));
+
+        let handle = auditor.clone().start();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.stop().await;
+
+        let report = auditor.last_report().expect("auditor did not audit");
+        assert!(report.is_balanced());
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(auditor.last_report().unwrap().audited_at, report.audited_at);
+    }
+
+    #[test]
+    fn test_convert_amount() {
+        assert_eq!(convert_amount(250_000_000, 8, 7), Some(25_000_000));
+        assert_eq!(convert_amount(15_000_000, 7, 8), Some(150_000_000));
+        assert_eq!(convert_amount(1, 8, 7), None);
+        assert_eq!(convert_amount(u64::MAX, 7, 8), None);
+    }
+}
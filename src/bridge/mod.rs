@@ -0,0 +1,6 @@
+// mod.rs
+
+pub mod auditor;
+pub mod bridge;
+pub mod relayer;
+pub mod bridge_tests;
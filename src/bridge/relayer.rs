@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use log::{info, error};
+use super::bridge::Bridge;
+
+/// Background service that polls the bridge on a fixed interval.
+pub struct Relayer {
+    bridge: Arc<Bridge>,
+    interval: Duration,
+}
+
+/// Handle to a running relayer.
+pub struct RelayerHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Relayer {
+    pub fn new(bridge: Arc<Bridge>, interval: Duration) -> Self {
+        Self { bridge, interval }
+    }
+
+    /// Spawns the relayer loop onto the current runtime.
+    pub fn start(self) -> RelayerHandle {
+        let (shutdown, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            info!("Bridge relayer started, polling every {:?}", self.interval);
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        match self.bridge.poll().await {
+                            Ok(changed) => {
+                                for (transfer_id, state) in changed {
+                                    info!("Bridge transfer {} is now {:?}", transfer_id, state);
+                                }
+                            }
+                            Err(e) => error!("Bridge poll failed: {}", e),
+                        }
+                    }
+                    _ = stopped.changed() => break,
+                }
+            }
+            info!("Bridge relayer stopped");
+        });
+        RelayerHandle { shutdown, task }
+    }
+}
+
+impl RelayerHandle {
+    /// Stops the relayer after its current poll and waits for it to exit.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
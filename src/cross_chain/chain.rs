@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use log::warn;
+use super::error::ChainError;
+
+/// An asset on a chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Asset {
+    /// The chain's native currency.
+    Native,
+    /// An asset issued on the chain by `issuer`.
+    Issued { code: String, issuer: String },
+}
+
+/// A payment into or out of an account, as reported by the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    /// ID unique to this payment on its chain.
+    pub id: String,
+    /// Transaction carrying the payment.
+    pub tx_id: String,
+    pub from: String,
+    pub to: String,
+    pub asset: Asset,
+    /// Amount in the chain's smallest unit.
+    pub amount: u64,
+    pub memo: Option<String>,
+    /// Cursor for listing payments after this one.
+    pub cursor: String,
+}
+
+/// A transfer to send from an account controlled by this node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub asset: Asset,
+    /// Amount in the chain's smallest unit.
+    pub amount: u64,
+    pub memo: Option<String>,
+    /// Identifies the transfer across retries and restarts, e.g. a bridge transfer ID, so the
+    /// chain can recognise a resubmission.
+    pub reference: Option<String>,
+}
+
+/// A transfer that has been built and signed and is ready to submit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedTransfer {
+    pub transfer: Transfer,
+    /// The chain-specific payload to submit, e.g. a base64 Stellar envelope.
+    pub payload: String,
+    /// ID the transaction will have once submitted, where the chain derives it from the signed
+    /// payload, e.g. a Stellar transaction hash. `None` where the chain assigns it on submission.
+    #[serde(default)]
+    pub tx_id: Option<String>,
+    /// Time after which the chain will never apply the payload, where the payload carries one,
+    /// e.g. the `max_time` of a Stellar envelope.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// What a chain reports about a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainTransaction {
+    pub tx_id: String,
+    /// Block (Pi) or ledger (Stellar) that includes the transaction.
+    pub block: u64,
+    /// Number of blocks that have confirmed the transaction, counting its own.
+    pub confirmations: u64,
+    pub successful: bool,
+}
+
+/// Where a submitted transaction stands.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// Submitted but not yet in a block.
+    Pending,
+    /// In the latest block.
+    Included,
+    /// In a block with later blocks on top; `confirmations` counts its own block.
+    Confirmed { confirmations: u64 },
+    /// In a block but rejected, e.g. for an insufficient balance. Terminal.
+    Failed { reason: String },
+    /// Never made it into a block in time. Terminal.
+    Expired,
+}
+
+impl TransactionStatus {
+    /// The status of a transaction the chain has included.
+    pub fn of(transaction: &ChainTransaction) -> Self {
+        if !transaction.successful {
+            TransactionStatus::Failed { reason: format!("Transaction failed in block {}", transaction.block) }
+        } else if transaction.confirmations <= 1 {
+            TransactionStatus::Included
+        } else {
+            TransactionStatus::Confirmed { confirmations: transaction.confirmations }
+        }
+    }
+
+    /// Number of blocks confirming the transaction so far.
+    pub fn confirmations(&self) -> u64 {
+        match self {
+            TransactionStatus::Included => 1,
+            TransactionStatus::Confirmed { confirmations } => *confirmations,
+            _ => 0,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, TransactionStatus::Failed { .. } | TransactionStatus::Expired)
+    }
+}
+
+/// What the rest of the system needs from a blockchain. Implemented by `PiIntegration` and
+/// `StellarIntegration`; supporting a new chain means implementing this trait.
+///
+/// Amounts are in the chain's smallest unit, see `decimals`.
+#[async_trait]
+pub trait Chain: Send + Sync {
+    /// Short name identifying the chain, e.g. "pi" or "stellar".
+    fn chain_name(&self) -> &str;
+    /// Decimal places of the chain's amounts.
+    fn decimals(&self) -> u32;
+    /// Whether `address` can receive transfers on this chain; the Stellar and Pi integrations
+    /// accept checksummed `G...` and `M...` StrKeys.
+    fn is_valid_address(&self, _address: &str) -> bool {
+        true
+    }
+    /// Returns the balance of `account` in `asset`; zero if the account cannot hold it.
+    async fn balance(&self, account: &str, asset: &Asset) -> Result<u64, ChainError>;
+    /// Builds and signs a transfer without submitting it.
+    async fn build_transfer(&self, transfer: &Transfer) -> Result<SignedTransfer, ChainError>;
+    /// Submits a signed transfer and returns its transaction ID, which is `signed.tx_id` when
+    /// the payload determines it.
+    async fn submit_transfer(&self, signed: &SignedTransfer) -> Result<String, ChainError>;
+    /// Looks up a transaction.
+    async fn transaction(&self, tx_id: &str) -> Result<ChainTransaction, ChainError>;
+    /// Lists payments involving `account` after `cursor`, oldest first.
+    async fn payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<Payment>, ChainError>;
+    /// Returns the height of the latest block.
+    async fn latest_block(&self) -> Result<u64, ChainError>;
+    /// Returns the amount of an asset issued on this chain that is in circulation.
+    async fn issued_supply(&self, asset: &Asset) -> Result<u64, ChainError> {
+        Err(ChainError::Other(format!("{} does not report the supply of {:?}", self.chain_name(), asset)))
+    }
+
+    /// Builds, signs and submits a transfer, returning its transaction ID.
+    async fn transfer(&self, transfer: &Transfer) -> Result<String, ChainError> {
+        let signed = self.build_transfer(transfer).await?;
+        self.submit_transfer(&signed).await
+    }
+
+    /// Returns the number of confirmations of a transaction.
+    async fn confirmations(&self, tx_id: &str) -> Result<u64, ChainError> {
+        Ok(self.transaction(tx_id).await?.confirmations)
+    }
+
+    /// Returns the status of a transaction; `Pending` while the chain does not know it.
+    async fn transaction_status(&self, tx_id: &str) -> Result<TransactionStatus, ChainError> {
+        match self.transaction(tx_id).await {
+            Ok(transaction) => Ok(TransactionStatus::of(&transaction)),
+            Err(e) if e.is_not_found() => Ok(TransactionStatus::Pending),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// New block heights of a chain, oldest first. Dropping the stream stops polling.
+pub struct BlockStream {
+    receiver: mpsc::Receiver<u64>,
+}
+
+impl BlockStream {
+    /// Waits for the next block; `None` once the chain task has stopped.
+    pub async fn next(&mut self) -> Option<u64> {
+        self.receiver.recv().await
+    }
+}
+
+/// Streams the heights of new blocks by polling `latest_block` every `interval`. Every height
+/// after the first one seen is reported, even when several blocks close between polls.
+pub fn subscribe_blocks(chain: Arc<dyn Chain>, interval: Duration) -> BlockStream {
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut last: Option<u64> = None;
+        loop {
+            ticker.tick().await;
+            if sender.is_closed() {
+                return;
+            }
+            let latest = match chain.latest_block().await {
+                Ok(latest) => latest,
+                Err(e) => {
+                    warn!("Failed to poll {} for new blocks: {}", chain.chain_name(), e);
+                    continue;
+                }
+            };
+            let first = last.map_or(latest, |last| last + 1);
+            for height in first..=latest {
+                if sender.send(height).await.is_err() {
+                    return;
+                }
+            }
+            last = Some(latest.max(last.unwrap_or(0)));
+        }
+    });
+    BlockStream { receiver }
+}
@@ -1,25 +1,45 @@
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cross_chain::horizon::{HorizonError, TransactionResultCode};
+    use crate::cross_chain::mock_server::{MockResponse, MockServer};
+    use crate::cross_chain::pi_integration::{PiIntegration, PiTransaction};
+    use crate::cross_chain::stellar_integration::StellarIntegration;
+    use crate::cross_chain::swap::{AtomicSwap, SwapStore};
+    use serde_json::json;
+    use std::sync::Mutex;
     use tokio;
 
     #[tokio::test]
     async fn test_stellar_integration() {
-        let stellar = StellarIntegration::new("https://api.stellar.org/transactions");
-        let transaction = StellarTransaction {
-            source_account: "GABC1234567890".to_string(),
-            destination_account: "GXYZ1234567890".to_string(),
-            amount: 10.0,
-        };
+        let horizon = MockServer::start().await;
+        horizon.respond("POST", "/transactions", MockResponse::json(200, json!({
+            "hash": "3389e9f0f1a65f19736cacf544c2e825313e8447f569233bb8db39aa607c8889",
+            "ledger": 48,
+            "successful": true,
+            "envelope_xdr": "AAAA+b/c=",
+            "result_xdr": "AAAAAAAAAGQAAAAAAAAAAQAAAAAAAAABAAAAAAAAAAA="
+        })));
+        let stellar = StellarIntegration::new(&horizon.url());
 
         // Attempt to send the transaction and assert the result
-        let result = stellar.send_transaction(transaction).await;
+        let result = stellar.send_transaction("AAAA+b/c=").await;
         assert!(result.is_ok(), "Failed to send Stellar transaction: {:?}", result.err());
+        let response = result.unwrap();
+        assert_eq!(response.ledger, 48);
+        assert!(response.successful);
+
+        // Envelopes are submitted form-encoded as `tx`
+        let submitted = &horizon.requests_to("POST", "/transactions")[0];
+        assert_eq!(submitted.body, "tx=AAAA%2Bb%2Fc%3D");
+        assert_eq!(submitted.headers["content-type"], "application/x-www-form-urlencoded");
     }
 
     #[tokio::test]
     async fn test_pi_integration() {
-        let pi = PiIntegration::new("https://api.pi.network/transactions");
+        let api = MockServer::start().await;
+        api.respond("POST", "/", MockResponse::json(200, json!("pi-tx-1")));
+        let pi = PiIntegration::new(&api.url());
         let transaction = PiTransaction {
             sender: "PiUser  1".to_string(),
             receiver: "PiUser  2".to_string(),
@@ -29,33 +49,2511 @@ mod tests {
         // Attempt to send the transaction and assert the result
         let result = pi.send_transaction(transaction).await;
         assert!(result.is_ok(), "Failed to send Pi transaction: {:?}", result.err());
+        assert_eq!(result.unwrap(), "pi-tx-1");
+
+        // The transaction is posted as JSON
+        let sent: serde_json::Value = serde_json::from_str(&api.requests_to("POST", "/")[0].body).unwrap();
+        assert_eq!(sent, json!({ "sender": "PiUser  1", "receiver": "PiUser  2", "amount": 5.0 }));
     }
 
     #[tokio::test]
     async fn test_stellar_integration_invalid_account() {
-        let stellar = StellarIntegration::new("https://api.stellar.org/transactions");
-        let transaction = StellarTransaction {
-            source_account: "INVALID_ACCOUNT".to_string(),
-            destination_account: "GXYZ1234567890".to_string(),
-            amount: 10.0,
-        };
+        let horizon = MockServer::start().await;
+        horizon.respond("POST", "/transactions", MockResponse::json(400, json!({
+            "type": "https://stellar.org/horizon-errors/transaction_failed",
+            "title": "Transaction Failed",
+            "status": 400,
+            "extras": {
+                "envelope_xdr": "AAAA",
+                "result_codes": { "transaction": "tx_no_source_account" },
+                "result_xdr": "AAAAAAAAAAD////4AAAAAA=="
+            }
+        })));
+        let stellar = StellarIntegration::new(&horizon.url());
 
         // Attempt to send the transaction with an invalid account
-        let result = stellar.send_transaction(transaction).await;
+        let result = stellar.send_transaction("AAAA").await;
         assert!(result.is_err(), "Expected error for invalid source account");
+        match result.unwrap_err() {
+            HorizonError::TransactionFailed { transaction, operations, result_xdr } => {
+                assert_eq!(transaction, TransactionResultCode::NoSourceAccount);
+                assert!(operations.is_empty());
+                assert_eq!(result_xdr.as_deref(), Some("AAAAAAAAAAD////4AAAAAA=="));
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
     }
 
     #[tokio::test]
     async fn test_pi_integration_insufficient_balance() {
-        let pi = PiIntegration::new("https://api.pi.network/transactions");
+        let api = MockServer::start().await;
+        api.respond("POST", "/", MockResponse::json(400, json!({ "error": "insufficient_balance" })));
+        let pi = PiIntegration::new(&api.url());
         let transaction = PiTransaction {
             sender: "PiUser  1".to_string(),
             receiver: "PiUser  2".to_string(),
-            amount: 1000.0, // Assuming this amount exceeds the balance
+            amount: 1000.0,
         };
 
         // Attempt to send the transaction with insufficient balance
         let result = pi.send_transaction(transaction).await;
         assert!(result.is_err(), "Expected error for insufficient balance");
     }
+
+    // Keeps swaps in memory for the swap and devnet tests
+    struct MemorySwapStore {
+        saved: Mutex<Vec<AtomicSwap>>,
+    }
+
+    impl SwapStore for MemorySwapStore {
+        fn load(&self) -> Result<Vec<AtomicSwap>, String> {
+            Ok(self.saved.lock().unwrap().clone())
+        }
+
+        fn save(&self, swaps: &[AtomicSwap]) -> Result<(), String> {
+            *self.saved.lock().unwrap() = swaps.to_vec();
+            Ok(())
+        }
+    }
+
+    mod swap_tests {
+        use crate::cross_chain::chain::{Asset, Chain, ChainTransaction, Payment, SignedTransfer, Transfer};
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::htlc::{hashlock_for, HtlcChain, HtlcLock, HtlcParams, HtlcStatus};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::pi_integration::PiIntegration;
+        use crate::cross_chain::swap::{AtomicSwap, JsonFileSwapStore, SwapCoordinator, SwapState, SwapStore, SwapTerms};
+        use super::MemorySwapStore;
+        use async_trait::async_trait;
+        use chrono::Utc;
+        use serde_json::json;
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        // In-memory HTLC chain that enforces hashlocks and expiries
+        struct MockHtlcChain {
+            name: String,
+            locks: Mutex<HashMap<String, HtlcLock>>,
+        }
+
+        impl MockHtlcChain {
+            fn new(name: &str) -> Arc<Self> {
+                Arc::new(Self { name: name.to_string(), locks: Mutex::new(HashMap::new()) })
+            }
+
+            fn lock_count(&self) -> usize {
+                self.locks.lock().unwrap().len()
+            }
+
+            fn status(&self, lock_id: &str) -> HtlcStatus {
+                self.locks.lock().unwrap()[lock_id].status
+            }
+        }
+
+        // Only the HTLC side is exercised by the swap coordinator
+        #[async_trait]
+        impl Chain for MockHtlcChain {
+            fn chain_name(&self) -> &str {
+                &self.name
+            }
+
+            fn decimals(&self) -> u32 {
+                7
+            }
+
+            async fn balance(&self, _account: &str, _asset: &Asset) -> Result<u64, ChainError> {
+                Ok(0)
+            }
+
+            async fn build_transfer(&self, _transfer: &Transfer) -> Result<SignedTransfer, ChainError> {
+                Err(ChainError::Other("transfers are not supported".to_string()))
+            }
+
+            async fn submit_transfer(&self, _signed: &SignedTransfer) -> Result<String, ChainError> {
+                Err(ChainError::Other("transfers are not supported".to_string()))
+            }
+
+            async fn transaction(&self, tx_id: &str) -> Result<ChainTransaction, ChainError> {
+                Err(ChainError::NotFound(tx_id.to_string()))
+            }
+
+            async fn payments(&self, _account: &str, _cursor: Option<&str>) -> Result<Vec<Payment>, ChainError> {
+                Ok(Vec::new())
+            }
+
+            async fn latest_block(&self) -> Result<u64, ChainError> {
+                Ok(0)
+            }
+        }
+
+        #[async_trait]
+        impl HtlcChain for MockHtlcChain {
+            async fn lock_funds(&self, params: &HtlcParams) -> Result<String, ChainError> {
+                let mut locks = self.locks.lock().unwrap();
+                let lock_id = format!("{}-lock-{}", self.name, locks.len() + 1);
+                locks.insert(lock_id.clone(), HtlcLock {
+                    lock_id: lock_id.clone(),
+                    sender: params.sender.clone(),
+                    receiver: params.receiver.clone(),
+                    amount: params.amount,
+                    hashlock: params.hashlock.clone(),
+                    expires_at: params.expires_at,
+                    status: HtlcStatus::Locked,
+                    preimage: None,
+                });
+                Ok(lock_id)
+            }
+
+            async fn get_lock(&self, lock_id: &str) -> Result<HtlcLock, ChainError> {
+                self.locks.lock().unwrap().get(lock_id).cloned().ok_or_else(|| ChainError::NotFound(lock_id.to_string()))
+            }
+
+            async fn find_lock(&self, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError> {
+                Ok(self.locks.lock().unwrap().values().find(|l| l.hashlock == hashlock).cloned())
+            }
+
+            async fn claim(&self, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError> {
+                let mut locks = self.locks.lock().unwrap();
+                let lock = locks.get_mut(lock_id).ok_or_else(|| ChainError::NotFound(lock_id.to_string()))?;
+                if hashlock_for(preimage) != lock.hashlock || Utc::now() >= lock.expires_at {
+                    return Err(ChainError::Other("claim rejected".to_string()));
+                }
+                lock.status = HtlcStatus::Claimed;
+                lock.preimage = Some(hex::encode(preimage));
+                Ok(format!("{}-claim", lock_id))
+            }
+
+            async fn refund(&self, lock_id: &str) -> Result<String, ChainError> {
+                let mut locks = self.locks.lock().unwrap();
+                let lock = locks.get_mut(lock_id).ok_or_else(|| ChainError::NotFound(lock_id.to_string()))?;
+                if Utc::now() < lock.expires_at {
+                    return Err(ChainError::Other("lock has not expired".to_string()));
+                }
+                lock.status = HtlcStatus::Refunded;
+                Ok(format!("{}-refund", lock_id))
+            }
+        }
+
+        fn terms(lock_duration: Duration) -> SwapTerms {
+            SwapTerms {
+                initiator_chain: "pi".to_string(),
+                counterparty_chain: "stellar".to_string(),
+                initiator_address: "pi-alice".to_string(),
+                initiator_receive_address: "GALICE".to_string(),
+                counterparty_address: "pi-bob".to_string(),
+                amount: 1_000,
+                counterparty_amount: 5_000,
+                lock_duration,
+                safety_margin: Duration::from_secs(30),
+            }
+        }
+
+        // The counterparty locks on Stellar with a shorter expiry than ours
+        async fn counterparty_locks(stellar: &MockHtlcChain, swap: &AtomicSwap, amount: u64) {
+            stellar.lock_funds(&HtlcParams {
+                sender: "GBOB".to_string(),
+                receiver: "GALICE".to_string(),
+                amount,
+                hashlock: swap.hashlock.clone(),
+                expires_at: swap.lock_expires_at - chrono::Duration::seconds(60),
+            }).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_swap_completes_when_counterparty_locks() {
+            let pi = MockHtlcChain::new("pi");
+            let stellar = MockHtlcChain::new("stellar");
+            let store = Arc::new(MemorySwapStore { saved: Mutex::new(Vec::new()) });
+            let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], store).unwrap();
+
+            let swap = coordinator.create_swap(terms(Duration::from_secs(3600))).unwrap();
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Locked);
+            // Nothing to do until the counterparty locks
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Locked);
+
+            counterparty_locks(&stellar, &swap, 5_000).await;
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::CounterpartyLocked);
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Completed);
+
+            let swap = coordinator.get_swap(&swap.id).unwrap();
+            assert_eq!(stellar.status(swap.counterparty_lock_id.as_ref().unwrap()), HtlcStatus::Claimed);
+            assert_eq!(pi.status(swap.lock_id.as_ref().unwrap()), HtlcStatus::Locked);
+        }
+
+        #[tokio::test]
+        async fn test_swap_ignores_insufficient_counterparty_lock() {
+            let pi = MockHtlcChain::new("pi");
+            let stellar = MockHtlcChain::new("stellar");
+            let store = Arc::new(MemorySwapStore { saved: Mutex::new(Vec::new()) });
+            let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], store).unwrap();
+
+            let swap = coordinator.create_swap(terms(Duration::from_secs(3600))).unwrap();
+            coordinator.step(&swap.id).await.unwrap();
+            counterparty_locks(&stellar, &swap, 4_999).await;
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Locked);
+        }
+
+        #[tokio::test]
+        async fn test_swap_refunds_after_timeout() {
+            let pi = MockHtlcChain::new("pi");
+            let stellar = MockHtlcChain::new("stellar");
+            let store = Arc::new(MemorySwapStore { saved: Mutex::new(Vec::new()) });
+            let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], store).unwrap();
+
+            let swap = coordinator.create_swap(terms(Duration::from_secs(1))).unwrap();
+            let state = coordinator.run_until_settled(&swap.id, Duration::from_millis(200)).await.unwrap();
+            assert_eq!(state, SwapState::Refunded);
+
+            let swap = coordinator.get_swap(&swap.id).unwrap();
+            assert_eq!(pi.status(swap.lock_id.as_ref().unwrap()), HtlcStatus::Refunded);
+            assert!(swap.refund_tx_id.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_swap_resumes_after_restart() {
+            let path = std::env::temp_dir().join(format!("swap_store_{}.json", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let pi = MockHtlcChain::new("pi");
+            let stellar = MockHtlcChain::new("stellar");
+
+            let swap = {
+                let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], Arc::new(JsonFileSwapStore::new(&path))).unwrap();
+                let swap = coordinator.create_swap(terms(Duration::from_secs(3600))).unwrap();
+                coordinator.step(&swap.id).await.unwrap();
+                swap
+            };
+
+            counterparty_locks(&stellar, &swap, 5_000).await;
+
+            // A fresh coordinator picks up the persisted swap, including its secret
+            let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], Arc::new(JsonFileSwapStore::new(&path))).unwrap();
+            assert_eq!(coordinator.resume_all().await, vec![(swap.id.clone(), SwapState::CounterpartyLocked)]);
+            assert_eq!(coordinator.resume_all().await, vec![(swap.id.clone(), SwapState::Completed)]);
+            assert_eq!(pi.lock_count(), 1);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_encrypted_store_hides_the_preimage() {
+            let path = std::env::temp_dir().join(format!("swap_store_encrypted_{}.json", std::process::id()));
+            let key = [3u8; 32];
+            let coordinator = SwapCoordinator::new(
+                vec![MockHtlcChain::new("pi"), MockHtlcChain::new("stellar")],
+                Arc::new(JsonFileSwapStore::encrypted(&path, &key)),
+            )
+            .unwrap();
+            let swap = coordinator.create_swap(terms(Duration::from_secs(3600))).unwrap();
+
+            let raw = std::fs::read(&path).unwrap();
+            assert!(!String::from_utf8_lossy(&raw).contains(&hex::encode(&swap.preimage)));
+            assert!(JsonFileSwapStore::new(&path).load().is_err());
+            assert!(JsonFileSwapStore::encrypted(&path, &[4u8; 32]).load().is_err());
+            assert_eq!(JsonFileSwapStore::encrypted(&path, &key).load().unwrap()[0].preimage, swap.preimage);
+
+            // Lock durations too large for a timestamp saturate instead of overflowing
+            let forever = coordinator.create_swap(terms(Duration::from_secs(u64::MAX))).unwrap();
+            assert_eq!(forever.lock_expires_at, chrono::DateTime::<chrono::Utc>::MAX_UTC);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_pi_integration_htlc_endpoints() {
+            let server = MockServer::start().await;
+            server.respond("POST", "/htlc", MockResponse::json(200, json!({ "lock_id": "pi-lock-7" })));
+            server.respond("POST", "/htlc/pi-lock-7/claim", MockResponse::json(200, json!({ "tx_id": "pi-tx-9" })));
+            server.respond("GET", "/htlc", MockResponse::json(404, json!({ "error": "not found" })));
+
+            let pi = PiIntegration::new(&server.url());
+            let preimage = b"secret".to_vec();
+            let lock_id = pi.lock_funds(&HtlcParams {
+                sender: "pi-alice".to_string(),
+                receiver: "pi-bob".to_string(),
+                amount: 1_000,
+                hashlock: hashlock_for(&preimage),
+                expires_at: Utc::now(),
+            }).await.unwrap();
+            assert_eq!(lock_id, "pi-lock-7");
+            assert_eq!(pi.claim(&lock_id, &preimage).await.unwrap(), "pi-tx-9");
+            assert!(pi.find_lock(&hashlock_for(&preimage)).await.unwrap().is_none());
+
+            let claim = &server.requests_to("POST", "/htlc/pi-lock-7/claim")[0];
+            assert!(claim.body.contains(&hex::encode(&preimage)));
+        }
+    }
+
+    mod horizon_tests {
+        use crate::cross_chain::chain::{Asset, Chain};
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::fees::{bump_base_fee, estimate_base_fee, FeeConfig, FeePercentile};
+        use crate::cross_chain::horizon::{format_amount, parse_amount, parse_problem, FeeStats, HorizonError, OperationResultCode, TransactionResultCode};
+        use crate::cross_chain::htlc::{hashlock_for, HtlcChain, HtlcParams, HtlcStatus};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::stellar_htlc::{ESCROW_CONTEXT, ESCROW_RESERVE};
+        use crate::cross_chain::stellar_integration::{EnvelopeBuilder, StellarAsset, StellarIntegration, StellarPaymentRequest};
+        use crate::cross_chain::stellar_signer::KeypairEnvelopeBuilder;
+        use crate::fixtures::keypair;
+        use crate::models::stellar::transaction::{
+            AccountId, FeeBumpTransactionEnvelope, Memo, Network, OperationBody, SignerKey, TransactionEnvelope,
+        };
+        use crate::models::stellar::xdr::{XdrDecode, XdrEncode};
+        use chrono::{TimeZone, Utc};
+        use ed25519_dalek::PublicKey;
+        use serde_json::json;
+        use std::sync::Arc;
+
+        const ACCOUNT: &str = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN7";
+        // A muxed address: account [2; 32] with ID 7
+        const MUXED: &str = "MABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAAAAAAAAAAAA7FA2";
+        // Account [2; 32]
+        const OTHER: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+
+        struct RecordingBuilder;
+
+        impl EnvelopeBuilder for RecordingBuilder {
+            fn build_payment(&self, payment: &StellarPaymentRequest, sequence: i64, base_fee: u32) -> Result<String, String> {
+                Ok(format!("{}-{}-{}", payment.amount, sequence, base_fee))
+            }
+        }
+
+        fn fee_stats() -> serde_json::Value {
+            json!({
+                "last_ledger": "22606298",
+                "last_ledger_base_fee": "100",
+                "ledger_capacity_usage": "0.97",
+                "fee_charged": { "min": "100", "mode": "150", "p10": "100", "p50": "150", "p90": "400", "p99": "1000", "max": "5000" },
+                "max_fee": { "min": "100", "mode": "200", "p10": "100", "p50": "250", "p90": "2000", "p99": "10000", "max": "100000" }
+            })
+        }
+
+        #[tokio::test]
+        async fn test_load_account_and_fee_stats() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", ACCOUNT), MockResponse::json(200, json!({
+                "id": ACCOUNT,
+                "account_id": ACCOUNT,
+                "sequence": "120192344791990273",
+                "subentry_count": 2,
+                "balances": [
+                    { "balance": "9999.9999800", "asset_type": "native" },
+                    { "balance": "12.5000000", "asset_type": "credit_alphanum4", "asset_code": "USD", "asset_issuer": ACCOUNT }
+                ]
+            })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            let stellar = StellarIntegration::new(&horizon.url());
+
+            let account = stellar.load_account(ACCOUNT).await.unwrap();
+            assert_eq!(account.sequence, 120192344791990273);
+            assert_eq!(account.balances.len(), 2);
+            assert_eq!(parse_amount(&account.balances[1].balance).unwrap(), 125_000_000);
+
+            let fees = stellar.fee_stats().await.unwrap();
+            assert_eq!(fees.last_ledger_base_fee, 100);
+            assert_eq!(fees.fee_charged.mode, 150);
+            assert_eq!(fees.max_fee.p99, 10000);
+        }
+
+        #[tokio::test]
+        async fn test_missing_account_is_not_found() {
+            let horizon = MockServer::start().await;
+            let stellar = StellarIntegration::new(&horizon.url());
+            assert!(matches!(stellar.load_account(ACCOUNT).await, Err(HorizonError::NotFound(_))));
+            // An account that does not exist yet cannot hold anything
+            assert_eq!(stellar.balance(ACCOUNT, &Asset::Native).await.unwrap(), 0);
+        }
+
+        #[tokio::test]
+        async fn test_path_payments_are_listed_with_the_received_amount() {
+            let horizon = MockServer::start().await;
+            let usd = json!({ "asset_type": "credit_alphanum4", "asset_code": "USD", "asset_issuer": OTHER });
+            let record = |id: &str, kind: &str, amount: &str| {
+                let mut record = json!({
+                    "id": id, "paging_token": id, "transaction_hash": format!("t{}", id), "type": kind,
+                    "from": OTHER, "to": ACCOUNT, "amount": amount,
+                    "source_asset_type": "native", "source_amount": "9.0000000"
+                });
+                record.as_object_mut().unwrap().extend(usd.as_object().unwrap().clone());
+                record
+            };
+            horizon.respond("GET", &format!("/accounts/{}/payments", ACCOUNT), MockResponse::json(200, json!({ "_embedded": { "records": [
+                record("1", "path_payment_strict_receive", "2.0000000"),
+                record("2", "path_payment_strict_send", "3.0000000"),
+                { "id": "3", "paging_token": "3", "transaction_hash": "t3", "type": "create_account", "funder": OTHER, "account": ACCOUNT }
+            ] } })));
+            let stellar = StellarIntegration::new(&horizon.url());
+
+            let payments = stellar.list_payments(ACCOUNT, None).await.unwrap();
+            let usd = StellarAsset::Credit { code: "USD".to_string(), issuer: OTHER.to_string() };
+            let received: Vec<_> = payments.iter().map(|p| (p.id.as_str(), p.asset.clone(), p.amount)).collect();
+            assert_eq!(received, vec![("1", usd.clone(), 20_000_000), ("2", usd, 30_000_000)]);
+        }
+
+        #[tokio::test]
+        async fn test_send_payment_builds_envelope_from_account_and_fees() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", ACCOUNT), MockResponse::json(200, json!({ "id": ACCOUNT, "sequence": "41" })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "abc", "ledger": 7, "successful": true })));
+            let payment = StellarPaymentRequest {
+                source_account: ACCOUNT.to_string(),
+                destination_account: MUXED.to_string(),
+                asset: StellarAsset::Native,
+                amount: 5_000_000,
+                memo: None,
+            };
+
+            let unsigned = StellarIntegration::new(&horizon.url());
+            assert!(matches!(unsigned.send_payment(&payment).await, Err(ChainError::Other(_))));
+
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(RecordingBuilder));
+            assert_eq!(stellar.send_payment(&payment).await.unwrap(), "abc");
+            assert_eq!(horizon.requests_to("POST", "/transactions")[0].body, "tx=5000000-42-150");
+        }
+
+        fn insufficient_fee() -> MockResponse {
+            MockResponse::json(400, json!({
+                "type": "https://stellar.org/horizon-errors/transaction_failed",
+                "title": "Transaction Failed",
+                "status": 400,
+                "extras": { "result_codes": { "transaction": "tx_insufficient_fee" } }
+            }))
+        }
+
+        // The base64 envelope of a form-encoded submission
+        fn submitted_envelope(body: &str) -> String {
+            body.strip_prefix("tx=").unwrap().replace("%2B", "+").replace("%2F", "/").replace("%3D", "=")
+        }
+
+        #[test]
+        fn test_fee_estimates_follow_the_configured_percentile() {
+            let stats: FeeStats = serde_json::from_value(fee_stats()).unwrap();
+            let config = FeeConfig::default();
+            assert_eq!(estimate_base_fee(&stats, &config, 1).unwrap(), 150);
+
+            let p90 = FeeConfig { percentile: FeePercentile::P90, ..FeeConfig::default() };
+            assert_eq!(FeePercentile::P10.of(&stats.fee_charged), 100);
+            assert_eq!(estimate_base_fee(&stats, &p90, 1).unwrap(), 400);
+            // Capped so two operations stay within the maximum fee
+            let capped = FeeConfig { max_fee: 600, ..p90 };
+            assert_eq!(estimate_base_fee(&stats, &capped, 2).unwrap(), 300);
+            // Never below the network's base fee
+            let min = FeeConfig { percentile: FeePercentile::Min, max_fee: 150, ..FeeConfig::default() };
+            assert!(matches!(estimate_base_fee(&stats, &min, 2), Err(ChainError::FeeLimit { required: 200, max: 150 })));
+
+            assert_eq!(bump_base_fee(150, 200, &config, 1), Some(1_500));
+            assert_eq!(bump_base_fee(150, 2_000, &config, 1), Some(2_000));
+            assert_eq!(bump_base_fee(150, 200, &FeeConfig { max_fee: 1_000, ..FeeConfig::default() }, 1), Some(500));
+            assert_eq!(bump_base_fee(150, 200, &FeeConfig { max_fee: 300, ..FeeConfig::default() }, 1), None);
+        }
+
+        fn signing_builder() -> KeypairEnvelopeBuilder {
+            builder_for(9)
+        }
+
+        fn builder_for(seed: u8) -> KeypairEnvelopeBuilder {
+            KeypairEnvelopeBuilder::new(Network::testnet(), keypair(seed))
+        }
+
+        #[tokio::test]
+        async fn test_stuck_payments_are_fee_bumped() {
+            let builder = signing_builder();
+            let source = builder.account_id().to_string();
+
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", source), MockResponse::json(200, json!({ "id": source, "sequence": "41" })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            horizon.respond("POST", "/transactions", insufficient_fee());
+            horizon.respond("POST", "/transactions", insufficient_fee());
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "bumped", "ledger": 7, "successful": true })));
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(builder));
+
+            let payment = StellarPaymentRequest {
+                source_account: source.clone(),
+                destination_account: MUXED.to_string(),
+                asset: StellarAsset::Native,
+                amount: 5_000_000,
+                memo: None,
+            };
+            assert_eq!(stellar.send_payment(&payment).await.unwrap(), "bumped");
+
+            let submissions = horizon.requests_to("POST", "/transactions");
+            assert_eq!(submissions.len(), 3);
+            let original = TransactionEnvelope::from_xdr_base64(&submitted_envelope(&submissions[0].body)).unwrap();
+            assert_eq!(original.tx.fee, 150);
+            // Each bump offers ten times the last rate for the payment plus the bump itself
+            for (submission, fee) in submissions[1..].iter().zip([3_000, 30_000]) {
+                let bump = FeeBumpTransactionEnvelope::from_xdr_base64(&submitted_envelope(&submission.body)).unwrap();
+                assert_eq!(bump.tx.fee, fee);
+                assert_eq!(bump.tx.fee_source.to_strkey(), source);
+                assert_eq!(bump.tx.inner, original);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_fee_bumps_stop_at_the_maximum_fee() {
+            let builder = signing_builder();
+            let source = builder.account_id().to_string();
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", source), MockResponse::json(200, json!({ "id": source, "sequence": "41" })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            horizon.respond("POST", "/transactions", insufficient_fee());
+            // Room for the payment at the recent mode, but not for bumping it
+            let stellar = StellarIntegration::new(&horizon.url())
+                .with_envelope_builder(Arc::new(builder))
+                .with_fee_config(FeeConfig { max_fee: 300, ..FeeConfig::default() });
+
+            let payment = StellarPaymentRequest {
+                source_account: source.clone(),
+                destination_account: MUXED.to_string(),
+                asset: StellarAsset::Native,
+                amount: 5_000_000,
+                memo: None,
+            };
+            let error = stellar.send_payment(&payment).await.unwrap_err();
+            assert!(matches!(error, ChainError::Horizon(HorizonError::TransactionFailed { transaction: TransactionResultCode::InsufficientFee, .. })));
+            assert_eq!(horizon.requests_to("POST", "/transactions").len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_timed_out_payment_applied_meanwhile_is_not_bumped() {
+            let builder = signing_builder();
+            let source = builder.account_id().to_string();
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", source), MockResponse::json(200, json!({ "id": source, "sequence": "41" })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            let timeout = json!({ "type": "https://stellar.org/horizon-errors/timeout", "title": "Timeout", "status": 504 });
+            horizon.respond("POST", "/transactions", MockResponse::json(504, timeout));
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(builder));
+
+            let envelope = stellar.build_payment(&StellarPaymentRequest {
+                source_account: source.clone(),
+                destination_account: MUXED.to_string(),
+                asset: StellarAsset::Native,
+                amount: 5_000_000,
+                memo: None,
+            }).await.unwrap();
+            let hash = hex::encode(TransactionEnvelope::from_xdr_base64(&envelope).unwrap().tx.hash(&Network::testnet()));
+            horizon.respond("GET", &format!("/transactions/{}", hash), MockResponse::json(200, json!({ "hash": hash, "ledger": 8, "successful": true })));
+
+            let applied = stellar.submit_with_fee_bumps(&envelope).await.unwrap();
+            assert_eq!((applied.hash.as_str(), applied.ledger), (hash.as_str(), 8));
+            // Horizon only ever saw the original envelope, never a fee bump
+            let submissions = horizon.requests_to("POST", "/transactions");
+            assert!(submissions.iter().all(|submission| submitted_envelope(&submission.body) == envelope));
+            assert_eq!(horizon.requests_to("GET", "/fee_stats").len(), 1);
+        }
+
+        fn record(envelope_xdr: &str) -> serde_json::Value {
+            json!({ "hash": "h", "ledger": 1, "successful": true, "envelope_xdr": envelope_xdr })
+        }
+
+        // Horizon as seen after the escrow has been created and locked
+        async fn escrow_horizon(escrow: &str, sequence: i64, history: &[String]) -> MockServer {
+            let horizon = MockServer::start().await;
+            let records: Vec<_> = history.iter().map(|envelope| record(envelope)).collect();
+            let account = json!({ "id": escrow, "sequence": sequence.to_string() });
+            horizon.respond("GET", &format!("/accounts/{}/transactions", escrow), MockResponse::json(200, json!({ "_embedded": { "records": records } })));
+            horizon.respond("GET", &format!("/accounts/{}", escrow), MockResponse::json(200, account.clone()));
+            horizon.respond("GET", "/accounts", MockResponse::json(200, json!({ "_embedded": { "records": [account] } })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "settled", "ledger": 9, "successful": true })));
+            horizon
+        }
+
+        #[tokio::test]
+        async fn test_stellar_htlc_escrow_lock_claim_and_refund() {
+            let sender = signing_builder();
+            let receiver = builder_for(8);
+            let preimage = [7u8; 32];
+            let hashlock = hashlock_for(&preimage);
+            let escrow = sender.derive_keypair(&[ESCROW_CONTEXT, &hashlock[..]].concat()).unwrap();
+            let escrow_id = AccountId::from(&escrow.public).to_strkey();
+            let created_at: i64 = 1 << 32;
+            let params = HtlcParams {
+                sender: sender.account_id().to_string(),
+                receiver: receiver.account_id().to_string(),
+                amount: 10_000_000,
+                hashlock: hashlock.clone(),
+                expires_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            };
+
+            // Locking creates the escrow from the sender, then hands it to the lock's signers
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", escrow_id), MockResponse::json(404, json!({ "status": 404 })));
+            horizon.respond("GET", &format!("/accounts/{}", escrow_id), MockResponse::json(200, json!({ "id": escrow_id, "sequence": created_at.to_string() })));
+            horizon.respond("GET", &format!("/accounts/{}", params.sender), MockResponse::json(200, json!({ "id": params.sender, "sequence": "41" })));
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, fee_stats()));
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "locked", "ledger": 7, "successful": true })));
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(signing_builder()));
+            assert_eq!(stellar.lock_funds(&params).await.unwrap(), escrow_id);
+
+            let history: Vec<String> = horizon.requests_to("POST", "/transactions").iter().map(|r| submitted_envelope(&r.body)).collect();
+            assert_eq!(history.len(), 2);
+            let creation = TransactionEnvelope::from_xdr_base64(&history[0]).unwrap();
+            assert_eq!(creation.tx.source_account.to_strkey(), params.sender);
+            assert_eq!(creation.tx.seq_num, 42);
+            assert_eq!(creation.tx.memo, Memo::Id(1_700_000_000));
+            assert!(matches!(creation.tx.operations[0].body, OperationBody::CreateAccount { starting_balance, .. } if starting_balance == 10_000_000 + ESCROW_RESERVE as i64));
+            let setup = TransactionEnvelope::from_xdr_base64(&history[1]).unwrap();
+            assert_eq!(setup.tx.seq_num, created_at + 1);
+            assert!(setup.is_signed_by(&Network::testnet(), &escrow.public));
+            let signers: Vec<_> = setup.tx.operations.iter().filter_map(|op| match &op.body {
+                OperationBody::SetOptions { signer: Some(signer), .. } => Some(*signer),
+                _ => None,
+            }).collect();
+            let refund_hash = match signers[0].key {
+                SignerKey::PreAuthTx(hash) if signers[0].weight == 2 => hash,
+                other => panic!("expected a pre-authorized refund, found {:?}", other),
+            };
+            assert_eq!(signers[1].key, SignerKey::from(receiver.account_id()));
+            assert_eq!(signers[2].key, SignerKey::HashX(hashlock.clone().try_into().unwrap()));
+            assert!(matches!(setup.tx.operations[3].body, OperationBody::SetOptions { master_weight: Some(0), high_threshold: Some(2), .. }));
+
+            // The lock is found by its hash-x signer and read back from the escrow's transactions
+            let horizon = escrow_horizon(&escrow_id, created_at + 1, &history).await;
+            let stellar = StellarIntegration::new(&horizon.url());
+            let lock = stellar.find_lock(&hashlock).await.unwrap().unwrap();
+            assert_eq!((lock.lock_id.as_str(), lock.sender.as_str(), lock.receiver.as_str()), (escrow_id.as_str(), params.sender.as_str(), params.receiver.as_str()));
+            assert_eq!((lock.amount, lock.expires_at, lock.status), (params.amount, params.expires_at, HtlcStatus::Locked));
+
+            // Anyone can submit the refund after the expiry: it is the pre-authorized transaction
+            assert_eq!(stellar.refund(&escrow_id).await.unwrap(), "settled");
+            let refund = TransactionEnvelope::from_xdr_base64(&submitted_envelope(&horizon.requests_to("POST", "/transactions")[0].body)).unwrap();
+            assert!(refund.signatures.is_empty());
+            assert_eq!(refund.tx.hash(&Network::testnet()), refund_hash);
+
+            // The receiver claims with its signature and the preimage, valid only until the expiry
+            let horizon = escrow_horizon(&escrow_id, created_at + 1, &history).await;
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(builder_for(8)));
+            assert_eq!(stellar.claim(&escrow_id, &preimage).await.unwrap(), "settled");
+            let claim_xdr = submitted_envelope(&horizon.requests_to("POST", "/transactions")[0].body);
+            let claim = TransactionEnvelope::from_xdr_base64(&claim_xdr).unwrap();
+            assert_eq!(claim.tx.seq_num, refund.tx.seq_num);
+            assert_eq!(claim.tx.time_bounds.unwrap().max_time, 1_700_000_000);
+            assert!(claim.is_signed_by(&Network::testnet(), &PublicKey::from_bytes(&receiver.account_id().0).unwrap()));
+            assert_eq!(claim.hash_x_preimage(&hashlock.clone().try_into().unwrap()), Some(&preimage[..]));
+
+            // Settled escrows report how they ended
+            let claimed = escrow_horizon(&escrow_id, created_at + 2, &[history.clone(), vec![claim_xdr]].concat()).await;
+            let lock = StellarIntegration::new(&claimed.url()).get_lock(&escrow_id).await.unwrap();
+            assert_eq!((lock.status, lock.preimage), (HtlcStatus::Claimed, Some(hex::encode(preimage))));
+            let refunded = escrow_horizon(&escrow_id, created_at + 2, &[history.clone(), vec![refund.to_xdr_base64()]].concat()).await;
+            let lock = StellarIntegration::new(&refunded.url()).get_lock(&escrow_id).await.unwrap();
+            assert_eq!((lock.status, lock.preimage), (HtlcStatus::Refunded, None));
+            assert!(StellarIntegration::new(&refunded.url()).refund(&escrow_id).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_invalid_addresses_are_rejected_before_any_request() {
+            let horizon = MockServer::start().await;
+            let stellar = StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(RecordingBuilder));
+
+            // Last character changed, so the checksum no longer matches
+            let corrupted = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN6";
+            assert!(matches!(stellar.load_account(corrupted).await, Err(HorizonError::InvalidAddress { .. })));
+            assert!(matches!(stellar.list_payments(MUXED, None).await, Err(HorizonError::InvalidAddress { .. })));
+
+            let mut payment = StellarPaymentRequest {
+                source_account: ACCOUNT.to_string(),
+                destination_account: "SAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQC5MY".to_string(),
+                asset: StellarAsset::Native,
+                amount: 1,
+                memo: None,
+            };
+            match stellar.send_payment(&payment).await {
+                Err(ChainError::Horizon(HorizonError::InvalidAddress { reason, .. })) => {
+                    assert_eq!(reason, "S keys cannot receive payments");
+                }
+                other => panic!("Unexpected result: {:?}", other),
+            }
+            payment.destination_account = ACCOUNT.to_string();
+            payment.asset = StellarAsset::Credit { code: "USD".to_string(), issuer: "GISSUER".to_string() };
+            assert!(matches!(stellar.send_payment(&payment).await, Err(ChainError::Horizon(HorizonError::InvalidAddress { .. }))));
+
+            assert!(horizon.requests().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_operation_failures_are_typed() {
+            let horizon = MockServer::start().await;
+            horizon.respond("POST", "/transactions", MockResponse::json(400, json!({
+                "type": "https://stellar.org/horizon-errors/transaction_failed",
+                "title": "Transaction Failed",
+                "status": 400,
+                "extras": { "result_codes": { "transaction": "tx_failed", "operations": ["op_success", "op_underfunded"] } }
+            })));
+            let stellar = StellarIntegration::new(&horizon.url());
+
+            match stellar.send_transaction("AAAA").await {
+                Err(HorizonError::TransactionFailed { transaction, operations, .. }) => {
+                    assert_eq!(transaction, TransactionResultCode::Failed);
+                    assert_eq!(operations, vec![OperationResultCode::Success, OperationResultCode::Underfunded]);
+                }
+                other => panic!("Unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_parse_problem() {
+            let rate_limited = parse_problem(429, &json!({
+                "type": "https://stellar.org/horizon-errors/rate_limit_exceeded",
+                "title": "Rate Limit Exceeded",
+                "status": 429,
+                "detail": "The rate limit for the requesting IP address is over its alloted limit."
+            }).to_string());
+            assert!(matches!(rate_limited, HorizonError::Problem { status: 429, ref kind, .. } if kind == "rate_limit_exceeded"));
+
+            let timeout = parse_problem(504, &json!({ "type": "https://stellar.org/horizon-errors/timeout", "title": "Timeout" }).to_string());
+            assert!(timeout.is_timeout());
+
+            let unknown = parse_problem(502, "Bad Gateway");
+            assert!(matches!(unknown, HorizonError::Problem { status: 502, ref detail, .. } if detail == "Bad Gateway"));
+
+            let bad_seq = parse_problem(400, &json!({ "extras": { "result_codes": { "transaction": "tx_bad_seq" } } }).to_string());
+            assert!(matches!(bad_seq, HorizonError::TransactionFailed { transaction: TransactionResultCode::BadSequence, .. }));
+        }
+
+        #[tokio::test]
+        async fn test_issued_supply_sums_every_holding() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", "/assets", MockResponse::json(200, json!({ "_embedded": { "records": [{
+                "asset_type": "credit_alphanum4",
+                "asset_code": "PI",
+                "asset_issuer": ACCOUNT,
+                "balances": { "authorized": "100.0000000", "authorized_to_maintain_liabilities": "2.5000000", "unauthorized": "0.0000000" },
+                "claimable_balances_amount": "1.0000000",
+                "liquidity_pools_amount": "10.0000000",
+                "contracts_amount": "0.0000000"
+            }] } })));
+            horizon.respond("GET", "/assets", MockResponse::json(200, json!({ "_embedded": { "records": [] } })));
+
+            let stellar = StellarIntegration::new(&horizon.url());
+            let wrapped = Asset::Issued { code: "PI".to_string(), issuer: ACCOUNT.to_string() };
+            assert_eq!(stellar.issued_supply(&wrapped).await.unwrap(), 1_135_000_000);
+            assert!(horizon.requests_to("GET", "/assets")[0].path.contains("asset_code=PI"));
+            // No trustline has ever been opened
+            assert_eq!(stellar.issued_supply(&wrapped).await.unwrap(), 0);
+            assert!(matches!(stellar.issued_supply(&Asset::Native).await, Err(ChainError::Other(_))));
+        }
+
+        #[test]
+        fn test_amounts() {
+            assert_eq!(parse_amount("10.5000000").unwrap(), 105_000_000);
+            assert_eq!(parse_amount("0.0000001").unwrap(), 1);
+            assert_eq!(parse_amount("3").unwrap(), 30_000_000);
+            assert!(parse_amount("1.00000001").is_err());
+            assert!(parse_amount("-1.0").is_err());
+            assert!(parse_amount("abc").is_err());
+            assert_eq!(format_amount(105_000_000), "10.5000000");
+            assert_eq!(format_amount(1), "0.0000001");
+        }
+    }
+
+    mod pi_client_tests {
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::pi_client::{
+            PiClient, PiNetworkConfig, PiPaymentCreation, PiTransactionState, PiTransferRequest,
+        };
+        use serde_json::{json, Value};
+
+        fn transaction(id: &str) -> Value {
+            json!({
+                "transaction_id": id,
+                "source_account_id": "pi-alice",
+                "destination_account_id": "pi-bob",
+                "amount": 100,
+                "status": "confirmed",
+                "created_at": "2024-01-01T00:00:00Z"
+            })
+        }
+
+        fn payment(approved: bool, completed: bool, txid: Option<&str>) -> Value {
+            json!({
+                "identifier": "pay-1",
+                "recipient": "pi-bob",
+                "amount": 314_000_000,
+                "memo": "order 7",
+                "metadata": { "order": 7 },
+                "status": {
+                    "developer_approved": approved,
+                    "transaction_verified": txid.is_some(),
+                    "developer_completed": completed,
+                    "cancelled": false
+                },
+                "transaction": txid.map(|txid| json!({ "txid": txid, "verified": true }))
+            })
+        }
+
+        #[test]
+        fn test_network_configs_come_from_the_registry() {
+            let testnet = PiNetworkConfig::testnet().unwrap();
+            assert_eq!(testnet.name, "testnet");
+            assert_eq!(testnet.api_url, "https://api.testnet.pi.network");
+            assert_eq!(testnet.chain_id, "pi-testnet-1");
+            assert_eq!(PiNetworkConfig::mainnet().unwrap().chain_id, "pi-mainnet-1");
+        }
+
+        #[tokio::test]
+        async fn test_account_balance_and_transaction() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/pi/account", MockResponse::json(200, json!({
+                "account_info": { "account_id": "pi-alice", "balance": 500_000_000, "created_at": "2023-06-28T12:00:00Z" }
+            })));
+            server.respond("GET", "/pi/balance", MockResponse::json(200, json!({ "balance": 500_000_000 })));
+            server.respond("POST", "/pi/transaction", MockResponse::json(200, json!({ "transaction_id": "pi-tx-1" })));
+            let client = PiClient::new(&format!("{}/", server.url()));
+
+            let account = client.get_account("pi-alice").await.unwrap();
+            assert_eq!(account.account_id, "pi-alice");
+            assert_eq!(account.balance, 500_000_000);
+            assert!(account.created_at.is_some());
+            assert_eq!(client.get_balance("pi-alice").await.unwrap(), 500_000_000);
+            assert!(server.requests_to("GET", "/pi/balance")[0].path.ends_with("?account_id=pi-alice"));
+
+            let transfer = PiTransferRequest {
+                source_account_id: "pi-alice".to_string(),
+                destination_account_id: "pi-bob".to_string(),
+                amount: 100,
+                memo: None,
+                idempotency_key: Some("transfer-1".to_string()),
+            };
+            assert_eq!(client.create_transaction(&transfer).await.unwrap(), "pi-tx-1");
+            let created = &server.requests_to("POST", "/pi/transaction")[0];
+            assert_eq!(created.headers["idempotency-key"], "transfer-1");
+            let body: Value = serde_json::from_str(&created.body).unwrap();
+            assert_eq!(body, json!({ "source_account_id": "pi-alice", "destination_account_id": "pi-bob", "amount": 100 }));
+        }
+
+        #[tokio::test]
+        async fn test_missing_account_and_malformed_balance() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/pi/balance", MockResponse::json(200, json!({ "balance": "lots" })));
+            let client = PiClient::new(&server.url());
+
+            assert!(matches!(client.get_account("pi-nobody").await, Err(ChainError::NotFound(_))));
+            assert!(matches!(client.get_balance("pi-alice").await, Err(ChainError::InvalidResponse(_))));
+        }
+
+        #[tokio::test]
+        async fn test_transactions_are_paginated() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/pi/transactions", MockResponse::json(200, json!({
+                "records": [transaction("pi-tx-3"), transaction("pi-tx-2")],
+                "next_cursor": "c2"
+            })));
+            server.respond("GET", "/pi/transactions", MockResponse::json(200, json!({
+                "records": [transaction("pi-tx-1")],
+                "next_cursor": null
+            })));
+            let client = PiClient::new(&server.url());
+
+            let transactions = client.all_transactions("pi-alice").await.unwrap();
+            let ids: Vec<&str> = transactions.iter().map(|t| t.transaction_id.as_str()).collect();
+            assert_eq!(ids, vec!["pi-tx-3", "pi-tx-2", "pi-tx-1"]);
+            assert!(transactions.iter().all(|t| t.status == PiTransactionState::Confirmed));
+
+            let pages = server.requests_to("GET", "/pi/transactions");
+            assert_eq!(pages.len(), 2);
+            assert!(!pages[0].path.contains("cursor="));
+            assert!(pages[1].path.contains("cursor=c2"));
+        }
+
+        #[tokio::test]
+        async fn test_payment_approval_and_completion() {
+            let server = MockServer::start().await;
+            server.respond("POST", "/pi/payments", MockResponse::json(200, payment(false, false, None)));
+            server.respond("POST", "/pi/payments/pay-1/approve", MockResponse::json(200, payment(true, false, None)));
+            server.respond("GET", "/pi/payments/pay-1", MockResponse::json(200, payment(true, false, Some("pi-tx-5"))));
+            server.respond("POST", "/pi/payments/pay-1/complete", MockResponse::json(200, payment(true, true, Some("pi-tx-5"))));
+            let client = PiClient::for_network(&PiNetworkConfig { api_url: server.url(), ..PiNetworkConfig::testnet().unwrap() });
+
+            let created = client.create_payment(&PiPaymentCreation {
+                recipient: "pi-bob".to_string(),
+                amount: 314_000_000,
+                memo: "order 7".to_string(),
+                metadata: json!({ "order": 7 }),
+            }).await.unwrap();
+            assert_eq!(created.identifier, "pay-1");
+            assert!(!created.status.developer_approved);
+
+            assert!(client.approve_payment("pay-1").await.unwrap().status.developer_approved);
+            let submitted = client.get_payment("pay-1").await.unwrap();
+            let txid = submitted.transaction.unwrap().txid;
+            let completed = client.complete_payment("pay-1", &txid).await.unwrap();
+            assert!(completed.status.developer_completed);
+
+            let completion = &server.requests_to("POST", "/pi/payments/pay-1/complete")[0];
+            assert_eq!(serde_json::from_str::<Value>(&completion.body).unwrap(), json!({ "txid": "pi-tx-5" }));
+        }
+    }
+
+    mod transport_tests {
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::horizon::HorizonError;
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::pi_integration::{PiIntegration, PiPaymentRequest};
+        use crate::cross_chain::stellar_integration::StellarIntegration;
+        use crate::cross_chain::transport::{CircuitState, Transport, TransportConfig, TransportError};
+        use serde_json::json;
+        use std::time::Duration;
+
+        fn config() -> TransportConfig {
+            TransportConfig {
+                timeout: Duration::from_millis(200),
+                connect_timeout: Duration::from_millis(200),
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(20),
+                failure_threshold: 3,
+                reset_timeout: Duration::from_millis(100),
+            }
+        }
+
+        fn unavailable() -> MockResponse {
+            MockResponse::json(503, json!({ "error": "unavailable" }))
+        }
+
+        fn endpoint(server: &MockServer) -> String {
+            server.url().trim_start_matches("http://").to_string()
+        }
+
+        fn payment() -> PiPaymentRequest {
+            PiPaymentRequest {
+                from: "pi-custody".to_string(),
+                to: "pi-bob".to_string(),
+                amount: 10,
+                memo: None,
+                idempotency_key: None,
+            }
+        }
+
+        #[tokio::test]
+        async fn test_reads_are_retried_on_transient_failures() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/transactions/pi-tx-1", unavailable());
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(200, json!({ "confirmations": 1 })).with_delay(Duration::from_millis(500)));
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(200, json!({ "confirmations": 4 })));
+            let pi = PiIntegration::new(&server.url()).with_transport(Transport::new(config()));
+
+            // 503, then a response slower than the timeout, then success
+            assert_eq!(pi.get_confirmations("pi-tx-1").await.unwrap(), 4);
+            assert_eq!(server.requests_to("GET", "/transactions/pi-tx-1").len(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_retries_give_up_with_the_last_response() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/transactions/pi-tx-1", unavailable());
+            let pi = PiIntegration::new(&server.url()).with_transport(Transport::new(config()));
+
+            assert!(matches!(pi.get_confirmations("pi-tx-1").await, Err(ChainError::Api { status: 503, .. })));
+            assert_eq!(server.requests_to("GET", "/transactions/pi-tx-1").len(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_submissions_reuse_their_idempotency_key() {
+            let server = MockServer::start().await;
+            server.respond("POST", "/payments", MockResponse::json(500, json!({ "error": "internal" })));
+            server.respond("POST", "/payments", unavailable());
+            server.respond("POST", "/payments", MockResponse::json(200, json!({ "tx_id": "pi-tx-9" })));
+            let pi = PiIntegration::new(&server.url()).with_transport(Transport::new(config()));
+
+            let mut request = payment();
+            request.idempotency_key = Some("stellar:103".to_string());
+            assert_eq!(pi.send_payment(&request).await.unwrap(), "pi-tx-9");
+
+            let attempts = server.requests_to("POST", "/payments");
+            assert_eq!(attempts.len(), 3);
+            assert!(attempts.iter().all(|a| a.headers["idempotency-key"] == "stellar:103"));
+            assert!(!attempts[0].body.contains("idempotency"));
+
+            // Without a key, one random key is shared by the attempts of a call
+            let server = MockServer::start().await;
+            server.respond("POST", "/payments", unavailable());
+            server.respond("POST", "/payments", MockResponse::json(200, json!({ "tx_id": "pi-tx-10" })));
+            let pi = PiIntegration::new(&server.url()).with_transport(Transport::new(config()));
+            assert_eq!(pi.send_payment(&payment()).await.unwrap(), "pi-tx-10");
+            let attempts = server.requests_to("POST", "/payments");
+            assert_eq!(attempts.len(), 2);
+            assert_eq!(attempts[0].headers["idempotency-key"], attempts[1].headers["idempotency-key"]);
+            assert_ne!(attempts[0].headers["idempotency-key"], "stellar:103");
+        }
+
+        #[tokio::test]
+        async fn test_plain_posts_are_not_retried_after_reaching_the_server() {
+            let server = MockServer::start().await;
+            server.respond("POST", "/", unavailable());
+            let transport = Transport::new(config());
+
+            let response = transport.send(transport.client().post(server.url())).await.unwrap();
+            assert_eq!(response.status(), 503);
+            assert_eq!(server.requests().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_circuit_opens_per_endpoint_and_recovers() {
+            let failing = MockServer::start().await;
+            let healthy = MockServer::start().await;
+            failing.respond("GET", "/fee_stats", unavailable());
+            healthy.respond("GET", "/", MockResponse::json(200, json!({ "history_latest_ledger": 7 })));
+            let transport = Transport::new(TransportConfig { max_retries: 0, ..config() });
+            let broken = StellarIntegration::new(&failing.url()).with_transport(transport.clone());
+            let working = StellarIntegration::new(&healthy.url()).with_transport(transport.clone());
+
+            for _ in 0..3 {
+                assert!(matches!(broken.fee_stats().await, Err(HorizonError::Problem { status: 503, .. })));
+            }
+            assert_eq!(transport.circuit_state(&endpoint(&failing)), CircuitState::Open);
+            assert!(matches!(broken.fee_stats().await, Err(HorizonError::CircuitOpen(_))));
+            assert_eq!(failing.requests().len(), 3);
+
+            // Other endpoints sharing the transport are unaffected
+            assert_eq!(working.latest_ledger().await.unwrap(), 7);
+            assert_eq!(transport.circuit_state(&endpoint(&healthy)), CircuitState::Closed);
+
+            // After the reset timeout a failed trial reopens the circuit at once...
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            assert_eq!(transport.circuit_state(&endpoint(&failing)), CircuitState::HalfOpen);
+            assert!(broken.fee_stats().await.is_err());
+            assert_eq!(transport.circuit_state(&endpoint(&failing)), CircuitState::Open);
+
+            // ...and a successful one closes it
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            failing.respond("GET", "/", MockResponse::json(200, json!({ "history_latest_ledger": 8 })));
+            assert_eq!(broken.latest_ledger().await.unwrap(), 8);
+            assert_eq!(transport.circuit_state(&endpoint(&failing)), CircuitState::Closed);
+        }
+
+        #[tokio::test]
+        async fn test_cancelled_trial_frees_the_half_open_circuit() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/fee_stats", unavailable());
+            let transport = Transport::new(TransportConfig { max_retries: 0, failure_threshold: 1, ..config() });
+            let stellar = StellarIntegration::new(&server.url()).with_transport(transport.clone());
+            assert!(stellar.fee_stats().await.is_err());
+            assert_eq!(transport.circuit_state(&endpoint(&server)), CircuitState::Open);
+
+            // The caller gives up on the trial before the endpoint answers
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let ledger = json!({ "history_latest_ledger": 9 });
+            server.respond("GET", "/", MockResponse::json(200, ledger.clone()).with_delay(Duration::from_millis(150)));
+            server.respond("GET", "/", MockResponse::json(200, ledger));
+            assert!(tokio::time::timeout(Duration::from_millis(30), stellar.latest_ledger()).await.is_err());
+
+            // The next request becomes the trial instead of finding the circuit stuck open
+            assert_eq!(stellar.latest_ledger().await.unwrap(), 9);
+            assert_eq!(transport.circuit_state(&endpoint(&server)), CircuitState::Closed);
+        }
+
+        #[tokio::test]
+        async fn test_rate_limits_are_retried_without_tripping_the_circuit() {
+            let server = MockServer::start().await;
+            let limited = MockResponse::json(429, json!({ "type": "https://stellar.org/horizon-errors/rate_limit_exceeded" }));
+            server.respond("GET", "/", limited.with_header("Retry-After", "0"));
+            let transport = Transport::new(TransportConfig { failure_threshold: 1, ..config() });
+            let stellar = StellarIntegration::new(&server.url()).with_transport(transport.clone());
+
+            assert!(matches!(stellar.latest_ledger().await, Err(HorizonError::Problem { status: 429, .. })));
+            assert_eq!(server.requests().len(), 3);
+            assert_eq!(transport.circuit_state(&endpoint(&server)), CircuitState::Closed);
+        }
+
+        #[tokio::test]
+        async fn test_unreachable_endpoint() {
+            // Bind and drop a listener to find a port nothing is listening on
+            let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let transport = Transport::new(config());
+            let result = transport.send(transport.client().get(format!("http://127.0.0.1:{}/", port))).await;
+            assert!(matches!(result, Err(TransportError::Http(ref e)) if e.is_connect()));
+        }
+
+        #[test]
+        fn test_backoff_is_jittered_and_capped() {
+            let config = TransportConfig { base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), ..config() };
+            for retry in 0..8 {
+                let expected = (Duration::from_millis(100) * 2u32.pow(retry)).min(Duration::from_secs(1));
+                let delay = config.backoff(retry);
+                assert!(delay >= expected / 2 && delay <= expected, "retry {}: {:?}", retry, delay);
+            }
+            assert!(config.backoff(u32::MAX) <= Duration::from_secs(1));
+        }
+    }
+
+    mod chain_tests {
+        use crate::cross_chain::chain::{subscribe_blocks, Asset, Chain, Transfer};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::pi_integration::PiIntegration;
+        use crate::cross_chain::stellar_integration::{EnvelopeBuilder, StellarIntegration, StellarPaymentRequest};
+        use serde_json::{json, Value};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        const ALICE: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+        const BOB: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+
+        struct RecordingBuilder;
+
+        impl EnvelopeBuilder for RecordingBuilder {
+            fn build_payment(&self, payment: &StellarPaymentRequest, sequence: i64, base_fee: u32) -> Result<String, String> {
+                Ok(format!("{}-{}-{}", payment.amount, sequence, base_fee))
+            }
+        }
+
+        fn transfer(asset: Asset) -> Transfer {
+            Transfer {
+                from: ALICE.to_string(),
+                to: BOB.to_string(),
+                asset,
+                amount: 25,
+                memo: None,
+                reference: Some("bridge:1".to_string()),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_stellar_balances_by_asset() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", ALICE), MockResponse::json(200, json!({
+                "id": ALICE,
+                "sequence": "1",
+                "balances": [
+                    { "balance": "12.5000000", "asset_type": "credit_alphanum4", "asset_code": "USD", "asset_issuer": BOB },
+                    { "balance": "100.0000000", "asset_type": "native" }
+                ]
+            })));
+            let stellar: Arc<dyn Chain> = Arc::new(StellarIntegration::new(&horizon.url()));
+
+            let usd = Asset::Issued { code: "USD".to_string(), issuer: BOB.to_string() };
+            let eur = Asset::Issued { code: "EUR".to_string(), issuer: BOB.to_string() };
+            assert_eq!(stellar.balance(ALICE, &Asset::Native).await.unwrap(), 1_000_000_000);
+            assert_eq!(stellar.balance(ALICE, &usd).await.unwrap(), 125_000_000);
+            assert_eq!(stellar.balance(ALICE, &eur).await.unwrap(), 0);
+            assert_eq!(stellar.decimals(), 7);
+        }
+
+        #[tokio::test]
+        async fn test_stellar_transfer_and_transaction() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", &format!("/accounts/{}", ALICE), MockResponse::json(200, json!({ "id": ALICE, "sequence": "41" })));
+            let fees = json!({ "min": "100", "mode": "100", "p50": "100", "p90": "100", "p99": "100", "max": "100" });
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, json!({
+                "last_ledger": "8",
+                "last_ledger_base_fee": "100",
+                "ledger_capacity_usage": "0.5",
+                "fee_charged": fees,
+                "max_fee": fees
+            })));
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "abc", "ledger": 7, "successful": true })));
+            horizon.respond("GET", "/transactions/abc", MockResponse::json(200, json!({ "hash": "abc", "ledger": 7, "successful": true })));
+            horizon.respond("GET", "/", MockResponse::json(200, json!({ "history_latest_ledger": 9 })));
+            let stellar: Arc<dyn Chain> = Arc::new(StellarIntegration::new(&horizon.url()).with_envelope_builder(Arc::new(RecordingBuilder)));
+
+            let signed = stellar.build_transfer(&transfer(Asset::Native)).await.unwrap();
+            assert_eq!(signed.payload, "25-42-100");
+            assert!(horizon.requests_to("POST", "/transactions").is_empty());
+
+            assert_eq!(stellar.submit_transfer(&signed).await.unwrap(), "abc");
+            let transaction = stellar.transaction("abc").await.unwrap();
+            assert_eq!((transaction.block, transaction.confirmations, transaction.successful), (7, 3, true));
+            assert_eq!(stellar.confirmations("abc").await.unwrap(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_pi_transfer_carries_its_reference() {
+            let server = MockServer::start().await;
+            server.respond("POST", "/payments", MockResponse::json(200, json!({ "tx_id": "pi-tx-9" })));
+            server.respond("GET", "/transactions/pi-tx-9", MockResponse::json(200, json!({ "block": 12, "confirmations": 2 })));
+            let pi: Arc<dyn Chain> = Arc::new(PiIntegration::new(&server.url()));
+
+            assert!(pi.build_transfer(&transfer(Asset::Issued { code: "USD".to_string(), issuer: BOB.to_string() })).await.is_err());
+            assert_eq!(pi.transfer(&transfer(Asset::Native)).await.unwrap(), "pi-tx-9");
+
+            let request = &server.requests_to("POST", "/payments")[0];
+            assert_eq!(request.headers.get("idempotency-key").map(String::as_str), Some("bridge:1"));
+            let body: Value = serde_json::from_str(&request.body).unwrap();
+            assert_eq!(body, json!({ "from": ALICE, "to": BOB, "amount": 25 }));
+
+            let transaction = pi.transaction("pi-tx-9").await.unwrap();
+            assert_eq!((transaction.block, transaction.confirmations, transaction.successful), (12, 2, true));
+        }
+
+        #[tokio::test]
+        async fn test_block_stream_reports_every_new_block() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/blocks/latest", MockResponse::json(200, json!({ "height": 5 })));
+            server.respond("GET", "/blocks/latest", MockResponse::json(200, json!({ "height": 5 })));
+            server.respond("GET", "/blocks/latest", MockResponse::json(500, json!({ "error": "internal" })));
+            // Three blocks closed between two polls
+            server.respond("GET", "/blocks/latest", MockResponse::json(200, json!({ "height": 8 })));
+            let pi: Arc<dyn Chain> = Arc::new(PiIntegration::new(&server.url()));
+
+            let mut blocks = subscribe_blocks(pi, Duration::from_millis(5));
+            let mut heights = Vec::new();
+            for _ in 0..4 {
+                heights.push(tokio::time::timeout(Duration::from_secs(2), blocks.next()).await.unwrap().unwrap());
+            }
+            assert_eq!(heights, vec![5, 6, 7, 8]);
+        }
+    }
+
+    mod watcher_tests {
+        use crate::cross_chain::chain::{Chain, TransactionStatus};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::pi_integration::PiIntegration;
+        use crate::cross_chain::watcher::{StatusUpdate, TransactionWatcher, WatcherConfig};
+        use serde_json::json;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::sync::mpsc::UnboundedReceiver;
+
+        fn watcher(server: &MockServer, pending_timeout: Duration) -> TransactionWatcher {
+            let pi: Arc<dyn Chain> = Arc::new(PiIntegration::new(&server.url()));
+            TransactionWatcher::new(vec![pi], WatcherConfig { poll_interval: Duration::from_millis(5), pending_timeout })
+        }
+
+        fn latest_blocks(server: &MockServer, heights: &[u64]) {
+            for height in heights {
+                server.respond("GET", "/blocks/latest", MockResponse::json(200, json!({ "height": height })));
+            }
+        }
+
+        fn drain(receiver: &mut UnboundedReceiver<StatusUpdate>) -> Vec<(TransactionStatus, bool)> {
+            let mut updates = Vec::new();
+            while let Ok(update) = receiver.try_recv() {
+                updates.push((update.status, update.depth_reached));
+            }
+            updates
+        }
+
+        #[test]
+        fn test_status_of_included_transactions() {
+            use crate::cross_chain::chain::ChainTransaction;
+            let transaction = |confirmations, successful| ChainTransaction { tx_id: "tx".to_string(), block: 4, confirmations, successful };
+            assert_eq!(TransactionStatus::of(&transaction(1, true)), TransactionStatus::Included);
+            assert_eq!(TransactionStatus::of(&transaction(3, true)), TransactionStatus::Confirmed { confirmations: 3 });
+            assert!(matches!(TransactionStatus::of(&transaction(3, false)), TransactionStatus::Failed { .. }));
+            assert_eq!(TransactionStatus::Confirmed { confirmations: 3 }.confirmations(), 3);
+            assert!(TransactionStatus::Expired.is_terminal());
+            assert!(!TransactionStatus::Included.is_terminal());
         }
+
+        #[tokio::test]
+        async fn test_pending_status_for_unknown_transactions() {
+            let server = MockServer::start().await;
+            server.respond("GET", "/transactions/pi-tx-2", MockResponse::json(200, json!({ "block": 3, "confirmations": 2 })));
+            let pi = PiIntegration::new(&server.url());
+
+            assert_eq!(pi.get_transaction_status("pi-tx-1").await.unwrap(), TransactionStatus::Pending);
+            assert_eq!(pi.get_transaction_status("pi-tx-2").await.unwrap(), TransactionStatus::Confirmed { confirmations: 2 });
+        }
+
+        #[tokio::test]
+        async fn test_watch_until_depth() {
+            let server = MockServer::start().await;
+            latest_blocks(&server, &[10, 10, 11, 12, 13]);
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(404, json!({ "error": "not found" })));
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(200, json!({ "block": 11, "confirmations": 1 })));
+            let watcher = watcher(&server, Duration::from_secs(60));
+
+            let mut deep = watcher.watch("pi", "pi-tx-1", 3).unwrap();
+            let mut shallow = watcher.watch("pi", "pi-tx-1", 1).unwrap();
+            assert_eq!(watcher.watched(), 1);
+
+            for _ in 0..5 {
+                watcher.poll().await;
+            }
+            assert_eq!(drain(&mut shallow), vec![(TransactionStatus::Included, true)]);
+            assert_eq!(
+                drain(&mut deep),
+                vec![
+                    (TransactionStatus::Included, false),
+                    (TransactionStatus::Confirmed { confirmations: 2 }, false),
+                    (TransactionStatus::Confirmed { confirmations: 3 }, true),
+                ]
+            );
+            assert_eq!(watcher.watched(), 0);
+            // Looked up once while pending (not again in the same block) and once when included
+            assert_eq!(server.requests_to("GET", "/transactions/pi-tx-1").len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_failed_and_expired_transactions_end_the_watch() {
+            let server = MockServer::start().await;
+            latest_blocks(&server, &[5]);
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(200, json!({ "block": 5, "confirmations": 1, "successful": false })));
+            let watcher = watcher(&server, Duration::ZERO);
+
+            let mut failed = watcher.watch("pi", "pi-tx-1", 1).unwrap();
+            let mut expired = watcher.watch("pi", "pi-tx-2", 1).unwrap();
+            watcher.poll().await;
+
+            assert!(matches!(failed.recv().await.unwrap().status, TransactionStatus::Failed { .. }));
+            assert_eq!(expired.recv().await.unwrap().status, TransactionStatus::Expired);
+            assert!(failed.recv().await.is_none());
+            assert_eq!(watcher.watched(), 0);
+        }
+
+        #[tokio::test]
+        async fn test_running_watcher_and_late_subscribers() {
+            let server = MockServer::start().await;
+            latest_blocks(&server, &[7]);
+            server.respond("GET", "/transactions/pi-tx-1", MockResponse::json(200, json!({ "block": 6, "confirmations": 2 })));
+            let watcher = Arc::new(watcher(&server, Duration::from_secs(60)));
+            assert!(watcher.watch("bitcoin", "tx", 1).is_err());
+
+            let mut first = watcher.watch("pi", "pi-tx-1", 5).unwrap();
+            let handle = watcher.clone().start();
+            let update = tokio::time::timeout(Duration::from_secs(2), first.recv()).await.unwrap().unwrap();
+            assert_eq!(update.status, TransactionStatus::Confirmed { confirmations: 2 });
+
+            // A subscriber joining later is told the current status straight away
+            let mut late = watcher.watch("pi", "pi-tx-1", 2).unwrap();
+            assert!(late.try_recv().unwrap().depth_reached);
+            assert_eq!(watcher.status("pi", "pi-tx-1"), Some(TransactionStatus::Confirmed { confirmations: 2 }));
+            handle.stop().await;
+        }
+    }
+
+    mod horizon_stream_tests {
+        use crate::cross_chain::horizon_stream::{
+            CursorStore, EventHandler, HorizonEvent, HorizonStreamer, JsonFileCursorStore, SseEvent, SseParser, StreamError, StreamKind,
+        };
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::stellar_integration::StellarAsset;
+        use async_trait::async_trait;
+        use serde_json::{json, Value};
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        const ACCOUNT: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+        const OTHER: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+
+        #[derive(Default)]
+        struct MemoryStore {
+            cursors: Mutex<HashMap<String, String>>,
+        }
+
+        impl CursorStore for MemoryStore {
+            fn load(&self) -> Result<HashMap<String, String>, String> {
+                Ok(self.cursors.lock().unwrap().clone())
+            }
+
+            fn save(&self, cursors: &HashMap<String, String>) -> Result<(), String> {
+                *self.cursors.lock().unwrap() = cursors.clone();
+                Ok(())
+            }
+        }
+
+        // Records events and fails on the paging token given in `fail_on`
+        #[derive(Default)]
+        struct Recorder {
+            events: Mutex<Vec<HorizonEvent>>,
+            fail_on: Option<String>,
+        }
+
+        #[async_trait]
+        impl EventHandler for Recorder {
+            async fn handle(&self, event: HorizonEvent) -> Result<(), String> {
+                if self.fail_on.as_deref() == Some(event.paging_token()) {
+                    return Err("database unavailable".to_string());
+                }
+                self.events.lock().unwrap().push(event);
+                Ok(())
+            }
+        }
+
+        // Renders records the way Horizon streams them
+        fn event_stream(records: &[Value]) -> MockResponse {
+            let mut body = "retry: 1000\nevent: open\ndata: \"hello\"\n\n".to_string();
+            for record in records {
+                body.push_str(&format!("id: {}\ndata: {}\n\n", record["paging_token"].as_str().unwrap(), record));
+            }
+            MockResponse::raw(200, "text/event-stream", &body)
+        }
+
+        fn ledger(sequence: u64) -> Value {
+            json!({
+                "sequence": sequence,
+                "hash": format!("hash-{}", sequence),
+                "paging_token": format!("{}", sequence << 32),
+                "closed_at": "2024-05-01T12:00:00Z",
+                "successful_transaction_count": 3,
+                "operation_count": 5
+            })
+        }
+
+        #[test]
+        fn test_sse_parser_handles_split_chunks() {
+            let mut parser = SseParser::default();
+            let stream = ": keep-alive\r\nretry: 2500\r\nid: 7\r\nevent: update\r\ndata: {\"a\":\r\ndata: 1}\r\n\r\ndata: café\n\n";
+            let bytes = stream.as_bytes();
+            let mut events = Vec::new();
+            // Feed a byte at a time so lines and UTF-8 sequences are split
+            for byte in bytes {
+                events.extend(parser.feed(std::slice::from_ref(byte)));
+            }
+            assert_eq!(
+                events,
+                vec![
+                    SseEvent { id: Some("7".to_string()), event: Some("update".to_string()), data: "{\"a\":\n1}".to_string() },
+                    SseEvent { id: Some("7".to_string()), event: None, data: "café".to_string() },
+                ]
+            );
+            assert_eq!(parser.retry, Some(Duration::from_millis(2500)));
+        }
+
+        #[tokio::test]
+        async fn test_ledger_stream_resumes_from_saved_cursor() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(10), ledger(11)]));
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(12)]));
+            let path = std::env::temp_dir().join(format!("cursor_store_{}.json", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let recorder = Recorder::default();
+
+            let streamer = HorizonStreamer::new(&horizon.url(), Arc::new(JsonFileCursorStore::new(&path))).unwrap();
+            let (handled, retry) = streamer.consume(&StreamKind::Ledgers, &recorder).await.unwrap();
+            assert_eq!((handled, retry), (2, Some(Duration::from_secs(1))));
+            assert_eq!(streamer.cursor(&StreamKind::Ledgers), Some((11u64 << 32).to_string()));
+
+            // A new streamer over the same store carries on after ledger 11
+            let streamer = HorizonStreamer::new(&horizon.url(), Arc::new(JsonFileCursorStore::new(&path))).unwrap();
+            streamer.consume(&StreamKind::Ledgers, &recorder).await.unwrap();
+            let requests = horizon.requests_to("GET", "/ledgers");
+            assert_eq!(requests[0].path, "/ledgers?cursor=now");
+            assert_eq!(requests[1].path, format!("/ledgers?cursor={}", 11u64 << 32));
+            assert_eq!(requests[1].headers.get("accept").map(String::as_str), Some("text/event-stream"));
+
+            let events = recorder.events.lock().unwrap();
+            let sequences: Vec<u64> = events.iter().map(|e| match e {
+                HorizonEvent::Ledger(ledger) => ledger.sequence,
+                other => panic!("unexpected event {:?}", other),
+            }).collect();
+            assert_eq!(sequences, vec![10, 11, 12]);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_payment_and_effect_streams() {
+            let horizon = MockServer::start().await;
+            let payments_path = format!("/accounts/{}/payments", ACCOUNT);
+            horizon.respond("GET", &payments_path, event_stream(&[
+                json!({ "id": "1", "paging_token": "1", "transaction_hash": "t1", "type": "create_account", "funder": OTHER, "account": ACCOUNT }),
+                json!({
+                    "id": "2", "paging_token": "2", "transaction_hash": "t2", "type": "payment",
+                    "from": OTHER, "to": ACCOUNT, "asset_type": "native", "amount": "1.5000000",
+                    "transaction": { "hash": "t2", "ledger": 9, "successful": true, "memo": "invoice-7" }
+                }),
+                json!({ "id": "3", "paging_token": "3", "transaction_hash": "t3", "type": "set_options" })
+            ]));
+            let effects_path = format!("/accounts/{}/effects", ACCOUNT);
+            horizon.respond("GET", &effects_path, event_stream(&[json!({
+                "id": "0000000012884905985-0000000001", "paging_token": "12884905985-1", "account": ACCOUNT,
+                "type": "account_credited", "created_at": "2024-05-01T12:00:00Z",
+                "asset_type": "credit_alphanum4", "asset_code": "USD", "asset_issuer": OTHER, "amount": "2.0000000"
+            })]));
+            let store = Arc::new(MemoryStore::default());
+            let streamer = HorizonStreamer::new(&horizon.url(), store.clone()).unwrap();
+            let recorder = Recorder::default();
+
+            let payments = StreamKind::Payments { account: ACCOUNT.to_string() };
+            assert_eq!(streamer.consume(&payments, &recorder).await.unwrap().0, 1);
+            // Skipped operations still move the cursor on
+            assert_eq!(store.cursors.lock().unwrap().get(&format!("payments:{}", ACCOUNT)).map(String::as_str), Some("3"));
+            assert!(horizon.requests_to("GET", &payments_path)[0].path.contains("join=transactions"));
+
+            let effects = StreamKind::Effects { account: ACCOUNT.to_string() };
+            assert_eq!(streamer.consume(&effects, &recorder).await.unwrap().0, 1);
+
+            let events = recorder.events.lock().unwrap();
+            match &events[0] {
+                HorizonEvent::Payment(payment) => {
+                    assert_eq!((payment.amount, payment.asset.clone()), (15_000_000, StellarAsset::Native));
+                    assert_eq!(payment.memo.as_deref(), Some("invoice-7"));
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+            match &events[1] {
+                HorizonEvent::Effect(effect) => {
+                    assert_eq!(effect.kind, "account_credited");
+                    assert_eq!(effect.details["asset_code"], "USD");
+                    assert_eq!(effect.details["amount"], "2.0000000");
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_failed_event_is_delivered_again() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(10), ledger(11), ledger(12)]));
+            let store = Arc::new(MemoryStore::default());
+            let streamer = HorizonStreamer::new(&horizon.url(), store).unwrap();
+
+            let failing = Recorder { fail_on: Some((11u64 << 32).to_string()), ..Recorder::default() };
+            let result = streamer.consume(&StreamKind::Ledgers, &failing).await;
+            assert!(matches!(result, Err(StreamError::Handler(_))));
+            assert_eq!(streamer.cursor(&StreamKind::Ledgers), Some((10u64 << 32).to_string()));
+
+            streamer.consume(&StreamKind::Ledgers, &Recorder::default()).await.unwrap();
+            assert_eq!(horizon.requests_to("GET", "/ledgers")[1].path, format!("/ledgers?cursor={}", 10u64 << 32));
+        }
+
+        #[tokio::test]
+        async fn test_unreadable_records_are_skipped() {
+            let horizon = MockServer::start().await;
+            let unreadable = json!({ "paging_token": (11u64 << 32).to_string(), "sequence": "eleven" });
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(10), unreadable, ledger(12)]));
+            let store = Arc::new(MemoryStore::default());
+            let streamer = HorizonStreamer::new(&horizon.url(), store.clone()).unwrap();
+            let recorder = Recorder::default();
+
+            assert_eq!(streamer.consume(&StreamKind::Ledgers, &recorder).await.unwrap().0, 2);
+            assert_eq!(store.cursors.lock().unwrap().get("ledgers"), Some(&(12u64 << 32).to_string()));
+            assert_eq!(recorder.events.lock().unwrap().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_started_stream_feeds_a_channel_and_reconnects() {
+            let horizon = MockServer::start().await;
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(10)]));
+            horizon.respond("GET", "/ledgers", MockResponse::json(503, json!({ "title": "Service Unavailable", "type": "service_unavailable" })));
+            horizon.respond("GET", "/ledgers", event_stream(&[ledger(11)]));
+            horizon.respond("GET", "/ledgers", event_stream(&[]));
+            let streamer = Arc::new(
+                HorizonStreamer::new(&horizon.url(), Arc::new(MemoryStore::default())).unwrap().with_reconnect_delay(Duration::from_millis(5)),
+            );
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(8);
+
+            let handle = streamer.clone().start(StreamKind::Ledgers, Arc::new(sender));
+            for sequence in [10, 11] {
+                let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+                assert!(matches!(event, HorizonEvent::Ledger(ref ledger) if ledger.sequence == sequence));
+            }
+            handle.stop().await;
+        }
+
+        #[tokio::test]
+        async fn test_invalid_account_is_rejected() {
+            let horizon = MockServer::start().await;
+            let streamer = HorizonStreamer::new(&horizon.url(), Arc::new(MemoryStore::default())).unwrap();
+            let kind = StreamKind::Effects { account: "not-an-account".to_string() };
+            assert!(matches!(streamer.consume(&kind, &Recorder::default()).await, Err(StreamError::Horizon(_))));
+            assert!(horizon.requests().is_empty());
+        }
+    }
+
+    mod dex_tests {
+        use crate::cross_chain::dex::{anchors, DexError, Market, Offer, Pool, QuoteKind, Venue};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::network::NetworkRegistry;
+        use crate::cross_chain::stellar_integration::{StellarAsset, StellarIntegration};
+        use crate::models::stellar::transaction::OperationBody;
+        use serde_json::json;
+
+        const USD_ISSUER: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+        const PI_ISSUER: &str = "GABQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQHGPC";
+        const ALICE: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+        const UNIT: u64 = 10_000_000;
+
+        fn usd() -> StellarAsset {
+            StellarAsset::Credit { code: "USD".to_string(), issuer: USD_ISSUER.to_string() }
+        }
+
+        fn pi() -> StellarAsset {
+            StellarAsset::Credit { code: "PI".to_string(), issuer: PI_ISSUER.to_string() }
+        }
+
+        // 100 USD at 2 PI each, then 100 USD at 2.5 PI each
+        fn pi_usd_book() -> Market {
+            let mut market = Market::new();
+            market.add_offers(pi(), usd(), vec![
+                Offer { price_n: 5, price_d: 2, amount: 100 * UNIT },
+                Offer { price_n: 2, price_d: 1, amount: 100 * UNIT },
+            ]);
+            market
+        }
+
+        #[test]
+        fn test_strict_send_walks_the_book() {
+            let quote = pi_usd_book().quote_strict_send(&pi(), 300 * UNIT, &usd(), 3).unwrap();
+            assert_eq!(quote.dest_amount, 140 * UNIT);
+            assert_eq!(quote.hops.len(), 1);
+            assert_eq!(quote.hops[0].venue, Venue::OrderBook);
+            assert!(quote.path().is_empty());
+            // 140 USD instead of the 150 the best price would give
+            assert_eq!(quote.price_impact_bps, 667);
+        }
+
+        #[test]
+        fn test_strict_receive_walks_the_book() {
+            let quote = pi_usd_book().quote_strict_receive(&pi(), &usd(), 140 * UNIT, 3).unwrap();
+            assert_eq!(quote.kind, QuoteKind::StrictReceive);
+            assert_eq!(quote.send_amount, 300 * UNIT);
+        }
+
+        #[test]
+        fn test_insufficient_liquidity_has_no_path() {
+            let market = pi_usd_book();
+            assert!(matches!(market.quote_strict_receive(&pi(), &usd(), 201 * UNIT, 3), Err(DexError::NoPath { .. })));
+            assert!(matches!(market.quote_strict_send(&pi(), 1000 * UNIT, &usd(), 3), Err(DexError::NoPath { .. })));
+            assert!(matches!(market.quote_strict_send(&usd(), UNIT, &pi(), 3), Err(DexError::NoPath { .. })));
+            assert!(market.quote_strict_send(&pi(), 0, &usd(), 3).is_err());
+        }
+
+        #[test]
+        fn test_routes_through_a_pool_when_cheaper() {
+            let mut market = Market::new();
+            // A thin direct book at 4 PI per USD
+            market.add_offers(pi(), usd(), vec![Offer { price_n: 4, price_d: 1, amount: 1000 * UNIT }]);
+            // 10 XLM per PI in a deep pool, then 5 XLM per USD on the book
+            market.add_pool(Pool {
+                id: "pi-xlm".to_string(),
+                fee_bp: 30,
+                reserves: [(pi(), 100_000 * UNIT), (StellarAsset::Native, 1_000_000 * UNIT)],
+            });
+            market.add_offers(StellarAsset::Native, usd(), vec![Offer { price_n: 5, price_d: 1, amount: 1000 * UNIT }]);
+
+            let quote = market.quote_strict_send(&pi(), 10 * UNIT, &usd(), 3).unwrap();
+            assert_eq!(quote.path(), vec![StellarAsset::Native]);
+            assert_eq!(quote.hops[0].venue, Venue::LiquidityPool("pi-xlm".to_string()));
+            assert_eq!(quote.hops[1].venue, Venue::OrderBook);
+            assert_eq!(quote.hops[0].amount_out, quote.hops[1].amount_in);
+            // Almost 20 USD after the pool fee, against 2.5 USD directly
+            assert!(quote.dest_amount > 199 * UNIT / 10 && quote.dest_amount < 20 * UNIT);
+            assert_eq!(quote.price_impact_bps, 1);
+
+            let direct = market.quote_strict_send(&pi(), 10 * UNIT, &usd(), 1).unwrap();
+            assert_eq!(direct.dest_amount, 25 * UNIT / 10);
+
+            let receive = market.quote_strict_receive(&pi(), &usd(), quote.dest_amount, 3).unwrap();
+            assert_eq!(receive.path(), vec![StellarAsset::Native]);
+            assert!(receive.send_amount >= 10 * UNIT && receive.send_amount <= 10 * UNIT + 2);
+        }
+
+        #[test]
+        fn test_quotes_become_path_payments() {
+            let market = pi_usd_book();
+            let send = market.quote_strict_send(&pi(), 300 * UNIT, &usd(), 3).unwrap();
+            match send.to_operation(ALICE, 100).unwrap().body {
+                OperationBody::PathPaymentStrictSend { send_amount, dest_min, path, .. } => {
+                    assert_eq!((send_amount, dest_min), (3_000_000_000, 1_386_000_000));
+                    assert!(path.is_empty());
+                }
+                other => panic!("unexpected operation {:?}", other),
+            }
+
+            let receive = market.quote_strict_receive(&pi(), &usd(), 140 * UNIT, 3).unwrap();
+            match receive.to_operation(ALICE, 100).unwrap().body {
+                OperationBody::PathPaymentStrictReceive { send_max, dest_amount, .. } => {
+                    assert_eq!((send_max, dest_amount), (3_030_000_000, 1_400_000_000));
+                }
+                other => panic!("unexpected operation {:?}", other),
+            }
+            assert!(send.to_operation("SAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQC5MY", 100).is_err());
+
+            let mut invalid = send.clone();
+            invalid.dest_asset = StellarAsset::Credit { code: "USD".to_string(), issuer: "GC2BKLYOOYPD".to_string() };
+            assert!(matches!(invalid.to_operation(ALICE, 100), Err(DexError::InvalidAsset(_))));
+        }
+
+        #[tokio::test]
+        async fn test_market_loads_books_and_pools() {
+            let horizon = MockServer::start().await;
+            // Requested for XLM -> USD, then USD -> XLM
+            horizon.respond("GET", "/order_book", MockResponse::json(200, json!({
+                "bids": [],
+                "asks": [{ "price_r": { "n": 5, "d": 1 }, "price": "5.0000000", "amount": "100.0000000" }]
+            })));
+            horizon.respond("GET", "/order_book", MockResponse::json(200, json!({ "bids": [], "asks": [] })));
+            horizon.respond("GET", "/liquidity_pools", MockResponse::json(200, json!({ "_embedded": { "records": [{
+                "id": "xlm-usd",
+                "fee_bp": 30,
+                "reserves": [
+                    { "asset": "native", "amount": "1000.0000000" },
+                    { "asset": format!("USD:{}", USD_ISSUER), "amount": "200.0000000" }
+                ]
+            }] } })));
+            let stellar = StellarIntegration::new(&horizon.url());
+
+            let market = Market::load(&stellar, &[StellarAsset::Native, usd()], 20).await.unwrap();
+            let books = horizon.requests_to("GET", "/order_book");
+            assert!(books[0].path.contains("selling_asset_type=credit_alphanum4&selling_asset_code=USD"));
+            assert!(books[0].path.contains("buying_asset_type=native"));
+            assert!(books[0].path.contains("limit=20"));
+            assert_eq!(horizon.requests_to("GET", "/liquidity_pools").len(), 1);
+
+            // The book beats the pool one way; only the pool trades the other way
+            let buy = market.quote_strict_send(&StellarAsset::Native, 10 * UNIT, &usd(), 2).unwrap();
+            assert_eq!((buy.dest_amount, buy.hops[0].venue.clone()), (2 * UNIT, Venue::OrderBook));
+            let sell = market.quote_strict_send(&usd(), UNIT, &StellarAsset::Native, 2).unwrap();
+            assert_eq!(sell.hops[0].venue, Venue::LiquidityPool("xlm-usd".to_string()));
+        }
+
+        #[test]
+        fn test_anchors_of_registered_network() {
+            let registry = NetworkRegistry::builtin().unwrap();
+            let anchors = anchors(registry.get("testnet").unwrap());
+            assert_eq!(anchors, vec![
+                StellarAsset::Credit { code: "USDC".to_string(), issuer: "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5".to_string() },
+                StellarAsset::Credit { code: "EURC".to_string(), issuer: "GB3Q6QDZYTHWT7E5PVS3W7FUT5GVAFC5KSZFFLPU25GO7VTC3NM2ZTVO".to_string() },
+            ]);
+        }
+    }
+
+    mod messaging_tests {
+        use crate::cross_chain::messaging::*;
+        use crate::fixtures::keypair;
+        use async_trait::async_trait;
+        use ed25519_dalek::Keypair;
+        use std::sync::{Arc, Mutex};
+
+        fn relayer_set(relayers: &[Keypair], threshold: usize) -> RelayerSet {
+            RelayerSet::new(relayers.iter().map(|k| k.public).collect(), threshold).unwrap()
+        }
+
+        fn signed(message: Message, signers: &[&Keypair]) -> SignedMessage {
+            let mut signed = SignedMessage::new(message);
+            for keypair in signers {
+                signed.sign(keypair);
+            }
+            signed
+        }
+
+        #[derive(Default)]
+        struct Recorder {
+            received: Mutex<Vec<(u64, Vec<u8>)>>,
+            fail_on: Mutex<Option<u64>>,
+        }
+
+        #[async_trait]
+        impl MessageHandler for Recorder {
+            async fn handle(&self, message: &Message) -> Result<(), String> {
+                if *self.fail_on.lock().unwrap() == Some(message.nonce) {
+                    return Err("handler unavailable".to_string());
+                }
+                self.received.lock().unwrap().push((message.nonce, message.payload.clone()));
+                Ok(())
+            }
+        }
+
+        fn nonces(recorder: &Recorder) -> Vec<u64> {
+            recorder.received.lock().unwrap().iter().map(|(nonce, _)| *nonce).collect()
+        }
+
+        fn inbox(relayers: &[Keypair], threshold: usize) -> (MessageInbox, Arc<Recorder>) {
+            let recorder = Arc::new(Recorder::default());
+            let mut inbox = MessageInbox::new("stellar", relayer_set(relayers, threshold));
+            inbox.register("pi", recorder.clone());
+            (inbox, recorder)
+        }
+
+        #[test]
+        fn test_relayer_set_validation() {
+            let relayers = [keypair(1), keypair(2)];
+            assert!(RelayerSet::new(vec![relayers[0].public, relayers[1].public], 2).is_ok());
+            assert!(matches!(RelayerSet::new(vec![relayers[0].public], 0), Err(MessagingError::InvalidRelayerSet(_))));
+            assert!(matches!(RelayerSet::new(vec![relayers[0].public], 2), Err(MessagingError::InvalidRelayerSet(_))));
+            let duplicate = RelayerSet::new(vec![relayers[0].public, relayers[0].public], 1);
+            assert!(matches!(duplicate, Err(MessagingError::InvalidRelayerSet(_))));
+        }
+
+        #[test]
+        fn test_outbox_nonces_per_destination() {
+            let outbox = MessageOutbox::new("pi");
+            assert_eq!(outbox.send("stellar", vec![1]).nonce, 0);
+            assert_eq!(outbox.send("stellar", vec![2]).nonce, 1);
+            assert_eq!(outbox.send("ethereum", vec![3]).nonce, 0);
+        }
+
+        #[test]
+        fn test_threshold_verification() {
+            let relayers = [keypair(1), keypair(2), keypair(3)];
+            let set = relayer_set(&relayers, 2);
+            let message = MessageOutbox::new("pi").send("stellar", b"mint 5".to_vec());
+
+            assert_eq!(set.verify(&signed(message.clone(), &[&relayers[0], &relayers[2]])), Ok(2));
+            assert_eq!(
+                set.verify(&signed(message.clone(), &[&relayers[0]])),
+                Err(MessagingError::InsufficientSignatures { valid: 1, threshold: 2 })
+            );
+
+            // Outsiders, duplicates and signatures over other bytes do not count
+            let mut padded = signed(message.clone(), &[&relayers[0], &keypair(9)]);
+            padded.signatures.push(padded.signatures[0].clone());
+            assert!(set.verify(&padded).is_err());
+
+            let mut tampered = signed(message, &[&relayers[0], &relayers[1]]);
+            tampered.message.payload = b"mint 500".to_vec();
+            assert_eq!(set.verify(&tampered), Err(MessagingError::InsufficientSignatures { valid: 0, threshold: 2 }));
+        }
+
+        #[tokio::test]
+        async fn test_ordered_delivery_and_replay_protection() {
+            let relayers = [keypair(1), keypair(2)];
+            let (inbox, recorder) = inbox(&relayers, 2);
+            let outbox = MessageOutbox::new("pi");
+            let messages: Vec<SignedMessage> = (0..3u8)
+                .map(|i| signed(outbox.send("stellar", vec![i]), &[&relayers[0], &relayers[1]]))
+                .collect();
+
+            // Messages arriving early wait for the gap to fill
+            assert_eq!(inbox.receive(messages[2].clone()).await, Ok(0));
+            assert_eq!(inbox.receive(messages[1].clone()).await, Ok(0));
+            assert_eq!(inbox.pending("pi").await, 2);
+            assert_eq!(inbox.receive(messages[0].clone()).await, Ok(3));
+            assert_eq!(nonces(&recorder), vec![0, 1, 2]);
+            assert_eq!(inbox.next_nonce("pi").await, Some(3));
+
+            let replay = inbox.receive(messages[1].clone()).await;
+            assert_eq!(replay, Err(MessagingError::Replay { source_chain: "pi".to_string(), nonce: 1 }));
+            assert_eq!(nonces(&recorder), vec![0, 1, 2]);
+        }
+
+        #[tokio::test]
+        async fn test_rejects_unverified_or_misaddressed_messages() {
+            let relayers = [keypair(1), keypair(2)];
+            let (inbox, recorder) = inbox(&relayers, 2);
+
+            let under_signed = signed(MessageOutbox::new("pi").send("stellar", vec![]), &[&relayers[0]]);
+            assert!(matches!(inbox.receive(under_signed).await, Err(MessagingError::InsufficientSignatures { .. })));
+
+            let elsewhere = signed(MessageOutbox::new("pi").send("ethereum", vec![]), &[&relayers[0], &relayers[1]]);
+            assert!(matches!(inbox.receive(elsewhere).await, Err(MessagingError::WrongDestination { .. })));
+
+            let unknown = signed(MessageOutbox::new("ethereum").send("stellar", vec![]), &[&relayers[0], &relayers[1]]);
+            assert_eq!(inbox.receive(unknown).await, Err(MessagingError::UnknownSource("ethereum".to_string())));
+
+            // A rejected message does not use up its nonce
+            let valid = signed(MessageOutbox::new("pi").send("stellar", vec![]), &[&relayers[0], &relayers[1]]);
+            assert_eq!(inbox.receive(valid).await, Ok(1));
+            assert_eq!(nonces(&recorder), vec![0]);
+        }
+
+        #[tokio::test]
+        async fn test_bounds_messages_held_ahead() {
+            let relayers = [keypair(1)];
+            let recorder = Arc::new(Recorder::default());
+            let mut inbox = MessageInbox::new("stellar", relayer_set(&relayers, 1)).with_max_pending(2);
+            inbox.register_from("pi", recorder.clone(), 10);
+
+            let message = |nonce| signed(
+                Message { source_chain: "pi".to_string(), destination_chain: "stellar".to_string(), nonce, payload: vec![] },
+                &[&relayers[0]],
+            );
+            assert!(matches!(inbox.receive(message(9)).await, Err(MessagingError::Replay { .. })));
+            assert_eq!(inbox.receive(message(11)).await, Ok(0));
+            let ahead = inbox.receive(message(12)).await;
+            assert_eq!(ahead, Err(MessagingError::TooFarAhead { source_chain: "pi".to_string(), nonce: 12, next: 10 }));
+            assert_eq!(inbox.receive(message(10)).await, Ok(2));
+        }
+
+        #[tokio::test]
+        async fn test_failed_handler_retries_in_order() {
+            let relayers = [keypair(1)];
+            let (inbox, recorder) = inbox(&relayers, 1);
+            let outbox = MessageOutbox::new("pi");
+            *recorder.fail_on.lock().unwrap() = Some(1);
+
+            assert_eq!(inbox.receive(signed(outbox.send("stellar", vec![0]), &[&relayers[0]])).await, Ok(1));
+            let failed = inbox.receive(signed(outbox.send("stellar", vec![1]), &[&relayers[0]])).await;
+            assert!(matches!(failed, Err(MessagingError::Handler { nonce: 1, .. })));
+            // Later messages queue behind the failed one rather than overtaking it
+            assert!(inbox.receive(signed(outbox.send("stellar", vec![2]), &[&relayers[0]])).await.is_err());
+            assert_eq!(nonces(&recorder), vec![0]);
+
+            *recorder.fail_on.lock().unwrap() = None;
+            assert_eq!(inbox.flush("pi").await, Ok(2));
+            assert_eq!(nonces(&recorder), vec![0, 1, 2]);
+            assert_eq!(inbox.pending("pi").await, 0);
+        }
+    }
+
+    mod network_tests {
+        use crate::cross_chain::network::*;
+        use crate::cross_chain::pi_client::PiNetworkConfig;
+        use crate::cross_chain::stellar_integration::StellarAsset;
+        use crate::models::stellar::strkey;
+        use crate::models::stellar::transaction::Network;
+        use serde_json::json;
+
+        fn args(args: &[&str]) -> Vec<String> {
+            args.iter().map(|arg| arg.to_string()).collect()
+        }
+
+        #[test]
+        fn test_registry_file_matches_known_networks() {
+            let registry = NetworkRegistry::from_file(DEFAULT_NETWORKS_FILE).unwrap();
+            assert_eq!(registry, NetworkRegistry::builtin().unwrap());
+            assert_eq!(registry.names(), vec!["local", "mainnet", "testnet"]);
+
+            let mainnet = registry.get("mainnet").unwrap();
+            assert_eq!(PiNetworkConfig::from(mainnet), PiNetworkConfig::mainnet().unwrap());
+            assert_eq!(Network::from(mainnet), Network::public());
+            let testnet = registry.get("testnet").unwrap();
+            assert_eq!(PiNetworkConfig::from(testnet), PiNetworkConfig::testnet().unwrap());
+            assert_eq!(Network::from(testnet), Network::testnet());
+
+            // A USD and a EUR anchor on each public network
+            for name in ["mainnet", "testnet"] {
+                let codes: Vec<&str> = registry.get(name).unwrap().stellar_assets.iter().map(|asset| asset.code.as_str()).collect();
+                assert_eq!(codes, vec!["USDC", "EURC"]);
+            }
+            for profile in registry.networks.values() {
+                for asset in &profile.stellar_assets {
+                    assert!(strkey::decode_account_id(&asset.issuer).is_ok(), "{} issuer on {}", asset.code, profile.name);
+                }
+            }
+        }
+
+        #[test]
+        fn test_select_precedence() {
+            let registry = NetworkRegistry::builtin().unwrap();
+            assert_eq!(registry.select_with(None, None).unwrap().name, "testnet");
+            assert_eq!(registry.select_with(None, Some("local")).unwrap().name, "local");
+            assert_eq!(registry.select_with(Some("mainnet"), Some("local")).unwrap().name, "mainnet");
+            assert_eq!(registry.select_with(Some(" "), None).unwrap().name, "testnet");
+
+            let unknown = registry.select_with(Some("devnet"), None).unwrap_err();
+            assert_eq!(unknown.to_string(), "Unknown network `devnet`, expected one of: local, mainnet, testnet");
+        }
+
+        #[test]
+        fn test_network_flag() {
+            assert_eq!(network_flag(args(&["server", "--network", "local"])), Some("local".to_string()));
+            assert_eq!(network_flag(args(&["server", "--network=mainnet", "-v"])), Some("mainnet".to_string()));
+            assert_eq!(network_flag(args(&["server", "-v"])), None);
+        }
+
+        #[test]
+        fn test_rejects_incomplete_registries() {
+            let path = std::env::temp_dir().join(format!("networks_{}.json", std::process::id()));
+            let mut registry = json!({
+                "default": "local",
+                "networks": {
+                    "local": {
+                        "pi": { "api_url": "http://localhost:3000", "chain_id": "pi-local-1" },
+                        "stellar": { "horizon_url": "", "network_passphrase": "Standalone Network ; February 2017" },
+                        "bind_address": "127.0.0.1:9000"
+                    }
+                }
+            });
+            std::fs::write(&path, registry.to_string()).unwrap();
+            let error = NetworkRegistry::from_file(&path).unwrap_err();
+            assert_eq!(error, NetworkError::Invalid { network: "local".to_string(), reason: "`stellar.horizon_url` is empty".to_string() });
+
+            registry["networks"]["local"]["stellar"]["horizon_url"] = json!("http://localhost:8000");
+            registry["default"] = json!("mainnet");
+            std::fs::write(&path, registry.to_string()).unwrap();
+            assert!(matches!(NetworkRegistry::from_file(&path), Err(NetworkError::UnknownNetwork { .. })));
+
+            registry["default"] = json!("local");
+            std::fs::write(&path, registry.to_string()).unwrap();
+            let local = NetworkRegistry::from_file(&path).unwrap();
+            let profile = local.get("local").unwrap();
+            assert!(profile.stellar_assets.is_empty());
+            assert_eq!(profile.bind_address, "127.0.0.1:9000");
+            let _ = std::fs::remove_file(&path);
+
+            assert!(matches!(NetworkRegistry::from_file("config/missing.json"), Err(NetworkError::Io { .. })));
+        }
+
+        #[test]
+        fn test_profile_assets_convert_to_stellar_assets() {
+            let registry = NetworkRegistry::builtin().unwrap();
+            let anchors: Vec<StellarAsset> = registry.get("testnet").unwrap().stellar_assets.iter().map(StellarAsset::from).collect();
+            assert_eq!(anchors[0].canonical(), "USDC:GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5");
+        }
+    }
+
+    mod devnet_tests {
+        use crate::cross_chain::chain::{Asset, Chain, TransactionStatus, Transfer};
+        use crate::cross_chain::devnet::{Devnet, DevnetCall, DevnetConfig, INJECTED_FAILURE_STATUS};
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::htlc::{hashlock_for, HtlcChain, HtlcParams, HtlcStatus};
+        use crate::cross_chain::swap::{SwapCoordinator, SwapState, SwapTerms};
+        use super::MemorySwapStore;
+        use chrono::Utc;
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant};
+
+        const ALICE: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+        const BOB: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+
+        fn transfer(from: &str, to: &str, asset: Asset, amount: u64, reference: Option<&str>) -> Transfer {
+            Transfer {
+                from: from.to_string(),
+                to: to.to_string(),
+                asset,
+                amount,
+                memo: Some("hello".to_string()),
+                reference: reference.map(str::to_string),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_transfers_settle_when_blocks_close() {
+            let pi = Devnet::pi();
+            pi.fund("alice", &Asset::Native, 1_000);
+
+            let tx_id = pi.transfer(&transfer("alice", "bob", Asset::Native, 400, None)).await.unwrap();
+            assert_eq!(pi.transaction_status(&tx_id).await.unwrap(), TransactionStatus::Pending);
+            assert_eq!(pi.balance("bob", &Asset::Native).await.unwrap(), 0);
+            assert_eq!(pi.pending_transactions(), 1);
+
+            assert_eq!(pi.close_block(), 1);
+            let included = pi.transaction(&tx_id).await.unwrap();
+            assert_eq!((included.block, included.confirmations, included.successful), (1, 1, true));
+            assert_eq!(pi.balance("alice", &Asset::Native).await.unwrap(), 600);
+            assert_eq!(pi.balance("bob", &Asset::Native).await.unwrap(), 400);
+
+            assert_eq!(pi.close_blocks(2), 3);
+            assert_eq!(pi.latest_block().await.unwrap(), 3);
+            assert_eq!(pi.confirmations(&tx_id).await.unwrap(), 3);
+
+            let payments = pi.payments("bob", None).await.unwrap();
+            assert_eq!(payments.len(), 1);
+            assert_eq!((payments[0].tx_id.as_str(), payments[0].amount), (tx_id.as_str(), 400));
+            assert_eq!(payments[0].memo.as_deref(), Some("hello"));
+            assert!(pi.payments("bob", Some(&payments[0].cursor)).await.unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_overdrafts_fail_and_references_are_idempotent() {
+            let pi = Devnet::pi();
+            pi.fund("alice", &Asset::Native, 100);
+
+            let first = pi.transfer(&transfer("alice", "bob", Asset::Native, 80, Some("bridge:1"))).await.unwrap();
+            let retried = pi.transfer(&transfer("alice", "bob", Asset::Native, 80, Some("bridge:1"))).await.unwrap();
+            let overdraft = pi.transfer(&transfer("alice", "bob", Asset::Native, 80, None)).await.unwrap();
+            assert_eq!(first, retried);
+            assert_ne!(first, overdraft);
+
+            pi.close_block();
+            assert!(pi.transaction(&first).await.unwrap().successful);
+            assert!(matches!(pi.transaction_status(&overdraft).await.unwrap(), TransactionStatus::Failed { .. }));
+            assert_eq!(pi.balance("bob", &Asset::Native).await.unwrap(), 80);
+            assert_eq!(pi.payments("alice", None).await.unwrap().len(), 1);
+
+            let error = pi.build_transfer(&transfer("alice", "bob", Asset::Native, 0, None)).await.unwrap_err();
+            assert!(matches!(error, ChainError::Api { status: 400, .. }));
+        }
+
+        #[tokio::test]
+        async fn test_stellar_recognises_transactions_not_references() {
+            let stellar = Devnet::stellar();
+            stellar.fund("alice", &Asset::Native, 100);
+
+            let first = stellar.build_transfer(&transfer("alice", "bob", Asset::Native, 30, Some("bridge:1"))).await.unwrap();
+            let rebuilt = stellar.build_transfer(&transfer("alice", "bob", Asset::Native, 30, Some("bridge:1"))).await.unwrap();
+            assert!(first.tx_id.is_some());
+            assert_ne!(first.tx_id, rebuilt.tx_id);
+
+            // Resubmitting the same transaction is recognised before and after it is included
+            let tx_id = stellar.submit_transfer(&first).await.unwrap();
+            assert_eq!(Some(&tx_id), first.tx_id.as_ref());
+            assert_eq!(stellar.submit_transfer(&first).await.unwrap(), tx_id);
+            assert_eq!(stellar.pending_transactions(), 1);
+            stellar.close_block();
+            assert_eq!(stellar.submit_transfer(&first).await.unwrap(), tx_id);
+            assert_eq!(stellar.pending_transactions(), 0);
+
+            // A rebuilt transfer is a new transaction, whatever its reference
+            assert_eq!(Some(stellar.submit_transfer(&rebuilt).await.unwrap()), rebuilt.tx_id);
+            stellar.close_block();
+            assert_eq!(stellar.balance("bob", &Asset::Native).await.unwrap(), 60);
+        }
+
+        #[tokio::test]
+        async fn test_issuers_mint_and_burn_their_assets() {
+            let stellar = Devnet::new(DevnetConfig { strict_addresses: true, ..DevnetConfig::stellar() });
+            let usd = Asset::Issued { code: "USD".to_string(), issuer: ALICE.to_string() };
+
+            assert!(!stellar.is_valid_address("bob"));
+            assert!(stellar.build_transfer(&transfer(ALICE, "bob", usd.clone(), 10, None)).await.is_err());
+
+            stellar.transfer(&transfer(ALICE, BOB, usd.clone(), 500, None)).await.unwrap();
+            stellar.close_block();
+            stellar.transfer(&transfer(BOB, ALICE, usd.clone(), 200, None)).await.unwrap();
+            stellar.close_block();
+
+            assert_eq!(stellar.balance(BOB, &usd).await.unwrap(), 300);
+            assert_eq!(stellar.balance(ALICE, &usd).await.unwrap(), 0);
+            assert_eq!(stellar.payments(ALICE, None).await.unwrap().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_injected_failures_delays_and_holds() {
+            let pi = Devnet::pi();
+            pi.fund("alice", &Asset::Native, 1_000);
+
+            pi.fail_next(DevnetCall::Submit, 1);
+            let error = pi.transfer(&transfer("alice", "bob", Asset::Native, 10, None)).await.unwrap_err();
+            assert!(matches!(error, ChainError::Api { status, .. } if status == INJECTED_FAILURE_STATUS));
+            assert_eq!(pi.pending_transactions(), 0);
+
+            // Injected failing transactions are included but move no funds
+            pi.fail_next_transactions(1);
+            let failed = pi.transfer(&transfer("alice", "bob", Asset::Native, 10, None)).await.unwrap();
+            pi.hold_transactions(2);
+            let held = pi.transfer(&transfer("alice", "bob", Asset::Native, 20, None)).await.unwrap();
+            pi.close_block();
+            assert!(matches!(pi.transaction_status(&failed).await.unwrap(), TransactionStatus::Failed { .. }));
+            assert_eq!(pi.transaction_status(&held).await.unwrap(), TransactionStatus::Pending);
+            pi.close_blocks(2);
+            assert!(pi.transaction(&held).await.unwrap().successful);
+            assert_eq!(pi.balance("bob", &Asset::Native).await.unwrap(), 20);
+
+            pi.set_delay(DevnetCall::LatestBlock, Duration::from_millis(100));
+            let started = Instant::now();
+            assert_eq!(pi.latest_block().await.unwrap(), 3);
+            assert!(started.elapsed() >= Duration::from_millis(100));
+            pi.set_delay(DevnetCall::LatestBlock, Duration::ZERO);
+            let started = Instant::now();
+            pi.latest_block().await.unwrap();
+            assert!(started.elapsed() < Duration::from_millis(100));
+        }
+
+        #[tokio::test]
+        async fn test_blocks_close_in_the_background_until_stopped() {
+            let pi = Arc::new(Devnet::new(DevnetConfig { block_interval: Duration::from_millis(20), ..DevnetConfig::pi() }));
+            let handle = pi.clone().start();
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            handle.stop().await;
+
+            let height = pi.latest_block().await.unwrap();
+            assert!(height >= 2, "closed {} blocks", height);
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            assert_eq!(pi.latest_block().await.unwrap(), height);
+        }
+
+        #[tokio::test]
+        async fn test_htlc_claims_and_refunds() {
+            let stellar = Devnet::stellar();
+            stellar.fund("bob", &Asset::Native, 1_000);
+            let preimage = b"devnet secret".to_vec();
+            let params = HtlcParams {
+                sender: "bob".to_string(),
+                receiver: "alice".to_string(),
+                amount: 600,
+                hashlock: hashlock_for(&preimage),
+                expires_at: Utc::now() + chrono::Duration::seconds(60),
+            };
+
+            let lock_id = stellar.lock_funds(&params).await.unwrap();
+            assert_eq!(stellar.lock_funds(&params).await.unwrap(), lock_id);
+            assert_eq!(stellar.balance("bob", &Asset::Native).await.unwrap(), 400);
+            assert!(stellar.refund(&lock_id).await.is_err());
+            assert!(stellar.claim(&lock_id, b"wrong").await.is_err());
+
+            let claim_tx = stellar.claim(&lock_id, &preimage).await.unwrap();
+            assert_eq!(stellar.balance("alice", &Asset::Native).await.unwrap(), 600);
+            let lock = stellar.get_lock(&lock_id).await.unwrap();
+            assert_eq!(lock.status, HtlcStatus::Claimed);
+            assert_eq!(lock.preimage, Some(hex::encode(&preimage)));
+            assert!(matches!(stellar.claim(&lock_id, &preimage).await, Err(ChainError::Api { status: 409, .. })));
+            stellar.close_block();
+            assert!(stellar.transaction(&claim_tx).await.unwrap().successful);
+
+            let expired = stellar.lock_funds(&HtlcParams {
+                amount: 400,
+                hashlock: hashlock_for(b"other"),
+                expires_at: Utc::now() - chrono::Duration::seconds(1),
+                ..params.clone()
+            }).await.unwrap();
+            assert!(stellar.claim(&expired, b"other").await.is_err());
+            stellar.refund(&expired).await.unwrap();
+            assert_eq!(stellar.balance("bob", &Asset::Native).await.unwrap(), 400);
+
+            stellar.fail_next(DevnetCall::Htlc, 1);
+            assert!(stellar.get_lock(&lock_id).await.is_err());
+            assert!(stellar.get_lock(&lock_id).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn test_atomic_swap_between_devnets() {
+            let pi = Arc::new(Devnet::pi());
+            let stellar = Arc::new(Devnet::stellar());
+            pi.fund("pi-alice", &Asset::Native, 1_000);
+            stellar.fund("GBOB", &Asset::Native, 5_000);
+            let store = Arc::new(MemorySwapStore { saved: Mutex::new(Vec::new()) });
+            let coordinator = SwapCoordinator::new(vec![pi.clone(), stellar.clone()], store).unwrap();
+
+            let swap = coordinator.create_swap(SwapTerms {
+                initiator_chain: "pi".to_string(),
+                counterparty_chain: "stellar".to_string(),
+                initiator_address: "pi-alice".to_string(),
+                initiator_receive_address: "GALICE".to_string(),
+                counterparty_address: "pi-bob".to_string(),
+                amount: 1_000,
+                counterparty_amount: 5_000,
+                lock_duration: Duration::from_secs(3600),
+                safety_margin: Duration::from_secs(30),
+            }).unwrap();
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Locked);
+            assert_eq!(pi.balance("pi-alice", &Asset::Native).await.unwrap(), 0);
+
+            stellar.lock_funds(&HtlcParams {
+                sender: "GBOB".to_string(),
+                receiver: "GALICE".to_string(),
+                amount: 5_000,
+                hashlock: swap.hashlock.clone(),
+                expires_at: swap.lock_expires_at - chrono::Duration::seconds(60),
+            }).await.unwrap();
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::CounterpartyLocked);
+
+            // A failed claim is retried on the next step
+            stellar.fail_next(DevnetCall::Htlc, 1);
+            assert!(coordinator.step(&swap.id).await.is_err());
+            assert_eq!(coordinator.step(&swap.id).await.unwrap(), SwapState::Completed);
+            assert_eq!(stellar.balance("GALICE", &Asset::Native).await.unwrap(), 5_000);
+
+            // The counterparty completes the swap with the preimage revealed on Stellar
+            let swap = coordinator.get_swap(&swap.id).unwrap();
+            let revealed = stellar.get_lock(swap.counterparty_lock_id.as_ref().unwrap()).await.unwrap().preimage.unwrap();
+            pi.claim(swap.lock_id.as_ref().unwrap(), &hex::decode(revealed).unwrap()).await.unwrap();
+            assert_eq!(pi.balance("pi-bob", &Asset::Native).await.unwrap(), 1_000);
+        }
+    }
+
+    mod issuance_tests {
+        use crate::cross_chain::error::ChainError;
+        use crate::cross_chain::horizon::HorizonError;
+        use crate::cross_chain::issuance::{AuthFlags, IssuanceConfig, IssuanceError, PiCoinIssuer};
+        use crate::cross_chain::mock_server::{MockResponse, MockServer};
+        use crate::cross_chain::smart_contract::SmartContract;
+        use crate::cross_chain::stellar_integration::StellarIntegration;
+        use crate::fixtures::keypair;
+        use crate::models::stellar::transaction::{
+            AccountId, Asset, MuxedAccount, Network, OperationBody, TransactionBuilder, TransactionEnvelope, AUTHORIZED_FLAG,
+            AUTH_CLAWBACK_ENABLED_FLAG, AUTH_REQUIRED_FLAG, AUTH_REVOCABLE_FLAG, TRUSTLINE_CLAWBACK_ENABLED_FLAG,
+        };
+        use crate::models::stellar::xdr::XdrDecode;
+        use crate::models::stellar::{StellarProtocol, StellarProtocolImpl};
+        use serde_json::json;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        const XLM: i64 = 10_000_000;
+
+        fn address(seed: u8) -> String {
+            AccountId::from(&keypair(seed).public).to_strkey()
+        }
+
+        fn pi_coin(url: &str, config: IssuanceConfig, ledger: SmartContract) -> PiCoinIssuer {
+            let stellar = Arc::new(StellarIntegration::new(url));
+            PiCoinIssuer::new(stellar, Network::testnet(), keypair(1), keypair(2), config, ledger).unwrap()
+        }
+
+        // Horizon with the given accounts and fee stats
+        async fn horizon(accounts: &[String]) -> MockServer {
+            let horizon = MockServer::start().await;
+            for account in accounts {
+                horizon.respond("GET", &format!("/accounts/{}", account), MockResponse::json(200, json!({ "id": account, "sequence": "100" })));
+            }
+            horizon.respond("GET", "/fee_stats", MockResponse::json(200, json!({
+                "last_ledger": "1",
+                "last_ledger_base_fee": "100",
+                "ledger_capacity_usage": "0.1",
+                "fee_charged": { "min": "100", "mode": "100", "p50": "100", "p90": "100", "p99": "100", "max": "100" },
+                "max_fee": { "min": "100", "mode": "100", "p50": "100", "p90": "100", "p99": "100", "max": "100" }
+            })));
+            horizon
+        }
+
+        fn submitted(horizon: &MockServer, index: usize) -> TransactionEnvelope {
+            let body = &horizon.requests_to("POST", "/transactions")[index].body;
+            let xdr = body.strip_prefix("tx=").unwrap().replace("%2B", "+").replace("%2F", "/").replace("%3D", "=");
+            TransactionEnvelope::from_xdr_base64(&xdr).unwrap()
+        }
+
+        #[test]
+        fn test_setup_configures_issuer_and_distribution() {
+            let config = IssuanceConfig { home_domain: Some("pi.example".to_string()), ..IssuanceConfig::new("PI") };
+            let pi = pi_coin("http://127.0.0.1:1", config, SmartContract::new());
+            let network = Network::testnet();
+            let funder = keypair(3);
+            let funder_id = AccountId::from(&funder.public);
+            let mut ledger = StellarProtocolImpl::new();
+            ledger.fund_account(funder_id, 100 * XLM).unwrap();
+
+            // The funder, issuer and distribution account all sign the one setup transaction
+            let sequence = ledger.account(&funder_id).unwrap().sequence + 1;
+            let setup = pi.setup_operations().into_iter().fold(TransactionBuilder::new(funder_id, sequence), |b, op| b.add_operation(op));
+            let mut envelope = setup.build().unwrap().sign(&network, &funder);
+            envelope.sign(&network, &keypair(1)).unwrap();
+            envelope.sign(&network, &keypair(2)).unwrap();
+            ledger.apply_transaction(&envelope).unwrap();
+
+            let issuer = ledger.account(&pi.issuer_id()).unwrap();
+            assert_eq!(issuer.flags, AUTH_REQUIRED_FLAG | AUTH_REVOCABLE_FLAG | AUTH_CLAWBACK_ENABLED_FLAG);
+            let distribution = &ledger.account(&pi.distribution_id()).unwrap().trustlines[pi.asset()];
+            assert_eq!(distribution.flags, AUTHORIZED_FLAG | TRUSTLINE_CLAWBACK_ENABLED_FLAG);
+            assert_eq!(pi.asset(), &Asset::credit("PI", pi.issuer_id()).unwrap());
+
+            // Without auth required, trustlines start out authorized
+            let open = IssuanceConfig { flags: AuthFlags { auth_required: false, ..AuthFlags::all() }, ..IssuanceConfig::new("PI") };
+            assert_eq!(pi_coin("http://127.0.0.1:1", open, SmartContract::new()).setup_operations().len(), 4);
+
+            let stellar = Arc::new(StellarIntegration::new("http://127.0.0.1:1"));
+            let no_revoke = IssuanceConfig { flags: AuthFlags { auth_revocable: false, ..AuthFlags::all() }, ..IssuanceConfig::new("PI") };
+            assert!(matches!(
+                PiCoinIssuer::new(stellar, network, keypair(1), keypair(2), no_revoke, SmartContract::new()),
+                Err(IssuanceError::InvalidConfig(_))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_mint_records_supply_and_pays_from_the_issuer() {
+            let holder = address(4);
+            let horizon = horizon(&[address(1)]).await;
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "minted", "ledger": 7, "successful": true })));
+            let pi = pi_coin(&horizon.url(), IssuanceConfig::new("PI"), SmartContract::new());
+
+            assert_eq!(pi.mint(&holder, 250_000_000).await.unwrap(), "minted");
+            assert_eq!(pi.total_supply(), 250_000_000);
+
+            let envelope = submitted(&horizon, 0);
+            assert_eq!(envelope.tx.source_account, MuxedAccount::from(pi.issuer_id()));
+            assert_eq!(envelope.tx.seq_num, 101);
+            assert!(envelope.is_signed_by(&Network::testnet(), &keypair(1).public));
+            match &envelope.tx.operations[0].body {
+                OperationBody::Payment { destination, asset, amount } => {
+                    assert_eq!(destination.to_strkey(), holder);
+                    assert_eq!(asset, pi.asset());
+                    assert_eq!(*amount, 250_000_000);
+                }
+                other => panic!("unexpected operation {:?}", other),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_failed_mints_are_undone_in_the_ledger() {
+            let holder = address(4);
+            let horizon = horizon(&[address(1)]).await;
+            horizon.respond("POST", "/transactions", MockResponse::json(400, json!({
+                "type": "https://stellar.org/horizon-errors/transaction_failed",
+                "title": "Transaction Failed",
+                "status": 400,
+                "extras": { "result_codes": { "transaction": "tx_failed", "operations": ["op_not_authorized"] } }
+            })));
+            let pi = pi_coin(&horizon.url(), IssuanceConfig::new("PI"), SmartContract::new());
+
+            let error = pi.mint(&holder, 250_000_000).await.unwrap_err();
+            assert!(matches!(error, IssuanceError::Chain(ChainError::Horizon(HorizonError::TransactionFailed { .. }))));
+            assert_eq!(pi.total_supply(), 0);
+
+            // The ledger's own limits apply before anything is submitted
+            assert!(matches!(pi.clawback(&holder, 1).await, Err(IssuanceError::Ledger(_))));
+            assert!(matches!(pi.mint(&holder, 0).await, Err(IssuanceError::InvalidAmount(0))));
+            assert!(matches!(pi.mint("nobody", 1).await, Err(IssuanceError::InvalidAccount(_))));
+            assert_eq!(horizon.requests_to("POST", "/transactions").len(), 1);
+        }
+
+        fn horizon_timeout() -> MockResponse {
+            MockResponse::json(504, json!({ "type": "https://stellar.org/horizon-errors/timeout", "title": "Timeout", "status": 504 }))
+        }
+
+        #[tokio::test]
+        async fn test_mint_applied_despite_a_timeout_is_kept() {
+            let holder = address(4);
+            let horizon = horizon(&[address(1)]).await;
+            horizon.respond("POST", "/transactions", horizon_timeout());
+            let pi = Arc::new(pi_coin(&horizon.url(), IssuanceConfig::new("PI"), SmartContract::new()));
+
+            let minting = tokio::spawn({
+                let (pi, holder) = (pi.clone(), holder.clone());
+                async move { pi.mint(&holder, 250_000_000).await }
+            });
+            // Horizon gives up waiting for the transaction, but the network applies it
+            while horizon.requests_to("POST", "/transactions").is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let hash = hex::encode(submitted(&horizon, 0).tx.hash(&Network::testnet()));
+            horizon.respond("GET", &format!("/transactions/{}", hash), MockResponse::json(200, json!({ "hash": hash, "ledger": 7, "successful": true })));
+
+            assert_eq!(minting.await.unwrap().unwrap(), hash);
+            assert_eq!(pi.total_supply(), 250_000_000);
+        }
+
+        #[tokio::test]
+        async fn test_mint_with_unknown_outcome_is_kept() {
+            let holder = address(4);
+            let horizon = horizon(&[address(1)]).await;
+            horizon.respond("POST", "/transactions", horizon_timeout());
+            let pi = pi_coin(&horizon.url(), IssuanceConfig::new("PI"), SmartContract::new());
+
+            // Not found yet, but it may still be applied before it expires
+            let error = pi.mint(&holder, 250_000_000).await.unwrap_err();
+            let hash = hex::encode(submitted(&horizon, 0).tx.hash(&Network::testnet()));
+            assert!(matches!(&error, IssuanceError::Unresolved { hash: unresolved, .. } if *unresolved == hash));
+            assert_eq!(pi.total_supply(), 250_000_000);
+        }
+
+        #[tokio::test]
+        async fn test_trustlines_and_clawback() {
+            let holder = keypair(4);
+            let horizon = horizon(&[address(1), address(4)]).await;
+            horizon.respond("POST", "/transactions", MockResponse::json(200, json!({ "hash": "done", "ledger": 7, "successful": true })));
+            let pi = pi_coin(&horizon.url(), IssuanceConfig::new("PI"), SmartContract::new());
+
+            // The holder trusts the asset and the issuer authorizes them in one transaction
+            pi.open_trustline(&holder, None).await.unwrap();
+            let envelope = submitted(&horizon, 0);
+            assert_eq!(envelope.tx.source_account, MuxedAccount::from(AccountId::from(&holder.public)));
+            assert!(matches!(&envelope.tx.operations[0].body, OperationBody::ChangeTrust { limit: i64::MAX, .. }));
+            assert!(matches!(&envelope.tx.operations[1].body, OperationBody::SetTrustLineFlags { set_flags: AUTHORIZED_FLAG, .. }));
+            assert!(envelope.is_signed_by(&Network::testnet(), &keypair(1).public));
+
+            pi.revoke(&address(4)).await.unwrap();
+            assert!(matches!(&submitted(&horizon, 1).tx.operations[0].body, OperationBody::SetTrustLineFlags { clear_flags: AUTHORIZED_FLAG, set_flags: 0, .. }));
+
+            pi.mint(&address(4), 100).await.unwrap();
+            assert!(matches!(pi.clawback(&address(4), 101).await, Err(IssuanceError::Ledger(_))));
+            pi.clawback(&address(4), 60).await.unwrap();
+            assert!(matches!(&submitted(&horizon, 3).tx.operations[0].body, OperationBody::Clawback { amount: 60, .. }));
+            assert_eq!(pi.total_supply(), 40);
+
+            let frozen = IssuanceConfig { flags: AuthFlags { auth_required: true, auth_revocable: false, clawback_enabled: false }, ..IssuanceConfig::new("PI") };
+            let frozen = pi_coin(&horizon.url(), frozen, SmartContract::new());
+            assert!(matches!(frozen.revoke(&address(4)).await, Err(IssuanceError::NotEnabled("Revocation"))));
+            assert!(matches!(frozen.clawback(&address(4), 1).await, Err(IssuanceError::NotEnabled("Clawback"))));
+        }
+
+        #[tokio::test]
+        async fn test_trustline_status_and_reconciliation() {
+            let horizon = MockServer::start().await;
+            let issuer = address(1);
+            horizon.respond("GET", &format!("/accounts/{}", address(4)), MockResponse::json(200, json!({
+                "id": address(4),
+                "sequence": "100",
+                "balances": [
+                    { "balance": "100.0000000", "asset_type": "native" },
+                    { "balance": "25.0000000", "limit": "1000.0000000", "asset_type": "credit_alphanum4", "asset_code": "PI", "asset_issuer": issuer, "is_authorized": true, "is_clawback_enabled": true }
+                ]
+            })));
+            horizon.respond("GET", &format!("/accounts/{}", address(5)), MockResponse::json(200, json!({ "id": address(5), "sequence": "1", "balances": [] })));
+            horizon.respond("GET", "/assets", MockResponse::json(200, json!({ "_embedded": { "records": [{
+                "asset_type": "credit_alphanum4",
+                "asset_code": "PI",
+                "asset_issuer": issuer,
+                "balances": { "authorized": "25.0000000", "authorized_to_maintain_liabilities": "0.0000000", "unauthorized": "0.0000000" }
+            }] } })));
+            let mut ledger = SmartContract::new();
+            ledger.mint(address(4), 250_000_000).unwrap();
+            let pi = pi_coin(&horizon.url(), IssuanceConfig::new("PI"), ledger);
+
+            let line = pi.trustline(&address(4)).await.unwrap().unwrap();
+            assert_eq!((line.balance, line.limit, line.authorized, line.clawback_enabled), (250_000_000, 10_000_000_000, true, true));
+            assert_eq!(pi.trustline(&address(5)).await.unwrap(), None);
+
+            let check = pi.reconcile().await.unwrap();
+            assert!(check.is_balanced());
+            assert_eq!(check.stellar_supply, 250_000_000);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+// In-process Pi and Stellar devnets for testing bridge and swap flows offline
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::Utc;
+use ring::digest::{digest, SHA256};
+use serde_json::json;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use log::{debug, info};
+use super::chain::{Asset, Chain, ChainTransaction, Payment, SignedTransfer, Transfer};
+use super::error::ChainError;
+use super::htlc::{hashlock_for, HtlcChain, HtlcLock, HtlcParams, HtlcStatus};
+use crate::models::stellar::strkey;
+
+/// HTTP status of the errors returned for injected failures, as from an unavailable node.
+pub const INJECTED_FAILURE_STATUS: u16 = 503;
+
+/// Settings of a simulated chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevnetConfig {
+    pub chain_name: String,
+    pub decimals: u32,
+    /// How often `start` closes a block.
+    pub block_interval: Duration,
+    /// Whether only `G...` and `M...` addresses are valid, as on the real chains. Off by
+    /// default so tests can use readable account names.
+    pub strict_addresses: bool,
+    /// Whether a resubmitted transfer with the same `reference` returns the first transaction,
+    /// as the Pi node's idempotency keys do. Without them, as on Stellar, each built transfer
+    /// gets its own transaction ID and only resubmitting that same transfer is recognised.
+    pub idempotent_references: bool,
+}
+
+impl DevnetConfig {
+    /// A Pi devnet: 8 decimals and blocks every 5 seconds.
+    pub fn pi() -> Self {
+        Self {
+            chain_name: "pi".to_string(),
+            decimals: 8,
+            block_interval: Duration::from_secs(5),
+            strict_addresses: false,
+            idempotent_references: true,
+        }
+    }
+
+    /// A Stellar devnet: 7 decimals and ledgers every 5 seconds.
+    pub fn stellar() -> Self {
+        Self {
+            chain_name: "stellar".to_string(),
+            decimals: 7,
+            block_interval: Duration::from_secs(5),
+            strict_addresses: false,
+            idempotent_references: false,
+        }
+    }
+}
+
+/// Calls that failures and delays can be injected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DevnetCall {
+    /// `balance` and `issued_supply`.
+    Balance,
+    Submit,
+    Transaction,
+    Payments,
+    LatestBlock,
+    /// Any of the HTLC calls.
+    Htlc,
+}
+
+// A submitted transaction waiting for a block
+struct PendingTx {
+    tx_id: String,
+    // `None` for HTLC transactions, whose effects apply when they are submitted
+    transfer: Option<Transfer>,
+    // First block that may include the transaction
+    ready_at: u64,
+    fail: bool,
+}
+
+struct IncludedTx {
+    block: u64,
+    successful: bool,
+}
+
+#[derive(Default)]
+struct DevnetState {
+    height: u64,
+    tx_count: u64,
+    // Transfers built so far, numbering their transaction IDs like sequence numbers
+    built_transfers: u64,
+    balances: HashMap<(String, Asset), u64>,
+    mempool: Vec<PendingTx>,
+    transactions: HashMap<String, IncludedTx>,
+    payments: Vec<Payment>,
+    // Transaction ID per transfer reference, so resubmissions are recognised
+    references: HashMap<String, String>,
+    locks: HashMap<String, HtlcLock>,
+    faults: HashMap<DevnetCall, usize>,
+    delays: HashMap<DevnetCall, Duration>,
+    failing_transactions: usize,
+    held_blocks: u64,
+}
+
+/// A simulated chain implementing `Chain` and `HtlcChain` entirely in memory.
+///
+/// Submitted transfers wait in a mempool until the next block closes, either when a test calls
+/// `close_block` or every `block_interval` once `start`ed. A resubmitted transfer is recognised
+/// by its `reference` on a Pi devnet, and by the transaction ID it was built with on a Stellar
+/// devnet. Transfers that overdraw their sender are included but fail. The issuer of an asset can pay it out without holding it, and
+/// payments back to the issuer burn it, as on Stellar. HTLC locks, claims and refunds take
+/// effect immediately, checked against the system clock like the swap coordinator.
+///
+/// Failures, delays and failing or held transactions can be injected to exercise error paths.
+pub struct Devnet {
+    config: DevnetConfig,
+    state: Mutex<DevnetState>,
+}
+
+/// Handle to a devnet closing blocks in the background.
+pub struct DevnetHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Devnet {
+    pub fn new(config: DevnetConfig) -> Self {
+        Self { config, state: Mutex::new(DevnetState::default()) }
+    }
+
+    pub fn pi() -> Self {
+        Self::new(DevnetConfig::pi())
+    }
+
+    pub fn stellar() -> Self {
+        Self::new(DevnetConfig::stellar())
+    }
+
+    /// Credits an account outside of any transaction, as at genesis.
+    pub fn fund(&self, account: &str, asset: &Asset, amount: u64) {
+        let mut state = self.state.lock().unwrap();
+        *state.balances.entry((account.to_string(), asset.clone())).or_insert(0) += amount;
+    }
+
+    /// Closes a block including every ready transaction in submission order, and returns its height.
+    pub fn close_block(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.height += 1;
+        let height = state.height;
+        let (ready, waiting): (Vec<PendingTx>, Vec<PendingTx>) = state.mempool.drain(..).partition(|tx| tx.ready_at <= height);
+        state.mempool = waiting;
+
+        for tx in ready {
+            let successful = match &tx.transfer {
+                _ if tx.fail => false,
+                Some(transfer) => apply_transfer(&mut state, &tx.tx_id, transfer),
+                None => true,
+            };
+            debug!("{} block {} includes {} (successful: {})", self.config.chain_name, height, tx.tx_id, successful);
+            state.transactions.insert(tx.tx_id, IncludedTx { block: height, successful });
+        }
+        height
+    }
+
+    /// Closes `count` blocks and returns the latest height.
+    pub fn close_blocks(&self, count: u64) -> u64 {
+        (0..count).map(|_| self.close_block()).last().unwrap_or_else(|| self.state.lock().unwrap().height)
+    }
+
+    /// Number of submitted transactions not yet in a block.
+    pub fn pending_transactions(&self) -> usize {
+        self.state.lock().unwrap().mempool.len()
+    }
+
+    /// Makes the next `times` calls of a kind fail with an `INJECTED_FAILURE_STATUS` error.
+    pub fn fail_next(&self, call: DevnetCall, times: usize) {
+        *self.state.lock().unwrap().faults.entry(call).or_insert(0) += times;
+    }
+
+    /// Delays every call of a kind by `delay`; a zero delay removes it.
+    pub fn set_delay(&self, call: DevnetCall, delay: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if delay.is_zero() {
+            state.delays.remove(&call);
+        } else {
+            state.delays.insert(call, delay);
+        }
+    }
+
+    /// Makes the next `count` submitted transfers fail when included, whatever their balance.
+    pub fn fail_next_transactions(&self, count: usize) {
+        self.state.lock().unwrap().failing_transactions += count;
+    }
+
+    /// Delays the inclusion of transactions submitted from now on by `blocks` extra blocks;
+    /// zero restores inclusion in the next block.
+    pub fn hold_transactions(&self, blocks: u64) {
+        self.state.lock().unwrap().held_blocks = blocks;
+    }
+
+    /// Spawns a loop closing a block every `block_interval` onto the current runtime.
+    pub fn start(self: Arc<Self>) -> DevnetHandle {
+        let (shutdown, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            info!("{} devnet started, closing blocks every {:?}", self.config.chain_name, self.config.block_interval);
+            let mut ticker = tokio::time::interval(self.config.block_interval);
+            // The first tick completes immediately; the first block closes one interval in
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        self.close_block();
+                    }
+                    _ = stopped.changed() => break,
+                }
+            }
+        });
+        DevnetHandle { shutdown, task }
+    }
+
+    // Applies any delay and injected failure for a call
+    async fn call(&self, call: DevnetCall) -> Result<(), ChainError> {
+        let (delay, fail) = {
+            let mut state = self.state.lock().unwrap();
+            let fail = match state.faults.get_mut(&call) {
+                Some(remaining) if *remaining > 0 => {
+                    *remaining -= 1;
+                    true
+                }
+                _ => false,
+            };
+            (state.delays.get(&call).copied(), fail)
+        };
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        if fail {
+            return Err(ChainError::Api {
+                status: INJECTED_FAILURE_STATUS,
+                message: format!("Injected {:?} failure on {}", call, self.config.chain_name),
+            });
+        }
+        Ok(())
+    }
+
+    // Queues a transaction for the next block that accepts it and returns its ID, which is
+    // assigned unless the transaction was built with one
+    fn queue(&self, state: &mut DevnetState, transfer: Option<Transfer>, tx_id: Option<String>) -> String {
+        let tx_id = tx_id.unwrap_or_else(|| {
+            state.tx_count += 1;
+            hex::encode(digest(&SHA256, format!("{}:{}", self.config.chain_name, state.tx_count).as_bytes()))
+        });
+        let fail = transfer.is_some() && state.failing_transactions > 0;
+        if fail {
+            state.failing_transactions -= 1;
+        }
+        let ready_at = state.height + 1 + state.held_blocks;
+        state.mempool.push(PendingTx { tx_id: tx_id.clone(), transfer, ready_at, fail });
+        tx_id
+    }
+}
+
+impl DevnetHandle {
+    /// Stops closing blocks and waits for the loop to exit.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+// Moves a transfer's funds, returning false if the sender cannot cover it
+fn apply_transfer(state: &mut DevnetState, tx_id: &str, transfer: &Transfer) -> bool {
+    let issuer = match &transfer.asset {
+        Asset::Issued { issuer, .. } => Some(issuer.as_str()),
+        Asset::Native => None,
+    };
+    if issuer != Some(transfer.from.as_str()) {
+        let balance = state.balances.entry((transfer.from.clone(), transfer.asset.clone())).or_insert(0);
+        if *balance < transfer.amount {
+            return false;
+        }
+        *balance -= transfer.amount;
+    }
+    if issuer != Some(transfer.to.as_str()) {
+        *state.balances.entry((transfer.to.clone(), transfer.asset.clone())).or_insert(0) += transfer.amount;
+    }
+    let cursor = (state.payments.len() + 1).to_string();
+    state.payments.push(Payment {
+        id: tx_id.to_string(),
+        tx_id: tx_id.to_string(),
+        from: transfer.from.clone(),
+        to: transfer.to.clone(),
+        asset: transfer.asset.clone(),
+        amount: transfer.amount,
+        memo: transfer.memo.clone(),
+        cursor,
+    });
+    true
+}
+
+fn rejected(message: String) -> ChainError {
+    ChainError::Api { status: 400, message }
+}
+
+#[async_trait]
+impl Chain for Devnet {
+    fn chain_name(&self) -> &str {
+        &self.config.chain_name
+    }
+
+    fn decimals(&self) -> u32 {
+        self.config.decimals
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        !self.config.strict_addresses || strkey::is_valid_destination(address)
+    }
+
+    async fn balance(&self, account: &str, asset: &Asset) -> Result<u64, ChainError> {
+        self.call(DevnetCall::Balance).await?;
+        let state = self.state.lock().unwrap();
+        Ok(state.balances.get(&(account.to_string(), asset.clone())).copied().unwrap_or(0))
+    }
+
+    async fn build_transfer(&self, transfer: &Transfer) -> Result<SignedTransfer, ChainError> {
+        if transfer.amount == 0 {
+            return Err(rejected("Amount must be positive".to_string()));
+        }
+        for address in [&transfer.from, &transfer.to] {
+            if !self.is_valid_address(address) {
+                return Err(rejected(format!("Invalid address {}", address)));
+            }
+        }
+        let mut payload = json!({
+            "from": transfer.from,
+            "to": transfer.to,
+            "asset": transfer.asset,
+            "amount": transfer.amount,
+            "memo": transfer.memo,
+        });
+        if self.config.idempotent_references {
+            return Ok(SignedTransfer { transfer: transfer.clone(), payload: payload.to_string(), tx_id: None, expires_at: None });
+        }
+        // Like a signed Stellar transaction, the transfer's ID is known before it is submitted
+        let sequence = {
+            let mut state = self.state.lock().unwrap();
+            state.built_transfers += 1;
+            state.built_transfers
+        };
+        payload["sequence"] = json!(sequence);
+        let tx_id = hex::encode(digest(&SHA256, format!("{}:built:{}", self.config.chain_name, sequence).as_bytes()));
+        Ok(SignedTransfer { transfer: transfer.clone(), payload: payload.to_string(), tx_id: Some(tx_id), expires_at: None })
+    }
+
+    async fn submit_transfer(&self, signed: &SignedTransfer) -> Result<String, ChainError> {
+        self.call(DevnetCall::Submit).await?;
+        let mut state = self.state.lock().unwrap();
+        if let Some(tx_id) = &signed.tx_id {
+            // The same transaction is only applied once, however often it is submitted
+            if state.transactions.contains_key(tx_id) || state.mempool.iter().any(|tx| &tx.tx_id == tx_id) {
+                return Ok(tx_id.clone());
+            }
+        }
+        let reference = signed.transfer.reference.as_ref().filter(|_| self.config.idempotent_references);
+        if let Some(tx_id) = reference.and_then(|reference| state.references.get(reference)) {
+            return Ok(tx_id.clone());
+        }
+        let tx_id = self.queue(&mut state, Some(signed.transfer.clone()), signed.tx_id.clone());
+        if let Some(reference) = reference {
+            state.references.insert(reference.clone(), tx_id.clone());
+        }
+        Ok(tx_id)
+    }
+
+    async fn transaction(&self, tx_id: &str) -> Result<ChainTransaction, ChainError> {
+        self.call(DevnetCall::Transaction).await?;
+        let state = self.state.lock().unwrap();
+        let included = state.transactions.get(tx_id).ok_or_else(|| ChainError::NotFound(format!("Transaction {}", tx_id)))?;
+        Ok(ChainTransaction {
+            tx_id: tx_id.to_string(),
+            block: included.block,
+            confirmations: state.height + 1 - included.block,
+            successful: included.successful,
+        })
+    }
+
+    async fn payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<Payment>, ChainError> {
+        self.call(DevnetCall::Payments).await?;
+        let after: usize = match cursor {
+            Some(cursor) => cursor.parse().map_err(|_| rejected(format!("Invalid cursor {}", cursor)))?,
+            None => 0,
+        };
+        let state = self.state.lock().unwrap();
+        Ok(state.payments
+            .iter()
+            .skip(after)
+            .filter(|payment| payment.from == account || payment.to == account)
+            .cloned()
+            .collect())
+    }
+
+    async fn latest_block(&self) -> Result<u64, ChainError> {
+        self.call(DevnetCall::LatestBlock).await?;
+        Ok(self.state.lock().unwrap().height)
+    }
+
+    async fn issued_supply(&self, asset: &Asset) -> Result<u64, ChainError> {
+        self.call(DevnetCall::Balance).await?;
+        let state = self.state.lock().unwrap();
+        Ok(state.balances.iter().filter(|((_, held), _)| held == asset).map(|(_, amount)| amount).sum())
+    }
+}
+
+#[async_trait]
+impl HtlcChain for Devnet {
+    async fn lock_funds(&self, params: &HtlcParams) -> Result<String, ChainError> {
+        self.call(DevnetCall::Htlc).await?;
+        let mut state = self.state.lock().unwrap();
+        // A hashlock is only locked once, so a repeated lock is a retry
+        if let Some(lock) = state.locks.values().find(|lock| lock.hashlock == params.hashlock) {
+            return Ok(lock.lock_id.clone());
+        }
+        if params.amount == 0 {
+            return Err(rejected("Amount must be positive".to_string()));
+        }
+        let balance = state.balances.entry((params.sender.clone(), Asset::Native)).or_insert(0);
+        if *balance < params.amount {
+            return Err(rejected(format!("{} cannot cover a lock of {}", params.sender, params.amount)));
+        }
+        *balance -= params.amount;
+
+        let lock_id = self.queue(&mut state, None, None);
+        state.locks.insert(lock_id.clone(), HtlcLock {
+            lock_id: lock_id.clone(),
+            sender: params.sender.clone(),
+            receiver: params.receiver.clone(),
+            amount: params.amount,
+            hashlock: params.hashlock.clone(),
+            expires_at: params.expires_at,
+            status: HtlcStatus::Locked,
+            preimage: None,
+        });
+        Ok(lock_id)
+    }
+
+    async fn get_lock(&self, lock_id: &str) -> Result<HtlcLock, ChainError> {
+        self.call(DevnetCall::Htlc).await?;
+        let state = self.state.lock().unwrap();
+        state.locks.get(lock_id).cloned().ok_or_else(|| ChainError::NotFound(format!("Lock {}", lock_id)))
+    }
+
+    async fn find_lock(&self, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError> {
+        self.call(DevnetCall::Htlc).await?;
+        let state = self.state.lock().unwrap();
+        Ok(state.locks.values().find(|lock| lock.hashlock == hashlock).cloned())
+    }
+
+    async fn claim(&self, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError> {
+        self.call(DevnetCall::Htlc).await?;
+        let mut state = self.state.lock().unwrap();
+        let lock = state.locks.get(lock_id).cloned().ok_or_else(|| ChainError::NotFound(format!("Lock {}", lock_id)))?;
+        if lock.status != HtlcStatus::Locked {
+            return Err(ChainError::Api { status: 409, message: format!("Lock {} is {:?}", lock_id, lock.status) });
+        }
+        if hashlock_for(preimage) != lock.hashlock {
+            return Err(rejected("Preimage does not match the hashlock".to_string()));
+        }
+        if Utc::now() >= lock.expires_at {
+            return Err(rejected(format!("Lock {} has expired", lock_id)));
+        }
+        *state.balances.entry((lock.receiver.clone(), Asset::Native)).or_insert(0) += lock.amount;
+        let stored = state.locks.get_mut(lock_id).expect("lock exists");
+        stored.status = HtlcStatus::Claimed;
+        stored.preimage = Some(hex::encode(preimage));
+        Ok(self.queue(&mut state, None, None))
+    }
+
+    async fn refund(&self, lock_id: &str) -> Result<String, ChainError> {
+        self.call(DevnetCall::Htlc).await?;
+        let mut state = self.state.lock().unwrap();
+        let lock = state.locks.get(lock_id).cloned().ok_or_else(|| ChainError::NotFound(format!("Lock {}", lock_id)))?;
+        if lock.status != HtlcStatus::Locked {
+            return Err(ChainError::Api { status: 409, message: format!("Lock {} is {:?}", lock_id, lock.status) });
+        }
+        if Utc::now() < lock.expires_at {
+            return Err(rejected(format!("Lock {} has not expired", lock_id)));
+        }
+        *state.balances.entry((lock.sender.clone(), Asset::Native)).or_insert(0) += lock.amount;
+        state.locks.get_mut(lock_id).expect("lock exists").status = HtlcStatus::Refunded;
+        Ok(self.queue(&mut state, None, None))
+    }
+}
@@ -0,0 +1,442 @@
+// Finds conversion paths across the Stellar DEX's order books and liquidity pools and turns
+// quotes into path payment operations
+
+use std::collections::HashMap;
+use thiserror::Error;
+use super::horizon::{parse_amount, HorizonError, LiquidityPoolResponse};
+use super::network::NetworkProfile;
+use super::stellar_integration::{check_destination, StellarAsset, StellarIntegration};
+use super::stellar_signer::xdr_asset;
+use crate::models::stellar::transaction::{MuxedAccount, Operation, MAX_PATH_LENGTH};
+
+/// Most conversions a path payment can chain: its intermediate assets plus one.
+pub const MAX_HOPS: usize = MAX_PATH_LENGTH + 1;
+
+const BPS: u128 = 10_000;
+
+/// Errors returned while quoting conversions.
+#[derive(Debug, Error)]
+pub enum DexError {
+    #[error(transparent)]
+    Horizon(#[from] HorizonError),
+
+    #[error("No path from {from} to {to} with enough liquidity")]
+    NoPath { from: String, to: String },
+
+    #[error("Invalid asset: {0}")]
+    InvalidAsset(String),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+/// An offer available when converting one asset into another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offer {
+    /// Price of one unit of the received asset in the paid asset, as `price_n / price_d`.
+    pub price_n: u64,
+    pub price_d: u64,
+    /// Amount of the received asset on offer, in stroops.
+    pub amount: u64,
+}
+
+/// A constant product liquidity pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pool {
+    pub id: String,
+    /// Fee taken from every trade, in basis points.
+    pub fee_bp: u32,
+    /// The two assets and their reserves in stroops.
+    pub reserves: [(StellarAsset, u64); 2],
+}
+
+impl Pool {
+    fn from_response(pool: &LiquidityPoolResponse) -> Result<Option<Self>, HorizonError> {
+        let [a, b] = pool.reserves.as_slice() else {
+            return Ok(None);
+        };
+        let asset = |canonical: &str| {
+            StellarAsset::from_canonical(canonical)
+                .ok_or_else(|| HorizonError::InvalidResponse(format!("invalid reserve asset `{}`", canonical)))
+        };
+        Ok(Some(Self {
+            id: pool.id.clone(),
+            fee_bp: pool.fee_bp,
+            reserves: [(asset(&a.asset)?, parse_amount(&a.amount)?), (asset(&b.asset)?, parse_amount(&b.amount)?)],
+        }))
+    }
+
+    // Reserves of `from` and `to`, if the pool trades that pair
+    fn reserves(&self, from: &StellarAsset, to: &StellarAsset) -> Option<(u128, u128)> {
+        match &self.reserves {
+            [(a, ra), (b, rb)] if a == from && b == to => Some((*ra as u128, *rb as u128)),
+            [(a, ra), (b, rb)] if a == to && b == from => Some((*rb as u128, *ra as u128)),
+            _ => None,
+        }
+    }
+
+    fn fee_factor(&self) -> u128 {
+        BPS - (self.fee_bp as u128).min(BPS)
+    }
+}
+
+/// Where a hop trades.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Venue {
+    OrderBook,
+    /// The pool with this ID.
+    LiquidityPool(String),
+}
+
+/// One conversion along a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    pub from: StellarAsset,
+    pub to: StellarAsset,
+    pub venue: Venue,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
+
+/// Which side of a conversion is fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteKind {
+    /// Spend exactly the send amount.
+    StrictSend,
+    /// Deliver exactly the destination amount.
+    StrictReceive,
+}
+
+/// The best conversion found between two assets. Amounts are in stroops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub kind: QuoteKind,
+    pub send_asset: StellarAsset,
+    pub send_amount: u64,
+    pub dest_asset: StellarAsset,
+    pub dest_amount: u64,
+    pub hops: Vec<Hop>,
+    /// How much worse the quoted rate is than the rate for a tiny amount along the same hops,
+    /// in basis points; the slippage caused by the trade's own size.
+    pub price_impact_bps: u32,
+}
+
+impl Quote {
+    /// The intermediate assets, as used in a path payment's `path`.
+    pub fn path(&self) -> Vec<StellarAsset> {
+        self.hops.iter().skip(1).map(|hop| hop.from.clone()).collect()
+    }
+
+    /// Builds the path payment for this quote to `destination`, tolerating the rate moving
+    /// against it by up to `slippage_bps` before the payment is applied: a strict send
+    /// accepts that much less, a strict receive spends that much more.
+    pub fn to_operation(&self, destination: &str, slippage_bps: u32) -> Result<Operation, DexError> {
+        check_destination(destination)?;
+        let destination = MuxedAccount::from_strkey(destination).map_err(|e| DexError::Other(e.to_string()))?;
+        let asset = |asset: &StellarAsset| xdr_asset(asset).map_err(DexError::InvalidAsset);
+        let path = self.path().iter().map(asset).collect::<Result<Vec<_>, _>>()?;
+        let slippage = (slippage_bps as u128).min(BPS);
+        let stroops = |amount: u128| i64::try_from(amount).map_err(|_| DexError::Other(format!("Amount {} is too large", amount)));
+
+        Ok(match self.kind {
+            QuoteKind::StrictSend => Operation::path_payment_strict_send(
+                asset(&self.send_asset)?,
+                stroops(self.send_amount as u128)?,
+                destination,
+                asset(&self.dest_asset)?,
+                stroops((self.dest_amount as u128 * (BPS - slippage) / BPS).max(1))?,
+                path,
+            ),
+            QuoteKind::StrictReceive => Operation::path_payment_strict_receive(
+                asset(&self.send_asset)?,
+                stroops((self.send_amount as u128 * (BPS + slippage)).div_ceil(BPS))?,
+                destination,
+                asset(&self.dest_asset)?,
+                stroops(self.dest_amount as u128)?,
+                path,
+            ),
+        })
+    }
+}
+
+/// The anchor assets, such as USDC, that a registered network lists under `stellar_assets`.
+pub fn anchors(profile: &NetworkProfile) -> Vec<StellarAsset> {
+    profile.stellar_assets.iter().map(StellarAsset::from).collect()
+}
+
+/// A snapshot of the order books and liquidity pools between a set of assets.
+#[derive(Debug, Clone, Default)]
+pub struct Market {
+    // Offers for converting the first asset into the second, cheapest first
+    books: HashMap<(StellarAsset, StellarAsset), Vec<Offer>>,
+    pools: Vec<Pool>,
+}
+
+impl Market {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds offers for converting `from` into `to`.
+    pub fn add_offers(&mut self, from: StellarAsset, to: StellarAsset, offers: Vec<Offer>) {
+        let book = self.books.entry((from, to)).or_default();
+        book.extend(offers.into_iter().filter(|o| o.amount > 0 && o.price_n > 0 && o.price_d > 0));
+        book.sort_by(|a, b| (a.price_n as u128 * b.price_d as u128).cmp(&(b.price_n as u128 * a.price_d as u128)));
+    }
+
+    pub fn add_pool(&mut self, pool: Pool) {
+        self.pools.push(pool);
+    }
+
+    /// Loads the order books between every pair of `assets`, up to `depth` price levels each,
+    /// and the liquidity pools between them.
+    pub async fn load(stellar: &StellarIntegration, assets: &[StellarAsset], depth: u32) -> Result<Self, DexError> {
+        let mut market = Self::new();
+        for (i, from) in assets.iter().enumerate() {
+            for (j, to) in assets.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                // Converting `from` into `to` takes the asks of the book selling `to` for `from`
+                let book = stellar.order_book(to, from, depth).await?;
+                let offers = book
+                    .asks
+                    .iter()
+                    .map(|ask| Ok(Offer { price_n: ask.price_r.n, price_d: ask.price_r.d, amount: parse_amount(&ask.amount)? }))
+                    .collect::<Result<Vec<_>, HorizonError>>()?;
+                market.add_offers(from.clone(), to.clone(), offers);
+
+                if i < j {
+                    for pool in stellar.liquidity_pools(from, to).await? {
+                        if let Some(pool) = Pool::from_response(&pool)? {
+                            market.add_pool(pool);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(market)
+    }
+
+    /// Finds the path of up to `max_hops` conversions that delivers the most `dest_asset`
+    /// for exactly `send_amount` of `send_asset`.
+    pub fn quote_strict_send(
+        &self,
+        send_asset: &StellarAsset,
+        send_amount: u64,
+        dest_asset: &StellarAsset,
+        max_hops: usize,
+    ) -> Result<Quote, DexError> {
+        self.check_request(send_asset, send_amount, dest_asset)?;
+        let best = self
+            .paths(send_asset, dest_asset, max_hops)
+            .into_iter()
+            .filter_map(|path| self.send_along(&path, send_amount))
+            .max_by(|a, b| a.last().map(|h| h.amount_out).cmp(&b.last().map(|h| h.amount_out)).then(b.len().cmp(&a.len())));
+        self.quote(QuoteKind::StrictSend, send_asset, dest_asset, best)
+    }
+
+    /// Finds the path of up to `max_hops` conversions that delivers exactly `dest_amount` of
+    /// `dest_asset` for the least `send_asset`.
+    pub fn quote_strict_receive(
+        &self,
+        send_asset: &StellarAsset,
+        dest_asset: &StellarAsset,
+        dest_amount: u64,
+        max_hops: usize,
+    ) -> Result<Quote, DexError> {
+        self.check_request(send_asset, dest_amount, dest_asset)?;
+        let best = self
+            .paths(send_asset, dest_asset, max_hops)
+            .into_iter()
+            .filter_map(|path| self.receive_along(&path, dest_amount))
+            .min_by(|a, b| a[0].amount_in.cmp(&b[0].amount_in).then(a.len().cmp(&b.len())));
+        self.quote(QuoteKind::StrictReceive, send_asset, dest_asset, best)
+    }
+
+    fn check_request(&self, send_asset: &StellarAsset, amount: u64, dest_asset: &StellarAsset) -> Result<(), DexError> {
+        if send_asset == dest_asset {
+            return Err(DexError::Other("Source and destination assets are the same".to_string()));
+        }
+        if amount == 0 {
+            return Err(DexError::Other("Amount must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    fn quote(&self, kind: QuoteKind, send_asset: &StellarAsset, dest_asset: &StellarAsset, hops: Option<Vec<Hop>>) -> Result<Quote, DexError> {
+        let hops = hops.ok_or_else(|| DexError::NoPath { from: send_asset.canonical(), to: dest_asset.canonical() })?;
+        let send_amount = hops[0].amount_in;
+        let dest_amount = hops[hops.len() - 1].amount_out;
+        let spot: f64 = hops.iter().map(|hop| self.spot_rate(hop)).product();
+        let effective = dest_amount as f64 / send_amount as f64;
+        let impact = if spot > 0.0 { (1.0 - effective / spot).max(0.0) } else { 0.0 };
+        Ok(Quote {
+            kind,
+            send_asset: send_asset.clone(),
+            send_amount,
+            dest_asset: dest_asset.clone(),
+            dest_amount,
+            hops,
+            price_impact_bps: (impact * BPS as f64).round() as u32,
+        })
+    }
+
+    // Every simple path of at most `max_hops` conversions between the two assets
+    fn paths(&self, from: &StellarAsset, to: &StellarAsset, max_hops: usize) -> Vec<Vec<StellarAsset>> {
+        let mut paths = Vec::new();
+        let mut current = vec![from.clone()];
+        self.extend_paths(to, max_hops.min(MAX_HOPS), &mut current, &mut paths);
+        paths
+    }
+
+    fn extend_paths(&self, to: &StellarAsset, max_hops: usize, current: &mut Vec<StellarAsset>, paths: &mut Vec<Vec<StellarAsset>>) {
+        if current.len() > max_hops {
+            return;
+        }
+        let last = current[current.len() - 1].clone();
+        for next in self.neighbours(&last) {
+            if current.contains(&next) {
+                continue;
+            }
+            current.push(next.clone());
+            if &next == to {
+                paths.push(current.clone());
+            } else {
+                self.extend_paths(to, max_hops, current, paths);
+            }
+            current.pop();
+        }
+    }
+
+    fn neighbours(&self, from: &StellarAsset) -> Vec<StellarAsset> {
+        let mut neighbours: Vec<StellarAsset> = self
+            .books
+            .iter()
+            .filter(|((a, _), offers)| a == from && !offers.is_empty())
+            .map(|((_, b), _)| b.clone())
+            .chain(self.pools.iter().filter_map(|pool| match &pool.reserves {
+                [(a, _), (b, _)] if a == from => Some(b.clone()),
+                [(a, _), (b, _)] if b == from => Some(a.clone()),
+                _ => None,
+            }))
+            .collect();
+        // A stable order keeps quotes deterministic when paths tie
+        neighbours.sort_by_key(StellarAsset::canonical);
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn send_along(&self, path: &[StellarAsset], amount: u64) -> Option<Vec<Hop>> {
+        let mut hops = Vec::new();
+        let mut amount_in = amount;
+        for pair in path.windows(2) {
+            let (venue, amount_out) = self.best_send(&pair[0], &pair[1], amount_in)?;
+            hops.push(Hop { from: pair[0].clone(), to: pair[1].clone(), venue, amount_in, amount_out });
+            amount_in = amount_out;
+        }
+        Some(hops)
+    }
+
+    fn receive_along(&self, path: &[StellarAsset], amount: u64) -> Option<Vec<Hop>> {
+        let mut hops = Vec::new();
+        let mut amount_out = amount;
+        for pair in path.windows(2).rev() {
+            let (venue, amount_in) = self.best_receive(&pair[0], &pair[1], amount_out)?;
+            hops.push(Hop { from: pair[0].clone(), to: pair[1].clone(), venue, amount_in, amount_out });
+            amount_out = amount_in;
+        }
+        hops.reverse();
+        Some(hops)
+    }
+
+    // Like the network, each hop trades entirely on the order book or entirely in one pool,
+    // whichever is better
+    fn best_send(&self, from: &StellarAsset, to: &StellarAsset, amount_in: u64) -> Option<(Venue, u64)> {
+        let book = self.book(from, to).and_then(|offers| book_send(offers, amount_in)).map(|out| (Venue::OrderBook, out));
+        let pools = self.pools.iter().filter_map(|pool| {
+            let (reserve_in, reserve_out) = pool.reserves(from, to)?;
+            let amount_in = amount_in as u128 * pool.fee_factor();
+            let out = amount_in * reserve_out / (reserve_in * BPS + amount_in);
+            (out > 0).then(|| (Venue::LiquidityPool(pool.id.clone()), out as u64))
+        });
+        book.into_iter().chain(pools).max_by_key(|(_, out)| *out)
+    }
+
+    fn best_receive(&self, from: &StellarAsset, to: &StellarAsset, amount_out: u64) -> Option<(Venue, u64)> {
+        let book = self.book(from, to).and_then(|offers| book_receive(offers, amount_out)).map(|cost| (Venue::OrderBook, cost));
+        let pools = self.pools.iter().filter_map(|pool| {
+            let (reserve_in, reserve_out) = pool.reserves(from, to)?;
+            let amount_out = amount_out as u128;
+            if amount_out >= reserve_out {
+                return None;
+            }
+            let cost = (reserve_in * amount_out * BPS).div_ceil((reserve_out - amount_out) * pool.fee_factor());
+            u64::try_from(cost).ok().map(|cost| (Venue::LiquidityPool(pool.id.clone()), cost))
+        });
+        book.into_iter().chain(pools).min_by_key(|(_, cost)| *cost)
+    }
+
+    fn book(&self, from: &StellarAsset, to: &StellarAsset) -> Option<&[Offer]> {
+        self.books.get(&(from.clone(), to.clone())).map(Vec::as_slice)
+    }
+
+    // Units of `to` received per unit of `from` for a tiny amount at the hop's venue
+    fn spot_rate(&self, hop: &Hop) -> f64 {
+        match &hop.venue {
+            Venue::OrderBook => self
+                .book(&hop.from, &hop.to)
+                .and_then(|offers| offers.first())
+                .map_or(0.0, |best| best.price_d as f64 / best.price_n as f64),
+            Venue::LiquidityPool(id) => self
+                .pools
+                .iter()
+                .find(|pool| &pool.id == id)
+                .and_then(|pool| {
+                    let (reserve_in, reserve_out) = pool.reserves(&hop.from, &hop.to)?;
+                    Some(reserve_out as f64 / reserve_in as f64 * pool.fee_factor() as f64 / BPS as f64)
+                })
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+// Amount received for spending exactly `amount_in` against offers sorted cheapest first, or
+// `None` if the book cannot absorb it
+fn book_send(offers: &[Offer], amount_in: u64) -> Option<u64> {
+    let mut remaining = amount_in as u128;
+    let mut received = 0u128;
+    for offer in offers {
+        let (n, d) = (offer.price_n as u128, offer.price_d as u128);
+        let take = (remaining * d / n).min(offer.amount as u128);
+        if take == 0 {
+            break;
+        }
+        remaining -= (take * n).div_ceil(d);
+        received += take;
+    }
+    // Whatever is left must be too little to buy a single stroop even at the highest price
+    let last = offers.last()?;
+    if received == 0 || remaining * last.price_d as u128 / last.price_n as u128 > 0 {
+        return None;
+    }
+    u64::try_from(received).ok()
+}
+
+// Amount spent to receive exactly `amount_out` from offers sorted cheapest first
+fn book_receive(offers: &[Offer], amount_out: u64) -> Option<u64> {
+    let mut remaining = amount_out as u128;
+    let mut cost = 0u128;
+    for offer in offers {
+        if remaining == 0 {
+            break;
+        }
+        let take = remaining.min(offer.amount as u128);
+        cost += (take * offer.price_n as u128).div_ceil(offer.price_d as u128);
+        remaining -= take;
+    }
+    if remaining > 0 {
+        return None;
+    }
+    u64::try_from(cost).ok()
+}
@@ -0,0 +1,59 @@
+use thiserror::Error;
+use super::horizon::{HorizonError, TransactionResultCode};
+use super::transport::TransportError;
+
+/// Errors returned by cross-chain clients and the services built on them.
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Horizon(#[from] HorizonError),
+
+    #[error("Request failed with status {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Circuit breaker open for {0}")]
+    CircuitOpen(String),
+
+    #[error("Fee of {required} stroops exceeds the maximum of {max}")]
+    FeeLimit { required: u64, max: u64 },
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ChainError {
+    /// Whether the chain reported that the requested item does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ChainError::NotFound(_) | ChainError::Horizon(HorizonError::NotFound(_)))
+    }
+
+    /// Whether the chain refused a signed transaction that it can never apply, because its
+    /// time bounds have passed or its sequence number has been used. A new one must be signed.
+    pub fn is_stale(&self) -> bool {
+        matches!(
+            self,
+            ChainError::Horizon(HorizonError::TransactionFailed {
+                transaction: TransactionResultCode::TooLate | TransactionResultCode::BadSequence,
+                ..
+            })
+        )
+    }
+}
+
+impl From<TransportError> for ChainError {
+    fn from(error: TransportError) -> Self {
+        match error {
+            TransportError::Http(e) => ChainError::Http(e),
+            TransportError::CircuitOpen(endpoint) => ChainError::CircuitOpen(endpoint),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Fee estimation and fee bumps for Stellar submissions
+
+use serde::{Deserialize, Serialize};
+use log::warn;
+use super::error::ChainError;
+use super::horizon::{FeeDistribution, FeeStats};
+
+/// Factor by which a fee bump at least raises the fee of a stuck transaction. Stellar Core only
+/// replaces a transaction waiting in its queue with a fee bump offering ten times its fee.
+pub const FEE_BUMP_MULTIPLIER: u64 = 10;
+
+/// A statistic of the fees charged in recent ledgers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeePercentile {
+    Min,
+    Mode,
+    P10,
+    P20,
+    P30,
+    P40,
+    P50,
+    P60,
+    P70,
+    P80,
+    P90,
+    P95,
+    P99,
+    Max,
+}
+
+impl FeePercentile {
+    /// The statistic's value in `fees`, in stroops per operation.
+    pub fn of(self, fees: &FeeDistribution) -> u64 {
+        match self {
+            FeePercentile::Min => fees.min,
+            FeePercentile::Mode => fees.mode,
+            FeePercentile::P10 => fees.p10,
+            FeePercentile::P20 => fees.p20,
+            FeePercentile::P30 => fees.p30,
+            FeePercentile::P40 => fees.p40,
+            FeePercentile::P50 => fees.p50,
+            FeePercentile::P60 => fees.p60,
+            FeePercentile::P70 => fees.p70,
+            FeePercentile::P80 => fees.p80,
+            FeePercentile::P90 => fees.p90,
+            FeePercentile::P95 => fees.p95,
+            FeePercentile::P99 => fees.p99,
+            FeePercentile::Max => fees.max,
+        }
+    }
+}
+
+/// How `StellarIntegration` prices its transactions. Fees are in stroops.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeConfig {
+    /// Which statistic of recently charged fees to offer per operation. Higher percentiles get
+    /// transactions in sooner during surge pricing.
+    pub percentile: FeePercentile,
+    /// Highest total fee a single transaction may offer, fee bumps included.
+    pub max_fee: u64,
+    /// How many times a stuck submission is fee-bumped before giving up.
+    pub max_bumps: u32,
+}
+
+impl Default for FeeConfig {
+    /// The most common recent fee, at most 0.01 XLM per transaction, and up to two bumps.
+    fn default() -> Self {
+        Self { percentile: FeePercentile::Mode, max_fee: 100_000, max_bumps: 2 }
+    }
+}
+
+/// Fee per operation to offer for a transaction with `operations` operations.
+///
+/// This is the configured percentile of recently charged fees, never below the network's base
+/// fee. If the percentile would take the transaction over `max_fee`, the highest fee within it
+/// is offered instead; if even the base fee would, `ChainError::FeeLimit` is returned.
+pub fn estimate_base_fee(stats: &FeeStats, config: &FeeConfig, operations: u32) -> Result<u32, ChainError> {
+    let operations = u64::from(operations.max(1));
+    let floor = stats.last_ledger_base_fee;
+    let wanted = config.percentile.of(&stats.fee_charged).max(floor);
+    let cap = config.max_fee / operations;
+    if cap < floor {
+        return Err(ChainError::FeeLimit { required: floor * operations, max: config.max_fee });
+    }
+    if wanted > cap {
+        warn!("Offering {} stroops per operation instead of {} to stay within the maximum fee of {}", cap, wanted, config.max_fee);
+    }
+    let fee = wanted.min(cap);
+    u32::try_from(fee).map_err(|_| ChainError::InvalidResponse(format!("fee {} out of range", fee)))
+}
+
+/// Fee per operation for a fee bump of a stuck transaction with `operations` operations that
+/// offered `previous` per operation.
+///
+/// The bump offers `FEE_BUMP_MULTIPLIER` times the previous fee or the current estimate,
+/// whichever is higher, capped so the total, which counts the bump as an extra operation,
+/// stays within `max_fee`. Returns `None` if the cap leaves no room above the previous fee.
+pub fn bump_base_fee(previous: u32, estimate: u32, config: &FeeConfig, operations: u32) -> Option<u32> {
+    let wanted = (u64::from(previous) * FEE_BUMP_MULTIPLIER).max(u64::from(estimate));
+    let cap = config.max_fee / (u64::from(operations.max(1)) + 1);
+    let fee = wanted.min(cap);
+    if fee <= u64::from(previous) {
+        return None;
+    }
+    u32::try_from(fee).ok()
+}
@@ -0,0 +1,506 @@
+use std::fmt;
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use log::error;
+use super::transport::{Transport, TransportError};
+
+/// Number of decimal places in Stellar amounts; one stroop is 10^-7 units.
+pub const STELLAR_DECIMALS: u32 = 7;
+
+/// Transaction-level result codes reported by Horizon in `extras.result_codes.transaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionResultCode {
+    /// One of the operations failed; see the operation result codes.
+    Failed,
+    TooEarly,
+    TooLate,
+    MissingOperation,
+    BadSequence,
+    BadAuth,
+    InsufficientBalance,
+    NoSourceAccount,
+    InsufficientFee,
+    BadAuthExtra,
+    InternalError,
+    NotSupported,
+    FeeBumpInnerFailed,
+    BadSponsorship,
+    Malformed,
+    Other(String),
+}
+
+impl From<&str> for TransactionResultCode {
+    fn from(code: &str) -> Self {
+        match code {
+            "tx_failed" => Self::Failed,
+            "tx_too_early" => Self::TooEarly,
+            "tx_too_late" => Self::TooLate,
+            "tx_missing_operation" => Self::MissingOperation,
+            "tx_bad_seq" => Self::BadSequence,
+            "tx_bad_auth" => Self::BadAuth,
+            "tx_insufficient_balance" => Self::InsufficientBalance,
+            "tx_no_source_account" => Self::NoSourceAccount,
+            "tx_insufficient_fee" => Self::InsufficientFee,
+            "tx_bad_auth_extra" => Self::BadAuthExtra,
+            "tx_internal_error" => Self::InternalError,
+            "tx_not_supported" => Self::NotSupported,
+            "tx_fee_bump_inner_failed" => Self::FeeBumpInnerFailed,
+            "tx_bad_sponsorship" => Self::BadSponsorship,
+            "tx_malformed" => Self::Malformed,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Operation-level result codes reported by Horizon in `extras.result_codes.operations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationResultCode {
+    Success,
+    Malformed,
+    Underfunded,
+    SourceNoTrust,
+    SourceNotAuthorized,
+    NoDestination,
+    NoTrust,
+    NotAuthorized,
+    LineFull,
+    NoIssuer,
+    LowReserve,
+    AlreadyExists,
+    TooFewOffers,
+    OverSourceMax,
+    UnderDestinationMin,
+    CrossSelf,
+    OfferNotFound,
+    BadAuth,
+    NoAccount,
+    Other(String),
+}
+
+impl From<&str> for OperationResultCode {
+    fn from(code: &str) -> Self {
+        match code {
+            "op_success" => Self::Success,
+            "op_malformed" => Self::Malformed,
+            "op_underfunded" => Self::Underfunded,
+            "op_src_no_trust" => Self::SourceNoTrust,
+            "op_src_not_authorized" => Self::SourceNotAuthorized,
+            "op_no_destination" => Self::NoDestination,
+            "op_no_trust" => Self::NoTrust,
+            "op_not_authorized" => Self::NotAuthorized,
+            "op_line_full" => Self::LineFull,
+            "op_no_issuer" => Self::NoIssuer,
+            "op_low_reserve" => Self::LowReserve,
+            "op_already_exists" => Self::AlreadyExists,
+            "op_too_few_offers" => Self::TooFewOffers,
+            "op_over_source_max" => Self::OverSourceMax,
+            "op_under_dest_min" => Self::UnderDestinationMin,
+            "op_cross_self" => Self::CrossSelf,
+            "op_offer_not_found" => Self::OfferNotFound,
+            "op_bad_auth" => Self::BadAuth,
+            "op_no_account" => Self::NoAccount,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TransactionResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Errors returned by the Horizon client.
+#[derive(Debug, Error)]
+pub enum HorizonError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// The transaction was rejected by the network.
+    #[error("Transaction failed: {transaction} {operations:?}")]
+    TransactionFailed {
+        transaction: TransactionResultCode,
+        operations: Vec<OperationResultCode>,
+        /// Base64 `TransactionResult` XDR, when Horizon provides it.
+        result_xdr: Option<String>,
+    },
+
+    /// Any other Horizon problem response, e.g. `rate_limit_exceeded` or `timeout`.
+    #[error("Horizon returned {status} {kind}: {detail}")]
+    Problem { status: u16, kind: String, detail: String },
+
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Invalid Stellar address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error("Circuit breaker open for {0}")]
+    CircuitOpen(String),
+}
+
+impl From<TransportError> for HorizonError {
+    fn from(error: TransportError) -> Self {
+        match error {
+            TransportError::Http(e) => HorizonError::Http(e),
+            TransportError::CircuitOpen(endpoint) => HorizonError::CircuitOpen(endpoint),
+        }
+    }
+}
+
+impl HorizonError {
+    /// Whether a submission timed out in Horizon; the transaction may still be applied.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, HorizonError::Problem { kind, .. } if kind == "timeout")
+    }
+}
+
+// Horizon's application/problem+json error body
+#[derive(Deserialize)]
+struct Problem {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    detail: String,
+    #[serde(default)]
+    extras: Option<ProblemExtras>,
+}
+
+#[derive(Deserialize)]
+struct ProblemExtras {
+    #[serde(default)]
+    result_codes: Option<ResultCodes>,
+    #[serde(default)]
+    result_xdr: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResultCodes {
+    transaction: String,
+    #[serde(default)]
+    operations: Vec<String>,
+}
+
+/// Parses a Horizon error response body into a typed error.
+pub fn parse_problem(status: u16, body: &str) -> HorizonError {
+    let problem: Problem = match serde_json::from_str(body) {
+        Ok(problem) => problem,
+        Err(_) => {
+            return HorizonError::Problem { status, kind: "unknown".to_string(), detail: body.to_string() };
+        }
+    };
+    if let Some(ProblemExtras { result_codes: Some(codes), result_xdr }) = problem.extras {
+        return HorizonError::TransactionFailed {
+            transaction: codes.transaction.as_str().into(),
+            operations: codes.operations.iter().map(|code| code.as_str().into()).collect(),
+            result_xdr,
+        };
+    }
+    // Problem types are URLs such as https://stellar.org/horizon-errors/rate_limit_exceeded
+    let kind = problem.kind.rsplit('/').next().unwrap_or_default().to_string();
+    let detail = if problem.detail.is_empty() { problem.title } else { problem.detail };
+    HorizonError::Problem { status, kind, detail }
+}
+
+/// Sends a request to Horizon and deserializes the response.
+pub(crate) async fn send<T: DeserializeOwned>(transport: &Transport, request: RequestBuilder) -> Result<T, HorizonError> {
+    read(transport.send(request).await?).await
+}
+
+/// Submits a request to Horizon with an idempotency key, so that it is retried on transient failures.
+pub(crate) async fn submit<T: DeserializeOwned>(transport: &Transport, request: RequestBuilder) -> Result<T, HorizonError> {
+    read(transport.submit(request, None).await?).await
+}
+
+async fn read<T: DeserializeOwned>(response: Response) -> Result<T, HorizonError> {
+    let status = response.status();
+    if status.is_success() {
+        let body: Value = response.json().await?;
+        return serde_json::from_value(body).map_err(|e| HorizonError::InvalidResponse(e.to_string()));
+    }
+    Err(read_error(response).await)
+}
+
+/// Turns an unsuccessful Horizon response into an error.
+pub(crate) async fn read_error(response: Response) -> HorizonError {
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return HorizonError::NotFound(response.url().path().to_string());
+    }
+    let body = response.text().await.unwrap_or_default();
+    let error = parse_problem(status.as_u16(), &body);
+    error!("Horizon request failed: {}", error);
+    error
+}
+
+/// Parses a Horizon decimal amount such as "10.5000000" into stroops.
+pub fn parse_amount(amount: &str) -> Result<u64, HorizonError> {
+    let invalid = || HorizonError::InvalidResponse(format!("invalid amount `{}`", amount));
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() || fraction.len() > STELLAR_DECIMALS as usize || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let whole: u64 = whole.parse().map_err(|_| invalid())?;
+    let fraction: u64 = format!("{:0<width$}", fraction, width = STELLAR_DECIMALS as usize)
+        .parse()
+        .map_err(|_| invalid())?;
+    whole
+        .checked_mul(10u64.pow(STELLAR_DECIMALS))
+        .and_then(|w| w.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// Formats stroops as a Horizon decimal amount.
+pub fn format_amount(stroops: u64) -> String {
+    let scale = 10u64.pow(STELLAR_DECIMALS);
+    format!("{}.{:07}", stroops / scale, stroops % scale)
+}
+
+// Horizon encodes 64-bit integers such as sequence numbers and fees as strings
+fn from_str_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
+
+/// A balance line of an account.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Balance {
+    /// Decimal amount, see `parse_amount`.
+    pub balance: String,
+    pub asset_type: String,
+    #[serde(default)]
+    pub asset_code: Option<String>,
+    #[serde(default)]
+    pub asset_issuer: Option<String>,
+    /// Decimal trust limit of a credit balance.
+    #[serde(default)]
+    pub limit: Option<String>,
+    /// Whether the issuer has authorized the holder; always set for credit balances.
+    #[serde(default)]
+    pub is_authorized: Option<bool>,
+    #[serde(default)]
+    pub is_clawback_enabled: Option<bool>,
+}
+
+/// Authorization flags of an issuing account.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccountFlags {
+    #[serde(default)]
+    pub auth_required: bool,
+    #[serde(default)]
+    pub auth_revocable: bool,
+    #[serde(default)]
+    pub auth_immutable: bool,
+    #[serde(default)]
+    pub auth_clawback_enabled: bool,
+}
+
+/// A signer of an account; `key` is a `G...`, `T...` (pre-authorized transaction) or `X...`
+/// (hash-x) StrKey.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AccountSigner {
+    pub key: String,
+    pub weight: u32,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+}
+
+/// An account as returned by `GET /accounts/{id}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AccountResponse {
+    pub id: String,
+    /// Current sequence number; the next transaction must use `sequence + 1`.
+    #[serde(deserialize_with = "from_str_number")]
+    pub sequence: i64,
+    #[serde(default)]
+    pub subentry_count: u32,
+    #[serde(default)]
+    pub balances: Vec<Balance>,
+    #[serde(default)]
+    pub flags: AccountFlags,
+    /// Every key that can sign for the account, including its master key.
+    #[serde(default)]
+    pub signers: Vec<AccountSigner>,
+}
+
+/// Percentiles of a fee distribution, in stroops per operation. Percentiles that older Horizon
+/// versions omit default to zero.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct FeeDistribution {
+    #[serde(deserialize_with = "from_str_number")]
+    pub min: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub mode: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p10: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p20: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p30: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p40: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub p50: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p60: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p70: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p80: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub p90: u64,
+    #[serde(default, deserialize_with = "from_str_number")]
+    pub p95: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub p99: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub max: u64,
+}
+
+/// Recent network fees as returned by `GET /fee_stats`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct FeeStats {
+    #[serde(deserialize_with = "from_str_number")]
+    pub last_ledger: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub last_ledger_base_fee: u64,
+    #[serde(deserialize_with = "from_str_number")]
+    pub ledger_capacity_usage: f64,
+    /// Fees actually charged in recent ledgers.
+    pub fee_charged: FeeDistribution,
+    /// Maximum fees offered in recent ledgers.
+    pub max_fee: FeeDistribution,
+}
+
+/// A transaction as returned by `GET /transactions/{hash}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TransactionResponse {
+    pub hash: String,
+    pub ledger: u64,
+    pub successful: bool,
+    #[serde(default)]
+    pub source_account: String,
+    #[serde(default)]
+    pub envelope_xdr: String,
+    #[serde(default)]
+    pub result_xdr: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// `text`, `id`, `hash` or `return`; hash and return memos are base64-encoded in `memo`.
+    #[serde(default)]
+    pub memo_type: Option<String>,
+}
+
+/// The root resource, `GET /`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RootResponse {
+    pub history_latest_ledger: u64,
+    #[serde(default)]
+    pub network_passphrase: String,
+}
+
+/// A price as an exact fraction.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceR {
+    pub n: u64,
+    pub d: u64,
+}
+
+/// One price level of an order book.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OrderBookEntry {
+    /// Price of one unit of the base asset in the counter asset.
+    pub price_r: PriceR,
+    #[serde(default)]
+    pub price: String,
+    /// Decimal amount, see `parse_amount`.
+    pub amount: String,
+}
+
+/// An order book as returned by `GET /order_book`, with the selling asset as the base.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OrderBookResponse {
+    /// Offers buying the base asset; amounts in the counter asset.
+    #[serde(default)]
+    pub bids: Vec<OrderBookEntry>,
+    /// Offers selling the base asset, cheapest first; amounts in the base asset.
+    #[serde(default)]
+    pub asks: Vec<OrderBookEntry>,
+}
+
+/// One asset held by a liquidity pool.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PoolReserve {
+    /// "native" or "CODE:ISSUER".
+    pub asset: String,
+    /// Decimal amount, see `parse_amount`.
+    pub amount: String,
+}
+
+/// A constant product liquidity pool as returned by `GET /liquidity_pools`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct LiquidityPoolResponse {
+    pub id: String,
+    /// Fee taken from every trade, in basis points.
+    pub fee_bp: u32,
+    pub reserves: Vec<PoolReserve>,
+}
+
+/// Amounts of an asset held in trustlines, by authorization state.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AssetBalances {
+    #[serde(default)]
+    pub authorized: Option<String>,
+    #[serde(default)]
+    pub authorized_to_maintain_liabilities: Option<String>,
+    #[serde(default)]
+    pub unauthorized: Option<String>,
+}
+
+/// Statistics of an issued asset as returned by `GET /assets`. Amounts are decimal, see
+/// `parse_amount`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AssetResponse {
+    pub asset_code: String,
+    pub asset_issuer: String,
+    #[serde(default)]
+    pub balances: AssetBalances,
+    #[serde(default)]
+    pub claimable_balances_amount: Option<String>,
+    #[serde(default)]
+    pub liquidity_pools_amount: Option<String>,
+    #[serde(default)]
+    pub contracts_amount: Option<String>,
+}
+
+impl AssetResponse {
+    /// Total amount in circulation in stroops: trustline balances of every authorization state,
+    /// plus the amounts held in claimable balances, liquidity pools and contracts.
+    pub fn total_supply(&self) -> Result<u64, HorizonError> {
+        let amounts = [
+            &self.balances.authorized,
+            &self.balances.authorized_to_maintain_liabilities,
+            &self.balances.unauthorized,
+            &self.claimable_balances_amount,
+            &self.liquidity_pools_amount,
+            &self.contracts_amount,
+        ];
+        amounts.into_iter().flatten().try_fold(0u64, |total, amount| {
+            total
+                .checked_add(parse_amount(amount)?)
+                .ok_or_else(|| HorizonError::InvalidResponse(format!("supply of {} overflows", self.asset_code)))
+        })
+    }
+}
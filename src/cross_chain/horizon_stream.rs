@@ -0,0 +1,413 @@
+// Streams ledgers, payments and effects from Horizon over server-sent events, resuming from
+// the last processed paging token after a restart
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use log::{info, warn, error};
+use super::horizon::{self, HorizonError};
+use super::stellar_integration::{check_account_id, PaymentRecord, StellarPayment};
+use crate::utils::json::JsonFileStore;
+
+/// Errors returned while streaming from Horizon.
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error(transparent)]
+    Horizon(#[from] HorizonError),
+
+    #[error("Event handler failed: {0}")]
+    Handler(String),
+
+    #[error("Cursor store error: {0}")]
+    Store(String),
+}
+
+/// A Horizon collection to stream.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    /// Every ledger as it closes.
+    Ledgers,
+    /// Payments sent or received by an account.
+    Payments { account: String },
+    /// Effects on an account, e.g. credits, debits and trustline changes.
+    Effects { account: String },
+}
+
+impl StreamKind {
+    /// Name under which the stream's cursor is saved, e.g. `payments:G...`.
+    pub fn key(&self) -> String {
+        match self {
+            StreamKind::Ledgers => "ledgers".to_string(),
+            StreamKind::Payments { account } => format!("payments:{}", account),
+            StreamKind::Effects { account } => format!("effects:{}", account),
+        }
+    }
+
+    fn path(&self) -> String {
+        match self {
+            StreamKind::Ledgers => "/ledgers".to_string(),
+            StreamKind::Payments { account } => format!("/accounts/{}/payments", account),
+            StreamKind::Effects { account } => format!("/accounts/{}/effects", account),
+        }
+    }
+
+    fn account(&self) -> Option<&str> {
+        match self {
+            StreamKind::Ledgers => None,
+            StreamKind::Payments { account } | StreamKind::Effects { account } => Some(account),
+        }
+    }
+
+    // Parses a streamed record; `None` for records that are not reported, such as operations
+    // other than payments and path payments
+    fn parse(&self, record: Value) -> Result<Option<HorizonEvent>, HorizonError> {
+        let invalid = |e: serde_json::Error| HorizonError::InvalidResponse(e.to_string());
+        Ok(match self {
+            StreamKind::Ledgers => Some(HorizonEvent::Ledger(serde_json::from_value(record).map_err(invalid)?)),
+            StreamKind::Payments { .. } => {
+                let record: PaymentRecord = serde_json::from_value(record).map_err(invalid)?;
+                record.into_payment()?.map(HorizonEvent::Payment)
+            }
+            StreamKind::Effects { .. } => Some(HorizonEvent::Effect(serde_json::from_value(record).map_err(invalid)?)),
+        })
+    }
+}
+
+/// A closed ledger as streamed from `/ledgers`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LedgerRecord {
+    pub sequence: u64,
+    pub hash: String,
+    pub paging_token: String,
+    #[serde(default)]
+    pub closed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub successful_transaction_count: u32,
+    #[serde(default)]
+    pub failed_transaction_count: u32,
+    #[serde(default)]
+    pub operation_count: u32,
+}
+
+/// An effect as streamed from `/accounts/{id}/effects`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EffectRecord {
+    pub id: String,
+    pub paging_token: String,
+    pub account: String,
+    /// Effect type, e.g. "account_credited".
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Type-specific fields such as `amount` or `asset_code`.
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+/// An event from a Horizon stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HorizonEvent {
+    Ledger(LedgerRecord),
+    Payment(StellarPayment),
+    Effect(EffectRecord),
+}
+
+impl HorizonEvent {
+    pub fn paging_token(&self) -> &str {
+        match self {
+            HorizonEvent::Ledger(ledger) => &ledger.paging_token,
+            HorizonEvent::Payment(payment) => &payment.paging_token,
+            HorizonEvent::Effect(effect) => &effect.paging_token,
+        }
+    }
+}
+
+/// Receives streamed events.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    /// Processes an event. An error stops the stream before the event's cursor is saved, so the
+    /// event is delivered again when the stream resumes.
+    async fn handle(&self, event: HorizonEvent) -> Result<(), String>;
+}
+
+/// Forwards events to a channel; an event counts as processed once the channel accepts it.
+#[async_trait]
+impl EventHandler for mpsc::Sender<HorizonEvent> {
+    async fn handle(&self, event: HorizonEvent) -> Result<(), String> {
+        self.send(event).await.map_err(|_| "event receiver was dropped".to_string())
+    }
+}
+
+/// Trait defining where stream cursors are saved between restarts
+pub trait CursorStore: Send + Sync {
+    /// Returns the saved cursors keyed by `StreamKind::key`.
+    fn load(&self) -> Result<HashMap<String, String>, String>;
+    fn save(&self, cursors: &HashMap<String, String>) -> Result<(), String>;
+}
+
+/// Stores stream cursors as a JSON document on disk
+pub struct JsonFileCursorStore {
+    file: JsonFileStore<HashMap<String, String>>,
+}
+
+impl JsonFileCursorStore {
+    /// Creates a new store backed by the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self { file: JsonFileStore::new("cursor store", path) }
+    }
+}
+
+impl CursorStore for JsonFileCursorStore {
+    fn load(&self) -> Result<HashMap<String, String>, String> {
+        Ok(self.file.load()?.unwrap_or_default())
+    }
+
+    fn save(&self, cursors: &HashMap<String, String>) -> Result<(), String> {
+        self.file.save(cursors)
+    }
+}
+
+/// One event of a `text/event-stream`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// The last event ID seen on the stream; Horizon sends the record's paging token.
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incremental parser for a `text/event-stream` body that may arrive in arbitrary chunks.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    // Bytes of an incomplete line, kept as bytes so split UTF-8 sequences survive
+    partial: Vec<u8>,
+    last_id: Option<String>,
+    event: Option<String>,
+    data: Vec<String>,
+    /// Reconnection delay requested by the server.
+    pub retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Feeds a chunk of the body and returns the events it completes.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.partial.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+            if let Some(event) = self.line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            // A blank line dispatches the event, if it has any data
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            let data = std::mem::take(&mut self.data).join("\n");
+            return Some(SseEvent { id: self.last_id.clone(), event, data });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "id" => self.last_id = Some(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "retry" => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Consumes Horizon streams and hands typed events to a handler.
+///
+/// The paging token of the last processed event is saved after each chunk of the stream, so a
+/// restarted streamer picks up after it. Records that cannot be parsed are logged and skipped.
+/// A stream without a saved cursor starts from now.
+pub struct HorizonStreamer {
+    // Streams stay open indefinitely, so they cannot share a client with a request timeout
+    client: Client,
+    api_url: String,
+    store: Arc<dyn CursorStore>,
+    cursors: Mutex<HashMap<String, String>>,
+    saving: tokio::sync::Mutex<()>,
+    reconnect_delay: Duration,
+}
+
+/// Handle to a running stream.
+pub struct StreamHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl HorizonStreamer {
+    /// Creates a streamer for a Horizon server, reloading saved cursors.
+    pub fn new(api_url: &str, store: Arc<dyn CursorStore>) -> Result<Self, StreamError> {
+        let cursors = store.load().map_err(StreamError::Store)?;
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Ok(Self {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            store,
+            cursors: Mutex::new(cursors),
+            saving: tokio::sync::Mutex::new(()),
+            reconnect_delay: Duration::from_secs(1),
+        })
+    }
+
+    /// Sets how long to wait before reconnecting after a stream ends or fails, unless the
+    /// server asks for a different delay.
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// The paging token of the last processed event of a stream.
+    pub fn cursor(&self, kind: &StreamKind) -> Option<String> {
+        self.cursors.lock().unwrap().get(&kind.key()).cloned()
+    }
+
+    /// Opens a stream and handles its events until the server closes it, returning the
+    /// number of events handled and the reconnection delay the server asked for.
+    pub async fn consume(&self, kind: &StreamKind, handler: &dyn EventHandler) -> Result<(usize, Option<Duration>), StreamError> {
+        if let Some(account) = kind.account() {
+            check_account_id(account)?;
+        }
+        let cursor = self.cursor(kind).unwrap_or_else(|| "now".to_string());
+        let mut request = self.client
+            .get(format!("{}{}", self.api_url, kind.path()))
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .query(&[("cursor", cursor.as_str())]);
+        if let StreamKind::Payments { .. } = kind {
+            request = request.query(&[("join", "transactions")]);
+        }
+        info!("Streaming {} from cursor {}", kind.key(), cursor);
+        let mut response = request.send().await.map_err(HorizonError::from)?;
+        if !response.status().is_success() {
+            return Err(horizon::read_error(response).await.into());
+        }
+
+        let mut parser = SseParser::default();
+        let mut handled = 0;
+        while let Some(chunk) = response.chunk().await.map_err(HorizonError::from)? {
+            // The cursor is saved once per chunk, after the last event processed in it
+            let mut processed = None;
+            for event in parser.feed(&chunk) {
+                // Horizon greets with "hello" and says "byebye" before closing
+                if !event.data.trim_start().starts_with('{') {
+                    continue;
+                }
+                let (paging_token, parsed) = match serde_json::from_str::<Value>(&event.data) {
+                    Ok(record) => (
+                        event.id.clone().or_else(|| record["paging_token"].as_str().map(str::to_string)),
+                        kind.parse(record),
+                    ),
+                    Err(e) => (event.id.clone(), Err(HorizonError::InvalidResponse(e.to_string()))),
+                };
+                match parsed {
+                    Ok(Some(parsed)) => {
+                        if let Err(e) = handler.handle(parsed).await {
+                            self.save_cursor(kind, processed).await?;
+                            return Err(StreamError::Handler(e));
+                        }
+                        handled += 1;
+                    }
+                    Ok(None) => {}
+                    // A record that cannot be read now never will be, so waiting on it would
+                    // stop the stream for good
+                    Err(e) => error!("Skipping unreadable {} event {:?}: {}", kind.key(), paging_token, e),
+                }
+                if paging_token.is_some() {
+                    processed = paging_token;
+                }
+            }
+            self.save_cursor(kind, processed).await?;
+        }
+        Ok((handled, parser.retry))
+    }
+
+    // Writes the cursors off the async runtime, one save at a time so an older snapshot never
+    // replaces a newer one
+    async fn save_cursor(&self, kind: &StreamKind, paging_token: Option<String>) -> Result<(), StreamError> {
+        let Some(paging_token) = paging_token else {
+            return Ok(());
+        };
+        let _saving = self.saving.lock().await;
+        let cursors = {
+            let mut cursors = self.cursors.lock().unwrap();
+            cursors.insert(kind.key(), paging_token);
+            cursors.clone()
+        };
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || store.save(&cursors))
+            .await
+            .map_err(|e| StreamError::Store(e.to_string()))?
+            .map_err(StreamError::Store)
+    }
+
+    /// Spawns a task that keeps the stream open, reconnecting from the saved cursor whenever
+    /// it ends or fails.
+    pub fn start(self: Arc<Self>, kind: StreamKind, handler: Arc<dyn EventHandler>) -> StreamHandle {
+        let (shutdown, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            loop {
+                let delay = tokio::select! {
+                    result = self.consume(&kind, handler.as_ref()) => match result {
+                        Ok((handled, retry)) => {
+                            info!("Stream {} closed after {} events", kind.key(), handled);
+                            retry.unwrap_or(self.reconnect_delay)
+                        }
+                        Err(e) => {
+                            error!("Stream {} failed: {}", kind.key(), e);
+                            self.reconnect_delay
+                        }
+                    },
+                    _ = stopped.changed() => break,
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = stopped.changed() => break,
+                }
+                warn!("Reconnecting stream {}", kind.key());
+            }
+            info!("Stream {} stopped", kind.key());
+        });
+        StreamHandle { shutdown, task }
+    }
+}
+
+impl StreamHandle {
+    /// Stops the stream and waits for it to exit.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use log::info;
+use super::chain::Chain;
+use super::error::ChainError;
+use super::http::{send, string_field, submit};
+use super::transport::Transport;
+
+/// State of a hashed timelock on its chain.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HtlcStatus {
+    Locked,
+    Claimed,
+    Refunded,
+}
+
+/// Parameters for locking funds under a hashlock and timeout.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HtlcParams {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: u64,
+    #[serde(with = "hex::serde")]
+    pub hashlock: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A hashed timelock as reported by the chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HtlcLock {
+    pub lock_id: String,
+    pub sender: String,
+    pub receiver: String,
+    pub amount: u64,
+    #[serde(with = "hex::serde")]
+    pub hashlock: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub status: HtlcStatus,
+    /// Hex-encoded preimage, present once the lock has been claimed.
+    #[serde(default)]
+    pub preimage: Option<String>,
+}
+
+/// A chain that supports hashed timelock contracts.
+///
+/// Amounts are in the chain's smallest unit.
+#[async_trait]
+pub trait HtlcChain: Chain {
+    /// Locks funds and returns the lock ID.
+    async fn lock_funds(&self, params: &HtlcParams) -> Result<String, ChainError>;
+    /// Retrieves a lock by its ID.
+    async fn get_lock(&self, lock_id: &str) -> Result<HtlcLock, ChainError>;
+    /// Finds a lock by its hashlock, if one exists.
+    async fn find_lock(&self, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError>;
+    /// Claims a lock by revealing its preimage and returns the transaction ID.
+    async fn claim(&self, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError>;
+    /// Refunds an expired lock to its sender and returns the transaction ID.
+    async fn refund(&self, lock_id: &str) -> Result<String, ChainError>;
+}
+
+/// Computes the hashlock for a preimage (SHA-256).
+pub fn hashlock_for(preimage: &[u8]) -> Vec<u8> {
+    digest(&SHA256, preimage).as_ref().to_vec()
+}
+
+// HTTP helpers for the HTLC endpoints of the Pi API: POST /htlc, GET /htlc/{id},
+// GET /htlc?hashlock=, POST /htlc/{id}/claim and POST /htlc/{id}/refund. Stellar has no such
+// endpoints; its locks are escrow accounts, see `stellar_htlc`.
+
+pub(crate) async fn lock_funds(transport: &Transport, api_url: &str, params: &HtlcParams) -> Result<String, ChainError> {
+    info!("Locking {} for {} under hashlock {}", params.amount, params.receiver, hex::encode(&params.hashlock));
+    // A hashlock is only ever locked once per chain, so it identifies retries of this lock
+    let key = format!("lock:{}", hex::encode(&params.hashlock));
+    let response: Value = submit(transport, transport.client().post(format!("{}/htlc", api_url)).json(params), Some(&key)).await?;
+    string_field(&response, "lock_id")
+}
+
+pub(crate) async fn get_lock(transport: &Transport, api_url: &str, lock_id: &str) -> Result<HtlcLock, ChainError> {
+    let response = send(transport, transport.client().get(format!("{}/htlc/{}", api_url, lock_id))).await?;
+    serde_json::from_value(response).map_err(|e| ChainError::InvalidResponse(e.to_string()))
+}
+
+pub(crate) async fn find_lock(transport: &Transport, api_url: &str, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError> {
+    let request = transport.client().get(format!("{}/htlc", api_url)).query(&[("hashlock", hex::encode(hashlock))]);
+    match send(transport, request).await {
+        Ok(response) => serde_json::from_value(response)
+            .map(Some)
+            .map_err(|e| ChainError::InvalidResponse(e.to_string())),
+        Err(ChainError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) async fn claim(transport: &Transport, api_url: &str, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError> {
+    info!("Claiming lock {}", lock_id);
+    let body = json!({ "preimage": hex::encode(preimage) });
+    let request = transport.client().post(format!("{}/htlc/{}/claim", api_url, lock_id)).json(&body);
+    let response = submit(transport, request, Some(&format!("claim:{}", lock_id))).await?;
+    string_field(&response, "tx_id")
+}
+
+pub(crate) async fn refund(transport: &Transport, api_url: &str, lock_id: &str) -> Result<String, ChainError> {
+    info!("Refunding lock {}", lock_id);
+    let request = transport.client().post(format!("{}/htlc/{}/refund", api_url, lock_id));
+    let response = submit(transport, request, Some(&format!("refund:{}", lock_id))).await?;
+    string_field(&response, "tx_id")
+}
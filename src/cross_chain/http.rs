@@ -0,0 +1,54 @@
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use log::error;
+use super::error::ChainError;
+use super::transport::Transport;
+
+// JSON request helpers shared by the chain integrations
+
+/// Sends a request and parses a JSON response, mapping 404s and other failures to `ChainError`.
+pub(crate) async fn send(transport: &Transport, request: RequestBuilder) -> Result<Value, ChainError> {
+    read(transport.send(request).await?).await
+}
+
+/// Like `send`, but with an idempotency key so that the submission is retried on transient
+/// failures. `idempotency_key` defaults to a random key shared by all attempts.
+pub(crate) async fn submit(transport: &Transport, request: RequestBuilder, idempotency_key: Option<&str>) -> Result<Value, ChainError> {
+    read(transport.submit(request, idempotency_key).await?).await
+}
+
+async fn read(response: Response) -> Result<Value, ChainError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response.json().await?)
+    } else if status == reqwest::StatusCode::NOT_FOUND {
+        Err(ChainError::NotFound(response.url().path().to_string()))
+    } else {
+        let message = response.text().await.unwrap_or_default();
+        error!("Request failed: {} {}", status, message);
+        Err(ChainError::Api { status: status.as_u16(), message })
+    }
+}
+
+/// Reads a required string field from a JSON object.
+pub(crate) fn string_field(value: &Value, field: &str) -> Result<String, ChainError> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| ChainError::InvalidResponse(format!("missing field `{}`", field)))
+}
+
+/// Reads a required unsigned integer field from a JSON object.
+pub(crate) fn u64_field(value: &Value, field: &str) -> Result<u64, ChainError> {
+    value
+        .get(field)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| ChainError::InvalidResponse(format!("missing field `{}`", field)))
+}
+
+/// Deserializes a JSON response into a typed value.
+pub(crate) fn parse<T: DeserializeOwned>(value: Value) -> Result<T, ChainError> {
+    serde_json::from_value(value).map_err(|e| ChainError::InvalidResponse(e.to_string()))
+}
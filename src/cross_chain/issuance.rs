@@ -0,0 +1,407 @@
+// Issues Pi Coin as a Stellar asset from an issuing and a distribution account, with the
+// stablecoin ledger as the record of its supply
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ed25519_dalek::Keypair;
+use log::{error, info};
+use thiserror::Error;
+use super::chain::{self, Chain};
+use super::error::ChainError;
+use super::horizon::{parse_amount, HorizonError};
+use super::smart_contract::SmartContract;
+use super::stellar_integration::StellarIntegration;
+use crate::models::stellar::transaction::{
+    AccountId, Asset, MuxedAccount, Network, Operation, Transaction, TransactionBuilder, TransactionEnvelope, TransactionError,
+    AUTHORIZED_FLAG, AUTH_CLAWBACK_ENABLED_FLAG, AUTH_REQUIRED_FLAG, AUTH_REVOCABLE_FLAG,
+};
+use crate::models::stellar::xdr::XdrEncode;
+
+/// Starting balance of each new account: enough for the reserves of an account with a
+/// trustline, plus fees.
+pub const DEFAULT_STARTING_BALANCE: i64 = 50_000_000;
+
+const ISSUANCE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Errors returned while issuing the asset.
+#[derive(Debug, Error)]
+pub enum IssuanceError {
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+
+    #[error(transparent)]
+    Horizon(#[from] HorizonError),
+
+    #[error("Invalid transaction: {0}")]
+    Transaction(#[from] TransactionError),
+
+    #[error("Supply ledger rejected the change: {0}")]
+    Ledger(String),
+
+    #[error("{0} is not enabled for this asset")]
+    NotEnabled(&'static str),
+
+    #[error("Invalid issuance configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid account: {0}")]
+    InvalidAccount(String),
+
+    #[error("Amount must be between 1 and {} stroops, got {0}", i64::MAX)]
+    InvalidAmount(u64),
+
+    /// The transaction may still be applied, so the supply ledger keeps the change.
+    #[error("Outcome of transaction {hash} is unknown ({reason}); the supply ledger keeps the change")]
+    Unresolved { hash: String, reason: String },
+}
+
+/// Authorization flags set on the issuing account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthFlags {
+    /// Holders need the issuer's authorization before they can receive the asset.
+    pub auth_required: bool,
+    /// The issuer can revoke a holder's authorization, freezing their balance.
+    pub auth_revocable: bool,
+    /// The issuer can claw the asset back from holders. Requires `auth_revocable`.
+    pub clawback_enabled: bool,
+}
+
+impl AuthFlags {
+    /// Every control a regulated stablecoin issuer needs.
+    pub fn all() -> Self {
+        Self { auth_required: true, auth_revocable: true, clawback_enabled: true }
+    }
+
+    /// The flags as `AUTH_*_FLAG` bits.
+    pub fn bits(&self) -> u32 {
+        let mut bits = 0;
+        if self.auth_required {
+            bits |= AUTH_REQUIRED_FLAG;
+        }
+        if self.auth_revocable {
+            bits |= AUTH_REVOCABLE_FLAG;
+        }
+        if self.clawback_enabled {
+            bits |= AUTH_CLAWBACK_ENABLED_FLAG;
+        }
+        bits
+    }
+}
+
+/// How the asset is issued.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssuanceConfig {
+    pub asset_code: String,
+    pub flags: AuthFlags,
+    /// Domain whose stellar.toml describes the asset.
+    pub home_domain: Option<String>,
+    /// Lumens the funding account gives each new account, in stroops.
+    pub starting_balance: i64,
+}
+
+impl IssuanceConfig {
+    /// Issues `asset_code` with every authorization flag and no home domain.
+    pub fn new(asset_code: &str) -> Self {
+        Self {
+            asset_code: asset_code.to_string(),
+            flags: AuthFlags::all(),
+            home_domain: None,
+            starting_balance: DEFAULT_STARTING_BALANCE,
+        }
+    }
+}
+
+/// A holder's trustline to the asset, as Horizon reports it. Amounts are in stroops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HolderTrustline {
+    pub balance: u64,
+    pub limit: u64,
+    pub authorized: bool,
+    pub clawback_enabled: bool,
+}
+
+/// Supply of the asset according to the stablecoin ledger and to Stellar, in stroops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupplyCheck {
+    pub ledger_supply: u64,
+    pub stellar_supply: u64,
+}
+
+impl SupplyCheck {
+    pub fn is_balanced(&self) -> bool {
+        self.ledger_supply == self.stellar_supply
+    }
+}
+
+/// Issues a stablecoin on Stellar.
+///
+/// The asset lives on two accounts: the issuer, whose authorization flags control who may
+/// hold the asset and which creates it by paying it out, and the distribution account, which
+/// receives new supply and hands it on. The stablecoin's `SmartContract` ledger stays the
+/// source of truth: a mint or clawback is recorded there first, so Stellar never carries supply the ledger does
+/// not know about. When the submission fails, the transaction is looked up by its hash, and the
+/// change is undone only if Stellar did not apply it and cannot apply it later. If that cannot
+/// be told, the change is kept and `IssuanceError::Unresolved` names the transaction; `reconcile`
+/// shows whether it landed. Ledger amounts are in stroops of the Stellar asset.
+pub struct PiCoinIssuer {
+    stellar: Arc<StellarIntegration>,
+    network: Network,
+    issuer: Keypair,
+    distribution: Keypair,
+    config: IssuanceConfig,
+    asset: Asset,
+    ledger: Mutex<SmartContract>,
+}
+
+impl PiCoinIssuer {
+    pub fn new(
+        stellar: Arc<StellarIntegration>,
+        network: Network,
+        issuer: Keypair,
+        distribution: Keypair,
+        config: IssuanceConfig,
+        ledger: SmartContract,
+    ) -> Result<Self, IssuanceError> {
+        if config.flags.clawback_enabled && !config.flags.auth_revocable {
+            return Err(IssuanceError::InvalidConfig("clawback requires revocable authorization".to_string()));
+        }
+        let asset = Asset::credit(&config.asset_code, AccountId::from(&issuer.public))?;
+        Ok(Self { stellar, network, issuer, distribution, config, asset, ledger: Mutex::new(ledger) })
+    }
+
+    pub fn issuer_id(&self) -> AccountId {
+        AccountId::from(&self.issuer.public)
+    }
+
+    pub fn distribution_id(&self) -> AccountId {
+        AccountId::from(&self.distribution.public)
+    }
+
+    pub fn asset(&self) -> &Asset {
+        &self.asset
+    }
+
+    /// The asset as the chain abstraction identifies it.
+    pub fn chain_asset(&self) -> chain::Asset {
+        chain::Asset::Issued { code: self.config.asset_code.clone(), issuer: self.issuer_id().to_strkey() }
+    }
+
+    /// Supply according to the stablecoin ledger.
+    pub fn total_supply(&self) -> u64 {
+        self.ledger.lock().unwrap().get_total_supply()
+    }
+
+    /// Operations creating both accounts from `funder`, setting the issuer's flags and opening
+    /// the distribution account's authorized trustline. All of them go into one transaction,
+    /// which the funder, issuer and distribution account sign.
+    pub fn setup_operations(&self) -> Vec<Operation> {
+        let issuer = MuxedAccount::from(self.issuer_id());
+        let distribution = MuxedAccount::from(self.distribution_id());
+        let mut operations = vec![
+            Operation::create_account(self.issuer_id(), self.config.starting_balance),
+            Operation::create_account(self.distribution_id(), self.config.starting_balance),
+            // The flags go first so the distribution trustline gets clawback too
+            Operation::set_options(None, Some(self.config.flags.bits()), self.config.home_domain.clone()).with_source(issuer),
+            Operation::change_trust(self.asset.clone(), i64::MAX).with_source(distribution),
+        ];
+        if self.config.flags.auth_required {
+            operations.push(self.authorize_operation(self.distribution_id()).with_source(issuer));
+        }
+        operations
+    }
+
+    /// Creates and configures the issuing and distribution accounts, funded by `funder`.
+    pub async fn create_accounts(&self, funder: &Keypair) -> Result<String, IssuanceError> {
+        let funder_id = AccountId::from(&funder.public);
+        let transaction = self.prepare(&funder_id, self.setup_operations()).await?;
+        let hash = self.submit(transaction, &[funder, &self.issuer, &self.distribution]).await?;
+        info!("Created issuer {} and distribution account {} for {}", self.issuer_id(), self.distribution_id(), self.config.asset_code);
+        Ok(hash)
+    }
+
+    /// Opens a trustline from `holder` to the asset, authorized in the same transaction when
+    /// the asset requires authorization. `None` trusts any amount.
+    pub async fn open_trustline(&self, holder: &Keypair, limit: Option<i64>) -> Result<String, IssuanceError> {
+        let holder_id = AccountId::from(&holder.public);
+        let mut operations = vec![Operation::change_trust(self.asset.clone(), limit.unwrap_or(i64::MAX))];
+        if self.config.flags.auth_required {
+            operations.push(self.authorize_operation(holder_id).with_source(MuxedAccount::from(self.issuer_id())));
+        }
+        let transaction = self.prepare(&holder_id, operations).await?;
+        let signers: Vec<&Keypair> = if self.config.flags.auth_required { vec![holder, &self.issuer] } else { vec![holder] };
+        self.submit(transaction, &signers).await
+    }
+
+    /// Authorizes `holder` to send and receive the asset.
+    pub async fn authorize(&self, holder: &str) -> Result<String, IssuanceError> {
+        let holder = account_id(holder)?;
+        let transaction = self.prepare(&self.issuer_id(), vec![self.authorize_operation(holder)]).await?;
+        let hash = self.submit(transaction, &[&self.issuer]).await?;
+        info!("Authorized {} to hold {}", holder, self.config.asset_code);
+        Ok(hash)
+    }
+
+    /// Revokes the authorization of `holder`, freezing their balance.
+    pub async fn revoke(&self, holder: &str) -> Result<String, IssuanceError> {
+        if !self.config.flags.auth_revocable {
+            return Err(IssuanceError::NotEnabled("Revocation"));
+        }
+        let holder = account_id(holder)?;
+        let revoke = Operation::set_trust_line_flags(holder, self.asset.clone(), AUTHORIZED_FLAG, 0);
+        let transaction = self.prepare(&self.issuer_id(), vec![revoke]).await?;
+        let hash = self.submit(transaction, &[&self.issuer]).await?;
+        info!("Revoked authorization of {} to hold {}", holder, self.config.asset_code);
+        Ok(hash)
+    }
+
+    /// The trustline of `holder` to the asset, or `None` if it has none.
+    pub async fn trustline(&self, holder: &str) -> Result<Option<HolderTrustline>, IssuanceError> {
+        let holder = account_id(holder)?;
+        let account = self.stellar.load_account(&holder.to_strkey()).await?;
+        let issuer = self.issuer_id().to_strkey();
+        let Some(line) = account
+            .balances
+            .iter()
+            .find(|b| b.asset_code.as_deref() == Some(&self.config.asset_code) && b.asset_issuer.as_deref() == Some(&issuer))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(HolderTrustline {
+            balance: parse_amount(&line.balance)?,
+            limit: line.limit.as_deref().map(parse_amount).transpose()?.unwrap_or(u64::MAX),
+            authorized: line.is_authorized.unwrap_or(false),
+            clawback_enabled: line.is_clawback_enabled.unwrap_or(false),
+        }))
+    }
+
+    /// Mints `amount` to `to` by paying it from the issuer, after recording it in the ledger.
+    pub async fn mint(&self, to: &str, amount: u64) -> Result<String, IssuanceError> {
+        let destination = MuxedAccount::from_strkey(to).map_err(|e| IssuanceError::InvalidAccount(format!("{}: {}", to, e)))?;
+        let stroops = stroops(amount)?;
+        self.ledger.lock().unwrap().mint(to.to_string(), amount).map_err(IssuanceError::Ledger)?;
+
+        let payment = Operation::payment(destination, self.asset.clone(), stroops);
+        let hash = self.submit_recorded(payment, |ledger| ledger.burn(to.to_string(), amount)).await?;
+        info!("Minted {} {} to {} in {}", amount, self.config.asset_code, to, hash);
+        Ok(hash)
+    }
+
+    /// Claws `amount` back from `from`, burning it in the ledger as well.
+    pub async fn clawback(&self, from: &str, amount: u64) -> Result<String, IssuanceError> {
+        if !self.config.flags.clawback_enabled {
+            return Err(IssuanceError::NotEnabled("Clawback"));
+        }
+        let holder = MuxedAccount::from_strkey(from).map_err(|e| IssuanceError::InvalidAccount(format!("{}: {}", from, e)))?;
+        let stroops = stroops(amount)?;
+        self.ledger.lock().unwrap().burn(from.to_string(), amount).map_err(IssuanceError::Ledger)?;
+
+        let clawback = Operation::clawback(self.asset.clone(), holder, stroops);
+        let hash = self.submit_recorded(clawback, |ledger| ledger.mint(from.to_string(), amount)).await?;
+        info!("Clawed back {} {} from {} in {}", amount, self.config.asset_code, from, hash);
+        Ok(hash)
+    }
+
+    /// Compares the ledger's supply with the supply Horizon reports for the asset.
+    pub async fn reconcile(&self) -> Result<SupplyCheck, IssuanceError> {
+        let stellar_supply = self.stellar.issued_supply(&self.chain_asset()).await?;
+        let check = SupplyCheck { ledger_supply: self.total_supply(), stellar_supply };
+        if !check.is_balanced() {
+            error!(
+                "{} supply mismatch: {} in the ledger, {} on Stellar",
+                self.config.asset_code, check.ledger_supply, check.stellar_supply
+            );
+        }
+        Ok(check)
+    }
+
+    fn authorize_operation(&self, holder: AccountId) -> Operation {
+        Operation::set_trust_line_flags(holder, self.asset.clone(), 0, AUTHORIZED_FLAG)
+    }
+
+    // Submits an issuer operation for a ledger change that is already recorded, and reverts the
+    // change only once Stellar has certainly not applied the transaction
+    async fn submit_recorded(&self, operation: Operation, revert: impl FnOnce(&mut SmartContract) -> Result<(), String>) -> Result<String, IssuanceError> {
+        let envelope = match self.prepare(&self.issuer_id(), vec![operation]).await {
+            Ok(transaction) => self.sign(transaction, &[&self.issuer]),
+            Err(e) => Err(e),
+        };
+        let envelope = match envelope {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                // Nothing was submitted
+                self.undo(revert);
+                return Err(e);
+            }
+        };
+        let error = match self.stellar.submit_with_fee_bumps(&envelope.to_xdr_base64()).await {
+            Ok(response) => return Ok(response.hash),
+            Err(error) => error,
+        };
+
+        // The network may have applied the transaction even though the submission failed
+        let hash = hex::encode(envelope.tx.hash(&self.network));
+        match self.stellar.get_transaction(&hash).await {
+            Ok(response) if response.successful => {
+                info!("{} transaction {} was applied despite a failed submission: {}", self.config.asset_code, hash, error);
+                Ok(hash)
+            }
+            Ok(_) => {
+                self.undo(revert);
+                Err(error.into())
+            }
+            // A transaction Horizon rejected outright can never be applied
+            Err(HorizonError::NotFound(_)) if is_rejected(&error) => {
+                self.undo(revert);
+                Err(error.into())
+            }
+            Err(lookup) => {
+                error!("Outcome of {} transaction {} is unknown ({}; lookup: {})", self.config.asset_code, hash, error, lookup);
+                Err(IssuanceError::Unresolved { hash, reason: error.to_string() })
+            }
+        }
+    }
+
+    // Reverts a ledger change whose Stellar transaction failed
+    fn undo(&self, revert: impl FnOnce(&mut SmartContract) -> Result<(), String>) {
+        if let Err(e) = revert(&mut self.ledger.lock().unwrap()) {
+            error!("Could not revert the supply ledger after a failed {} transaction: {}", self.config.asset_code, e);
+        }
+    }
+
+    async fn prepare(&self, source: &AccountId, operations: Vec<Operation>) -> Result<Transaction, IssuanceError> {
+        let account = self.stellar.load_account(&source.to_strkey()).await?;
+        let base_fee = self.stellar.estimate_fee(operations.len() as u32).await?;
+        let builder = TransactionBuilder::new(*source, account.sequence + 1).base_fee(base_fee).timeout(ISSUANCE_TIMEOUT);
+        Ok(operations.into_iter().fold(builder, |builder, operation| builder.add_operation(operation)).build()?)
+    }
+
+    fn sign(&self, transaction: Transaction, signers: &[&Keypair]) -> Result<TransactionEnvelope, IssuanceError> {
+        let (first, others) = signers.split_first().expect("a transaction has a source to sign it");
+        let mut envelope = transaction.sign(&self.network, first);
+        for signer in others {
+            envelope.sign(&self.network, signer)?;
+        }
+        Ok(envelope)
+    }
+
+    async fn submit(&self, transaction: Transaction, signers: &[&Keypair]) -> Result<String, IssuanceError> {
+        let envelope = self.sign(transaction, signers)?;
+        Ok(self.stellar.submit_with_fee_bumps(&envelope.to_xdr_base64()).await?.hash)
+    }
+}
+
+// Whether Horizon rejected a submission with result codes, so the transaction was not applied
+fn is_rejected(error: &ChainError) -> bool {
+    matches!(error, ChainError::Horizon(HorizonError::TransactionFailed { .. }))
+}
+
+fn stroops(amount: u64) -> Result<i64, IssuanceError> {
+    match i64::try_from(amount) {
+        Ok(stroops) if stroops > 0 => Ok(stroops),
+        _ => Err(IssuanceError::InvalidAmount(amount)),
+    }
+}
+
+fn account_id(address: &str) -> Result<AccountId, IssuanceError> {
+    AccountId::from_strkey(address).map_err(|e| IssuanceError::InvalidAccount(format!("{}: {}", address, e)))
+}
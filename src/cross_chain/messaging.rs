@@ -0,0 +1,314 @@
+// Passes payloads between chains, verified by a threshold of relayer signatures
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use log::{info, warn};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// Prefix of the signed bytes, so relayer signatures cannot be replayed in another protocol
+const DOMAIN: &[u8] = b"pi-stellar-nexus/message/v1";
+
+/// Messages buffered ahead of the next expected nonce before further ones are rejected.
+pub const DEFAULT_MAX_PENDING: u64 = 1024;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MessagingError {
+    #[error("Invalid relayer set: {0}")]
+    InvalidRelayerSet(String),
+
+    #[error("No handler registered for messages from {0}")]
+    UnknownSource(String),
+
+    #[error("Message is addressed to {found}, not {expected}")]
+    WrongDestination { expected: String, found: String },
+
+    #[error("Message {nonce} from {source_chain} was already received")]
+    Replay { source_chain: String, nonce: u64 },
+
+    #[error("Message {nonce} from {source_chain} is too far ahead of the next expected nonce {next}")]
+    TooFarAhead { source_chain: String, nonce: u64, next: u64 },
+
+    #[error("Message has {valid} valid relayer signatures, {threshold} required")]
+    InsufficientSignatures { valid: usize, threshold: usize },
+
+    #[error("Handler failed on message {nonce} from {source_chain}: {reason}")]
+    Handler { source_chain: String, nonce: u64, reason: String },
+}
+
+/// A payload sent from one chain to another.
+///
+/// Nonces count up from zero for each source and destination pair, and the receiving side
+/// delivers messages in nonce order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub source_chain: String,
+    pub destination_chain: String,
+    pub nonce: u64,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    /// Bytes signed by relayers: the domain tag followed by every field, length-prefixed.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = DOMAIN.to_vec();
+        for field in [self.source_chain.as_bytes(), self.destination_chain.as_bytes()] {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field);
+        }
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Hex SHA-256 of the signed bytes, identifying the message in logs.
+    pub fn id(&self) -> String {
+        hex::encode(digest(&SHA256, &self.signing_bytes()))
+    }
+}
+
+/// One relayer's signature over a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayerSignature {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl RelayerSignature {
+    pub fn sign(message: &Message, keypair: &Keypair) -> Self {
+        Self {
+            public_key: keypair.public.to_bytes().to_vec(),
+            signature: keypair.sign(&message.signing_bytes()).to_bytes().to_vec(),
+        }
+    }
+}
+
+/// A message with the relayer signatures collected for it so far.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub message: Message,
+    pub signatures: Vec<RelayerSignature>,
+}
+
+impl SignedMessage {
+    pub fn new(message: Message) -> Self {
+        Self { message, signatures: Vec::new() }
+    }
+
+    /// Adds `keypair`'s signature, replacing any earlier one from the same key.
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let signature = RelayerSignature::sign(&self.message, keypair);
+        self.signatures.retain(|s| s.public_key != signature.public_key);
+        self.signatures.push(signature);
+    }
+}
+
+/// The relayers trusted to attest messages and how many must agree.
+#[derive(Debug, Clone)]
+pub struct RelayerSet {
+    relayers: Vec<PublicKey>,
+    threshold: usize,
+}
+
+impl RelayerSet {
+    pub fn new(relayers: Vec<PublicKey>, threshold: usize) -> Result<Self, MessagingError> {
+        let distinct: HashSet<[u8; 32]> = relayers.iter().map(|r| r.to_bytes()).collect();
+        if distinct.len() != relayers.len() {
+            return Err(MessagingError::InvalidRelayerSet("relayers must be distinct".to_string()));
+        }
+        if threshold == 0 || threshold > relayers.len() {
+            return Err(MessagingError::InvalidRelayerSet(format!(
+                "threshold {} must be between 1 and {}", threshold, relayers.len()
+            )));
+        }
+        Ok(Self { relayers, threshold })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn relayers(&self) -> &[PublicKey] {
+        &self.relayers
+    }
+
+    /// Counts the distinct relayers in the set with a valid signature over the message and
+    /// fails unless they reach the threshold. Signatures from unknown keys are ignored.
+    pub fn verify(&self, signed: &SignedMessage) -> Result<usize, MessagingError> {
+        let bytes = signed.message.signing_bytes();
+        let mut signers = HashSet::new();
+        for entry in &signed.signatures {
+            let relayer = match self.relayers.iter().find(|r| r.as_bytes()[..] == entry.public_key[..]) {
+                Some(relayer) => relayer,
+                None => continue,
+            };
+            let valid = Signature::from_bytes(&entry.signature)
+                .map(|signature| relayer.verify(&bytes, &signature).is_ok())
+                .unwrap_or(false);
+            if valid {
+                signers.insert(relayer.to_bytes());
+            }
+        }
+        if signers.len() < self.threshold {
+            return Err(MessagingError::InsufficientSignatures { valid: signers.len(), threshold: self.threshold });
+        }
+        Ok(signers.len())
+    }
+}
+
+/// Assigns nonces to messages leaving a chain.
+pub struct MessageOutbox {
+    source_chain: String,
+    nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl MessageOutbox {
+    pub fn new(source_chain: &str) -> Self {
+        Self { source_chain: source_chain.to_string(), nonces: Mutex::new(HashMap::new()) }
+    }
+
+    /// Builds the next message to `destination_chain`, ready for relayers to sign.
+    pub fn send(&self, destination_chain: &str, payload: Vec<u8>) -> Message {
+        let mut nonces = self.nonces.lock().unwrap();
+        let nonce = nonces.entry(destination_chain.to_string()).or_insert(0);
+        let message = Message {
+            source_chain: self.source_chain.clone(),
+            destination_chain: destination_chain.to_string(),
+            nonce: *nonce,
+            payload,
+        };
+        *nonce += 1;
+        message
+    }
+}
+
+/// Receives verified messages from one source chain, in nonce order.
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+    async fn handle(&self, message: &Message) -> Result<(), String>;
+}
+
+// Messages from one source waiting for delivery
+struct SourceQueue {
+    next_nonce: u64,
+    pending: BTreeMap<u64, Message>,
+}
+
+struct Source {
+    handler: Arc<dyn MessageHandler>,
+    queue: tokio::sync::Mutex<SourceQueue>,
+}
+
+/// Verifies messages arriving at a chain and delivers them to the handler for their source.
+///
+/// A message is accepted once a threshold of relayers has signed it and its nonce has not been
+/// seen from that source. Messages arriving ahead of a gap wait until the gap is filled, so each
+/// handler sees its source's messages exactly once and in order. If a handler fails, its message
+/// stays at the head of the queue and is retried by the next `receive` or `flush` for that source.
+/// Delivery state is held in memory; `next_nonce` lets callers persist it.
+pub struct MessageInbox {
+    chain: String,
+    relayers: RelayerSet,
+    max_pending: u64,
+    sources: HashMap<String, Source>,
+}
+
+impl MessageInbox {
+    pub fn new(chain: &str, relayers: RelayerSet) -> Self {
+        Self { chain: chain.to_string(), relayers, max_pending: DEFAULT_MAX_PENDING, sources: HashMap::new() }
+    }
+
+    pub fn with_max_pending(mut self, max_pending: u64) -> Self {
+        self.max_pending = max_pending.max(1);
+        self
+    }
+
+    /// Registers the handler for messages from `source_chain`, starting at nonce zero.
+    /// Messages from chains without a handler are rejected.
+    pub fn register(&mut self, source_chain: &str, handler: Arc<dyn MessageHandler>) {
+        self.register_from(source_chain, handler, 0);
+    }
+
+    /// Registers a handler that resumes delivery at `next_nonce`, for example after a restart.
+    pub fn register_from(&mut self, source_chain: &str, handler: Arc<dyn MessageHandler>, next_nonce: u64) {
+        let queue = SourceQueue { next_nonce, pending: BTreeMap::new() };
+        self.sources.insert(source_chain.to_string(), Source { handler, queue: tokio::sync::Mutex::new(queue) });
+    }
+
+    /// Next nonce to be delivered from `source_chain`.
+    pub async fn next_nonce(&self, source_chain: &str) -> Option<u64> {
+        match self.sources.get(source_chain) {
+            Some(source) => Some(source.queue.lock().await.next_nonce),
+            None => None,
+        }
+    }
+
+    /// Number of messages from `source_chain` waiting for an earlier nonce or a retry.
+    pub async fn pending(&self, source_chain: &str) -> usize {
+        match self.sources.get(source_chain) {
+            Some(source) => source.queue.lock().await.pending.len(),
+            None => 0,
+        }
+    }
+
+    /// Verifies and queues a message, then delivers every message from its source that is
+    /// now in order. Returns how many were delivered.
+    pub async fn receive(&self, signed: SignedMessage) -> Result<usize, MessagingError> {
+        let message = signed.message.clone();
+        if message.destination_chain != self.chain {
+            return Err(MessagingError::WrongDestination {
+                expected: self.chain.clone(),
+                found: message.destination_chain,
+            });
+        }
+        let source = self.sources.get(&message.source_chain)
+            .ok_or_else(|| MessagingError::UnknownSource(message.source_chain.clone()))?;
+        self.relayers.verify(&signed)?;
+
+        let mut queue = source.queue.lock().await;
+        if message.nonce < queue.next_nonce || queue.pending.contains_key(&message.nonce) {
+            warn!("Rejected replayed message {} from {}", message.nonce, message.source_chain);
+            return Err(MessagingError::Replay { source_chain: message.source_chain, nonce: message.nonce });
+        }
+        if message.nonce - queue.next_nonce >= self.max_pending {
+            return Err(MessagingError::TooFarAhead {
+                source_chain: message.source_chain,
+                nonce: message.nonce,
+                next: queue.next_nonce,
+            });
+        }
+        queue.pending.insert(message.nonce, message);
+        deliver(source, &mut queue).await
+    }
+
+    /// Retries delivery of queued messages from `source_chain`.
+    pub async fn flush(&self, source_chain: &str) -> Result<usize, MessagingError> {
+        let source = self.sources.get(source_chain)
+            .ok_or_else(|| MessagingError::UnknownSource(source_chain.to_string()))?;
+        let mut queue = source.queue.lock().await;
+        deliver(source, &mut queue).await
+    }
+}
+
+// Hands queued messages to the handler while they follow on from the last delivered nonce
+async fn deliver(source: &Source, queue: &mut SourceQueue) -> Result<usize, MessagingError> {
+    let mut delivered = 0;
+    while let Some(message) = queue.pending.get(&queue.next_nonce) {
+        if let Err(reason) = source.handler.handle(message).await {
+            return Err(MessagingError::Handler {
+                source_chain: message.source_chain.clone(),
+                nonce: message.nonce,
+                reason,
+            });
+        }
+        info!("Delivered message {} from {} ({})", message.nonce, message.source_chain, message.id());
+        queue.pending.remove(&queue.next_nonce);
+        queue.next_nonce += 1;
+        delivered += 1;
+    }
+    Ok(delivered)
+}
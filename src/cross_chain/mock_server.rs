@@ -0,0 +1,202 @@
+// Scripted HTTP server used by the cross-chain tests in place of real network endpoints
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A canned response returned for a matching request.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+    pub headers: Vec<(String, String)>,
+    pub delay: Option<Duration>,
+}
+
+impl MockResponse {
+    /// A JSON response with the given status.
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            body: body.to_string(),
+            headers: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// A raw response body with an explicit content type.
+    pub fn raw(status: u16, content_type: &str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: content_type.to_string(),
+            body: body.to_string(),
+            headers: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// Adds a response header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Delays the response, e.g. to trigger client timeouts.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request as received by the mock server.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct Routes {
+    // (method, path) -> queued responses; the last response repeats once the queue is drained
+    responses: HashMap<(String, String), VecDeque<MockResponse>>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// A minimal HTTP/1.1 server answering each request from a per-route script.
+///
+/// Routes are matched on method and path, ignoring the query string. Unmatched requests
+/// receive a 404.
+#[derive(Clone)]
+pub struct MockServer {
+    address: String,
+    routes: Arc<Mutex<Routes>>,
+}
+
+impl MockServer {
+    /// Starts the server on a random local port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind mock server");
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(Mutex::new(Routes::default()));
+
+        let accept_routes = routes.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let routes = accept_routes.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, routes).await;
+                });
+            }
+        });
+
+        Self { address, routes }
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Queues a response for the route; queued responses are served in order.
+    pub fn respond(&self, method: &str, path: &str, response: MockResponse) -> &Self {
+        let mut routes = self.routes.lock().unwrap();
+        routes
+            .responses
+            .entry((method.to_string(), path.to_string()))
+            .or_insert_with(VecDeque::new)
+            .push_back(response);
+        self
+    }
+
+    /// Returns every request received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.routes.lock().unwrap().requests.clone()
+    }
+
+    /// Returns the requests received for one route.
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == method && r.path.split('?').next() == Some(path))
+            .collect()
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, routes: Arc<Mutex<Routes>>) -> std::io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Read until the end of the headers, then the body according to Content-Length
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8_lossy(&buffer[header_end..]).to_string();
+
+    let route_path = path.split('?').next().unwrap_or_default().to_string();
+    let response = {
+        let mut routes = routes.lock().unwrap();
+        routes.requests.push(RecordedRequest {
+            method: method.clone(),
+            path: path.clone(),
+            headers,
+            body,
+        });
+        match routes.responses.get_mut(&(method, route_path)) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        }
+    };
+    let response = response.unwrap_or_else(|| MockResponse::json(404, serde_json::json!({ "error": "not found" })));
+
+    if let Some(delay) = response.delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let extra_headers: String = response.headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    let reply = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        extra_headers,
+        response.body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await
+}
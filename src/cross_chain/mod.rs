@@ -1,5 +1,31 @@
 // mod.rs
 
+pub mod chain;
+pub mod devnet;
+pub mod dex;
+pub mod error;
+// The stablecoin's ledger, shared with the stablecoin crate so that Pi Coin issued on Stellar
+// is minted and burned in the same records
+#[path = "../stablecoin/src/events.rs"]
+pub mod events;
+pub mod fees;
+pub mod horizon;
+pub mod horizon_stream;
+pub mod htlc;
+pub mod http;
+pub mod issuance;
+pub mod messaging;
+pub mod network;
+pub mod stellar_htlc;
 pub mod stellar_integration;
+pub mod stellar_signer;
+pub mod pi_client;
 pub mod pi_integration;
+#[path = "../stablecoin/src/smart_contract.rs"]
+pub mod smart_contract;
+pub mod swap;
+pub mod transport;
+pub mod watcher;
 pub mod cross_chain_tests;
+#[cfg(test)]
+pub mod mock_server;
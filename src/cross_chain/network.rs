@@ -0,0 +1,199 @@
+// Named networks (mainnet, testnet, local) and the endpoints and assets of each
+//
+// Self-contained so that it can be shared with the stablecoin crate.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Registry file read by `NetworkRegistry::load` unless `NETWORKS_FILE_VAR` names another.
+pub const DEFAULT_NETWORKS_FILE: &str = "config/networks.json";
+
+/// Environment variable naming the registry file.
+pub const NETWORKS_FILE_VAR: &str = "PISTELLAR_NETWORKS_FILE";
+
+/// Environment variable selecting a network when no `--network` flag is given.
+pub const NETWORK_VAR: &str = "PISTELLAR_NETWORK";
+
+// Built in so a binary started outside the repository still knows the standard networks
+const BUILTIN_NETWORKS: &str = include_str!("../../config/networks.json");
+
+/// Errors returned when loading the registry or selecting a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The registry file could not be read.
+    Io { path: String, message: String },
+    /// The registry file is not valid JSON of the expected shape.
+    Parse { path: String, message: String },
+    /// No network with this name is defined.
+    UnknownNetwork { name: String, available: Vec<String> },
+    /// A network is missing a required setting.
+    Invalid { network: String, reason: String },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Io { path, message } => write!(f, "Failed to read {}: {}", path, message),
+            NetworkError::Parse { path, message } => write!(f, "Failed to parse {}: {}", path, message),
+            NetworkError::UnknownNetwork { name, available } => {
+                write!(f, "Unknown network `{}`, expected one of: {}", name, available.join(", "))
+            }
+            NetworkError::Invalid { network, reason } => write!(f, "Invalid network `{}`: {}", network, reason),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+/// Where to reach the Pi network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PiEndpoint {
+    pub api_url: String,
+    pub chain_id: String,
+}
+
+/// Where to reach Stellar and which network to sign for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StellarEndpoint {
+    pub horizon_url: String,
+    pub network_passphrase: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PiAssetInfo {
+    pub symbol: String,
+    pub decimals: u8,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A Stellar asset traded on the network, such as a fiat anchor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StellarAssetInfo {
+    pub code: String,
+    pub issuer: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Everything the services need to run against one network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    #[serde(default)]
+    pub name: String,
+    pub pi: PiEndpoint,
+    pub stellar: StellarEndpoint,
+    #[serde(default)]
+    pub pi_assets: Vec<PiAssetInfo>,
+    #[serde(default)]
+    pub stellar_assets: Vec<StellarAssetInfo>,
+    /// Address the HTTP servers listen on.
+    pub bind_address: String,
+}
+
+impl NetworkProfile {
+    fn validate(&self) -> Result<(), NetworkError> {
+        let required = [
+            ("pi.api_url", &self.pi.api_url),
+            ("pi.chain_id", &self.pi.chain_id),
+            ("stellar.horizon_url", &self.stellar.horizon_url),
+            ("stellar.network_passphrase", &self.stellar.network_passphrase),
+            ("bind_address", &self.bind_address),
+        ];
+        match required.iter().find(|(_, value)| value.trim().is_empty()) {
+            Some((field, _)) => Err(NetworkError::Invalid { network: self.name.clone(), reason: format!("`{}` is empty", field) }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The networks the application can run against, loaded once at startup.
+///
+/// The network is chosen by the `--network` flag, then the `PISTELLAR_NETWORK` environment
+/// variable, then the registry's `default`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkRegistry {
+    pub default: String,
+    pub networks: BTreeMap<String, NetworkProfile>,
+}
+
+impl NetworkRegistry {
+    /// The mainnet, testnet and local networks shipped in `config/networks.json`.
+    pub fn builtin() -> Result<Self, NetworkError> {
+        Self::parse(BUILTIN_NETWORKS, DEFAULT_NETWORKS_FILE)
+    }
+
+    /// Reads a registry from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, NetworkError> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)
+            .map_err(|e| NetworkError::Io { path: path.display().to_string(), message: e.to_string() })?;
+        Self::parse(&data, &path.display().to_string())
+    }
+
+    /// Reads the file named by `PISTELLAR_NETWORKS_FILE`, or `config/networks.json`. The
+    /// built-in registry is used when the default file does not exist.
+    pub fn load() -> Result<Self, NetworkError> {
+        match env::var(NETWORKS_FILE_VAR) {
+            Ok(path) => Self::from_file(path),
+            Err(_) if !Path::new(DEFAULT_NETWORKS_FILE).exists() => Self::builtin(),
+            Err(_) => Self::from_file(DEFAULT_NETWORKS_FILE),
+        }
+    }
+
+    fn parse(data: &str, path: &str) -> Result<Self, NetworkError> {
+        let mut registry: Self = serde_json::from_str(data)
+            .map_err(|e| NetworkError::Parse { path: path.to_string(), message: e.to_string() })?;
+        for (name, profile) in registry.networks.iter_mut() {
+            profile.name = name.clone();
+            profile.validate()?;
+        }
+        registry.get(&registry.default)?;
+        Ok(registry)
+    }
+
+    /// Names of the defined networks, in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        self.networks.keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&NetworkProfile, NetworkError> {
+        self.networks.get(name).ok_or_else(|| NetworkError::UnknownNetwork { name: name.to_string(), available: self.names() })
+    }
+
+    /// Selects the network named by `flag`, else by `PISTELLAR_NETWORK`, else the default.
+    pub fn select(&self, flag: Option<&str>) -> Result<&NetworkProfile, NetworkError> {
+        self.select_with(flag, env::var(NETWORK_VAR).ok().as_deref())
+    }
+
+    pub(crate) fn select_with(&self, flag: Option<&str>, env_value: Option<&str>) -> Result<&NetworkProfile, NetworkError> {
+        let name = flag.or(env_value).map(str::trim).filter(|name| !name.is_empty()).unwrap_or(self.default.as_str());
+        self.get(name)
+    }
+}
+
+/// Finds the value of `--network <name>` or `--network=<name>` in command-line arguments.
+pub fn network_flag<I: IntoIterator<Item = String>>(args: I) -> Option<String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--network" {
+            return args.next();
+        }
+        if let Some(name) = arg.strip_prefix("--network=") {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Loads the registry and selects the network for this process from its arguments and
+/// environment.
+pub fn load_selected<I: IntoIterator<Item = String>>(args: I) -> Result<NetworkProfile, NetworkError> {
+    let registry = NetworkRegistry::load()?;
+    let profile = registry.select(network_flag(args).as_deref())?;
+    Ok(profile.clone())
+}
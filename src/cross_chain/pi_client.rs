@@ -0,0 +1,263 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use log::info;
+use super::error::ChainError;
+use super::http::{parse, send, string_field, submit};
+use super::network::{NetworkError, NetworkProfile, NetworkRegistry};
+use super::transport::Transport;
+
+/// Default page size for transaction listings.
+pub const DEFAULT_PAGE_LIMIT: u32 = 50;
+
+/// Connection settings for one Pi network, usually taken from the `pi` section of a
+/// `NetworkProfile`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiNetworkConfig {
+    #[serde(default)]
+    pub name: String,
+    pub api_url: String,
+    pub chain_id: String,
+}
+
+impl PiNetworkConfig {
+    /// Pi mainnet as defined in the built-in network registry.
+    pub fn mainnet() -> Result<Self, NetworkError> {
+        Self::builtin("mainnet")
+    }
+
+    /// Pi testnet as defined in the built-in network registry.
+    pub fn testnet() -> Result<Self, NetworkError> {
+        Self::builtin("testnet")
+    }
+
+    fn builtin(name: &str) -> Result<Self, NetworkError> {
+        Ok(Self::from(NetworkRegistry::builtin()?.get(name)?))
+    }
+}
+
+impl From<&NetworkProfile> for PiNetworkConfig {
+    fn from(profile: &NetworkProfile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            api_url: profile.pi.api_url.clone(),
+            chain_id: profile.pi.chain_id.clone(),
+        }
+    }
+}
+
+/// Account details returned by `GET /pi/account`. Amounts are in the smallest Pi unit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiAccount {
+    pub account_id: String,
+    pub balance: u64,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Lifecycle of a transaction on the Pi network.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PiTransactionState {
+    Pending,
+    Confirmed,
+    Failed,
+    #[serde(other)]
+    Unknown,
+}
+
+/// A transfer to create with `POST /pi/transaction`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiTransferRequest {
+    pub source_account_id: String,
+    pub destination_account_id: String,
+    pub amount: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Identifies the transfer across retries and restarts; sent as the `Idempotency-Key` header.
+    #[serde(skip)]
+    pub idempotency_key: Option<String>,
+}
+
+/// A transaction as listed by `GET /pi/transactions`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiTransactionRecord {
+    pub transaction_id: String,
+    pub source_account_id: String,
+    pub destination_account_id: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub memo: Option<String>,
+    pub status: PiTransactionState,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// One page of a listing; `next_cursor` is `None` on the last page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPage<T> {
+    pub records: Vec<T>,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// A payment for an app user to sign, created with `POST /pi/payments`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPaymentCreation {
+    pub recipient: String,
+    pub amount: u64,
+    pub memo: String,
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+/// Server-side progress of a payment through approval and completion.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PiPaymentStatus {
+    pub developer_approved: bool,
+    pub transaction_verified: bool,
+    pub developer_completed: bool,
+    pub cancelled: bool,
+}
+
+/// The blockchain transaction attached to a payment once the user has submitted it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPaymentTransaction {
+    pub txid: String,
+    pub verified: bool,
+}
+
+/// A payment as returned by the `/pi/payments` endpoints.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPaymentRecord {
+    pub identifier: String,
+    pub recipient: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub memo: String,
+    #[serde(default)]
+    pub metadata: Value,
+    #[serde(default)]
+    pub status: PiPaymentStatus,
+    #[serde(default)]
+    pub transaction: Option<PiPaymentTransaction>,
+}
+
+/// Typed client for the Pi API documented in `docs/api/pi_api.md`.
+pub struct PiClient {
+    transport: Transport,
+    api_url: String,
+}
+
+impl PiClient {
+    pub fn new(api_url: &str) -> Self {
+        Self {
+            transport: Transport::default(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn for_network(network: &PiNetworkConfig) -> Self {
+        Self::new(&network.api_url)
+    }
+
+    /// Sends requests through `transport`, e.g. to share its circuit breakers or change its timeouts.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Looks up an account.
+    pub async fn get_account(&self, account_id: &str) -> Result<PiAccount, ChainError> {
+        let request = self.transport.client().get(format!("{}/pi/account", self.api_url)).query(&[("account_id", account_id)]);
+        let response = send(&self.transport, request).await?;
+        parse(response.get("account_info").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Returns the balance of an account in the smallest Pi unit.
+    pub async fn get_balance(&self, account_id: &str) -> Result<u64, ChainError> {
+        let request = self.transport.client().get(format!("{}/pi/balance", self.api_url)).query(&[("account_id", account_id)]);
+        let response = send(&self.transport, request).await?;
+        parse(response.get("balance").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Creates a transaction and returns its ID.
+    pub async fn create_transaction(&self, transfer: &PiTransferRequest) -> Result<String, ChainError> {
+        info!("Creating Pi transaction of {} from {} to {}", transfer.amount, transfer.source_account_id, transfer.destination_account_id);
+        let request = self.transport.client().post(format!("{}/pi/transaction", self.api_url)).json(transfer);
+        let response = submit(&self.transport, request, transfer.idempotency_key.as_deref()).await?;
+        string_field(&response, "transaction_id")
+    }
+
+    /// Lists transactions of an account, newest first, one page at a time.
+    pub async fn list_transactions(
+        &self,
+        account_id: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<PiPage<PiTransactionRecord>, ChainError> {
+        let mut request = self
+            .transport
+            .client()
+            .get(format!("{}/pi/transactions", self.api_url))
+            .query(&[("account_id", account_id), ("limit", &limit.to_string())]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        parse(send(&self.transport, request).await?)
+    }
+
+    /// Follows `next_cursor` until the listing is exhausted.
+    pub async fn all_transactions(&self, account_id: &str) -> Result<Vec<PiTransactionRecord>, ChainError> {
+        let mut transactions = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.list_transactions(account_id, cursor.as_deref(), DEFAULT_PAGE_LIMIT).await?;
+            transactions.extend(page.records);
+            match page.next_cursor {
+                // Guard against a server that keeps returning the same cursor
+                Some(next) if cursor.as_deref() != Some(next.as_str()) => cursor = Some(next),
+                _ => return Ok(transactions),
+            }
+        }
+    }
+
+    /// Creates a payment that the recipient still has to sign.
+    pub async fn create_payment(&self, payment: &PiPaymentCreation) -> Result<PiPaymentRecord, ChainError> {
+        let request = self.transport.client().post(format!("{}/pi/payments", self.api_url)).json(payment);
+        parse(submit(&self.transport, request, None).await?)
+    }
+
+    pub async fn get_payment(&self, payment_id: &str) -> Result<PiPaymentRecord, ChainError> {
+        parse(send(&self.transport, self.transport.client().get(format!("{}/pi/payments/{}", self.api_url, payment_id))).await?)
+    }
+
+    /// Approves a payment so the user can submit its transaction.
+    pub async fn approve_payment(&self, payment_id: &str) -> Result<PiPaymentRecord, ChainError> {
+        self.payment_action(payment_id, "approve", Value::Null).await
+    }
+
+    /// Completes a payment once its transaction `txid` is on chain.
+    pub async fn complete_payment(&self, payment_id: &str, txid: &str) -> Result<PiPaymentRecord, ChainError> {
+        self.payment_action(payment_id, "complete", serde_json::json!({ "txid": txid })).await
+    }
+
+    pub async fn cancel_payment(&self, payment_id: &str) -> Result<PiPaymentRecord, ChainError> {
+        self.payment_action(payment_id, "cancel", Value::Null).await
+    }
+
+    async fn payment_action(&self, payment_id: &str, action: &str, body: Value) -> Result<PiPaymentRecord, ChainError> {
+        info!("Payment {}: {}", payment_id, action);
+        let mut request = self.transport.client().post(format!("{}/pi/payments/{}/{}", self.api_url, payment_id, action));
+        if !body.is_null() {
+            request = request.json(&body);
+        }
+        // Each action can only be applied once to a payment, so it names its own retries
+        let key = format!("{}:{}", action, payment_id);
+        parse(submit(&self.transport, request, Some(&key)).await?)
+    }
+}
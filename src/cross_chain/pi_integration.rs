@@ -1,7 +1,17 @@
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
 use std::error::Error;
+use async_trait::async_trait;
 use log::{info, error};
+use super::chain::{Asset, Chain, ChainTransaction, Payment, SignedTransfer, Transfer, TransactionStatus};
+use super::error::ChainError;
+use super::htlc::{self, HtlcChain, HtlcLock, HtlcParams};
+use super::pi_client::{PiClient, PiNetworkConfig};
+use super::http::{parse, send, string_field, submit, u64_field};
+use super::transport::Transport;
+use crate::models::stellar::strkey;
+
+/// Decimal places of PI amounts.
+pub const PI_DECIMALS: u32 = 8;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PiTransaction {
@@ -10,29 +20,88 @@ pub struct PiTransaction {
     pub amount: f64,
 }
 
+/// A payment received or sent by a Pi account. Amounts are in the smallest Pi unit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPayment {
+    /// ID of the transaction carrying the payment.
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// Opaque cursor for resuming a payment listing after this payment.
+    pub paging_token: String,
+}
+
+/// A payment to submit from an account controlled by this node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiPaymentRequest {
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Identifies the payment across retries and restarts; sent as the `Idempotency-Key` header.
+    #[serde(skip)]
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PiPaymentPage {
+    payments: Vec<PiPayment>,
+}
+
+// Transaction as returned by GET /transactions/{id}
+#[derive(Deserialize)]
+struct PiTransactionInfo {
+    #[serde(default)]
+    block: u64,
+    confirmations: u64,
+    #[serde(default = "successful_by_default")]
+    successful: bool,
+}
+
+fn successful_by_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct PiLatestBlock {
+    height: u64,
+}
+
 pub struct PiIntegration {
-    client: Client,
+    transport: Transport,
     pi_api_url: String,
 }
 
 impl PiIntegration {
     /// Creates a new instance of PiIntegration with the specified API URL.
     pub fn new(api_url: &str) -> Self {
-        let client = Client::new();
         Self {
-            client,
+            transport: Transport::default(),
             pi_api_url: api_url.to_string(),
         }
     }
 
+    /// Creates a new instance of PiIntegration for a configured network.
+    pub fn for_network(network: &PiNetworkConfig) -> Self {
+        Self::new(network.api_url.trim_end_matches('/'))
+    }
+
+    /// Sends requests through `transport`, e.g. to share its circuit breakers or change its timeouts.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
     /// Sends a transaction to the Pi network.
     pub async fn send_transaction(&self, transaction: PiTransaction) -> Result<String, Box<dyn Error>> {
         info!("Sending transaction: {:?}", transaction);
         
-        let response = self.client.post(&self.pi_api_url)
-            .json(&transaction)
-            .send()
-            .await?;
+        let request = self.transport.client().post(&self.pi_api_url).json(&transaction);
+        let response = self.transport.send(request).await?;
 
         if response.status().is_success() {
             let tx_id: String = response.json().await?;
@@ -46,21 +115,138 @@ impl PiIntegration {
     }
 
     /// Retrieves the status of a transaction by its ID.
-    pub async fn get_transaction_status(&self, tx_id: &str) -> Result<String, Box<dyn Error>> {
+    pub async fn get_transaction_status(&self, tx_id: &str) -> Result<TransactionStatus, ChainError> {
         info!("Retrieving status for transaction ID: {}", tx_id);
-        
-        let response = self.client.get(format!("{}/{}", self.pi_api_url, tx_id))
-            .send()
-            .await?;
+        self.transaction_status(tx_id).await
+    }
 
-        if response.status().is_success() {
-            let status: String = response.json().await?;
-            info!("Transaction status retrieved successfully: {}", status);
-            Ok(status)
-        } else {
-            let error_message = format!("Failed to retrieve transaction status: {}", response.status());
-            error!("{}", error_message);
-            Err(error_message.into())
+    /// Lists payments involving `account`, oldest first, starting after `cursor`.
+    pub async fn list_payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<PiPayment>, ChainError> {
+        let mut request = self.transport.client()
+            .get(format!("{}/accounts/{}/payments", self.pi_api_url, account))
+            .query(&[("order", "asc")]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
         }
+        let page: PiPaymentPage = parse(send(&self.transport, request).await?)?;
+        Ok(page.payments)
+    }
+
+    /// Returns how many blocks have confirmed a transaction, counting the block it is in.
+    pub async fn get_confirmations(&self, tx_id: &str) -> Result<u64, ChainError> {
+        let request = self.transport.client().get(format!("{}/transactions/{}", self.pi_api_url, tx_id));
+        let response = send(&self.transport, request).await?;
+        u64_field(&response, "confirmations")
+    }
+
+    /// Submits a payment and returns its transaction ID.
+    pub async fn send_payment(&self, payment: &PiPaymentRequest) -> Result<String, ChainError> {
+        info!("Sending {} from {} to {}", payment.amount, payment.from, payment.to);
+        let request = self.transport.client().post(format!("{}/payments", self.pi_api_url)).json(payment);
+        let response = submit(&self.transport, request, payment.idempotency_key.as_deref()).await?;
+        string_field(&response, "tx_id")
+    }
+}
+
+#[async_trait]
+impl Chain for PiIntegration {
+    fn chain_name(&self) -> &str {
+        "pi"
+    }
+
+    fn decimals(&self) -> u32 {
+        PI_DECIMALS
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        strkey::is_valid_destination(address)
+    }
+
+    async fn balance(&self, account: &str, asset: &Asset) -> Result<u64, ChainError> {
+        if *asset != Asset::Native {
+            return Ok(0);
+        }
+        PiClient::new(&self.pi_api_url).with_transport(self.transport.clone()).get_balance(account).await
+    }
+
+    /// Pi payments are signed by the API node, so the payload is the JSON payment request.
+    async fn build_transfer(&self, transfer: &Transfer) -> Result<SignedTransfer, ChainError> {
+        if transfer.asset != Asset::Native {
+            return Err(ChainError::Other(format!("Pi transfers must be native, got {:?}", transfer.asset)));
+        }
+        let request = PiPaymentRequest {
+            from: transfer.from.clone(),
+            to: transfer.to.clone(),
+            amount: transfer.amount,
+            memo: transfer.memo.clone(),
+            idempotency_key: None,
+        };
+        let payload = serde_json::to_string(&request).map_err(|e| ChainError::Other(e.to_string()))?;
+        Ok(SignedTransfer { transfer: transfer.clone(), payload, tx_id: None, expires_at: None })
+    }
+
+    async fn submit_transfer(&self, signed: &SignedTransfer) -> Result<String, ChainError> {
+        let mut request: PiPaymentRequest = serde_json::from_str(&signed.payload)
+            .map_err(|e| ChainError::Other(format!("Invalid Pi payment payload: {}", e)))?;
+        request.idempotency_key = signed.transfer.reference.clone();
+        self.send_payment(&request).await
+    }
+
+    async fn transaction(&self, tx_id: &str) -> Result<ChainTransaction, ChainError> {
+        let request = self.transport.client().get(format!("{}/transactions/{}", self.pi_api_url, tx_id));
+        let info: PiTransactionInfo = parse(send(&self.transport, request).await?)?;
+        Ok(ChainTransaction {
+            tx_id: tx_id.to_string(),
+            block: info.block,
+            confirmations: info.confirmations,
+            successful: info.successful,
+        })
+    }
+
+    async fn payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<Payment>, ChainError> {
+        let payments = self.list_payments(account, cursor).await?;
+        Ok(payments
+            .into_iter()
+            .map(|payment| Payment {
+                id: payment.id.clone(),
+                tx_id: payment.id,
+                from: payment.from,
+                to: payment.to,
+                asset: Asset::Native,
+                amount: payment.amount,
+                memo: payment.memo,
+                cursor: payment.paging_token,
+            })
+            .collect())
+    }
+
+    /// Reads the height from `GET /blocks/latest`.
+    async fn latest_block(&self) -> Result<u64, ChainError> {
+        let request = self.transport.client().get(format!("{}/blocks/latest", self.pi_api_url));
+        let latest: PiLatestBlock = parse(send(&self.transport, request).await?)?;
+        Ok(latest.height)
+    }
+}
+
+#[async_trait]
+impl HtlcChain for PiIntegration {
+    async fn lock_funds(&self, params: &HtlcParams) -> Result<String, ChainError> {
+        htlc::lock_funds(&self.transport, &self.pi_api_url, params).await
+    }
+
+    async fn get_lock(&self, lock_id: &str) -> Result<HtlcLock, ChainError> {
+        htlc::get_lock(&self.transport, &self.pi_api_url, lock_id).await
+    }
+
+    async fn find_lock(&self, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError> {
+        htlc::find_lock(&self.transport, &self.pi_api_url, hashlock).await
+    }
+
+    async fn claim(&self, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError> {
+        htlc::claim(&self.transport, &self.pi_api_url, lock_id, preimage).await
+    }
+
+    async fn refund(&self, lock_id: &str) -> Result<String, ChainError> {
+        htlc::refund(&self.transport, &self.pi_api_url, lock_id).await
     }
 }
@@ -0,0 +1,309 @@
+// Hashed timelocks on Stellar, built from escrow accounts guarded by hash-x and pre-authorized
+// transaction signers
+
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use log::info;
+use super::error::ChainError;
+use super::horizon::{HorizonError, TransactionResponse};
+use super::htlc::{HtlcChain, HtlcLock, HtlcParams, HtlcStatus};
+use super::stellar_integration::{EnvelopeBuilder, StellarIntegration};
+use crate::models::stellar::transaction::{
+    AccountId, Asset, FeeBumpTransactionEnvelope, Memo, Network, Operation, OperationBody, Signer, SignerKey, TimeBounds,
+    Transaction, TransactionBuilder, TransactionEnvelope, TransactionError,
+};
+use crate::models::stellar::xdr::{XdrDecode, XdrEncode};
+
+/// Stroops an escrow account holds on top of the locked amount: its minimum balance with three
+/// extra signers (2.5 XLM) plus the fees of its own transactions. What is left of it goes back
+/// to the sender when the lock is claimed or refunded.
+pub const ESCROW_RESERVE: u64 = 30_000_000;
+
+/// Prefix of the context escrow keys are derived from with `EnvelopeBuilder::derive_keypair`,
+/// followed by the hashlock.
+pub const ESCROW_CONTEXT: &[u8] = b"stellar-htlc-escrow:";
+
+// How long the escrow setup and claim transactions stay valid
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// The terms of a lock, as recorded by the transactions that set up its escrow
+struct Escrow {
+    sender: AccountId,
+    receiver: Option<AccountId>,
+    amount: u64,
+    hashlock: Option<[u8; 32]>,
+    expires_at: u64,
+    settlement: Option<TransactionEnvelope>,
+}
+
+fn transaction_error(error: TransactionError) -> ChainError {
+    ChainError::Other(format!("Invalid transaction: {}", error))
+}
+
+fn account_id(address: &str) -> Result<AccountId, ChainError> {
+    AccountId::from_strkey(address).map_err(|e| {
+        ChainError::Horizon(HorizonError::InvalidAddress { address: address.to_string(), reason: e.to_string() })
+    })
+}
+
+fn parse_hashlock(hashlock: &[u8]) -> Result<[u8; 32], ChainError> {
+    hashlock.try_into().map_err(|_| ChainError::Other("A Stellar hashlock must be a 32-byte SHA-256 hash".to_string()))
+}
+
+fn timestamp(seconds: u64) -> Result<DateTime<Utc>, ChainError> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+        .ok_or_else(|| ChainError::InvalidResponse(format!("escrow expiry {} is out of range", seconds)))
+}
+
+// The transaction that returns an expired lock to its sender. It is built the same way every
+// time, so its hash can be authorized as a signer before it exists and it can be rebuilt later.
+fn refund_transaction(escrow: AccountId, sequence: i64, sender: AccountId, expires_at: u64) -> Result<Transaction, ChainError> {
+    TransactionBuilder::new(escrow, sequence)
+        .time_bounds(TimeBounds { min_time: expires_at, max_time: 0 })
+        .add_operation(Operation::account_merge(sender.into()))
+        .build()
+        .map_err(transaction_error)
+}
+
+// Horizon reports fee-bumped transactions with their fee bump envelope
+fn decode_envelope(transaction: &TransactionResponse) -> Result<TransactionEnvelope, ChainError> {
+    TransactionEnvelope::from_xdr_base64(&transaction.envelope_xdr)
+        .or_else(|_| FeeBumpTransactionEnvelope::from_xdr_base64(&transaction.envelope_xdr).map(|bump| bump.tx.inner))
+        .map_err(|e| ChainError::InvalidResponse(format!("transaction {}: {}", transaction.hash, e)))
+}
+
+// Reads the terms of the escrow from its successful transactions, oldest first
+fn parse_escrow(escrow: AccountId, transactions: &[TransactionResponse]) -> Result<Option<Escrow>, ChainError> {
+    let mut found: Option<Escrow> = None;
+    for transaction in transactions.iter().filter(|t| t.successful) {
+        let envelope = decode_envelope(transaction)?;
+        let source = envelope.tx.source_account.account_id();
+        let Some(lock) = found.as_mut() else {
+            // The first transaction creates the escrow, with the expiry as its memo
+            let creation = envelope.tx.operations.iter().find_map(|operation| match &operation.body {
+                OperationBody::CreateAccount { destination, starting_balance } if *destination == escrow => {
+                    Some((operation.source_account.map_or(source, |s| s.account_id()), *starting_balance))
+                }
+                _ => None,
+            });
+            let (Some((sender, balance)), Memo::Id(expires_at)) = (creation, &envelope.tx.memo) else {
+                return Err(ChainError::InvalidResponse(format!("{} is not an escrow account", escrow)));
+            };
+            let amount = u64::try_from(balance).unwrap_or(0).saturating_sub(ESCROW_RESERVE);
+            found = Some(Escrow { sender, receiver: None, amount, hashlock: None, expires_at: *expires_at, settlement: None });
+            continue;
+        };
+        if source != escrow {
+            continue;
+        }
+        if lock.hashlock.is_none() {
+            for operation in &envelope.tx.operations {
+                if let OperationBody::SetOptions { signer: Some(signer), .. } = &operation.body {
+                    match signer.key {
+                        SignerKey::HashX(hash) => lock.hashlock = Some(hash),
+                        SignerKey::Ed25519(key) => lock.receiver = Some(AccountId(key)),
+                        SignerKey::PreAuthTx(_) => {}
+                    }
+                }
+            }
+        } else if lock.settlement.is_none() {
+            lock.settlement = Some(envelope);
+        }
+    }
+    Ok(found)
+}
+
+impl StellarIntegration {
+    fn htlc_signer(&self) -> Result<(&dyn EnvelopeBuilder, Network), ChainError> {
+        let builder = self.envelope_builder()
+            .ok_or_else(|| ChainError::Other("No envelope builder configured for Stellar hashed timelocks".to_string()))?;
+        let network = builder.network()
+            .cloned()
+            .ok_or_else(|| ChainError::Other("The envelope builder does not name its network".to_string()))?;
+        Ok((builder.as_ref(), network))
+    }
+
+    async fn escrow(&self, lock_id: &str) -> Result<Option<Escrow>, ChainError> {
+        let escrow = account_id(lock_id)?;
+        let transactions = self.account_transactions(lock_id).await?;
+        parse_escrow(escrow, &transactions)
+    }
+
+    async fn sign_and_submit(&self, builder: &dyn EnvelopeBuilder, transaction: Transaction) -> Result<String, ChainError> {
+        let envelope = builder.sign_transaction(transaction).map_err(ChainError::Other)?;
+        Ok(self.submit_with_fee_bumps(&envelope.to_xdr_base64()).await?.hash)
+    }
+}
+
+/// Hashed timelocks of native lumens, each held by its own escrow account; the lock ID is the
+/// escrow's address.
+///
+/// `lock_funds` creates the escrow from the sender's account with the amount plus
+/// `ESCROW_RESERVE` and the expiry as its memo. The escrow then gives up its master key to three
+/// signers: the receiver and the hashlock (an `X...` hash-x signer) with weight 1 each, so a
+/// claim needs the receiver's signature and the preimage, and a pre-authorized transaction with
+/// weight 2 that merges the escrow back into the sender from the expiry on. Claims expire at the
+/// expiry, and the claim and the refund use the same sequence number, so only one of them can
+/// ever be applied.
+///
+/// The escrow key is derived from the hashlock by the envelope builder, so a lock interrupted
+/// by a crash is finished by calling `lock_funds` again. Locks and claims need an envelope
+/// builder that signs for the sender and receiver respectively; anyone can submit a refund.
+#[async_trait]
+impl HtlcChain for StellarIntegration {
+    async fn lock_funds(&self, params: &HtlcParams) -> Result<String, ChainError> {
+        let (builder, network) = self.htlc_signer()?;
+        let hashlock = parse_hashlock(&params.hashlock)?;
+        let sender = account_id(&params.sender)?;
+        let receiver = account_id(&params.receiver)?;
+        let expires_at = u64::try_from(params.expires_at.timestamp())
+            .map_err(|_| ChainError::Other(format!("Expiry {} is before 1970", params.expires_at)))?;
+        let balance = params.amount
+            .checked_add(ESCROW_RESERVE)
+            .and_then(|balance| i64::try_from(balance).ok())
+            .ok_or_else(|| ChainError::Other(format!("Amount {} is too large", params.amount)))?;
+
+        let escrow = builder.derive_keypair(&[ESCROW_CONTEXT, &hashlock[..]].concat()).map_err(ChainError::Other)?;
+        let escrow_id = AccountId::from(&escrow.public);
+        let lock_id = escrow_id.to_strkey();
+
+        let account = match self.load_account(&lock_id).await {
+            Ok(account) => {
+                // Finishing a lock interrupted after the escrow was created: it must hold these terms
+                match self.escrow(&lock_id).await? {
+                    Some(existing)
+                        if existing.sender == sender
+                            && existing.amount == params.amount
+                            && existing.expires_at == expires_at
+                            && existing.receiver.map_or(true, |r| r == receiver) => {}
+                    _ => return Err(ChainError::Other(format!("Escrow {} holds different terms", lock_id))),
+                }
+                account
+            }
+            Err(HorizonError::NotFound(_)) => {
+                // A settled escrow has been merged away and must not be funded again
+                if !self.account_transactions(&lock_id).await?.is_empty() {
+                    return Err(ChainError::Other(format!("The lock for this hashlock was already settled in {}", lock_id)));
+                }
+                info!("Locking {} stroops for {} in escrow {}", params.amount, params.receiver, lock_id);
+                let source = self.load_account(&params.sender).await?;
+                let creation = TransactionBuilder::new(sender, source.sequence + 1)
+                    .base_fee(self.estimate_fee(1).await?)
+                    .memo(Memo::Id(expires_at))
+                    .timeout(SUBMIT_TIMEOUT)
+                    .add_operation(Operation::create_account(escrow_id, balance))
+                    .build()
+                    .map_err(transaction_error)?;
+                self.sign_and_submit(builder, creation).await?;
+                self.load_account(&lock_id).await?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let hash_x = SignerKey::HashX(hashlock).to_strkey();
+        if account.signers.iter().any(|signer| signer.key == hash_x) {
+            return Ok(lock_id);
+        }
+        let refund = refund_transaction(escrow_id, account.sequence + 2, sender, expires_at)?;
+        let signer = |key: SignerKey, weight: u32| Operation::set_signer(Signer { key, weight });
+        let setup = TransactionBuilder::new(escrow_id, account.sequence + 1)
+            .base_fee(self.estimate_fee(4).await?)
+            .timeout(SUBMIT_TIMEOUT)
+            .add_operation(signer(SignerKey::PreAuthTx(refund.hash(&network)), 2))
+            .add_operation(signer(SignerKey::from(receiver), 1))
+            .add_operation(signer(SignerKey::HashX(hashlock), 1))
+            .add_operation(Operation::set_thresholds(0, 2, 2, 2))
+            .build()
+            .map_err(transaction_error)?;
+        self.submit_with_fee_bumps(&setup.sign(&network, &escrow).to_xdr_base64()).await?;
+        info!("Escrow {} locked under hashlock {}", lock_id, hex::encode(hashlock));
+        Ok(lock_id)
+    }
+
+    async fn get_lock(&self, lock_id: &str) -> Result<HtlcLock, ChainError> {
+        let escrow = self.escrow(lock_id).await?.ok_or_else(|| ChainError::NotFound(lock_id.to_string()))?;
+        let (Some(receiver), Some(hashlock)) = (escrow.receiver, escrow.hashlock) else {
+            return Err(ChainError::Other(format!("Escrow {} is not locked yet", lock_id)));
+        };
+
+        let (status, preimage) = match &escrow.settlement {
+            None => (HtlcStatus::Locked, None),
+            Some(settlement) => {
+                let paid = settlement.tx.operations.iter().any(|op| matches!(op.body, OperationBody::Payment { .. }));
+                if paid {
+                    (HtlcStatus::Claimed, settlement.hash_x_preimage(&hashlock).map(hex::encode))
+                } else {
+                    (HtlcStatus::Refunded, None)
+                }
+            }
+        };
+        Ok(HtlcLock {
+            lock_id: lock_id.to_string(),
+            sender: escrow.sender.to_strkey(),
+            receiver: receiver.to_strkey(),
+            amount: escrow.amount,
+            hashlock: hashlock.to_vec(),
+            expires_at: timestamp(escrow.expires_at)?,
+            status,
+            preimage,
+        })
+    }
+
+    async fn find_lock(&self, hashlock: &[u8]) -> Result<Option<HtlcLock>, ChainError> {
+        let signer = SignerKey::HashX(parse_hashlock(hashlock)?).to_strkey();
+        // Only open escrows still list their signers
+        match self.accounts_with_signer(&signer).await?.first() {
+            Some(account) => self.get_lock(&account.id).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn claim(&self, lock_id: &str, preimage: &[u8]) -> Result<String, ChainError> {
+        let (builder, _) = self.htlc_signer()?;
+        let lock = self.get_lock(lock_id).await?;
+        if lock.status != HtlcStatus::Locked {
+            return Err(ChainError::Other(format!("Lock {} is already {:?}", lock_id, lock.status)));
+        }
+        let escrow = account_id(lock_id)?;
+        let account = self.load_account(lock_id).await?;
+        let expires_at = u64::try_from(lock.expires_at.timestamp()).unwrap_or(0);
+        let now = u64::try_from(Utc::now().timestamp()).unwrap_or(0);
+        let amount = i64::try_from(lock.amount).map_err(|_| ChainError::Other(format!("Amount {} is too large", lock.amount)))?;
+
+        // Valid only until the expiry, after which the refund takes over
+        let transaction = TransactionBuilder::new(escrow, account.sequence + 1)
+            .base_fee(self.estimate_fee(2).await?)
+            .time_bounds(TimeBounds { min_time: 0, max_time: expires_at.min(now.saturating_add(SUBMIT_TIMEOUT.as_secs())) })
+            .add_operation(Operation::payment(account_id(&lock.receiver)?.into(), Asset::Native, amount))
+            .add_operation(Operation::account_merge(account_id(&lock.sender)?.into()))
+            .build()
+            .map_err(transaction_error)?;
+        let mut envelope = builder.sign_transaction(transaction).map_err(ChainError::Other)?;
+        envelope.sign_hash_x(preimage).map_err(transaction_error)?;
+
+        info!("Claiming escrow {}", lock_id);
+        Ok(self.submit_with_fee_bumps(&envelope.to_xdr_base64()).await?.hash)
+    }
+
+    async fn refund(&self, lock_id: &str) -> Result<String, ChainError> {
+        let lock = self.get_lock(lock_id).await?;
+        if lock.status != HtlcStatus::Locked {
+            return Err(ChainError::Other(format!("Lock {} is already {:?}", lock_id, lock.status)));
+        }
+        if Utc::now() < lock.expires_at {
+            return Err(ChainError::Other(format!("Lock {} does not expire before {}", lock_id, lock.expires_at)));
+        }
+        let escrow = account_id(lock_id)?;
+        let account = self.load_account(lock_id).await?;
+        let expires_at = u64::try_from(lock.expires_at.timestamp()).unwrap_or(0);
+        let refund = refund_transaction(escrow, account.sequence + 1, account_id(&lock.sender)?, expires_at)?;
+
+        // Authorized by its hash, so it is submitted without signatures
+        info!("Refunding escrow {} to {}", lock_id, lock.sender);
+        let envelope = TransactionEnvelope { tx: refund, signatures: Vec::new() };
+        Ok(self.submit_with_fee_bumps(&envelope.to_xdr_base64()).await?.hash)
+    }
+}
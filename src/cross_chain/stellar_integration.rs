@@ -1,66 +1,615 @@
+use std::sync::Arc;
+use ed25519_dalek::Keypair;
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
-use std::error::Error;
-use log::{info, error};
+use serde::de::DeserializeOwned;
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use log::{info, warn};
+use super::chain::{Asset, Chain, ChainTransaction, Payment, SignedTransfer, Transfer};
+use super::error::ChainError;
+use super::fees::{self, FeeConfig};
+use super::horizon::{
+    self, parse_amount, AccountResponse, AssetResponse, FeeStats, HorizonError, LiquidityPoolResponse, OrderBookResponse, RootResponse,
+    TransactionResponse, TransactionResultCode, STELLAR_DECIMALS,
+};
+use super::network::{NetworkProfile, StellarAssetInfo};
+use super::transport::Transport;
+use crate::models::stellar::strkey::{self, StrKey};
+use crate::models::stellar::transaction::{Network, Transaction, TransactionEnvelope};
+use crate::models::stellar::xdr::XdrDecode;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct StellarTransaction {
+/// An asset on the Stellar network.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StellarAsset {
+    Native,
+    Credit { code: String, issuer: String },
+}
+
+impl StellarAsset {
+    /// Horizon's canonical form: "native" or "CODE:ISSUER".
+    pub fn canonical(&self) -> String {
+        match self {
+            StellarAsset::Native => "native".to_string(),
+            StellarAsset::Credit { code, issuer } => format!("{}:{}", code, issuer),
+        }
+    }
+
+    /// Parses Horizon's canonical form.
+    pub fn from_canonical(asset: &str) -> Option<Self> {
+        match asset.split_once(':') {
+            None if asset == "native" => Some(StellarAsset::Native),
+            Some((code, issuer)) if !code.is_empty() && !issuer.is_empty() => {
+                Some(StellarAsset::Credit { code: code.to_string(), issuer: issuer.to_string() })
+            }
+            _ => None,
+        }
+    }
+
+    // Query parameters selecting the asset as `{prefix}_asset_*`
+    fn query(&self, prefix: &str) -> Vec<(String, String)> {
+        match self {
+            StellarAsset::Native => vec![(format!("{}_asset_type", prefix), "native".to_string())],
+            StellarAsset::Credit { code, issuer } => {
+                let kind = if code.len() <= 4 { "credit_alphanum4" } else { "credit_alphanum12" };
+                vec![
+                    (format!("{}_asset_type", prefix), kind.to_string()),
+                    (format!("{}_asset_code", prefix), code.clone()),
+                    (format!("{}_asset_issuer", prefix), issuer.clone()),
+                ]
+            }
+        }
+    }
+}
+
+impl From<&StellarAssetInfo> for StellarAsset {
+    fn from(asset: &StellarAssetInfo) -> Self {
+        StellarAsset::Credit { code: asset.code.clone(), issuer: asset.issuer.clone() }
+    }
+}
+
+/// A payment received or sent by a Stellar account. Amounts are in stroops.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StellarPayment {
+    /// Horizon operation ID of the payment.
+    pub id: String,
+    pub transaction_hash: String,
+    pub from: String,
+    pub to: String,
+    pub asset: StellarAsset,
+    pub amount: u64,
+    /// Text and ID memos as they are, hash and return memos in hex.
+    pub memo: Option<String>,
+    /// Horizon cursor for resuming a payment listing after this payment.
+    pub paging_token: String,
+}
+
+/// A payment to submit from an account controlled by this node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StellarPaymentRequest {
     pub source_account: String,
     pub destination_account: String,
-    pub amount: f64,
+    pub asset: StellarAsset,
+    pub amount: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
 }
 
+/// Builds and signs transaction envelopes for payments sent by this node.
+pub trait EnvelopeBuilder: Send + Sync {
+    /// Returns a signed, base64-encoded `TransactionEnvelope` for `payment` using the given
+    /// sequence number and fee per operation in stroops.
+    fn build_payment(&self, payment: &StellarPaymentRequest, sequence: i64, base_fee: u32) -> Result<String, String>;
+
+    /// Wraps a signed envelope in a fee bump paying `base_fee` per operation from the builder's
+    /// account, returning a base64 `FeeBumpTransactionEnvelope`.
+    fn fee_bump(&self, _envelope_xdr: &str, _base_fee: u32) -> Result<String, String> {
+        Err("This envelope builder cannot sign fee bumps".to_string())
+    }
+
+    /// The network the builder signs for, needed to hash transactions before they are signed.
+    fn network(&self) -> Option<&Network> {
+        None
+    }
+
+    /// Signs a transaction other than a plain payment, e.g. the escrow transactions of a
+    /// hashed timelock.
+    fn sign_transaction(&self, _transaction: Transaction) -> Result<TransactionEnvelope, String> {
+        Err("This envelope builder cannot sign arbitrary transactions".to_string())
+    }
+
+    /// Derives a keypair from `context` that only this builder can derive again, so that an
+    /// escrow account created before a crash can still be controlled afterwards.
+    fn derive_keypair(&self, _context: &[u8]) -> Result<Keypair, String> {
+        Err("This envelope builder cannot derive keys".to_string())
+    }
+}
+
+// Operation types that move an asset to the record's `to` account
+const PAYMENT_KINDS: [&str; 3] = ["payment", "path_payment_strict_receive", "path_payment_strict_send"];
+
+// Payment operation record as returned by /accounts/{id}/payments?join=transactions
+#[derive(Deserialize)]
+pub(crate) struct PaymentRecord {
+    id: String,
+    paging_token: String,
+    transaction_hash: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    from: String,
+    #[serde(default)]
+    to: String,
+    #[serde(default)]
+    asset_type: String,
+    #[serde(default)]
+    asset_code: Option<String>,
+    #[serde(default)]
+    asset_issuer: Option<String>,
+    #[serde(default)]
+    amount: String,
+    #[serde(default)]
+    transaction: Option<TransactionResponse>,
+}
+
+impl PaymentRecord {
+    /// Converts the record, or returns `None` for operations other than payments and path
+    /// payments. For a path payment, the asset and amount are those the destination received.
+    pub(crate) fn into_payment(self) -> Result<Option<StellarPayment>, HorizonError> {
+        if !PAYMENT_KINDS.contains(&self.kind.as_str()) {
+            return Ok(None);
+        }
+        let asset = match (self.asset_type.as_str(), self.asset_code, self.asset_issuer) {
+            ("native", _, _) => StellarAsset::Native,
+            (_, Some(code), Some(issuer)) => StellarAsset::Credit { code, issuer },
+            _ => return Err(HorizonError::InvalidResponse(format!("payment {} has no asset", self.id))),
+        };
+        Ok(Some(StellarPayment {
+            amount: parse_amount(&self.amount)?,
+            id: self.id,
+            transaction_hash: self.transaction_hash,
+            from: self.from,
+            to: self.to,
+            asset,
+            memo: self.transaction.and_then(payment_memo),
+            paging_token: self.paging_token,
+        }))
+    }
+}
+
+// Horizon encodes 32-byte memos in base64; they are reported in hex like transaction hashes
+fn payment_memo(transaction: TransactionResponse) -> Option<String> {
+    let memo = transaction.memo?;
+    match transaction.memo_type.as_deref() {
+        Some("hash") | Some("return") => base64::decode(&memo).ok().map(hex::encode),
+        _ => Some(memo),
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    #[serde(rename = "_embedded")]
+    embedded: Records<T>,
+}
+
+#[derive(Deserialize)]
+struct Records<T> {
+    records: Vec<T>,
+}
+
+// Whether a submission failed only because its fee was too low to be included in time
+fn is_stuck(error: &HorizonError) -> bool {
+    error.is_timeout()
+        || matches!(error, HorizonError::TransactionFailed { transaction: TransactionResultCode::InsufficientFee, .. })
+}
+
+/// Checks that `address` is a `G...` account address.
+pub fn check_account_id(address: &str) -> Result<(), HorizonError> {
+    strkey::decode_account_id(address)
+        .map(|_| ())
+        .map_err(|e| HorizonError::InvalidAddress { address: address.to_string(), reason: e.to_string() })
+}
+
+/// Checks that `address` is a `G...` or `M...` payment destination.
+pub fn check_destination(address: &str) -> Result<(), HorizonError> {
+    match StrKey::decode(address) {
+        Ok(StrKey::AccountId(_)) | Ok(StrKey::MuxedAccount { .. }) => Ok(()),
+        Ok(other) => Err(HorizonError::InvalidAddress {
+            address: address.to_string(),
+            reason: format!("{} keys cannot receive payments", other.prefix()),
+        }),
+        Err(e) => Err(HorizonError::InvalidAddress { address: address.to_string(), reason: e.to_string() }),
+    }
+}
+
+/// Client for a Stellar Horizon server.
 pub struct StellarIntegration {
-    client: Client,
+    transport: Transport,
     stellar_api_url: String,
+    envelope_builder: Option<Arc<dyn EnvelopeBuilder>>,
+    fees: FeeConfig,
 }
 
 impl StellarIntegration {
-    /// Creates a new instance of StellarIntegration with the specified API URL.
+    /// Creates a new instance of StellarIntegration for the Horizon server at `api_url`.
     pub fn new(api_url: &str) -> Self {
-        let client = Client::new();
         Self {
-            client,
-            stellar_api_url: api_url.to_string(),
+            transport: Transport::default(),
+            stellar_api_url: api_url.trim_end_matches('/').to_string(),
+            envelope_builder: None,
+            fees: FeeConfig::default(),
         }
     }
 
-    /// Sends a transaction to the Stellar network.
-    pub async fn send_transaction(&self, transaction: StellarTransaction) -> Result<String, Box<dyn Error>> {
-        info!("Sending Stellar transaction: {:?}", transaction);
-        
-        let response = self.client.post(&self.stellar_api_url)
-            .json(&transaction)
-            .send()
-            .await?;
+    /// Creates a new instance of StellarIntegration for the Horizon server of a registered network.
+    pub fn for_network(profile: &NetworkProfile) -> Self {
+        Self::new(&profile.stellar.horizon_url)
+    }
+
+    /// Sends requests through `transport`, e.g. to share its circuit breakers or change its timeouts.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets the builder used to sign payments sent with `send_payment`.
+    pub fn with_envelope_builder(mut self, builder: Arc<dyn EnvelopeBuilder>) -> Self {
+        self.envelope_builder = Some(builder);
+        self
+    }
+
+    /// Sets how fees are estimated and bumped.
+    pub fn with_fee_config(mut self, fees: FeeConfig) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// The builder that signs for this node, if one is configured.
+    pub fn envelope_builder(&self) -> Option<&Arc<dyn EnvelopeBuilder>> {
+        self.envelope_builder.as_ref()
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, HorizonError> {
+        horizon::send(&self.transport, self.transport.client().get(format!("{}{}", self.stellar_api_url, path))).await
+    }
+
+    /// Loads an account, including its current sequence number.
+    pub async fn load_account(&self, account_id: &str) -> Result<AccountResponse, HorizonError> {
+        check_account_id(account_id)?;
+        self.get(&format!("/accounts/{}", account_id)).await
+    }
+
+    /// Fetches recent fee statistics.
+    pub async fn fee_stats(&self) -> Result<FeeStats, HorizonError> {
+        self.get("/fee_stats").await
+    }
+
+    /// Estimates the fee per operation for a transaction with `operations` operations, from
+    /// recent fee statistics and the fee configuration.
+    pub async fn estimate_fee(&self, operations: u32) -> Result<u32, ChainError> {
+        let stats = self.fee_stats().await?;
+        fees::estimate_base_fee(&stats, &self.fees, operations)
+    }
+
+    /// Submits a signed, base64-encoded transaction envelope and waits for it to be applied.
+    ///
+    /// Rejected transactions return `HorizonError::TransactionFailed` with the result codes.
+    pub async fn send_transaction(&self, envelope_xdr: &str) -> Result<TransactionResponse, HorizonError> {
+        info!("Submitting Stellar transaction");
+        // Resubmitting the same signed envelope cannot apply it twice, so it is safe to retry
+        let request = self.transport.client()
+            .post(format!("{}/transactions", self.stellar_api_url))
+            .form(&[("tx", envelope_xdr)]);
+        let response: TransactionResponse = horizon::submit(&self.transport, request).await?;
+        info!("Stellar transaction {} applied in ledger {}", response.hash, response.ledger);
+        Ok(response)
+    }
+
+    /// Retrieves a transaction by its hash.
+    pub async fn get_transaction(&self, tx_hash: &str) -> Result<TransactionResponse, HorizonError> {
+        self.get(&format!("/transactions/{}", tx_hash)).await
+    }
 
-        if response.status().is_success() {
-            let tx_id: String = response.json().await?;
-            info!("Stellar transaction sent successfully, ID: {}", tx_id);
-            Ok(tx_id)
-        } else {
-            let error_message = format!("Failed to send transaction: {}", response.status());
-            error!("{}", error_message);
-            Err(error_message.into())
+    /// Lists up to 200 transactions involving `account`, oldest first, including those of an
+    /// account that has since been merged. An account that never existed has none.
+    pub async fn account_transactions(&self, account: &str) -> Result<Vec<TransactionResponse>, HorizonError> {
+        check_account_id(account)?;
+        let request = self.transport.client()
+            .get(format!("{}/accounts/{}/transactions", self.stellar_api_url, account))
+            .query(&[("order", "asc"), ("limit", "200")]);
+        match horizon::send::<Page<TransactionResponse>>(&self.transport, request).await {
+            Ok(page) => Ok(page.embedded.records),
+            Err(HorizonError::NotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
         }
     }
 
-    /// Retrieves the status of a transaction by its ID.
-    pub async fn get_transaction_status(&self, tx_id: &str) -> Result<String, Box<dyn Error>> {
-        info!("Retrieving status for Stellar transaction ID: {}", tx_id);
-        
-        let response = self.client.get(format!("{}/{}", self.stellar_api_url, tx_id))
-            .send()
-            .await?;
+    /// Lists the accounts that have `signer` (a `G...`, `T...` or `X...` key) as a signer.
+    pub async fn accounts_with_signer(&self, signer: &str) -> Result<Vec<AccountResponse>, HorizonError> {
+        let request = self.transport.client()
+            .get(format!("{}/accounts", self.stellar_api_url))
+            .query(&[("signer", signer)]);
+        let page: Page<AccountResponse> = horizon::send(&self.transport, request).await?;
+        Ok(page.embedded.records)
+    }
+
+    /// Returns the latest ledger Horizon has ingested.
+    pub async fn latest_ledger(&self) -> Result<u64, HorizonError> {
+        let root: RootResponse = self.get("/").await?;
+        Ok(root.history_latest_ledger)
+    }
 
-        if response.status().is_success() {
-            let status: String = response.json().await?;
-            info!("Transaction status retrieved successfully: {}", status);
-            Ok(status)
-        } else {
-            let error_message = format!("Failed to retrieve transaction status: {}", response.status());
-            error!("{}", error_message);
-            Err(error_message.into())
+    /// Lists payments involving `account`, oldest first, starting after `cursor`.
+    ///
+    /// Path payments are listed with the asset and amount the destination received; account
+    /// creations and merges are skipped.
+    pub async fn list_payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<StellarPayment>, HorizonError> {
+        check_account_id(account)?;
+        let mut request = self.transport.client()
+            .get(format!("{}/accounts/{}/payments", self.stellar_api_url, account))
+            .query(&[("order", "asc"), ("join", "transactions")]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
         }
+        let page: Page<PaymentRecord> = horizon::send(&self.transport, request).await?;
+
+        page.embedded
+            .records
+            .into_iter()
+            .filter_map(|record| record.into_payment().transpose())
+            .collect()
+    }
+
+    /// Loads the order book of offers trading `selling` for `buying`, up to `limit` price levels
+    /// per side.
+    pub async fn order_book(&self, selling: &StellarAsset, buying: &StellarAsset, limit: u32) -> Result<OrderBookResponse, HorizonError> {
+        let request = self.transport.client()
+            .get(format!("{}/order_book", self.stellar_api_url))
+            .query(&selling.query("selling"))
+            .query(&buying.query("buying"))
+            .query(&[("limit", limit)]);
+        horizon::send(&self.transport, request).await
+    }
+
+    /// Lists the liquidity pools holding both assets.
+    pub async fn liquidity_pools(&self, a: &StellarAsset, b: &StellarAsset) -> Result<Vec<LiquidityPoolResponse>, HorizonError> {
+        let reserves = format!("{},{}", a.canonical(), b.canonical());
+        let request = self.transport.client()
+            .get(format!("{}/liquidity_pools", self.stellar_api_url))
+            .query(&[("reserves", reserves.as_str())]);
+        let page: Page<LiquidityPoolResponse> = horizon::send(&self.transport, request).await?;
+        Ok(page.embedded.records)
+    }
+
+    /// Loads the statistics of an issued asset; `None` if no account has ever trusted it.
+    pub async fn asset(&self, code: &str, issuer: &str) -> Result<Option<AssetResponse>, HorizonError> {
+        check_account_id(issuer)?;
+        let request = self.transport.client()
+            .get(format!("{}/assets", self.stellar_api_url))
+            .query(&[("asset_code", code), ("asset_issuer", issuer)]);
+        let page: Page<AssetResponse> = horizon::send(&self.transport, request).await?;
+        Ok(page.embedded.records.into_iter().next())
     }
+
+    /// Returns how many ledgers have closed since a transaction was included, counting its own.
+    pub async fn get_confirmations(&self, tx_hash: &str) -> Result<u64, HorizonError> {
+        let transaction = self.get_transaction(tx_hash).await?;
+        let latest = self.latest_ledger().await?;
+        Ok((latest + 1).saturating_sub(transaction.ledger))
     }
+
+    /// Builds and signs a payment with the next sequence number of its source account,
+    /// returning the base64 envelope.
+    ///
+    /// Requires an envelope builder, see `with_envelope_builder`.
+    pub async fn build_payment(&self, payment: &StellarPaymentRequest) -> Result<String, ChainError> {
+        let builder = self.envelope_builder.as_ref()
+            .ok_or_else(|| ChainError::Other("No envelope builder configured for Stellar payments".to_string()))?;
+        check_account_id(&payment.source_account)?;
+        check_destination(&payment.destination_account)?;
+        if let StellarAsset::Credit { issuer, .. } = &payment.asset {
+            check_account_id(issuer)?;
+        }
+
+        let account = self.load_account(&payment.source_account).await?;
+        let base_fee = self.estimate_fee(1).await?;
+
+        builder
+            .build_payment(payment, account.sequence + 1, base_fee)
+            .map_err(ChainError::Other)
+    }
+
+    /// Builds, signs and submits a payment, returning its transaction hash.
+    ///
+    /// Requires an envelope builder, see `with_envelope_builder`.
+    pub async fn send_payment(&self, payment: &StellarPaymentRequest) -> Result<String, ChainError> {
+        let envelope = self.build_payment(payment).await?;
+        info!("Sending {} stroops from {} to {}", payment.amount, payment.source_account, payment.destination_account);
+        Ok(self.submit_with_fee_bumps(&envelope).await?.hash)
+    }
+
+    /// Submits a signed envelope, fee-bumping it while it is stuck.
+    ///
+    /// A submission is stuck when Horizon times out waiting for it to be applied or rejects its
+    /// fee as too low. A timed-out transaction may still have been applied, so when the envelope
+    /// builder knows the network, the transaction is first looked up by its hash and returned if
+    /// found. Otherwise it is wrapped in a fee bump paid by the envelope builder's account,
+    /// priced by `fees::bump_base_fee`, and resubmitted, up to `max_bumps` times. The network
+    /// applies a transaction at most once, whether it arrives bare or inside any of its fee
+    /// bumps. Without an envelope builder, stuck submissions fail as they are.
+    pub async fn submit_with_fee_bumps(&self, envelope_xdr: &str) -> Result<TransactionResponse, ChainError> {
+        let mut envelope = envelope_xdr.to_string();
+        let mut rate = None;
+        let mut bumps = 0;
+        loop {
+            let error = match self.send_transaction(&envelope).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            if !is_stuck(&error) {
+                return Err(error.into());
+            }
+            if let Some(response) = self.find_applied(envelope_xdr).await? {
+                info!("Stuck Stellar transaction {} was applied in ledger {}", response.hash, response.ledger);
+                return Ok(response);
+            }
+            let builder = match &self.envelope_builder {
+                Some(builder) if bumps < self.fees.max_bumps => builder,
+                _ => return Err(error.into()),
+            };
+
+            let inner = TransactionEnvelope::from_xdr_base64(envelope_xdr)
+                .map_err(|e| ChainError::Other(format!("Cannot fee-bump an undecodable envelope: {}", e)))?;
+            let operations = inner.tx.operations.len().max(1) as u32;
+            let previous = rate.unwrap_or(inner.tx.fee / operations);
+            let estimate = self.estimate_fee(operations).await?;
+            let Some(next) = fees::bump_base_fee(previous, estimate, &self.fees, operations) else {
+                warn!("Stuck Stellar transaction cannot be bumped above {} stroops per operation within the maximum fee", previous);
+                return Err(error.into());
+            };
+
+            warn!("Stellar transaction stuck ({}), fee-bumping from {} to {} stroops per operation", error, previous, next);
+            envelope = builder.fee_bump(envelope_xdr, next).map_err(ChainError::Other)?;
+            rate = Some(next);
+            bumps += 1;
+        }
+    }
+
+    // Looks up the transaction inside `envelope_xdr` by its hash, which needs the envelope
+    // builder's network. Horizon finds it whether it was applied bare or fee-bumped.
+    async fn find_applied(&self, envelope_xdr: &str) -> Result<Option<TransactionResponse>, ChainError> {
+        let Some(network) = self.envelope_builder.as_ref().and_then(|builder| builder.network()) else {
+            return Ok(None);
+        };
+        let inner = TransactionEnvelope::from_xdr_base64(envelope_xdr)
+            .map_err(|e| ChainError::Other(format!("Cannot look up an undecodable envelope: {}", e)))?;
+        match self.get_transaction(&hex::encode(inner.tx.hash(network))).await {
+            Ok(response) if response.successful => Ok(Some(response)),
+            Ok(response) => Err(ChainError::Other(format!("Stellar transaction {} was applied but failed", response.hash))),
+            Err(HorizonError::NotFound(_)) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+impl From<&Asset> for StellarAsset {
+    fn from(asset: &Asset) -> Self {
+        match asset {
+            Asset::Native => StellarAsset::Native,
+            Asset::Issued { code, issuer } => StellarAsset::Credit { code: code.clone(), issuer: issuer.clone() },
+        }
+    }
+}
+
+impl From<StellarAsset> for Asset {
+    fn from(asset: StellarAsset) -> Self {
+        match asset {
+            StellarAsset::Native => Asset::Native,
+            StellarAsset::Credit { code, issuer } => Asset::Issued { code, issuer },
+        }
+    }
+}
+
+#[async_trait]
+impl Chain for StellarIntegration {
+    fn chain_name(&self) -> &str {
+        "stellar"
+    }
+
+    fn decimals(&self) -> u32 {
+        STELLAR_DECIMALS
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        strkey::is_valid_destination(address)
+    }
+
+    async fn balance(&self, account: &str, asset: &Asset) -> Result<u64, ChainError> {
+        // An account that does not exist yet holds nothing
+        let account = match self.load_account(account).await {
+            Ok(account) => account,
+            Err(HorizonError::NotFound(_)) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let line = account.balances.iter().find(|line| match asset {
+            Asset::Native => line.asset_type == "native",
+            Asset::Issued { code, issuer } => {
+                line.asset_code.as_ref() == Some(code) && line.asset_issuer.as_ref() == Some(issuer)
+            }
+        });
+        match line {
+            Some(line) => Ok(parse_amount(&line.balance)?),
+            None => Ok(0),
+        }
+    }
+
+    async fn build_transfer(&self, transfer: &Transfer) -> Result<SignedTransfer, ChainError> {
+        let payload = self
+            .build_payment(&StellarPaymentRequest {
+                source_account: transfer.from.clone(),
+                destination_account: transfer.to.clone(),
+                asset: StellarAsset::from(&transfer.asset),
+                amount: transfer.amount,
+                memo: transfer.memo.clone(),
+            })
+            .await?;
+        // The hash stays the transaction's ID even if it is later fee-bumped
+        let envelope = TransactionEnvelope::from_xdr_base64(&payload).ok();
+        let tx_id = match (&envelope, self.envelope_builder.as_ref().and_then(|builder| builder.network())) {
+            (Some(envelope), Some(network)) => Some(hex::encode(envelope.tx.hash(network))),
+            _ => None,
+        };
+        let expires_at = envelope
+            .and_then(|envelope| envelope.tx.time_bounds)
+            .filter(|bounds| bounds.max_time != 0)
+            .and_then(|bounds| Utc.timestamp_opt(i64::try_from(bounds.max_time).ok()?, 0).single());
+        Ok(SignedTransfer { transfer: transfer.clone(), payload, tx_id, expires_at })
+    }
+
+    /// Returns the hash of the signed transaction, not that of a fee bump wrapping it.
+    async fn submit_transfer(&self, signed: &SignedTransfer) -> Result<String, ChainError> {
+        let response = self.submit_with_fee_bumps(&signed.payload).await?;
+        Ok(signed.tx_id.clone().unwrap_or(response.hash))
+    }
+
+    async fn transaction(&self, tx_id: &str) -> Result<ChainTransaction, ChainError> {
+        let transaction = self.get_transaction(tx_id).await?;
+        let latest = self.latest_ledger().await?;
+        Ok(ChainTransaction {
+            tx_id: transaction.hash,
+            block: transaction.ledger,
+            confirmations: (latest + 1).saturating_sub(transaction.ledger),
+            successful: transaction.successful,
+        })
+    }
+
+    async fn payments(&self, account: &str, cursor: Option<&str>) -> Result<Vec<Payment>, ChainError> {
+        let payments = self.list_payments(account, cursor).await?;
+        Ok(payments
+            .into_iter()
+            .map(|payment| Payment {
+                id: payment.id,
+                tx_id: payment.transaction_hash,
+                from: payment.from,
+                to: payment.to,
+                asset: payment.asset.into(),
+                amount: payment.amount,
+                memo: payment.memo,
+                cursor: payment.paging_token,
+            })
+            .collect())
+    }
+
+    async fn latest_block(&self) -> Result<u64, ChainError> {
+        Ok(self.latest_ledger().await?)
+    }
+
+    async fn issued_supply(&self, asset: &Asset) -> Result<u64, ChainError> {
+        let Asset::Issued { code, issuer } = asset else {
+            return Err(ChainError::Other("The supply of native XLM is not tracked".to_string()));
+        };
+        match self.asset(code, issuer).await? {
+            Some(stats) => Ok(stats.total_supply()?),
+            None => Ok(0),
+        }
+    }
+}
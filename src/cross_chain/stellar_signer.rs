@@ -0,0 +1,116 @@
+// Signs Stellar payment envelopes, fee bumps and escrow transactions with a local ed25519 key.
+// Seeds and addresses are decoded as StrKeys, so a mistyped address is refused before signing.
+
+use std::time::Duration;
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use ring::digest::{digest, SHA256};
+
+use super::network::NetworkProfile;
+use super::stellar_integration::{EnvelopeBuilder, StellarAsset, StellarPaymentRequest};
+use crate::models::stellar::strkey;
+use crate::models::stellar::transaction::{
+    AccountId, Asset, FeeBumpTransaction, Memo, MuxedAccount, Network, Operation, Transaction, TransactionBuilder,
+    TransactionEnvelope,
+};
+use crate::models::stellar::xdr::{XdrDecode, XdrEncode};
+
+/// How long a built payment stays valid before the network rejects it as too late.
+const PAYMENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+impl From<&NetworkProfile> for Network {
+    fn from(profile: &NetworkProfile) -> Self {
+        Network::new(&profile.stellar.network_passphrase)
+    }
+}
+
+/// An `EnvelopeBuilder` that signs payments from the account of a single keypair, and fee
+/// bumps paid by that account.
+pub struct KeypairEnvelopeBuilder {
+    network: Network,
+    keypair: Keypair,
+}
+
+impl KeypairEnvelopeBuilder {
+    pub fn new(network: Network, keypair: Keypair) -> Self {
+        Self { network, keypair }
+    }
+
+    /// Creates a builder that signs for the Stellar network of a registered profile.
+    pub fn for_network(profile: &NetworkProfile, keypair: Keypair) -> Self {
+        Self::new(Network::from(profile), keypair)
+    }
+
+    /// Creates a builder from an `S...` secret seed.
+    pub fn from_seed(network: Network, seed: &str) -> Result<Self, String> {
+        let seed = strkey::decode_seed(seed).map_err(|e| format!("Invalid secret seed: {}", e))?;
+        let secret = SecretKey::from_bytes(&seed).map_err(|e| e.to_string())?;
+        let public = PublicKey::from(&secret);
+        Ok(Self::new(network, Keypair { secret, public }))
+    }
+
+    /// The account this builder signs for.
+    pub fn account_id(&self) -> AccountId {
+        AccountId::from(&self.keypair.public)
+    }
+}
+
+impl EnvelopeBuilder for KeypairEnvelopeBuilder {
+    fn build_payment(&self, payment: &StellarPaymentRequest, sequence: i64, base_fee: u32) -> Result<String, String> {
+        let source = AccountId::from_strkey(&payment.source_account).map_err(|e| format!("Invalid source account: {}", e))?;
+        if source != self.account_id() {
+            return Err(format!("Cannot sign for {}, the signing key belongs to {}", source, self.account_id()));
+        }
+        let destination = MuxedAccount::from_strkey(&payment.destination_account)
+            .map_err(|e| format!("Invalid destination account: {}", e))?;
+        let asset = xdr_asset(&payment.asset)?;
+        let amount = i64::try_from(payment.amount).map_err(|_| format!("Amount {} is too large", payment.amount))?;
+        let memo = match &payment.memo {
+            Some(text) => Memo::Text(text.clone()),
+            None => Memo::None,
+        };
+
+        let transaction = TransactionBuilder::new(source, sequence)
+            .base_fee(base_fee)
+            .memo(memo)
+            .timeout(PAYMENT_TIMEOUT)
+            .add_operation(Operation::payment(destination, asset, amount))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(transaction.sign(&self.network, &self.keypair).to_xdr_base64())
+    }
+
+    fn fee_bump(&self, envelope_xdr: &str, base_fee: u32) -> Result<String, String> {
+        let inner = TransactionEnvelope::from_xdr_base64(envelope_xdr).map_err(|e| format!("Invalid envelope: {}", e))?;
+        let bump = FeeBumpTransaction::new(self.account_id(), base_fee, inner).map_err(|e| e.to_string())?;
+        Ok(bump.sign(&self.network, &self.keypair).to_xdr_base64())
+    }
+
+    fn network(&self) -> Option<&Network> {
+        Some(&self.network)
+    }
+
+    fn sign_transaction(&self, transaction: Transaction) -> Result<TransactionEnvelope, String> {
+        Ok(transaction.sign(&self.network, &self.keypair))
+    }
+
+    fn derive_keypair(&self, context: &[u8]) -> Result<Keypair, String> {
+        // Ed25519 signatures are deterministic, so the same context always yields the same seed
+        let signature = self.keypair.sign(context);
+        let seed = digest(&SHA256, &signature.to_bytes());
+        let secret = SecretKey::from_bytes(seed.as_ref()).map_err(|e| e.to_string())?;
+        let public = PublicKey::from(&secret);
+        Ok(Keypair { secret, public })
+    }
+}
+
+/// Converts an asset to its XDR form, checking the code and issuer.
+pub fn xdr_asset(asset: &StellarAsset) -> Result<Asset, String> {
+    match asset {
+        StellarAsset::Native => Ok(Asset::Native),
+        StellarAsset::Credit { code, issuer } => {
+            let issuer = AccountId::from_strkey(issuer).map_err(|e| format!("Invalid asset issuer: {}", e))?;
+            Asset::credit(code, issuer).map_err(|e| e.to_string())
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use log::{info, warn, error};
+use super::chain::Chain;
+use super::error::ChainError;
+use super::htlc::{hashlock_for, HtlcChain, HtlcLock, HtlcParams, HtlcStatus};
+use crate::utils::json::JsonFileStore;
+
+/// Errors returned by the swap coordinator.
+#[derive(Debug, Error)]
+pub enum SwapError {
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+
+    #[error("Unknown chain: {0}")]
+    UnknownChain(String),
+
+    #[error("Swap not found: {0}")]
+    NotFound(String),
+
+    #[error("Swap store error: {0}")]
+    Store(String),
+
+    #[error("Failed to generate a swap secret")]
+    Random,
+}
+
+/// Progress of an atomic swap, from the initiator's point of view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapState {
+    /// Terms agreed, nothing locked yet.
+    Created,
+    /// Our funds are locked; waiting for the counterparty lock.
+    Locked,
+    /// A matching counterparty lock was found; ready to reveal the preimage.
+    CounterpartyLocked,
+    /// We claimed the counterparty lock, revealing the preimage. Terminal.
+    Completed,
+    /// Our lock expired and the funds were returned. Terminal.
+    Refunded,
+}
+
+impl SwapState {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, SwapState::Completed | SwapState::Refunded)
+    }
+}
+
+/// Terms of a swap between an initiator and a counterparty on two chains.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwapTerms {
+    /// Chain on which the initiator locks funds.
+    pub initiator_chain: String,
+    /// Chain on which the counterparty locks funds.
+    pub counterparty_chain: String,
+    /// Initiator's sending account on the initiator chain.
+    pub initiator_address: String,
+    /// Initiator's receiving account on the counterparty chain.
+    pub initiator_receive_address: String,
+    /// Counterparty's receiving account on the initiator chain.
+    pub counterparty_address: String,
+    /// Amount the initiator locks, in the initiator chain's smallest unit.
+    pub amount: u64,
+    /// Minimum amount the counterparty must lock, in the counterparty chain's smallest unit.
+    pub counterparty_amount: u64,
+    /// How long the initiator's lock stays claimable.
+    pub lock_duration: Duration,
+    /// Minimum gap between the counterparty's expiry and ours, so the counterparty can still
+    /// claim our lock after we reveal the preimage.
+    pub safety_margin: Duration,
+}
+
+/// A swap and everything needed to resume it after a restart.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AtomicSwap {
+    pub id: String,
+    pub terms: SwapTerms,
+    #[serde(with = "hex::serde")]
+    pub hashlock: Vec<u8>,
+    /// The secret preimage. Stored so a restarted coordinator can still claim; anyone who reads
+    /// it before we claim can take the counterparty's funds, so keep the store encrypted (see
+    /// `JsonFileSwapStore::encrypted`).
+    #[serde(with = "hex::serde")]
+    pub preimage: Vec<u8>,
+    pub state: SwapState,
+    pub lock_expires_at: DateTime<Utc>,
+    pub lock_id: Option<String>,
+    pub counterparty_lock_id: Option<String>,
+    pub claim_tx_id: Option<String>,
+    pub refund_tx_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Trait defining where swaps are saved between restarts
+pub trait SwapStore: Send + Sync {
+    fn load(&self) -> Result<Vec<AtomicSwap>, String>;
+    fn save(&self, swaps: &[AtomicSwap]) -> Result<(), String>;
+}
+
+/// Stores all swaps as a JSON document on disk, optionally encrypted
+pub struct JsonFileSwapStore {
+    file: JsonFileStore<Vec<AtomicSwap>>,
+}
+
+impl JsonFileSwapStore {
+    /// Creates a new store backed by the file at `path`, in plaintext
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self { file: JsonFileStore::new("swap store", path) }
+    }
+
+    /// Creates a new store backed by the file at `path`, encrypted with AES-GCM under `key`
+    /// (16 or 32 bytes) so that the swap preimages are not readable on disk
+    pub fn encrypted<P: AsRef<Path>>(path: P, key: &[u8]) -> Self {
+        Self { file: JsonFileStore::new("swap store", path).encrypted(key) }
+    }
+}
+
+impl SwapStore for JsonFileSwapStore {
+    fn load(&self) -> Result<Vec<AtomicSwap>, String> {
+        Ok(self.file.load()?.unwrap_or_default())
+    }
+
+    fn save(&self, swaps: &[AtomicSwap]) -> Result<(), String> {
+        self.file.save(swaps)
+    }
+}
+
+/// Drives HTLC-based atomic swaps between chains on behalf of the initiator.
+///
+/// The coordinator locks the initiator's funds, waits for a counterparty lock with the same
+/// hashlock, verifies its terms, claims it by revealing the preimage, and refunds the
+/// initiator's lock if the swap does not complete before it expires. Every state change is
+/// saved so that `resume_all` can pick up in-flight swaps after a restart.
+pub struct SwapCoordinator {
+    chains: HashMap<String, Arc<dyn HtlcChain>>,
+    swaps: Mutex<HashMap<String, AtomicSwap>>,
+    store: Arc<dyn SwapStore>,
+}
+
+impl SwapCoordinator {
+    /// Creates a coordinator over the given chains, reloading any saved swaps.
+    pub fn new(chains: Vec<Arc<dyn HtlcChain>>, store: Arc<dyn SwapStore>) -> Result<Self, SwapError> {
+        let saved = store.load().map_err(SwapError::Store)?;
+        info!("Loaded {} swaps from store", saved.len());
+        Ok(Self {
+            chains: chains.into_iter().map(|c| (c.chain_name().to_string(), c)).collect(),
+            swaps: Mutex::new(saved.into_iter().map(|s| (s.id.clone(), s)).collect()),
+            store,
+        })
+    }
+
+    /// Registers a new swap with a freshly generated secret and returns it.
+    pub fn create_swap(&self, terms: SwapTerms) -> Result<AtomicSwap, SwapError> {
+        self.chain(&terms.initiator_chain)?;
+        self.chain(&terms.counterparty_chain)?;
+
+        let mut preimage = vec![0u8; 32];
+        SystemRandom::new().fill(&mut preimage).map_err(|_| SwapError::Random)?;
+        let hashlock = hashlock_for(&preimage);
+        let now = Utc::now();
+
+        let swap = AtomicSwap {
+            id: hex::encode(&hashlock),
+            lock_expires_at: add_duration(now, terms.lock_duration),
+            terms,
+            hashlock,
+            preimage,
+            state: SwapState::Created,
+            lock_id: None,
+            counterparty_lock_id: None,
+            claim_tx_id: None,
+            refund_tx_id: None,
+            created_at: now,
+            updated_at: now,
+        };
+        info!("Created swap {}", swap.id);
+        self.save_swap(swap.clone())?;
+        Ok(swap)
+    }
+
+    /// Returns a swap by ID.
+    pub fn get_swap(&self, swap_id: &str) -> Option<AtomicSwap> {
+        self.swaps.lock().unwrap().get(swap_id).cloned()
+    }
+
+    /// Advances a swap by at most one state and returns its new state.
+    pub async fn step(&self, swap_id: &str) -> Result<SwapState, SwapError> {
+        let mut swap = self.get_swap(swap_id).ok_or_else(|| SwapError::NotFound(swap_id.to_string()))?;
+        let initiator_chain = self.chain(&swap.terms.initiator_chain)?;
+        let counterparty_chain = self.chain(&swap.terms.counterparty_chain)?;
+        let now = Utc::now();
+
+        match swap.state {
+            SwapState::Created => {
+                // A lock may already exist if we crashed after locking but before saving
+                let lock_id = match initiator_chain.find_lock(&swap.hashlock).await? {
+                    Some(existing) => existing.lock_id,
+                    None => {
+                        let params = HtlcParams {
+                            sender: swap.terms.initiator_address.clone(),
+                            receiver: swap.terms.counterparty_address.clone(),
+                            amount: swap.terms.amount,
+                            hashlock: swap.hashlock.clone(),
+                            expires_at: swap.lock_expires_at,
+                        };
+                        initiator_chain.lock_funds(&params).await?
+                    }
+                };
+                info!("Swap {} locked on {} as {}", swap.id, swap.terms.initiator_chain, lock_id);
+                swap.lock_id = Some(lock_id);
+                swap.state = SwapState::Locked;
+            }
+            SwapState::Locked => {
+                if now >= swap.lock_expires_at {
+                    self.refund(&mut swap, initiator_chain.as_ref()).await?;
+                } else if let Some(lock) = counterparty_chain.find_lock(&swap.hashlock).await? {
+                    match self.verify_counterparty_lock(&swap, &lock, now) {
+                        Ok(()) => {
+                            info!("Swap {} found counterparty lock {}", swap.id, lock.lock_id);
+                            swap.counterparty_lock_id = Some(lock.lock_id);
+                            swap.state = SwapState::CounterpartyLocked;
+                        }
+                        Err(reason) => warn!("Ignoring counterparty lock {} for swap {}: {}", lock.lock_id, swap.id, reason),
+                    }
+                }
+            }
+            SwapState::CounterpartyLocked => {
+                let lock_id = swap.counterparty_lock_id.clone().unwrap_or_default();
+                match counterparty_chain.claim(&lock_id, &swap.preimage).await {
+                    Ok(tx_id) => {
+                        info!("Swap {} claimed counterparty lock in {}", swap.id, tx_id);
+                        swap.claim_tx_id = Some(tx_id);
+                        swap.state = SwapState::Completed;
+                    }
+                    Err(e) if now >= swap.lock_expires_at => {
+                        error!("Swap {} claim failed after expiry: {}", swap.id, e);
+                        self.refund(&mut swap, initiator_chain.as_ref()).await?;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            SwapState::Completed | SwapState::Refunded => return Ok(swap.state),
+        }
+
+        swap.updated_at = now;
+        let state = swap.state;
+        self.save_swap(swap)?;
+        Ok(state)
+    }
+
+    /// Steps a swap until it reaches a terminal state, polling every `interval`.
+    pub async fn run_until_settled(&self, swap_id: &str, interval: Duration) -> Result<SwapState, SwapError> {
+        loop {
+            let state = self.step(swap_id).await?;
+            if state.is_terminal() {
+                return Ok(state);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Steps every unfinished swap once; call after a restart and then periodically.
+    ///
+    /// Errors on one swap are logged and do not stop the others.
+    pub async fn resume_all(&self) -> Vec<(String, SwapState)> {
+        let pending: Vec<String> = self
+            .swaps
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| !s.state.is_terminal())
+            .map(|s| s.id.clone())
+            .collect();
+
+        let mut results = Vec::new();
+        for swap_id in pending {
+            match self.step(&swap_id).await {
+                Ok(state) => results.push((swap_id, state)),
+                Err(e) => error!("Failed to resume swap {}: {}", swap_id, e),
+            }
+        }
+        results
+    }
+
+    /// Checks that a counterparty lock pays us enough, under our hashlock, and expires early
+    /// enough that the counterparty can still claim our lock after we reveal the preimage.
+    fn verify_counterparty_lock(&self, swap: &AtomicSwap, lock: &HtlcLock, now: DateTime<Utc>) -> Result<(), String> {
+        if lock.status != HtlcStatus::Locked {
+            return Err(format!("lock is {:?}", lock.status));
+        }
+        if lock.hashlock != swap.hashlock {
+            return Err("hashlock mismatch".to_string());
+        }
+        if lock.receiver != swap.terms.initiator_receive_address {
+            return Err(format!("pays {} instead of {}", lock.receiver, swap.terms.initiator_receive_address));
+        }
+        if lock.amount < swap.terms.counterparty_amount {
+            return Err(format!("amount {} is below {}", lock.amount, swap.terms.counterparty_amount));
+        }
+        if add_duration(lock.expires_at, swap.terms.safety_margin) > swap.lock_expires_at {
+            return Err("expires too close to our own lock".to_string());
+        }
+        if lock.expires_at <= now {
+            return Err("already expired".to_string());
+        }
+        Ok(())
+    }
+
+    async fn refund(&self, swap: &mut AtomicSwap, chain: &dyn HtlcChain) -> Result<(), SwapError> {
+        let lock_id = swap.lock_id.clone().unwrap_or_default();
+        let tx_id = chain.refund(&lock_id).await?;
+        info!("Swap {} refunded in {}", swap.id, tx_id);
+        swap.refund_tx_id = Some(tx_id);
+        swap.state = SwapState::Refunded;
+        Ok(())
+    }
+
+    fn chain(&self, name: &str) -> Result<Arc<dyn HtlcChain>, SwapError> {
+        self.chains.get(name).cloned().ok_or_else(|| SwapError::UnknownChain(name.to_string()))
+    }
+
+    fn save_swap(&self, swap: AtomicSwap) -> Result<(), SwapError> {
+        let mut swaps = self.swaps.lock().unwrap();
+        swaps.insert(swap.id.clone(), swap);
+        let mut snapshot: Vec<AtomicSwap> = swaps.values().cloned().collect();
+        snapshot.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        self.store.save(&snapshot).map_err(SwapError::Store)
+    }
+}
+
+// Saturates at the latest representable time instead of overflowing
+fn add_duration(start: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|d| start.checked_add_signed(d))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
@@ -0,0 +1,329 @@
+// Resilient HTTP transport shared by the chain integrations: timeouts, retries with
+// jittered exponential backoff, idempotency keys and a circuit breaker per endpoint
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+use log::warn;
+
+/// Header carrying the key that lets a server recognise a retried submission.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Errors returned by the transport before a response could be read.
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// Recent requests to the endpoint kept failing, so it is not being called for now.
+    #[error("Circuit breaker open for {0}")]
+    CircuitOpen(String),
+}
+
+/// Timeouts, retry and circuit breaker settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportConfig {
+    /// Limit for a whole request, from connecting to reading the body.
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for every further retry.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Consecutive failures after which the endpoint's circuit opens.
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before letting a trial request through.
+    pub reset_timeout: Duration,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl TransportConfig {
+    /// The delay before retry number `retry` (starting at 0): exponential, capped at
+    /// `max_delay`, with the upper half randomised so that clients don't retry in lockstep.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(retry)).min(self.max_delay);
+        let half = delay / 2;
+        let jitter = half.as_nanos() as u64;
+        let jitter = if jitter == 0 { 0 } else { random_u64() % (jitter + 1) };
+        half + Duration::from_nanos(jitter)
+    }
+}
+
+/// Observable state of an endpoint's circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// The reset timeout has passed and one trial request decides whether to close again.
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+struct Inner {
+    client: Client,
+    config: TransportConfig,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+/// HTTP transport with retries and per-endpoint circuit breaking.
+///
+/// Cloning is cheap and clones share their circuit breakers, so integrations talking to the
+/// same server can share one transport.
+#[derive(Clone)]
+pub struct Transport {
+    inner: Arc<Inner>,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new(TransportConfig::default())
+    }
+}
+
+// What may be retried after a failure
+#[derive(Clone, Copy, PartialEq)]
+enum RetryPolicy {
+    // Only failures where the request cannot have reached the server
+    Unsent,
+    // Any transient failure, because repeating the request has no further effect
+    Idempotent,
+}
+
+impl Transport {
+    pub fn new(config: TransportConfig) -> Self {
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Self {
+            inner: Arc::new(Inner { client, config, breakers: Mutex::new(HashMap::new()) }),
+        }
+    }
+
+    /// The underlying client, for building requests.
+    pub fn client(&self) -> &Client {
+        &self.inner.client
+    }
+
+    pub fn config(&self) -> &TransportConfig {
+        &self.inner.config
+    }
+
+    /// Sends a request. Reads (GET and HEAD) are retried on transient failures; other
+    /// methods only when the connection could not be established.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, TransportError> {
+        let request = request.build()?;
+        let policy = if request.method() == Method::GET || request.method() == Method::HEAD {
+            RetryPolicy::Idempotent
+        } else {
+            RetryPolicy::Unsent
+        };
+        self.execute(request, policy).await
+    }
+
+    /// Sends a submission with an idempotency key so that it can be retried safely. Pass a key
+    /// derived from the operation, e.g. a transfer ID, to keep it stable across restarts;
+    /// otherwise a random key is used for all attempts of this call.
+    pub async fn submit(&self, request: RequestBuilder, idempotency_key: Option<&str>) -> Result<Response, TransportError> {
+        let key = idempotency_key.map(str::to_string).unwrap_or_else(new_idempotency_key);
+        let request = request.header(IDEMPOTENCY_KEY_HEADER, key).build()?;
+        self.execute(request, RetryPolicy::Idempotent).await
+    }
+
+    /// State of the circuit breaker for an endpoint such as `127.0.0.1:8000`.
+    pub fn circuit_state(&self, endpoint: &str) -> CircuitState {
+        let breakers = self.inner.breakers.lock().unwrap();
+        match breakers.get(endpoint).and_then(|b| b.open_until) {
+            None => CircuitState::Closed,
+            Some(until) if Instant::now() < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    async fn execute(&self, request: Request, policy: RetryPolicy) -> Result<Response, TransportError> {
+        let endpoint = endpoint(&request);
+        let config = &self.inner.config;
+        let mut retry = 0;
+        loop {
+            let permit = self.acquire(&endpoint)?;
+            // Bodies that cannot be cloned, such as streams, get a single attempt
+            let Some(attempt) = request.try_clone() else {
+                let result = self.inner.client.execute(request).await;
+                permit.record(outcome(&result));
+                return Ok(result?);
+            };
+            let result = self.inner.client.execute(attempt).await;
+            permit.record(outcome(&result));
+            let can_retry = retry < config.max_retries;
+
+            let delay = match result {
+                Ok(response) if !is_transient_status(response.status()) => return Ok(response),
+                Ok(response) => {
+                    if !can_retry || policy == RetryPolicy::Unsent {
+                        return Ok(response);
+                    }
+                    warn!("{} returned {}, retrying", endpoint, response.status());
+                    let requested = retry_after(&response).unwrap_or_default().min(config.max_delay);
+                    config.backoff(retry).max(requested)
+                }
+                Err(e) => {
+                    // A failed connection cannot have delivered the request; a timeout may have
+                    let retryable = e.is_connect() || (policy == RetryPolicy::Idempotent && e.is_timeout());
+                    if !can_retry || !retryable {
+                        return Err(e.into());
+                    }
+                    warn!("Request to {} failed ({}), retrying", endpoint, e);
+                    config.backoff(retry)
+                }
+            };
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+
+    // Rejects the request while the circuit is open; lets one trial through once it half-opens
+    fn acquire<'a>(&'a self, endpoint: &'a str) -> Result<Permit<'a>, TransportError> {
+        let mut breakers = self.inner.breakers.lock().unwrap();
+        let breaker = breakers.entry(endpoint.to_string()).or_default();
+        let trial = match breaker.open_until {
+            Some(until) if Instant::now() < until || breaker.trial_in_flight => {
+                return Err(TransportError::CircuitOpen(endpoint.to_string()));
+            }
+            Some(_) => {
+                breaker.trial_in_flight = true;
+                true
+            }
+            None => false,
+        };
+        Ok(Permit { transport: self, endpoint, trial })
+    }
+
+    fn record(&self, endpoint: &str, outcome: Outcome) {
+        let config = &self.inner.config;
+        let mut breakers = self.inner.breakers.lock().unwrap();
+        let breaker = breakers.entry(endpoint.to_string()).or_default();
+        match outcome {
+            Outcome::Healthy => *breaker = Breaker::default(),
+            Outcome::Neutral => breaker.trial_in_flight = false,
+            Outcome::Failed => {
+                breaker.failures += 1;
+                if breaker.trial_in_flight || breaker.failures >= config.failure_threshold {
+                    warn!("Opening circuit for {} after {} failures", endpoint, breaker.failures);
+                    breaker.open_until = Some(Instant::now() + config.reset_timeout);
+                    breaker.trial_in_flight = false;
+                }
+            }
+        }
+    }
+}
+
+// Permission to send one attempt to an endpoint. A half-open trial dropped before its outcome is
+// recorded, e.g. because the caller's future was cancelled, frees the trial slot again.
+struct Permit<'a> {
+    transport: &'a Transport,
+    endpoint: &'a str,
+    trial: bool,
+}
+
+impl Permit<'_> {
+    fn record(mut self, outcome: Outcome) {
+        self.transport.record(self.endpoint, outcome);
+        self.trial = false;
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.trial {
+            return;
+        }
+        if let Ok(mut breakers) = self.transport.inner.breakers.lock() {
+            if let Some(breaker) = breakers.get_mut(self.endpoint) {
+                breaker.trial_in_flight = false;
+            }
+        }
+    }
+}
+
+// How an attempt reflects on the endpoint's health
+enum Outcome {
+    Healthy,
+    // Rate limiting says nothing about whether the endpoint works
+    Neutral,
+    Failed,
+}
+
+fn outcome(result: &Result<Response, reqwest::Error>) -> Outcome {
+    match result {
+        Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => Outcome::Neutral,
+        Ok(response) if is_transient_status(response.status()) => Outcome::Failed,
+        Ok(_) => Outcome::Healthy,
+        Err(_) => Outcome::Failed,
+    }
+}
+
+fn endpoint(request: &Request) -> String {
+    let url = request.url();
+    match url.port_or_known_default() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// Retry-After in seconds; the HTTP date form is not used by the chain APIs
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    value.to_str().ok()?.trim().parse().ok().map(Duration::from_secs)
+}
+
+fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    // The system RNG only fails if the OS has no entropy source; fall back to no jitter
+    let _ = SystemRandom::new().fill(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+fn new_idempotency_key() -> String {
+    let mut bytes = [0u8; 16];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        // Keys must differ between calls even without an entropy source
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        bytes = nanos.to_le_bytes();
+    }
+    hex::encode(bytes)
+}
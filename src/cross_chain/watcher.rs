@@ -0,0 +1,245 @@
+// Tracks submitted transactions on any chain and tells subscribers as they confirm
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use log::{info, warn};
+use super::chain::{Chain, TransactionStatus};
+use super::error::ChainError;
+
+/// How often the watcher polls and when it gives up on pending transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatcherConfig {
+    pub poll_interval: Duration,
+    /// How long a transaction may stay unknown to its chain before it is reported `Expired`.
+    pub pending_timeout: Duration,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            pending_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// A change in a watched transaction, sent to its subscribers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusUpdate {
+    pub chain: String,
+    pub tx_id: String,
+    pub status: TransactionStatus,
+    /// Whether the transaction has the confirmations the subscriber asked for. Subscribers get
+    /// no further updates after this or after a terminal status.
+    pub depth_reached: bool,
+}
+
+struct Subscriber {
+    depth: u64,
+    sender: mpsc::UnboundedSender<StatusUpdate>,
+}
+
+struct Watch {
+    status: TransactionStatus,
+    // Block including the transaction once the chain reports it
+    block: Option<u64>,
+    // Latest block at the last lookup, so a pending transaction is looked up once per block
+    checked_at: Option<u64>,
+    since: Instant,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Watches in-flight transactions across chains.
+///
+/// Each poll asks every chain with watched transactions for its latest block once. Pending
+/// transactions are looked up only when a new block has closed, and included ones need no
+/// further lookups since their confirmations follow from the latest block.
+pub struct TransactionWatcher {
+    chains: HashMap<String, Arc<dyn Chain>>,
+    config: WatcherConfig,
+    // Keyed by (chain name, transaction ID)
+    watches: Mutex<HashMap<(String, String), Watch>>,
+}
+
+/// Handle to a running watcher.
+pub struct WatcherHandle {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl TransactionWatcher {
+    pub fn new(chains: Vec<Arc<dyn Chain>>, config: WatcherConfig) -> Self {
+        Self {
+            chains: chains.into_iter().map(|c| (c.chain_name().to_string(), c)).collect(),
+            config,
+            watches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Watches a transaction until it has `depth` confirmations or fails, returning a channel
+    /// that receives every status change.
+    ///
+    /// Several subscribers may watch the same transaction with different depths.
+    pub fn watch(&self, chain: &str, tx_id: &str, depth: u64) -> Result<mpsc::UnboundedReceiver<StatusUpdate>, ChainError> {
+        if !self.chains.contains_key(chain) {
+            return Err(ChainError::Other(format!("Unknown chain `{}`", chain)));
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let subscriber = Subscriber { depth: depth.max(1), sender };
+
+        let mut watches = self.watches.lock().unwrap();
+        let watch = watches.entry((chain.to_string(), tx_id.to_string())).or_insert_with(|| Watch {
+            status: TransactionStatus::Pending,
+            block: None,
+            checked_at: None,
+            since: Instant::now(),
+            subscribers: Vec::new(),
+        });
+        // Joining a watch that is already under way starts from its current status
+        if watch.status != TransactionStatus::Pending {
+            let update = StatusUpdate {
+                chain: chain.to_string(),
+                tx_id: tx_id.to_string(),
+                status: watch.status.clone(),
+                depth_reached: watch.status.confirmations() >= subscriber.depth,
+            };
+            let _ = subscriber.sender.send(update.clone());
+            if update.depth_reached {
+                return Ok(receiver);
+            }
+        }
+        watch.subscribers.push(subscriber);
+        Ok(receiver)
+    }
+
+    /// Number of transactions being watched.
+    pub fn watched(&self) -> usize {
+        self.watches.lock().unwrap().len()
+    }
+
+    /// Last known status of a watched transaction.
+    pub fn status(&self, chain: &str, tx_id: &str) -> Option<TransactionStatus> {
+        let watches = self.watches.lock().unwrap();
+        watches.get(&(chain.to_string(), tx_id.to_string())).map(|w| w.status.clone())
+    }
+
+    /// Checks every watched transaction once and notifies subscribers of changes. A chain that
+    /// cannot be reached is skipped until the next poll.
+    pub async fn poll(&self) {
+        let mut chains: Vec<String> = self.watches.lock().unwrap().keys().map(|(chain, _)| chain.clone()).collect();
+        chains.sort();
+        chains.dedup();
+        for name in chains {
+            if let Err(e) = self.poll_chain(&name).await {
+                warn!("Failed to poll {} for watched transactions: {}", name, e);
+            }
+        }
+    }
+
+    async fn poll_chain(&self, name: &str) -> Result<(), ChainError> {
+        let chain = &self.chains[name];
+        let latest = chain.latest_block().await?;
+
+        // Look up pending transactions without holding the lock across requests
+        let pending: Vec<String> = self.watches.lock().unwrap()
+            .iter()
+            .filter(|((chain, _), watch)| chain == name && watch.block.is_none() && watch.checked_at != Some(latest))
+            .map(|((_, tx_id), _)| tx_id.clone())
+            .collect();
+        let mut lookups = HashMap::new();
+        for tx_id in pending {
+            match chain.transaction(&tx_id).await {
+                Ok(transaction) => {
+                    lookups.insert(tx_id, Some((transaction.block, TransactionStatus::of(&transaction))));
+                }
+                Err(e) if e.is_not_found() => {
+                    lookups.insert(tx_id, None);
+                }
+                Err(e) => warn!("Failed to look up {} transaction {}: {}", name, tx_id, e),
+            }
+        }
+
+        let mut watches = self.watches.lock().unwrap();
+        watches.retain(|(chain, tx_id), watch| {
+            if chain != name {
+                return true;
+            }
+            let mut status = match (watch.block, lookups.remove(tx_id)) {
+                (Some(block), _) => status_at(block, latest),
+                (None, Some(lookup)) => {
+                    watch.checked_at = Some(latest);
+                    match lookup {
+                        Some((block, status)) => {
+                            watch.block = Some(block);
+                            status
+                        }
+                        None => TransactionStatus::Pending,
+                    }
+                }
+                (None, None) => watch.status.clone(),
+            };
+            if status == TransactionStatus::Pending && watch.since.elapsed() >= self.config.pending_timeout {
+                status = TransactionStatus::Expired;
+            }
+
+            if status != watch.status {
+                info!("{} transaction {} is now {:?}", chain, tx_id, status);
+                watch.status = status;
+                notify(chain, tx_id, watch);
+            } else {
+                watch.subscribers.retain(|s| !s.sender.is_closed());
+            }
+            !watch.subscribers.is_empty() && !watch.status.is_terminal()
+        });
+        Ok(())
+    }
+
+    /// Spawns a loop polling every `poll_interval` onto the current runtime.
+    pub fn start(self: Arc<Self>) -> WatcherHandle {
+        let (shutdown, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => self.poll().await,
+                    _ = stopped.changed() => break,
+                }
+            }
+        });
+        WatcherHandle { shutdown, task }
+    }
+}
+
+impl WatcherHandle {
+    /// Stops the watcher after its current poll and waits for it to exit.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+// Status of a successful transaction in `block` once `latest` has closed
+fn status_at(block: u64, latest: u64) -> TransactionStatus {
+    match (latest + 1).saturating_sub(block) {
+        confirmations if confirmations > 1 => TransactionStatus::Confirmed { confirmations },
+        _ => TransactionStatus::Included,
+    }
+}
+
+// Sends the watch's new status and drops subscribers that are done or gone
+fn notify(chain: &str, tx_id: &str, watch: &mut Watch) {
+    let status = &watch.status;
+    watch.subscribers.retain(|subscriber| {
+        let depth_reached = status.confirmations() >= subscriber.depth;
+        let update = StatusUpdate {
+            chain: chain.to_string(),
+            tx_id: tx_id.to_string(),
+            status: status.clone(),
+            depth_reached,
+        };
+        subscriber.sender.send(update).is_ok() && !depth_reached && !status.is_terminal()
+    });
+}
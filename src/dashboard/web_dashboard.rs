@@ -27,8 +27,9 @@ async fn increment_user_count(data: web::Data<AppState>) -> impl Responder {
     }))
 }
 
-/// Function to run the web dashboard server.
-pub async fn run_web_dashboard() -> std::io::Result<()> {
+/// Function to run the web dashboard server on `bind_address`, usually the selected
+/// network's `bind_address`.
+pub async fn run_web_dashboard(bind_address: &str) -> std::io::Result<()> {
     // Initialize application state with user count set to 0
     let state = AppState {
         user_count: Mutex::new(0),
@@ -42,7 +43,7 @@ pub async fn run_web_dashboard() -> std::io::Result<()> {
             .route("/", web::get().to(index)) // Route for the index page
             .route("/increment", web::post().to(increment_user_count)) // Route for incrementing user count
     })
-    .bind("127.0.0.1:8080")? // Bind to the specified address
+    .bind(bind_address)? // Bind to the specified address
     .run() // Run the server
     .await
 }
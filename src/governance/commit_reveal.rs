@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use super::governance::ballot_message;
+use super::strategy::BallotParams;
+
+/// A sealed ballot submitted during the commit phase of a commit-reveal proposal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Commitment {
+    pub voter_id: String,
+    pub commitment: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub committed_at: DateTime<Utc>,
+    pub revealed: bool,
+}
+
+/// Shortest salt accepted; a ballot has so few possible values that a short salt lets
+/// anyone recover it from the commitment by brute force.
+pub const MIN_SALT_LEN: usize = 16;
+/// Length of a commitment, a SHA-256 hash.
+pub const COMMITMENT_LEN: usize = 32;
+
+/// Computes the hash a voter commits to: SHA-256 over the ballot message followed by the salt.
+///
+/// The ballot message includes the proposal ID, so a commitment cannot be replayed on
+/// another proposal. Salts shorter than `MIN_SALT_LEN` are rejected.
+pub fn commitment_hash(proposal_id: u32, vote_for: bool, params: &BallotParams, salt: &[u8]) -> Result<Vec<u8>, String> {
+    if salt.len() < MIN_SALT_LEN {
+        return Err(format!("Salt must be at least {} bytes.", MIN_SALT_LEN));
+    }
+    let mut data = ballot_message(proposal_id, vote_for, params);
+    data.extend_from_slice(salt);
+    Ok(digest(&SHA256, &data).as_ref().to_vec())
+}
+
+/// Generates a random salt of `MIN_SALT_LEN` bytes for a commitment.
+pub fn generate_salt() -> Result<Vec<u8>, String> {
+    let mut salt = vec![0u8; MIN_SALT_LEN];
+    SystemRandom::new().fill(&mut salt).map_err(|_| "Failed to generate salt.".to_string())?;
+    Ok(salt)
+}
+
+/// Builds the message a voter must sign to submit a commitment.
+pub fn commit_message(proposal_id: u32, commitment: &[u8]) -> Vec<u8> {
+    let hex: String = commitment.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("governance:proposal:{}:commit:{}", proposal_id, hex).into_bytes()
+}
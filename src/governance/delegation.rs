@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use log::info;
+
+/// Tracks who has delegated their voting power to whom, globally or per topic.
+///
+/// A topic delegation takes precedence over a global one when resolving a proposal
+/// on that topic. Chains of delegations are followed transitively; cycles are rejected
+/// when a delegation is added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DelegationRegistry {
+    global: HashMap<String, String>,
+    by_topic: HashMap<String, HashMap<String, String>>,
+}
+
+impl DelegationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delegates `delegator`'s voting power to `delegate`, for every topic or for a single one.
+    pub fn delegate(&mut self, delegator: &str, delegate: &str, topic: Option<&str>) -> Result<(), String> {
+        if delegator == delegate {
+            return Err("Cannot delegate to yourself.".to_string());
+        }
+        if self.would_cycle(delegator, delegate, topic) {
+            return Err("Delegation would create a cycle.".to_string());
+        }
+
+        match topic {
+            Some(topic) => {
+                self.by_topic
+                    .entry(topic.to_string())
+                    .or_insert_with(HashMap::new)
+                    .insert(delegator.to_string(), delegate.to_string());
+            }
+            None => {
+                self.global.insert(delegator.to_string(), delegate.to_string());
+            }
+        }
+        info!("{} delegated to {} (topic: {:?})", delegator, delegate, topic);
+        Ok(())
+    }
+
+    /// Removes a delegation previously made for the same scope.
+    pub fn revoke(&mut self, delegator: &str, topic: Option<&str>) -> Result<(), String> {
+        let removed = match topic {
+            Some(topic) => self.by_topic.get_mut(topic).and_then(|d| d.remove(delegator)),
+            None => self.global.remove(delegator),
+        };
+
+        match removed {
+            Some(_) => {
+                info!("{} revoked delegation (topic: {:?})", delegator, topic);
+                Ok(())
+            }
+            None => Err("Delegation not found.".to_string()),
+        }
+    }
+
+    /// Returns who `voter` delegates to directly for the given topic, if anyone.
+    pub fn next(&self, voter: &str, topic: Option<&str>) -> Option<&String> {
+        topic
+            .and_then(|t| self.by_topic.get(t))
+            .and_then(|d| d.get(voter))
+            .or_else(|| self.global.get(voter))
+    }
+
+    /// Follows the delegation chain from `voter` and returns the final delegate.
+    pub fn resolve(&self, voter: &str, topic: Option<&str>) -> String {
+        let mut current = voter.to_string();
+        let mut visited = HashSet::new();
+        visited.insert(current.clone());
+        while let Some(next) = self.next(&current, topic) {
+            if !visited.insert(next.clone()) {
+                break;
+            }
+            current = next.clone();
+        }
+        current
+    }
+
+    /// Returns every identity that has a delegation applying to the given topic.
+    pub fn delegators(&self, topic: Option<&str>) -> Vec<String> {
+        let mut delegators: HashSet<String> = self.global.keys().cloned().collect();
+        if let Some(by_topic) = topic.and_then(|t| self.by_topic.get(t)) {
+            delegators.extend(by_topic.keys().cloned());
+        }
+        let mut delegators: Vec<String> = delegators.into_iter().collect();
+        delegators.sort();
+        delegators
+    }
+
+    /// Maps each delegator who did not vote to the direct voter their power flows to.
+    ///
+    /// `has_voted` tells whether an identity cast its own ballot. Power stops at the first
+    /// voter along the chain; delegators whose chain reaches no voter are left out.
+    pub fn resolve_for_voters<F>(&self, topic: Option<&str>, has_voted: F) -> HashMap<String, String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut resolved = HashMap::new();
+        for delegator in self.delegators(topic) {
+            if has_voted(&delegator) {
+                continue;
+            }
+
+            let mut visited = HashSet::new();
+            visited.insert(delegator.clone());
+            let mut current = delegator.clone();
+            while let Some(next) = self.next(&current, topic) {
+                if !visited.insert(next.clone()) {
+                    break;
+                }
+                if has_voted(next) {
+                    resolved.insert(delegator.clone(), next.clone());
+                    break;
+                }
+                current = next.clone();
+            }
+        }
+        resolved
+    }
+
+    /// Checks whether adding `delegator -> delegate` would make a chain loop back to `delegator`.
+    ///
+    /// A global delegation is checked under every known topic, since topic delegations
+    /// can route through it.
+    fn would_cycle(&self, delegator: &str, delegate: &str, topic: Option<&str>) -> bool {
+        let mut scopes: Vec<Option<&str>> = vec![topic];
+        if topic.is_none() {
+            scopes.extend(self.by_topic.keys().map(|t| Some(t.as_str())));
+        }
+
+        scopes.into_iter().any(|scope| {
+            let mut visited = HashSet::new();
+            let mut current = delegate.to_string();
+            loop {
+                if current == delegator {
+                    return true;
+                }
+                if !visited.insert(current.clone()) {
+                    return false;
+                }
+                match self.next(&current, scope) {
+                    Some(next) => current = next.clone(),
+                    None => return false,
+                }
+            }
+        })
+    }
+}
@@ -1,102 +1,714 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
 use log::{info, error};
+use crate::identity_management::identity::IdentityManager;
+use super::commit_reveal::{commit_message, commitment_hash, Commitment, COMMITMENT_LEN};
+use super::delegation::DelegationRegistry;
+use super::persistence::{GovernanceState, ProposalStore};
+use super::strategy::{credit_period, BallotParams, TallyResult, VotingStrategy, DEFAULT_CREDIT_PERIOD};
 
-#[derive(Debug, Clone)]
+/// A single signed ballot, kept on the proposal for auditing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRecord {
+    pub voter_id: String,
+    pub vote_for: bool,
+    /// Signature over `ballot_message`; empty for a revealed commit-reveal ballot.
+    pub signature: Vec<u8>,
+    pub cast_at: DateTime<Utc>,
+    #[serde(default)]
+    pub params: BallotParams,
+    /// For a revealed commit-reveal ballot, the signature over `commit_message` for the
+    /// voter's commitment, which stays on the proposal.
+    #[serde(default)]
+    pub commit_signature: Option<Vec<u8>>,
+}
+
+/// Lifecycle of a proposal.
+///
+/// `Closed` means voting was stopped early by `close_proposal`; `Revealing` is the second
+/// phase of a commit-reveal proposal; `Finalized` means the deadline has passed and the
+/// tally is final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProposalStatus {
+    Active,
+    Revealing,
+    Closed,
+    Finalized,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proposal {
     pub id: u32,
     pub description: String,
     pub votes_for: u32,
     pub votes_against: u32,
     pub is_active: bool,
-    pub created_at: Instant,
+    pub status: ProposalStatus,
+    pub created_at: DateTime<Utc>,
+    pub closes_at: DateTime<Utc>,
     pub voting_duration: Duration,
+    pub votes: HashMap<String, VoteRecord>,
+    #[serde(default)]
+    pub strategy: VotingStrategy,
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Delegators whose power followed another voter's ballot, captured when voting ended.
+    #[serde(default)]
+    pub delegations: HashMap<String, String>,
+    /// End of the reveal window for commit-reveal proposals; `closes_at` then ends the commit phase.
+    #[serde(default)]
+    pub reveal_closes_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub commitments: HashMap<String, Commitment>,
+}
+
+impl Proposal {
+    /// Returns true once the voting deadline has been reached.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.closes_at
+    }
+
+    /// Returns true if this proposal uses commit-reveal voting.
+    pub fn is_commit_reveal(&self) -> bool {
+        self.reveal_closes_at.is_some()
+    }
+
+    /// Returns true while individual ballots and tallies must stay hidden.
+    pub fn is_sealed(&self) -> bool {
+        self.is_commit_reveal() && self.status != ProposalStatus::Finalized
+    }
+
+    /// Returns the time at which the proposal is finalized.
+    pub fn final_deadline(&self) -> DateTime<Utc> {
+        self.reveal_closes_at.unwrap_or(self.closes_at)
+    }
+
+    /// Returns the status the proposal should move to at `now`, if it is due for a transition.
+    fn next_status(&self, now: DateTime<Utc>) -> Option<ProposalStatus> {
+        if self.status == ProposalStatus::Finalized {
+            return None;
+        }
+        if now >= self.final_deadline() {
+            return Some(ProposalStatus::Finalized);
+        }
+        if self.status == ProposalStatus::Active && self.is_commit_reveal() && self.is_expired(now) {
+            return Some(ProposalStatus::Revealing);
+        }
+        None
+    }
+
+    fn set_status(&mut self, status: ProposalStatus) {
+        self.status = status;
+        self.is_active = status == ProposalStatus::Active;
+    }
 }
 
+#[derive(Clone)]
 pub struct Governance {
     proposals: Arc<Mutex<HashMap<u32, Proposal>>>,
     next_id: Arc<Mutex<u32>>,
+    identity_manager: Arc<Mutex<IdentityManager>>,
+    delegations: Arc<Mutex<DelegationRegistry>>,
+    store: Option<Arc<dyn ProposalStore + Send + Sync>>,
+    credit_period: Duration,
+    // Held from reading a proposal until its change is saved, so changes cannot interleave;
+    // the proposals lock itself is never held during store I/O
+    write_lock: Arc<Mutex<()>>,
+}
+
+/// Builds the message a voter must sign to cast a ballot on a proposal.
+pub fn vote_message(proposal_id: u32, vote_for: bool) -> Vec<u8> {
+    let choice = if vote_for { "for" } else { "against" };
+    format!("governance:proposal:{}:vote:{}", proposal_id, choice).into_bytes()
+}
+
+/// Builds the message a voter must sign to cast a weighted ballot.
+///
+/// Ballots without options sign the same message as `vote_message`.
+pub fn ballot_message(proposal_id: u32, vote_for: bool, params: &BallotParams) -> Vec<u8> {
+    let mut message = vote_message(proposal_id, vote_for);
+    if *params != BallotParams::default() {
+        message.extend(format!(":credits:{}:lock:{}", params.credits, params.lock_periods).into_bytes());
+    }
+    message
+}
+
+/// Builds the message a delegator must sign to delegate their voting power.
+pub fn delegation_message(delegator: &str, delegate: &str, topic: Option<&str>) -> Vec<u8> {
+    format!("governance:delegate:{}:{}:topic:{}", delegator, delegate, topic.unwrap_or("*")).into_bytes()
+}
+
+/// Builds the message a delegator must sign to revoke a delegation.
+pub fn revocation_message(delegator: &str, topic: Option<&str>) -> Vec<u8> {
+    format!("governance:revoke:{}:topic:{}", delegator, topic.unwrap_or("*")).into_bytes()
 }
 
 impl Governance {
-    pub fn new() -> Self {
+    /// Creates a new Governance instance that checks voters against the given identity manager.
+    pub fn new(identity_manager: Arc<Mutex<IdentityManager>>) -> Self {
         Governance {
             proposals: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1)),
+            identity_manager,
+            delegations: Arc::new(Mutex::new(DelegationRegistry::new())),
+            store: None,
+            credit_period: DEFAULT_CREDIT_PERIOD,
+            write_lock: Arc::new(Mutex::new(())),
         }
     }
 
-    pub fn create_proposal(&self, description: String, voting_duration: Duration) -> u32 {
-        let mut proposals = self.proposals.lock().unwrap();
+    /// Creates a Governance instance backed by a proposal store.
+    ///
+    /// Previously saved proposals and delegations are reloaded, and any proposals whose
+    /// deadline passed while the process was down are finalized immediately.
+    pub fn with_store(
+        identity_manager: Arc<Mutex<IdentityManager>>,
+        store: Arc<dyn ProposalStore + Send + Sync>,
+    ) -> Result<Self, String> {
+        let saved = store.load()?;
+        let next_id = saved.proposals.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+        let proposals: HashMap<u32, Proposal> = saved.proposals.into_iter().map(|p| (p.id, p)).collect();
+        info!("Loaded {} proposals from store", proposals.len());
+
+        let governance = Governance {
+            proposals: Arc::new(Mutex::new(proposals)),
+            next_id: Arc::new(Mutex::new(next_id)),
+            identity_manager,
+            delegations: Arc::new(Mutex::new(saved.delegations)),
+            store: Some(store),
+            credit_period: DEFAULT_CREDIT_PERIOD,
+            write_lock: Arc::new(Mutex::new(())),
+        };
+        governance.finalize_expired()?;
+        Ok(governance)
+    }
+
+    /// Sets the period over which each voter's quadratic credits are budgeted.
+    pub fn with_credit_period(mut self, period: Duration) -> Self {
+        self.credit_period = period;
+        self
+    }
+
+    /// Creates a proposal; with a store configured, it is saved before its ID is returned.
+    pub fn create_proposal(&self, description: String, voting_duration: Duration) -> Result<u32, String> {
+        self.create_proposal_with_strategy(description, voting_duration, VotingStrategy::SimpleMajority)
+    }
+
+    /// Creates a proposal whose result is computed with the given voting strategy.
+    pub fn create_proposal_with_strategy(&self, description: String, voting_duration: Duration, strategy: VotingStrategy) -> Result<u32, String> {
+        self.create_topic_proposal(description, voting_duration, strategy, None)
+    }
+
+    /// Creates a proposal on a topic, so that topic-specific delegations apply to it.
+    pub fn create_topic_proposal(&self, description: String, voting_duration: Duration, strategy: VotingStrategy, topic: Option<String>) -> Result<u32, String> {
+        self.insert_proposal(description, voting_duration, None, strategy, topic)
+    }
+
+    /// Creates a proposal with private ballots.
+    ///
+    /// Voters submit `commit_vote` during `commit_duration`, then `reveal_vote` during the
+    /// following `reveal_duration`. Ballots and tallies stay hidden until the reveal window
+    /// closes, and commitments that were never revealed are discarded.
+    pub fn create_commit_reveal_proposal(
+        &self,
+        description: String,
+        commit_duration: Duration,
+        reveal_duration: Duration,
+        strategy: VotingStrategy,
+        topic: Option<String>,
+    ) -> Result<u32, String> {
+        self.insert_proposal(description, commit_duration, Some(reveal_duration), strategy, topic)
+    }
+
+    fn insert_proposal(
+        &self,
+        description: String,
+        voting_duration: Duration,
+        reveal_duration: Option<Duration>,
+        strategy: VotingStrategy,
+        topic: Option<String>,
+    ) -> Result<u32, String> {
+        strategy.validate_settings()?;
+        let _writer = self.write_lock.lock().unwrap();
         let mut next_id = self.next_id.lock().unwrap();
 
+        let created_at = Utc::now();
+        let closes_at = add_duration(created_at, voting_duration);
+        let reveal_closes_at = reveal_duration.map(|d| add_duration(closes_at, d));
+
         let proposal = Proposal {
             id: *next_id,
             description,
             votes_for: 0,
             votes_against: 0,
             is_active: true,
-            created_at: Instant::now(),
+            status: ProposalStatus::Active,
+            created_at,
+            closes_at,
             voting_duration,
+            votes: HashMap::new(),
+            strategy,
+            topic,
+            delegations: HashMap::new(),
+            reveal_closes_at,
+            commitments: HashMap::new(),
         };
 
-        proposals.insert(*next_id, proposal);
+        info!("Created proposal: {:?}", proposal);
+        self.apply(vec![proposal], None)?;
         *next_id += 1;
-        info!("Created proposal: {:?}", proposals.get(&(*next_id - 1)).unwrap());
-        *next_id - 1
+        Ok(*next_id - 1)
     }
 
-    pub fn vote(&self, proposal_id: u32, vote_for: bool) -> Result<(), String> {
-        let mut proposals = self.proposals.lock().unwrap();
+    /// Casts a vote on behalf of `voter_id`.
+    ///
+    /// The signature must be made by the voter's identity over `vote_message(proposal_id, vote_for)`.
+    /// Each identity may vote only once per proposal.
+    pub fn vote(&self, proposal_id: u32, voter_id: &str, vote_for: bool, signature: &Signature) -> Result<(), String> {
+        self.vote_with(proposal_id, voter_id, vote_for, BallotParams::default(), signature)
+    }
+
+    /// Casts a ballot with strategy-specific options such as quadratic credits or conviction locks.
+    ///
+    /// The signature must cover `ballot_message(proposal_id, vote_for, &params)`.
+    pub fn vote_with(&self, proposal_id: u32, voter_id: &str, vote_for: bool, params: BallotParams, signature: &Signature) -> Result<(), String> {
+        self.verify_voter(voter_id, &ballot_message(proposal_id, vote_for, &params), signature)?;
+
+        self.update(proposal_id, |proposal, now| {
+            if proposal.is_commit_reveal() {
+                return Err("Proposal requires commit-reveal voting.".to_string());
+            }
+
+            if proposal.is_expired(now) {
+                return Err("Voting period has expired.".to_string());
+            }
 
-        if let Some(proposal) = proposals.get_mut(&proposal_id) {
             if !proposal.is_active {
                 return Err("Proposal is no longer active.".to_string());
             }
 
-            if proposal.created_at.elapsed() > proposal.voting_duration {
-                proposal.is_active = false; // Automatically close the proposal if the voting period has expired
-                return Err("Voting period has expired.".to_string());
+            if proposal.votes.contains_key(voter_id) {
+                return Err("Voter has already voted on this proposal.".to_string());
             }
 
+            proposal.strategy.validate(&params)?;
+            self.check_voting_power(proposal, voter_id, &params, now)?;
+
             if vote_for {
                 proposal.votes_for += 1;
             } else {
                 proposal.votes_against += 1;
             }
-            info!("Vote recorded for proposal ID {}: {}", proposal_id, vote_for);
+            proposal.votes.insert(voter_id.to_string(), VoteRecord {
+                voter_id: voter_id.to_string(),
+                vote_for,
+                signature: signature.to_bytes().to_vec(),
+                cast_at: now,
+                params,
+                commit_signature: None,
+            });
+            info!("Vote recorded for proposal ID {} by {}: {}", proposal_id, voter_id, vote_for);
             Ok(())
-        } else {
-            error!("Proposal not found: ID {}", proposal_id);
-            Err("Proposal not found.".to_string())
+        })
+    }
+
+    /// Submits a sealed ballot during the commit phase of a commit-reveal proposal.
+    ///
+    /// `commitment` is `commitment_hash(proposal_id, vote_for, &params, salt)` and the signature
+    /// must cover `commit_message(proposal_id, &commitment)`. A voter may commit only once.
+    /// Only the hash is seen here, so a salt shorter than `MIN_SALT_LEN` is refused at reveal
+    /// and the ballot is then lost; `generate_salt` makes a suitable one.
+    pub fn commit_vote(&self, proposal_id: u32, voter_id: &str, commitment: Vec<u8>, signature: &Signature) -> Result<(), String> {
+        self.verify_voter(voter_id, &commit_message(proposal_id, &commitment), signature)?;
+
+        if commitment.len() != COMMITMENT_LEN {
+            return Err("Commitment must be a SHA-256 hash.".to_string());
         }
+
+        self.update(proposal_id, |proposal, now| {
+            if !proposal.is_commit_reveal() {
+                return Err("Proposal does not use commit-reveal voting.".to_string());
+            }
+            if proposal.status != ProposalStatus::Active {
+                return Err("Commit phase has ended.".to_string());
+            }
+            if proposal.commitments.contains_key(voter_id) {
+                return Err("Voter has already committed on this proposal.".to_string());
+            }
+
+            proposal.commitments.insert(voter_id.to_string(), Commitment {
+                voter_id: voter_id.to_string(),
+                commitment,
+                signature: signature.to_bytes().to_vec(),
+                committed_at: now,
+                revealed: false,
+            });
+            info!("Commitment recorded for proposal ID {} by {}", proposal_id, voter_id);
+            Ok(())
+        })
     }
 
-    pub fn close_proposal(&self, proposal_id: u32) -> Result<(), String> {
-        let mut proposals = self.proposals.lock().unwrap();
+    /// Reveals a previously committed ballot during the reveal window.
+    ///
+    /// The ballot is accepted only if it hashes, together with `salt`, to the voter's commitment.
+    pub fn reveal_vote(&self, proposal_id: u32, voter_id: &str, vote_for: bool, params: BallotParams, salt: &[u8]) -> Result<(), String> {
+        self.update(proposal_id, |proposal, now| {
+            if !proposal.is_commit_reveal() {
+                return Err("Proposal does not use commit-reveal voting.".to_string());
+            }
+            match proposal.status {
+                ProposalStatus::Revealing => {}
+                ProposalStatus::Active => return Err("Reveal phase has not started.".to_string()),
+                _ => return Err("Reveal phase has ended.".to_string()),
+            }
+
+            let commitment = match proposal.commitments.get(voter_id) {
+                Some(commitment) => commitment,
+                None => return Err("No commitment found for this voter.".to_string()),
+            };
+            if commitment.revealed {
+                return Err("Ballot has already been revealed.".to_string());
+            }
+            if commitment_hash(proposal_id, vote_for, &params, salt)? != commitment.commitment {
+                error!("Reveal from {} does not match commitment on proposal ID {}", voter_id, proposal_id);
+                return Err("Revealed ballot does not match commitment.".to_string());
+            }
+            let commit_signature = commitment.signature.clone();
+            proposal.strategy.validate(&params)?;
+            self.check_voting_power(proposal, voter_id, &params, now)?;
+
+            if let Some(commitment) = proposal.commitments.get_mut(voter_id) {
+                commitment.revealed = true;
+            }
+            proposal.votes.insert(voter_id.to_string(), VoteRecord {
+                voter_id: voter_id.to_string(),
+                vote_for,
+                signature: Vec::new(),
+                cast_at: now,
+                params,
+                commit_signature: Some(commit_signature),
+            });
+            info!("Ballot revealed for proposal ID {} by {}", proposal_id, voter_id);
+            Ok(())
+        })
+    }
 
-        if let Some(proposal) = proposals.get_mut(&proposal_id) {
-            proposal.is_active = false;
+    /// Checks a weighted ballot against what the voter committed on other proposals.
+    ///
+    /// Quadratic credits are budgeted across every quadratic ballot the voter casts in the
+    /// same credit period, and a running conviction lock blocks further conviction ballots.
+    fn check_voting_power(&self, proposal: &Proposal, voter_id: &str, params: &BallotParams, now: DateTime<Utc>) -> Result<(), String> {
+        let proposals = self.proposals.lock().unwrap();
+        let other_ballots = proposals
+            .values()
+            .filter(|p| p.id != proposal.id)
+            .filter_map(|p| p.votes.get(voter_id).map(|v| (p.strategy, v)));
+
+        match proposal.strategy {
+            VotingStrategy::Quadratic { credit_budget } => {
+                let period = credit_period(now, self.credit_period);
+                let spent = other_ballots
+                    .filter(|(strategy, v)| matches!(strategy, VotingStrategy::Quadratic { .. }) && credit_period(v.cast_at, self.credit_period) == period)
+                    .fold(0u64, |spent, (_, v)| spent.saturating_add(v.params.credits));
+                if spent.saturating_add(params.credits) > credit_budget {
+                    return Err(format!(
+                        "Credits spent exceed the remaining budget of {} for this period.",
+                        credit_budget.saturating_sub(spent)
+                    ));
+                }
+                Ok(())
+            }
+            VotingStrategy::Conviction { .. } => {
+                let locked_until = other_ballots
+                    .filter_map(|(strategy, v)| strategy.lock_expiry(v.cast_at, &v.params))
+                    .filter(|until| *until > now)
+                    .max();
+                match locked_until {
+                    Some(until) => Err(format!("Voting power is locked by a conviction ballot until {}.", until.to_rfc3339())),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the voter exists and that the signature covers this exact ballot.
+    fn verify_voter(&self, voter_id: &str, message: &[u8], signature: &Signature) -> Result<(), String> {
+        let identity_manager = self.identity_manager.lock().unwrap();
+
+        match identity_manager.verify_identity(voter_id, signature, message) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                error!("Invalid vote signature from {}", voter_id);
+                Err("Invalid vote signature.".to_string())
+            }
+            Err(_) => {
+                error!("Unknown voter identity: {}", voter_id);
+                Err("Voter identity not found.".to_string())
+            }
+        }
+    }
+
+    pub fn close_proposal(&self, proposal_id: u32) -> Result<(), String> {
+        self.update(proposal_id, |proposal, _| {
+            if proposal.status == ProposalStatus::Active {
+                self.conclude(proposal, ProposalStatus::Closed);
+            }
             info!("Closed proposal ID {}: {:?}", proposal_id, proposal);
             Ok(())
-        } else {
-            error!("Proposal not found: ID {}", proposal_id);
-            Err("Proposal not found.".to_string())
+        })
+    }
+
+    /// Moves a proposal to `status`, capturing delegations when voting ends.
+    ///
+    /// When a commit-reveal proposal is finalized, unrevealed commitments are discarded and
+    /// the revealed ballots are counted.
+    fn conclude(&self, proposal: &mut Proposal, status: ProposalStatus) {
+        let was_open = matches!(proposal.status, ProposalStatus::Active | ProposalStatus::Revealing);
+        if was_open && status != ProposalStatus::Revealing {
+            proposal.delegations = self.resolve_delegations(proposal);
         }
+
+        if proposal.is_commit_reveal() && status == ProposalStatus::Finalized {
+            let before = proposal.commitments.len();
+            proposal.commitments.retain(|_, c| c.revealed);
+            let discarded = before - proposal.commitments.len();
+            if discarded > 0 {
+                info!("Discarded {} unrevealed commitments on proposal ID {}", discarded, proposal.id);
+            }
+            proposal.votes_for = proposal.votes.values().filter(|v| v.vote_for).count() as u32;
+            proposal.votes_against = proposal.votes.values().filter(|v| !v.vote_for).count() as u32;
+        }
+
+        proposal.set_status(status);
+    }
+
+    /// Maps each non-voting delegator to the direct voter whose ballot carries their power.
+    fn resolve_delegations(&self, proposal: &Proposal) -> HashMap<String, String> {
+        let delegations = self.delegations.lock().unwrap();
+        delegations.resolve_for_voters(proposal.topic.as_deref(), |voter| proposal.votes.contains_key(voter))
+    }
+
+    /// Delegates `delegator`'s voting power to `delegate`, globally or for one topic.
+    ///
+    /// The signature must be made by the delegator over `delegation_message(delegator, delegate, topic)`.
+    /// A delegator who later votes directly overrides the delegation for that proposal.
+    pub fn delegate(&self, delegator: &str, delegate: &str, topic: Option<&str>, signature: &Signature) -> Result<(), String> {
+        self.verify_voter(delegator, &delegation_message(delegator, delegate, topic), signature)?;
+        if self.identity_manager.lock().unwrap().get_identity(delegate).is_none() {
+            return Err("Delegate identity not found.".to_string());
+        }
+        self.update_delegations(|delegations| delegations.delegate(delegator, delegate, topic))
+    }
+
+    /// Revokes a delegation; proposals that are still open stop counting it immediately.
+    pub fn revoke_delegation(&self, delegator: &str, topic: Option<&str>, signature: &Signature) -> Result<(), String> {
+        self.verify_voter(delegator, &revocation_message(delegator, topic), signature)?;
+        self.update_delegations(|delegations| delegations.revoke(delegator, topic))
+    }
+
+    /// Runs `change` on a copy of the delegations and saves it before it takes effect.
+    fn update_delegations<F>(&self, change: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut DelegationRegistry) -> Result<(), String>,
+    {
+        let _writer = self.write_lock.lock().unwrap();
+        let mut delegations = self.delegations.lock().unwrap().clone();
+        change(&mut delegations)?;
+        self.apply(Vec::new(), Some(delegations))
+    }
+
+    /// Finalizes every proposal whose deadline has passed, saves them and returns their IDs.
+    pub fn finalize_expired(&self) -> Result<Vec<u32>, String> {
+        let _writer = self.write_lock.lock().unwrap();
+        let now = Utc::now();
+
+        let changed: Vec<Proposal> = {
+            let proposals = self.proposals.lock().unwrap();
+            proposals
+                .values()
+                .filter(|p| p.next_status(now).is_some())
+                .map(|p| self.current(p.clone(), now))
+                .collect()
+        };
+        let mut finalized: Vec<u32> = changed.iter().filter(|p| p.status == ProposalStatus::Finalized).map(|p| p.id).collect();
+
+        if !changed.is_empty() {
+            finalized.sort();
+            self.apply(changed, None)?;
+            info!("Finalized proposals: {:?}", finalized);
+        }
+        Ok(finalized)
+    }
+
+    /// Spawns a background task that finalizes proposals at their deadline.
+    ///
+    /// The task checks every `interval` until the returned handle is aborted. Saving runs on
+    /// the blocking thread pool so store I/O does not stall the runtime.
+    pub fn start_scheduler(&self, interval: Duration) -> JoinHandle<()> {
+        let governance = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let governance = governance.clone();
+                match tokio::task::spawn_blocking(move || governance.finalize_expired()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => error!("Failed to finalize proposals: {}", e),
+                    Err(e) => error!("Finalization task failed: {}", e),
+                }
+            }
+        })
     }
 
+    /// Returns a proposal as of now; revealed ballots of a sealed commit-reveal proposal are withheld.
+    ///
+    /// A proposal past its deadline is reported as finalized even before the scheduler has
+    /// saved the transition; reads never change stored state.
     pub fn get_proposal(&self, proposal_id: u32) -> Option<Proposal> {
-        let proposals = self.proposals.lock().unwrap();
-        proposals.get(&proposal_id).cloned()
+        self.snapshot(proposal_id).map(|mut proposal| {
+            if proposal.is_sealed() {
+                proposal.votes.clear();
+            }
+            proposal
+        })
     }
 
     pub fn get_active_proposals(&self) -> Vec<Proposal> {
+        let now = Utc::now();
         let proposals = self.proposals.lock().unwrap();
-        proposals.values().filter(|p| p.is_active).cloned().collect()
+        proposals
+            .values()
+            .map(|p| self.current(p.clone(), now))
+            .filter(|p| p.is_active)
+            .collect()
+    }
+
+    /// Returns the recorded ballots for a proposal, whether it is still open or already closed.
+    pub fn get_votes(&self, proposal_id: u32) -> Result<Vec<VoteRecord>, String> {
+        match self.snapshot(proposal_id) {
+            Some(proposal) if proposal.is_sealed() => Err("Ballots are sealed until the reveal window closes.".to_string()),
+            Some(proposal) => {
+                let mut votes: Vec<VoteRecord> = proposal.votes.values().cloned().collect();
+                votes.sort_by(|a, b| a.cast_at.cmp(&b.cast_at).then_with(|| a.voter_id.cmp(&b.voter_id)));
+                Ok(votes)
+            }
+            None => Err("Proposal not found.".to_string()),
+        }
+    }
+
+    /// Computes the proposal's result under its voting strategy, with an explanation of the tally.
+    pub fn get_result(&self, proposal_id: u32) -> Result<TallyResult, String> {
+        match self.snapshot(proposal_id) {
+            Some(proposal) if proposal.is_sealed() => Err("Results are hidden until the reveal window closes.".to_string()),
+            Some(proposal) => {
+                let delegations = if proposal.status == ProposalStatus::Active {
+                    self.resolve_delegations(&proposal)
+                } else {
+                    proposal.delegations.clone()
+                };
+                let mut followers: HashMap<String, u64> = HashMap::new();
+                for voter in delegations.values() {
+                    *followers.entry(voter.clone()).or_insert(0) += 1;
+                }
+                Ok(proposal.strategy.tally(proposal.votes.values(), &followers))
+            }
+            None => Err("Proposal not found.".to_string()),
+        }
+    }
+
+    /// Returns the ballot a specific voter cast on a proposal, if any.
+    pub fn get_vote(&self, proposal_id: u32, voter_id: &str) -> Option<VoteRecord> {
+        self.snapshot(proposal_id)
+            .filter(|p| !p.is_sealed())
+            .and_then(|p| p.votes.get(voter_id).cloned())
+    }
+
+    /// Returns a copy of the proposal with any transition due by now applied.
+    fn snapshot(&self, proposal_id: u32) -> Option<Proposal> {
+        let proposal = self.proposals.lock().unwrap().get(&proposal_id).cloned()?;
+        Some(self.current(proposal, Utc::now()))
+    }
+
+    /// Applies the status transition due at `now`, if any, to a copy of a stored proposal.
+    fn current(&self, mut proposal: Proposal, now: DateTime<Utc>) -> Proposal {
+        if let Some(next) = proposal.next_status(now) {
+            self.conclude(&mut proposal, next);
+        }
+        proposal
+    }
+
+    /// Runs `change` on the current state of a proposal and saves the result.
+    ///
+    /// Nothing is stored when `change` fails, and a successful change is only acknowledged
+    /// once it has been saved.
+    fn update<T, F>(&self, proposal_id: u32, change: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Proposal, DateTime<Utc>) -> Result<T, String>,
+    {
+        let _writer = self.write_lock.lock().unwrap();
+        let now = Utc::now();
+        let stored = self.proposals.lock().unwrap().get(&proposal_id).cloned();
+        let mut proposal = match stored {
+            Some(proposal) => self.current(proposal, now),
+            None => {
+                error!("Proposal not found: ID {}", proposal_id);
+                return Err("Proposal not found.".to_string());
+            }
+        };
+
+        let result = change(&mut proposal, now)?;
+        self.apply(vec![proposal], None)?;
+        Ok(result)
+    }
+
+    /// Saves the proposal set with `changed` applied, and the new delegations if given, when a
+    /// store is configured, then makes the changes visible.
+    ///
+    /// Callers hold the write lock. The proposals and delegations locks are only held to copy
+    /// and to swap in the changes, never while the store is written.
+    fn apply(&self, changed: Vec<Proposal>, delegations: Option<DelegationRegistry>) -> Result<(), String> {
+        if let Some(store) = &self.store {
+            let mut snapshot = self.proposals.lock().unwrap().clone();
+            for proposal in &changed {
+                snapshot.insert(proposal.id, proposal.clone());
+            }
+            let mut proposals: Vec<Proposal> = snapshot.into_values().collect();
+            proposals.sort_by_key(|p| p.id);
+            let delegations = match &delegations {
+                Some(delegations) => delegations.clone(),
+                None => self.delegations.lock().unwrap().clone(),
+            };
+            store.save(&GovernanceState { proposals, delegations }).map_err(|e| {
+                error!("Failed to persist proposals: {}", e);
+                format!("Failed to save proposals: {}", e)
+            })?;
+        }
+
+        let mut proposals = self.proposals.lock().unwrap();
+        for proposal in changed {
+            proposals.insert(proposal.id, proposal);
+        }
+        if let Some(delegations) = delegations {
+            *self.delegations.lock().unwrap() = delegations;
+        }
+        Ok(())
     }
 }
 
+/// Adds a std duration to a timestamp, saturating at the latest representable time.
+fn add_duration(start: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|d| start.checked_add_signed(d))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
 // Example usage of logging setup
 fn setup_logging() {
     use log::LevelFilter;
@@ -107,17 +719,26 @@ fn setup_logging() {
 
 fn main() {
     setup_logging();
-    let governance = Governance::new();
-    
+    let identity_manager = Arc::new(Mutex::new(IdentityManager::new()));
+    identity_manager.lock().unwrap()
+        .create_identity("alice".to_string(), HashMap::new())
+        .expect("Failed to create identity");
+    let governance = Governance::new(identity_manager.clone());
+
     // Example of creating a proposal
-    let proposal_id = governance.create_proposal("Increase budget for project X".to_string(), Duration::new(60, 0));
+    let proposal_id = governance
+        .create_proposal("Increase budget for project X".to_string(), Duration::new(60, 0))
+        .expect("Failed to create proposal");
     println!("Created proposal with ID: {}", proposal_id);
-    
+
     // Example of voting
-    if let Err(e) = governance.vote(proposal_id, true) {
+    let signature = identity_manager.lock().unwrap()
+        .sign_message("alice", &vote_message(proposal_id, true))
+        .expect("Failed to sign vote");
+    if let Err(e) = governance.vote(proposal_id, "alice", true, &signature) {
         println!("Error voting: {}", e);
     }
-    
+
     // Example of closing a proposal
     if let Err(e) = governance.close_proposal(proposal_id) {
         println!("Error closing proposal: {}", e);
@@ -1,13 +1,80 @@
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::governance::governance::{Governance, Proposal, ProposalStatus, ballot_message, delegation_message, revocation_message, vote_message};
+    use crate::governance::commit_reveal::{commit_message, commitment_hash, generate_salt};
+    use crate::governance::persistence::{GovernanceState, JsonFileProposalStore, ProposalStore};
+    use crate::governance::strategy::{BallotParams, VotingStrategy};
+    use crate::identity_management::identity::IdentityManager;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
     use std::thread::sleep;
 
+    // Creates a governance instance backed by an identity manager holding the given voters
+    fn setup(voters: &[&str]) -> (Governance, Arc<Mutex<IdentityManager>>) {
+        let identity_manager = Arc::new(Mutex::new(IdentityManager::new()));
+        for voter in voters {
+            identity_manager.lock().unwrap().create_identity(voter.to_string(), HashMap::new()).unwrap();
+        }
+        (Governance::new(identity_manager.clone()), identity_manager)
+    }
+
+    // In-memory store that records the last saved state, and fails saves once `failing` is set
+    struct MemoryStore {
+        saved: Mutex<GovernanceState>,
+        failing: Mutex<bool>,
+    }
+
+    impl MemoryStore {
+        fn new() -> Self {
+            MemoryStore { saved: Mutex::new(GovernanceState::default()), failing: Mutex::new(false) }
+        }
+    }
+
+    impl ProposalStore for MemoryStore {
+        fn load(&self) -> Result<GovernanceState, String> {
+            Ok(self.saved.lock().unwrap().clone())
+        }
+
+        fn save(&self, state: &GovernanceState) -> Result<(), String> {
+            if *self.failing.lock().unwrap() {
+                return Err("disk full".to_string());
+            }
+            *self.saved.lock().unwrap() = state.clone();
+            Ok(())
+        }
+    }
+
+    // Signs and casts a ballot for the given voter
+    fn cast_vote(governance: &Governance, identities: &Arc<Mutex<IdentityManager>>, proposal_id: u32, voter: &str, vote_for: bool) -> Result<(), String> {
+        let signature = identities.lock().unwrap().sign_message(voter, &vote_message(proposal_id, vote_for)).unwrap();
+        governance.vote(proposal_id, voter, vote_for, &signature)
+    }
+
+    // Signs and casts a weighted ballot for the given voter
+    fn cast_ballot(governance: &Governance, identities: &Arc<Mutex<IdentityManager>>, proposal_id: u32, voter: &str, vote_for: bool, params: BallotParams) -> Result<(), String> {
+        let signature = identities.lock().unwrap().sign_message(voter, &ballot_message(proposal_id, vote_for, &params)).unwrap();
+        governance.vote_with(proposal_id, voter, vote_for, params, &signature)
+    }
+
+    // Signs and registers a delegation
+    fn delegate(governance: &Governance, identities: &Arc<Mutex<IdentityManager>>, delegator: &str, delegate: &str, topic: Option<&str>) -> Result<(), String> {
+        let signature = identities.lock().unwrap().sign_message(delegator, &delegation_message(delegator, delegate, topic)).unwrap();
+        governance.delegate(delegator, delegate, topic, &signature)
+    }
+
+    // Signs and submits a commitment to a ballot
+    fn commit(governance: &Governance, identities: &Arc<Mutex<IdentityManager>>, proposal_id: u32, voter: &str, vote_for: bool, salt: &[u8]) -> Result<(), String> {
+        let commitment = commitment_hash(proposal_id, vote_for, &BallotParams::default(), salt)?;
+        let signature = identities.lock().unwrap().sign_message(voter, &commit_message(proposal_id, &commitment)).unwrap();
+        governance.commit_vote(proposal_id, voter, commitment, &signature)
+    }
+
     #[test]
     fn test_create_proposal() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
+        let (governance, _) = setup(&[]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
 
         let proposal = governance.get_proposal(proposal_id).unwrap();
         assert_eq!(proposal.description, "Increase community funding");
@@ -18,10 +85,10 @@ mod tests {
 
     #[test]
     fn test_vote_for_proposal() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
 
-        governance.vote(proposal_id, true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
         let proposal = governance.get_proposal(proposal_id).unwrap();
         assert_eq!(proposal.votes_for, 1);
         assert_eq!(proposal.votes_against, 0);
@@ -29,10 +96,10 @@ mod tests {
 
     #[test]
     fn test_vote_against_proposal() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
 
-        governance.vote(proposal_id, false).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "alice", false).unwrap();
         let proposal = governance.get_proposal(proposal_id).unwrap();
         assert_eq!(proposal.votes_for, 0);
         assert_eq!(proposal.votes_against, 1);
@@ -40,8 +107,8 @@ mod tests {
 
     #[test]
     fn test_close_proposal() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
+        let (governance, _) = setup(&[]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
 
         governance.close_proposal(proposal_id).unwrap();
         let proposal = governance.get_proposal(proposal_id).unwrap();
@@ -50,20 +117,20 @@ mod tests {
 
     #[test]
     fn test_vote_on_closed_proposal() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
 
         governance.close_proposal(proposal_id).unwrap();
-        let result = governance.vote(proposal_id, true);
+        let result = cast_vote(&governance, &identities, proposal_id, "alice", true);
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), "Proposal is no longer active.");
     }
 
     #[test]
     fn test_get_active_proposals() {
-        let governance = Governance::new();
-        let proposal_id1 = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0));
-        let proposal_id2 = governance.create_proposal("Improve community safety".to_string(), Duration::new(60, 0));
+        let (governance, _) = setup(&[]);
+        let proposal_id1 = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+        let proposal_id2 = governance.create_proposal("Improve community safety".to_string(), Duration::new(60, 0)).unwrap();
 
         let active_proposals = governance.get_active_proposals();
         assert_eq!(active_proposals.len(), 2);
@@ -76,30 +143,584 @@ mod tests {
 
     #[test]
     fn test_get_nonexistent_proposal() {
-        let governance = Governance::new();
+        let (governance, _) = setup(&[]);
         let proposal = governance.get_proposal(999);
         assert!(proposal.is_none());
     }
 
     #[test]
     fn test_vote_after_voting_period_expires() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(1, 0)); // 1 second voting period
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(1, 0)).unwrap(); // 1 second voting period
 
         // Sleep for 2 seconds to ensure the voting period has expired
         sleep(Duration::new(2, 0));
 
-        let result = governance.vote(proposal_id, true);
+        let result = cast_vote(&governance, &identities, proposal_id, "alice", true);
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), "Voting period has expired.");
     }
 
     #[test]
     fn test_create_proposal_with_zero_duration() {
-        let governance = Governance::new();
-        let proposal_id = governance.create_proposal("Instant proposal".to_string(), Duration::new(0, 0)); // 0 seconds voting period
+        let (governance, _) = setup(&[]);
+        let proposal_id = governance.create_proposal("Instant proposal".to_string(), Duration::new(0, 0)).unwrap(); // 0 seconds voting period
 
         let proposal = governance.get_proposal(proposal_id).unwrap();
         assert!(!proposal.is_active); // Proposal should be inactive immediately
     }
-                        }
+
+    #[test]
+    fn test_duplicate_vote_rejected() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        let result = cast_vote(&governance, &identities, proposal_id, "alice", false);
+        assert_eq!(result.err().unwrap(), "Voter has already voted on this proposal.");
+
+        let proposal = governance.get_proposal(proposal_id).unwrap();
+        assert_eq!(proposal.votes_for, 1);
+        assert_eq!(proposal.votes_against, 0);
+    }
+
+    #[test]
+    fn test_vote_with_unknown_identity() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        let signature = identities.lock().unwrap().sign_message("alice", &vote_message(proposal_id, true)).unwrap();
+        let result = governance.vote(proposal_id, "mallory", true, &signature);
+        assert_eq!(result.err().unwrap(), "Voter identity not found.");
+    }
+
+    #[test]
+    fn test_vote_with_mismatched_signature() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        // Bob's signature presented as Alice's ballot
+        let signature = identities.lock().unwrap().sign_message("bob", &vote_message(proposal_id, true)).unwrap();
+        let result = governance.vote(proposal_id, "alice", true, &signature);
+        assert_eq!(result.err().unwrap(), "Invalid vote signature.");
+
+        // A signature for "against" cannot be replayed as a vote "for"
+        let signature = identities.lock().unwrap().sign_message("alice", &vote_message(proposal_id, false)).unwrap();
+        let result = governance.vote(proposal_id, "alice", true, &signature);
+        assert_eq!(result.err().unwrap(), "Invalid vote signature.");
+    }
+
+    #[test]
+    fn test_vote_records_available_after_close() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", false).unwrap();
+        governance.close_proposal(proposal_id).unwrap();
+
+        let votes = governance.get_votes(proposal_id).unwrap();
+        assert_eq!(votes.len(), 2);
+        assert_eq!(votes[0].voter_id, "alice");
+        assert!(votes[0].vote_for);
+        assert_eq!(votes[1].voter_id, "bob");
+        assert!(!votes[1].vote_for);
+
+        let record = governance.get_vote(proposal_id, "bob").unwrap();
+        let signature = ed25519_dalek::Signature::from_bytes(&record.signature).unwrap();
+        assert!(identities.lock().unwrap().verify_identity("bob", &signature, &vote_message(proposal_id, false)).unwrap());
+    }
+
+    #[test]
+    fn test_proposal_deadline_is_wall_clock() {
+        let (governance, _) = setup(&[]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        let proposal = governance.get_proposal(proposal_id).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Active);
+        assert_eq!((proposal.closes_at - proposal.created_at).num_seconds(), 60);
+    }
+
+    #[test]
+    fn test_closed_proposal_is_finalized_at_deadline() {
+        let (governance, _) = setup(&[]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(1, 0)).unwrap();
+
+        governance.close_proposal(proposal_id).unwrap();
+        assert_eq!(governance.get_proposal(proposal_id).unwrap().status, ProposalStatus::Closed);
+
+        sleep(Duration::new(2, 0));
+        assert_eq!(governance.finalize_expired().unwrap(), vec![proposal_id]);
+        assert_eq!(governance.get_proposal(proposal_id).unwrap().status, ProposalStatus::Finalized);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_finalizes_at_deadline() {
+        let identity_manager = Arc::new(Mutex::new(IdentityManager::new()));
+        let store = Arc::new(MemoryStore::new());
+        let governance = Governance::with_store(identity_manager, store.clone()).unwrap();
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(1, 0)).unwrap();
+
+        let handle = governance.start_scheduler(Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        handle.abort();
+
+        // The scheduler alone must have finalized and saved the proposal
+        let saved = store.saved.lock().unwrap().clone();
+        let proposal = saved.proposals.iter().find(|p| p.id == proposal_id).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Finalized);
+        assert!(!proposal.is_active);
+    }
+
+    #[test]
+    fn test_proposals_reload_from_store() {
+        let path = std::env::temp_dir().join(format!("governance_store_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (_, identities) = setup(&["alice"]);
+        let governance = Governance::with_store(identities.clone(), Arc::new(JsonFileProposalStore::new(&path))).unwrap();
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        drop(governance);
+
+        // Simulate a restart
+        let reloaded = Governance::with_store(identities.clone(), Arc::new(JsonFileProposalStore::new(&path))).unwrap();
+        let proposal = reloaded.get_proposal(proposal_id).unwrap();
+        assert_eq!(proposal.description, "Increase community funding");
+        assert_eq!(proposal.votes_for, 1);
+        assert!(proposal.is_active);
+        assert!(reloaded.get_vote(proposal_id, "alice").is_some());
+
+        // New proposals continue the ID sequence
+        let next_id = reloaded.create_proposal("Improve community safety".to_string(), Duration::new(60, 0)).unwrap();
+        assert_eq!(next_id, proposal_id + 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_store_failures_are_returned() {
+        let (_, identities) = setup(&["alice"]);
+        let store = Arc::new(MemoryStore::new());
+        let governance = Governance::with_store(identities.clone(), store.clone()).unwrap();
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        *store.failing.lock().unwrap() = true;
+        let result = cast_vote(&governance, &identities, proposal_id, "alice", true);
+        assert_eq!(result.err().unwrap(), "Failed to save proposals: disk full");
+        assert!(governance.create_proposal("Improve community safety".to_string(), Duration::new(60, 0)).is_err());
+
+        // Nothing that failed to save is visible, so the vote can be retried
+        assert!(governance.get_vote(proposal_id, "alice").is_none());
+        assert_eq!(governance.get_active_proposals().len(), 1);
+        *store.failing.lock().unwrap() = false;
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        assert_eq!(store.saved.lock().unwrap().proposals[0].votes_for, 1);
+    }
+
+    #[test]
+    fn test_reads_do_not_save() {
+        let identity_manager = Arc::new(Mutex::new(IdentityManager::new()));
+        let store = Arc::new(MemoryStore::new());
+        let governance = Governance::with_store(identity_manager, store.clone()).unwrap();
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(1, 0)).unwrap();
+
+        sleep(Duration::new(1, 100_000_000));
+        assert_eq!(governance.get_proposal(proposal_id).unwrap().status, ProposalStatus::Finalized);
+        assert!(governance.get_active_proposals().is_empty());
+        assert_eq!(store.saved.lock().unwrap().proposals[0].status, ProposalStatus::Active);
+
+        assert_eq!(governance.finalize_expired().unwrap(), vec![proposal_id]);
+        assert_eq!(store.saved.lock().unwrap().proposals[0].status, ProposalStatus::Finalized);
+    }
+
+    #[test]
+    fn test_simple_majority_result() {
+        let (governance, identities) = setup(&["alice", "bob", "carol"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "carol", false).unwrap();
+
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.strategy, VotingStrategy::SimpleMajority);
+        assert_eq!(result.weight_for, 2);
+        assert_eq!(result.weight_against, 1);
+        assert!(result.passed);
+    }
+
+    #[test]
+    fn test_supermajority_result() {
+        let (governance, identities) = setup(&["alice", "bob", "carol"]);
+        let strategy = VotingStrategy::Supermajority { threshold_percent: 75 };
+        let proposal_id = governance.create_proposal_with_strategy("Amend the charter".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        cast_vote(&governance, &identities, proposal_id, "alice", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "carol", false).unwrap();
+
+        // 2 of 3 is below the 75% threshold
+        let result = governance.get_result(proposal_id).unwrap();
+        assert!(!result.passed);
+        assert!(result.explanation.contains("75%"));
+    }
+
+    #[test]
+    fn test_quadratic_voting() {
+        let (governance, identities) = setup(&["whale", "alice", "bob"]);
+        let strategy = VotingStrategy::Quadratic { credit_budget: 100 };
+        let proposal_id = governance.create_proposal_with_strategy("Fund the grants pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        // The whale spends its whole budget for 10 votes; two smaller voters reach 12 together
+        cast_ballot(&governance, &identities, proposal_id, "whale", false, BallotParams { credits: 100, lock_periods: 0 }).unwrap();
+        cast_ballot(&governance, &identities, proposal_id, "alice", true, BallotParams { credits: 36, lock_periods: 0 }).unwrap();
+        cast_ballot(&governance, &identities, proposal_id, "bob", true, BallotParams { credits: 40, lock_periods: 0 }).unwrap();
+
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.weight_against, 10);
+        assert_eq!(result.weight_for, 12);
+        assert!(result.passed);
+    }
+
+    #[test]
+    fn test_quadratic_voting_rejects_over_budget() {
+        let (governance, identities) = setup(&["alice"]);
+        let strategy = VotingStrategy::Quadratic { credit_budget: 100 };
+        let proposal_id = governance.create_proposal_with_strategy("Fund the grants pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        let result = cast_ballot(&governance, &identities, proposal_id, "alice", true, BallotParams { credits: 101, lock_periods: 0 });
+        assert_eq!(result.err().unwrap(), "Credits spent exceed the budget of 100.");
+        assert!(governance.get_vote(proposal_id, "alice").is_none());
+    }
+
+    #[test]
+    fn test_conviction_voting() {
+        let (governance, identities) = setup(&["alice", "bob", "carol", "dave"]);
+        let strategy = VotingStrategy::Conviction { lock_period: Duration::new(86_400, 0), max_lock_periods: 6 };
+        let proposal_id = governance.create_proposal_with_strategy("Upgrade the bridge".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        cast_ballot(&governance, &identities, proposal_id, "alice", true, BallotParams { credits: 0, lock_periods: 4 }).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", false).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "carol", false).unwrap();
+
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.weight_for, 5);
+        assert_eq!(result.weight_against, 2);
+        assert!(result.passed);
+
+        let record = governance.get_vote(proposal_id, "alice").unwrap();
+        let locked_until = strategy.lock_expiry(record.cast_at, &record.params).unwrap();
+        assert_eq!((locked_until - record.cast_at).num_days(), 4);
+
+        let result = cast_ballot(&governance, &identities, proposal_id, "dave", true, BallotParams { credits: 0, lock_periods: 7 });
+        assert_eq!(result.err().unwrap(), "Lock periods exceed the maximum of 6.");
+    }
+
+    #[test]
+    fn test_quadratic_budget_spans_proposals() {
+        let (governance, identities) = setup(&["alice"]);
+        let strategy = VotingStrategy::Quadratic { credit_budget: 100 };
+        let first = governance.create_proposal_with_strategy("Fund the grants pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+        let second = governance.create_proposal_with_strategy("Fund the audit pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        // Spending the whole budget on one proposal leaves nothing for the next one this period
+        cast_ballot(&governance, &identities, first, "alice", true, BallotParams { credits: 64, lock_periods: 0 }).unwrap();
+        let result = cast_ballot(&governance, &identities, second, "alice", true, BallotParams { credits: 40, lock_periods: 0 });
+        assert_eq!(result.err().unwrap(), "Credits spent exceed the remaining budget of 36 for this period.");
+        cast_ballot(&governance, &identities, second, "alice", true, BallotParams { credits: 36, lock_periods: 0 }).unwrap();
+    }
+
+    #[test]
+    fn test_quadratic_budget_renews_each_period() {
+        let (governance, identities) = setup(&["alice"]);
+        let governance = governance.with_credit_period(Duration::new(1, 0));
+        let strategy = VotingStrategy::Quadratic { credit_budget: 100 };
+        let first = governance.create_proposal_with_strategy("Fund the grants pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+        let second = governance.create_proposal_with_strategy("Fund the audit pool".to_string(), Duration::new(60, 0), strategy).unwrap();
+
+        cast_ballot(&governance, &identities, first, "alice", true, BallotParams { credits: 100, lock_periods: 0 }).unwrap();
+        sleep(Duration::new(1, 100_000_000));
+        cast_ballot(&governance, &identities, second, "alice", true, BallotParams { credits: 100, lock_periods: 0 }).unwrap();
+    }
+
+    #[test]
+    fn test_conviction_lock_blocks_other_conviction_ballots() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let strategy = VotingStrategy::Conviction { lock_period: Duration::new(86_400, 0), max_lock_periods: 6 };
+        let first = governance.create_proposal_with_strategy("Upgrade the bridge".to_string(), Duration::new(60, 0), strategy).unwrap();
+        let second = governance.create_proposal_with_strategy("Upgrade the relayer".to_string(), Duration::new(60, 0), strategy).unwrap();
+        let general = governance.create_proposal("Rename the forum".to_string(), Duration::new(60, 0)).unwrap();
+
+        // Bob's unlocked ballot leaves him free to vote again
+        cast_ballot(&governance, &identities, first, "bob", false, BallotParams::default()).unwrap();
+        cast_ballot(&governance, &identities, second, "bob", false, BallotParams::default()).unwrap();
+
+        cast_ballot(&governance, &identities, first, "alice", true, BallotParams { credits: 0, lock_periods: 2 }).unwrap();
+        let result = cast_ballot(&governance, &identities, second, "alice", true, BallotParams::default());
+        assert!(result.err().unwrap().starts_with("Voting power is locked by a conviction ballot until"));
+        cast_vote(&governance, &identities, general, "alice", true).unwrap();
+    }
+
+    #[test]
+    fn test_strategy_settings_and_overflow() {
+        let (governance, _) = setup(&[]);
+        let result = governance.create_proposal_with_strategy("Amend the charter".to_string(), Duration::new(60, 0), VotingStrategy::Supermajority { threshold_percent: 101 });
+        assert_eq!(result.err().unwrap(), "Supermajority threshold of 101% exceeds 100%.");
+
+        let quadratic = VotingStrategy::Quadratic { credit_budget: u64::MAX };
+        assert_eq!(quadratic.weight(&BallotParams { credits: u64::MAX, lock_periods: 0 }), u32::MAX as u64);
+
+        let conviction = VotingStrategy::Conviction { lock_period: Duration::MAX, max_lock_periods: 6 };
+        assert_eq!(conviction.lock_expiry(chrono::Utc::now(), &BallotParams { credits: 0, lock_periods: 2 }), Some(chrono::DateTime::<chrono::Utc>::MAX_UTC));
+    }
+
+    #[test]
+    fn test_ballot_options_rejected_for_simple_majority() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        let result = cast_ballot(&governance, &identities, proposal_id, "alice", true, BallotParams { credits: 9, lock_periods: 0 });
+        assert_eq!(result.err().unwrap(), "Ballot options are not supported by this voting strategy.");
+    }
+
+    #[test]
+    fn test_transitive_delegation_counts_toward_final_voter() {
+        let (governance, identities) = setup(&["alice", "bob", "carol", "dave"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        // alice -> bob -> carol; carol votes for, dave votes against
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        delegate(&governance, &identities, "bob", "carol", None).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "carol", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "dave", false).unwrap();
+
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.weight_for, 3);
+        assert_eq!(result.weight_against, 1);
+        assert_eq!(result.delegated_votes, 2);
+    }
+
+    #[test]
+    fn test_direct_vote_overrides_delegation() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "alice", false).unwrap();
+
+        // Alice's own ballot is counted once and is not also added to Bob's
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.weight_for, 1);
+        assert_eq!(result.weight_against, 1);
+        assert_eq!(result.delegated_votes, 0);
+    }
+
+    #[test]
+    fn test_delegation_cycle_rejected() {
+        let (governance, identities) = setup(&["alice", "bob", "carol"]);
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        delegate(&governance, &identities, "bob", "carol", None).unwrap();
+        let result = delegate(&governance, &identities, "carol", "alice", None);
+        assert_eq!(result.err().unwrap(), "Delegation would create a cycle.");
+
+        // A topic delegation routing back through a global one is also a cycle
+        let result = delegate(&governance, &identities, "carol", "alice", Some("treasury"));
+        assert_eq!(result.err().unwrap(), "Delegation would create a cycle.");
+
+        let result = delegate(&governance, &identities, "alice", "alice", None);
+        assert_eq!(result.err().unwrap(), "Cannot delegate to yourself.");
+    }
+
+    #[test]
+    fn test_revoked_delegation_not_counted() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+        assert_eq!(governance.get_result(proposal_id).unwrap().weight_for, 2);
+
+        let signature = identities.lock().unwrap().sign_message("alice", &revocation_message("alice", None)).unwrap();
+        governance.revoke_delegation("alice", None, &signature).unwrap();
+        assert_eq!(governance.get_result(proposal_id).unwrap().weight_for, 1);
+    }
+
+    #[test]
+    fn test_topic_delegation_takes_precedence() {
+        let (governance, identities) = setup(&["alice", "bob", "carol"]);
+        let treasury = governance.create_topic_proposal("Fund audits".to_string(), Duration::new(60, 0), VotingStrategy::SimpleMajority, Some("treasury".to_string())).unwrap();
+        let general = governance.create_proposal("Rename the forum".to_string(), Duration::new(60, 0)).unwrap();
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        delegate(&governance, &identities, "alice", "carol", Some("treasury")).unwrap();
+        for proposal_id in [treasury, general] {
+            cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+            cast_vote(&governance, &identities, proposal_id, "carol", false).unwrap();
+        }
+
+        assert_eq!(governance.get_result(treasury).unwrap().weight_against, 2);
+        assert_eq!(governance.get_result(general).unwrap().weight_for, 2);
+    }
+
+    #[test]
+    fn test_delegations_reload_from_store() {
+        let (_, identities) = setup(&["alice", "bob"]);
+        let store = Arc::new(MemoryStore::new());
+        let governance = Governance::with_store(identities.clone(), store.clone()).unwrap();
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        drop(governance);
+
+        // Simulate a restart
+        let reloaded = Governance::with_store(identities.clone(), store).unwrap();
+        let proposal_id = reloaded.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+        cast_vote(&reloaded, &identities, proposal_id, "bob", true).unwrap();
+        assert_eq!(reloaded.get_result(proposal_id).unwrap().weight_for, 2);
+    }
+
+    #[test]
+    fn test_delegators_pool_quadratic_credits_and_carry_their_own_conviction_weight() {
+        let (governance, identities) = setup(&["alice", "bob", "carol", "dave"]);
+        let quadratic = governance.create_proposal_with_strategy("Fund the grants pool".to_string(), Duration::new(60, 0), VotingStrategy::Quadratic { credit_budget: 100 }).unwrap();
+        let conviction = VotingStrategy::Conviction { lock_period: Duration::new(86_400, 0), max_lock_periods: 6 };
+        let conviction = governance.create_proposal_with_strategy("Upgrade the bridge".to_string(), Duration::new(60, 0), conviction).unwrap();
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        delegate(&governance, &identities, "carol", "dave", None).unwrap();
+        cast_ballot(&governance, &identities, quadratic, "bob", true, BallotParams { credits: 100, lock_periods: 0 }).unwrap();
+        cast_ballot(&governance, &identities, conviction, "dave", true, BallotParams { credits: 0, lock_periods: 4 }).unwrap();
+
+        // Bob's 100 credits are spent for Alice too, and the pool of 200 counts its square root;
+        // Carol locked nothing, so she does not inherit Dave's conviction
+        let result = governance.get_result(quadratic).unwrap();
+        assert_eq!(result.weight_for, 14);
+        assert_eq!(result.delegated_votes, 1);
+        assert_eq!(governance.get_result(conviction).unwrap().weight_for, 6);
+    }
+
+    #[test]
+    fn test_delegations_frozen_when_proposal_closes() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_proposal("Increase community funding".to_string(), Duration::new(60, 0)).unwrap();
+
+        delegate(&governance, &identities, "alice", "bob", None).unwrap();
+        cast_vote(&governance, &identities, proposal_id, "bob", true).unwrap();
+        governance.close_proposal(proposal_id).unwrap();
+
+        let signature = identities.lock().unwrap().sign_message("alice", &revocation_message("alice", None)).unwrap();
+        governance.revoke_delegation("alice", None, &signature).unwrap();
+
+        let proposal = governance.get_proposal(proposal_id).unwrap();
+        assert_eq!(proposal.delegations.get("alice").unwrap(), "bob");
+        assert_eq!(governance.get_result(proposal_id).unwrap().weight_for, 2);
+    }
+
+    #[test]
+    fn test_commit_reveal_flow() {
+        let (governance, identities) = setup(&["alice", "bob", "carol"]);
+        let proposal_id = governance.create_commit_reveal_proposal("Private ballot".to_string(), Duration::new(1, 0), Duration::new(2, 0), VotingStrategy::SimpleMajority, None).unwrap();
+
+        commit(&governance, &identities, proposal_id, "alice", true, b"alice-salt-for-reveal").unwrap();
+        commit(&governance, &identities, proposal_id, "bob", false, b"bob-salt-for-reveal").unwrap();
+        commit(&governance, &identities, proposal_id, "carol", true, b"carol-salt-for-reveal").unwrap();
+
+        // Revealing is not possible while commits are still open
+        let result = governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"alice-salt-for-reveal");
+        assert_eq!(result.err().unwrap(), "Reveal phase has not started.");
+
+        sleep(Duration::new(1, 100_000_000));
+        governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"alice-salt-for-reveal").unwrap();
+        governance.reveal_vote(proposal_id, "bob", false, BallotParams::default(), b"bob-salt-for-reveal").unwrap();
+        // Carol never reveals
+
+        // Tallies stay hidden during the reveal window
+        assert_eq!(governance.get_proposal(proposal_id).unwrap().status, ProposalStatus::Revealing);
+        assert!(governance.get_result(proposal_id).is_err());
+        assert!(governance.get_votes(proposal_id).is_err());
+        assert!(governance.get_proposal(proposal_id).unwrap().votes.is_empty());
+
+        sleep(Duration::new(2, 0));
+        let proposal = governance.get_proposal(proposal_id).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Finalized);
+        assert_eq!(proposal.votes_for, 1);
+        assert_eq!(proposal.votes_against, 1);
+        assert!(!proposal.commitments.contains_key("carol"));
+
+        let result = governance.get_result(proposal_id).unwrap();
+        assert_eq!(result.weight_for, 1);
+        assert_eq!(result.weight_against, 1);
+    }
+
+    #[test]
+    fn test_reveal_must_match_commitment() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_commit_reveal_proposal("Private ballot".to_string(), Duration::new(1, 0), Duration::new(60, 0), VotingStrategy::SimpleMajority, None).unwrap();
+
+        commit(&governance, &identities, proposal_id, "alice", true, b"alice-salt-for-reveal").unwrap();
+        sleep(Duration::new(1, 100_000_000));
+
+        let result = governance.reveal_vote(proposal_id, "alice", false, BallotParams::default(), b"alice-salt-for-reveal");
+        assert_eq!(result.err().unwrap(), "Revealed ballot does not match commitment.");
+        let result = governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"wrong-salt-for-reveal");
+        assert_eq!(result.err().unwrap(), "Revealed ballot does not match commitment.");
+
+        governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"alice-salt-for-reveal").unwrap();
+        let result = governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"alice-salt-for-reveal");
+        assert_eq!(result.err().unwrap(), "Ballot has already been revealed.");
+    }
+
+    #[test]
+    fn test_short_salts_and_malformed_commitments_rejected() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_commit_reveal_proposal("Private ballot".to_string(), Duration::new(60, 0), Duration::new(60, 0), VotingStrategy::SimpleMajority, None).unwrap();
+
+        let result = commit(&governance, &identities, proposal_id, "alice", true, b"short");
+        assert_eq!(result.err().unwrap(), "Salt must be at least 16 bytes.");
+
+        let commitment = b"too short to be a hash".to_vec();
+        let signature = identities.lock().unwrap().sign_message("alice", &commit_message(proposal_id, &commitment)).unwrap();
+        let result = governance.commit_vote(proposal_id, "alice", commitment, &signature);
+        assert_eq!(result.err().unwrap(), "Commitment must be a SHA-256 hash.");
+
+        let salt = generate_salt().unwrap();
+        assert_eq!(salt.len(), 16);
+        commit(&governance, &identities, proposal_id, "alice", true, &salt).unwrap();
+    }
+
+    #[test]
+    fn test_revealed_ballot_keeps_commit_signature_separate() {
+        let (governance, identities) = setup(&["alice"]);
+        let proposal_id = governance.create_commit_reveal_proposal("Private ballot".to_string(), Duration::new(1, 0), Duration::new(1, 0), VotingStrategy::SimpleMajority, None).unwrap();
+
+        commit(&governance, &identities, proposal_id, "alice", true, b"alice-salt-for-reveal").unwrap();
+        sleep(Duration::new(1, 100_000_000));
+        governance.reveal_vote(proposal_id, "alice", true, BallotParams::default(), b"alice-salt-for-reveal").unwrap();
+        sleep(Duration::new(1, 0));
+
+        // The commit signature verifies against the commitment, not against a ballot message
+        let record = governance.get_vote(proposal_id, "alice").unwrap();
+        assert!(record.signature.is_empty());
+        let commitment = governance.get_proposal(proposal_id).unwrap().commitments["alice"].commitment.clone();
+        let signature = ed25519_dalek::Signature::from_bytes(&record.commit_signature.unwrap()).unwrap();
+        assert!(identities.lock().unwrap().verify_identity("alice", &signature, &commit_message(proposal_id, &commitment)).unwrap());
+    }
+
+    #[test]
+    fn test_commit_reveal_rejects_open_votes_and_late_commits() {
+        let (governance, identities) = setup(&["alice", "bob"]);
+        let proposal_id = governance.create_commit_reveal_proposal("Private ballot".to_string(), Duration::new(1, 0), Duration::new(60, 0), VotingStrategy::SimpleMajority, None).unwrap();
+
+        let result = cast_vote(&governance, &identities, proposal_id, "alice", true);
+        assert_eq!(result.err().unwrap(), "Proposal requires commit-reveal voting.");
+
+        commit(&governance, &identities, proposal_id, "alice", true, b"salt-for-the-commit").unwrap();
+        let result = commit(&governance, &identities, proposal_id, "alice", false, b"salt-for-the-commit");
+        assert_eq!(result.err().unwrap(), "Voter has already committed on this proposal.");
+
+        sleep(Duration::new(1, 100_000_000));
+        let result = commit(&governance, &identities, proposal_id, "bob", true, b"salt-for-the-commit");
+        assert_eq!(result.err().unwrap(), "Commit phase has ended.");
+    }
+}
@@ -1,4 +1,8 @@
 // mod.rs
 
 pub mod governance;
+pub mod commit_reveal;
+pub mod delegation;
+pub mod persistence;
+pub mod strategy;
 pub mod governance_tests;
@@ -0,0 +1,46 @@
+use std::path::Path;
+use log::info;
+use serde::{Deserialize, Serialize};
+use super::delegation::DelegationRegistry;
+use super::governance::Proposal;
+use crate::utils::json::JsonFileStore;
+
+/// Everything governance saves between restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GovernanceState {
+    pub proposals: Vec<Proposal>,
+    #[serde(default)]
+    pub delegations: DelegationRegistry,
+}
+
+/// Trait defining where governance proposals and delegations are saved between restarts
+pub trait ProposalStore {
+    fn load(&self) -> Result<GovernanceState, String>;
+    fn save(&self, state: &GovernanceState) -> Result<(), String>;
+}
+
+/// Stores the full proposal set as a JSON document on disk
+pub struct JsonFileProposalStore {
+    file: JsonFileStore<GovernanceState>,
+}
+
+impl JsonFileProposalStore {
+    /// Creates a new store backed by the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self { file: JsonFileStore::new("proposal store", path) }
+    }
+}
+
+impl ProposalStore for JsonFileProposalStore {
+    /// Loads all proposals and delegations, returning an empty state if the file does not exist yet
+    fn load(&self) -> Result<GovernanceState, String> {
+        Ok(self.file.load()?.unwrap_or_default())
+    }
+
+    /// Saves all proposals and delegations, replacing the file only once the new one is written
+    fn save(&self, state: &GovernanceState) -> Result<(), String> {
+        self.file.save(state)?;
+        info!("Saved {} proposals to {}", state.proposals.len(), self.file.path().display());
+        Ok(())
+    }
+}
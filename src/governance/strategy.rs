@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::governance::VoteRecord;
+
+/// How the ballots on a proposal are weighted and what it takes for the proposal to pass.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum VotingStrategy {
+    /// One vote per voter; passes when more voters are for than against.
+    SimpleMajority,
+    /// One vote per voter; passes when at least `threshold_percent` of votes cast are for.
+    Supermajority { threshold_percent: u32 },
+    /// Voters spend credits and receive the square root of the credits spent as votes.
+    ///
+    /// `credit_budget` caps what a voter spends across all quadratic proposals within one
+    /// credit period, not per ballot. A delegate's ballot spends its credits once for the
+    /// delegate and once for each delegator, and counts the square root of that pool.
+    Quadratic { credit_budget: u64 },
+    /// Each ballot is weighted by `1 + lock_periods`, where each period locks the vote for `lock_period`.
+    ///
+    /// While a lock runs, the voter cannot cast another conviction ballot.
+    Conviction { lock_period: Duration, max_lock_periods: u32 },
+}
+
+/// Length of the period over which quadratic credits are budgeted, unless configured otherwise.
+pub const DEFAULT_CREDIT_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+impl Default for VotingStrategy {
+    fn default() -> Self {
+        VotingStrategy::SimpleMajority
+    }
+}
+
+/// Optional ballot parameters used by the weighted strategies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BallotParams {
+    /// Credits spent under quadratic voting.
+    pub credits: u64,
+    /// Number of lock periods committed under conviction voting.
+    pub lock_periods: u32,
+}
+
+/// Outcome of a proposal under its voting strategy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TallyResult {
+    pub strategy: VotingStrategy,
+    pub weight_for: u64,
+    pub weight_against: u64,
+    pub delegated_votes: u64,
+    pub passed: bool,
+    pub explanation: String,
+}
+
+impl VotingStrategy {
+    /// Checks the strategy's own settings before a proposal is created with it.
+    pub fn validate_settings(&self) -> Result<(), String> {
+        match self {
+            VotingStrategy::Supermajority { threshold_percent } if *threshold_percent > 100 => {
+                Err(format!("Supermajority threshold of {}% exceeds 100%.", threshold_percent))
+            }
+            VotingStrategy::Quadratic { credit_budget: 0 } => Err("Quadratic voting needs a credit budget.".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the ballot parameters are allowed under this strategy.
+    pub fn validate(&self, params: &BallotParams) -> Result<(), String> {
+        match self {
+            VotingStrategy::SimpleMajority | VotingStrategy::Supermajority { .. } => {
+                if *params != BallotParams::default() {
+                    return Err("Ballot options are not supported by this voting strategy.".to_string());
+                }
+                Ok(())
+            }
+            VotingStrategy::Quadratic { credit_budget } => {
+                if params.lock_periods != 0 {
+                    return Err("Lock periods are not supported by quadratic voting.".to_string());
+                }
+                if params.credits == 0 {
+                    return Err("Quadratic votes must spend at least one credit.".to_string());
+                }
+                if params.credits > *credit_budget {
+                    return Err(format!("Credits spent exceed the budget of {}.", credit_budget));
+                }
+                Ok(())
+            }
+            VotingStrategy::Conviction { max_lock_periods, .. } => {
+                if params.credits != 0 {
+                    return Err("Credits are not supported by conviction voting.".to_string());
+                }
+                if params.lock_periods > *max_lock_periods {
+                    return Err(format!("Lock periods exceed the maximum of {}.", max_lock_periods));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the voting weight a single ballot carries under this strategy.
+    pub fn weight(&self, params: &BallotParams) -> u64 {
+        match self {
+            VotingStrategy::SimpleMajority | VotingStrategy::Supermajority { .. } => 1,
+            VotingStrategy::Quadratic { .. } => integer_sqrt(params.credits),
+            VotingStrategy::Conviction { .. } => 1 + params.lock_periods as u64,
+        }
+    }
+
+    /// Returns when a conviction vote's lock ends, or `None` for strategies without locks.
+    ///
+    /// A lock too long to represent never ends.
+    pub fn lock_expiry(&self, cast_at: DateTime<Utc>, params: &BallotParams) -> Option<DateTime<Utc>> {
+        match self {
+            VotingStrategy::Conviction { lock_period, .. } => Some(
+                lock_period
+                    .checked_mul(params.lock_periods)
+                    .and_then(|lock| chrono::Duration::from_std(lock).ok())
+                    .and_then(|lock| cast_at.checked_add_signed(lock))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Computes the weighted result of the given ballots and explains how it was reached.
+    ///
+    /// `delegated` counts, per direct voter, how many delegators' power flows to that voter.
+    /// Under quadratic voting the ballot's credits are pooled across the voter and its
+    /// delegators, so `n` delegators turn `c` credits into `sqrt((n + 1) * c)` votes rather
+    /// than `(n + 1) * sqrt(c)`. Under the other strategies each delegator adds the weight of a
+    /// ballot without options, since delegators do not lock their vote.
+    pub fn tally<'a, I>(&self, votes: I, delegated: &HashMap<String, u64>) -> TallyResult
+    where
+        I: IntoIterator<Item = &'a VoteRecord>,
+    {
+        let mut weight_for = 0u64;
+        let mut weight_against = 0u64;
+        let mut delegated_votes = 0u64;
+        let delegator_weight = self.weight(&BallotParams::default());
+        for vote in votes {
+            let followers = delegated.get(&vote.voter_id).copied().unwrap_or(0);
+            delegated_votes += followers;
+            let weight = match self {
+                VotingStrategy::Quadratic { .. } => integer_sqrt(vote.params.credits.saturating_mul(followers.saturating_add(1))),
+                _ => self.weight(&vote.params).saturating_add(delegator_weight.saturating_mul(followers)),
+            };
+            if vote.vote_for {
+                weight_for = weight_for.saturating_add(weight);
+            } else {
+                weight_against = weight_against.saturating_add(weight);
+            }
+        }
+
+        let total = weight_for.saturating_add(weight_against);
+        let (passed, explanation) = match self {
+            VotingStrategy::SimpleMajority => (
+                weight_for > weight_against,
+                format!(
+                    "Simple majority: {} for vs {} against; more votes for than against are required.",
+                    weight_for, weight_against
+                ),
+            ),
+            VotingStrategy::Supermajority { threshold_percent } => (
+                total > 0 && weight_for as u128 * 100 >= *threshold_percent as u128 * total as u128,
+                format!(
+                    "Supermajority: {} of {} votes for; at least {}% are required.",
+                    weight_for, total, threshold_percent
+                ),
+            ),
+            VotingStrategy::Quadratic { credit_budget } => (
+                weight_for > weight_against,
+                format!(
+                    "Quadratic voting: {} votes for vs {} against, each ballot counting the square root of the credits spent for its voter and delegators (budget {}).",
+                    weight_for, weight_against, credit_budget
+                ),
+            ),
+            VotingStrategy::Conviction { max_lock_periods, .. } => (
+                weight_for > weight_against,
+                format!(
+                    "Conviction voting: {} weighted votes for vs {} against, each ballot weighted 1 + lock periods (max {}).",
+                    weight_for, weight_against, max_lock_periods
+                ),
+            ),
+        };
+
+        let explanation = if delegated_votes > 0 {
+            format!("{} Includes {} delegated ballots.", explanation, delegated_votes)
+        } else {
+            explanation
+        };
+
+        TallyResult {
+            strategy: *self,
+            weight_for,
+            weight_against,
+            delegated_votes,
+            passed,
+            explanation,
+        }
+    }
+}
+
+/// Returns the index of the credit period containing `at`; periods are counted from the Unix epoch.
+pub fn credit_period(at: DateTime<Utc>, period: Duration) -> i64 {
+    let length = period.as_secs().clamp(1, i64::MAX as u64) as i64;
+    at.timestamp().div_euclid(length)
+}
+
+/// Largest integer whose square does not exceed `n`.
+fn integer_sqrt(n: u64) -> u64 {
+    // Squares are taken in u128 so roots near 2^32 cannot overflow
+    let square = |root: u64| root as u128 * root as u128;
+    let mut root = (n as f64).sqrt() as u64;
+    while square(root) > n as u128 {
+        root -= 1;
+    }
+    while square(root + 1) <= n as u128 {
+        root += 1;
+    }
+    root
+}
@@ -1,8 +1,18 @@
 mod lib;
+mod bridge;
+mod cross_chain;
+mod dashboard;
 mod models;
+mod security;
 mod utils;
 mod tools;
+#[cfg(test)]
+#[path = "tests/fixtures.rs"]
+mod fixtures;
 
+use std::env;
+use cross_chain::network;
+use dashboard::web_dashboard::run_web_dashboard;
 use lib::PiStellarNexusCore;
 use models::stellar::StellarProtocol;
 use models::pi::PiNetwork;
@@ -13,11 +23,22 @@ use tools::pi_tool::PiTool;
 use log::{info, error};
 use simplelog::{Config, LevelFilter, SimpleLogger};
 
-fn main() {
+#[tokio::main]
+async fn main() {
     // Initialize logging
     SimpleLogger::init(LevelFilter::Info, Config::default()).unwrap();
     info!("Starting PiStellar Nexus Core application...");
 
+    // Select the network from `--network`, `PISTELLAR_NETWORK` or the registry's default
+    let network = match network::load_selected(env::args()) {
+        Ok(network) => network,
+        Err(e) => {
+            error!("Failed to load network configuration: {}", e);
+            return;
+        }
+    };
+    info!("Using the {} network", network.name);
+
     // Initialize the PiStellar Nexus Core application
     let mut app = match PiStellarNexusCore::new() {
         Ok(app) => app,
@@ -84,7 +105,12 @@ fn main() {
     // Start the PiStellar Nexus Core application
     if let Err(e) = app.start(stellar_protocol, pi_network, crypto_utils, network_utils, stellar_tool, pi_tool) {
         error!("Application failed to start: {}", e);
-    } else {
-        info!("PiStellar Nexus Core application started successfully.");
+        return;
     }
-        }
+    info!("PiStellar Nexus Core application started successfully.");
+
+    // Serve the dashboard on the selected network's address
+    if let Err(e) = run_web_dashboard(&network.bind_address).await {
+        error!("Web dashboard failed: {}", e);
+    }
+}
@@ -0,0 +1,4 @@
+// mod.rs
+
+pub mod pi;
+pub mod stellar;
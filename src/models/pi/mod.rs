@@ -0,0 +1,5 @@
+// mod.rs
+
+pub mod pi_network;
+
+pub use pi_network::{PiLedgerError, PiNetwork, PiNetworkImpl, PiTransfer, SignedPiTransfer};
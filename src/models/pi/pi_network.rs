@@ -1,19 +1,173 @@
+use std::collections::HashMap;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use log::info;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::models::stellar::strkey;
+
+/// Chain ID the model validates against unless created with another.
+pub const DEFAULT_CHAIN_ID: &str = "pi-testnet-1";
+
+/// Minimum fee per transfer, in the smallest Pi unit (0.01 PI).
+pub const MIN_FEE: u64 = 1_000_000;
+
+// Prefix of the signed bytes, so transfer signatures cannot be reused in another protocol
+const DOMAIN: &[u8] = b"pi-network/transfer/v1";
+
+/// Errors returned when a transfer cannot be applied to the ledger.
+#[derive(Debug, Error, PartialEq)]
+pub enum PiLedgerError {
+    #[error("Invalid transfer encoding: {0}")]
+    Decode(String),
+
+    #[error("Transfer is for chain {found}, not {expected}")]
+    WrongChain { expected: String, found: String },
+
+    #[error("Invalid address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error("Transfer is not signed by {0}")]
+    BadSignature(String),
+
+    #[error("Account {0} does not exist")]
+    AccountNotFound(String),
+
+    #[error("Bad nonce: expected {expected}, got {found}")]
+    BadNonce { expected: u64, found: u64 },
+
+    #[error("Fee of {offered} is below the minimum of {required}")]
+    InsufficientFee { required: u64, offered: u64 },
+
+    #[error("{account} has {available} available, {needed} needed")]
+    InsufficientBalance { account: String, needed: u64, available: u64 },
+
+    #[error("Invalid transfer: {0}")]
+    Invalid(String),
+}
+
+/// A transfer of PI between two accounts. Amounts and fees are in the smallest Pi unit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PiTransfer {
+    pub chain_id: String,
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    pub fee: u64,
+    /// One more than the number of transfers the sender has made.
+    pub nonce: u64,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+impl PiTransfer {
+    /// Bytes signed by the sender: the domain tag followed by every field, length-prefixed.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = DOMAIN.to_vec();
+        let memo = self.memo.as_deref().unwrap_or("");
+        for field in [self.chain_id.as_str(), self.from.as_str(), self.to.as_str(), memo] {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        for value in [self.amount, self.fee, self.nonce] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Hex SHA-256 of the signed bytes, used as the transaction ID.
+    pub fn id(&self) -> String {
+        hex::encode(digest(&SHA256, &self.signing_bytes()))
+    }
+
+    pub fn sign(self, keypair: &Keypair) -> SignedPiTransfer {
+        let signature = keypair.sign(&self.signing_bytes()).to_bytes().to_vec();
+        SignedPiTransfer { transfer: self, signature }
+    }
+}
+
+/// A transfer with the sender's ed25519 signature; its JSON is the byte-level form.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedPiTransfer {
+    pub transfer: PiTransfer,
+    pub signature: Vec<u8>,
+}
+
+impl SignedPiTransfer {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("transfers serialize to JSON")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PiLedgerError> {
+        serde_json::from_slice(bytes).map_err(|e| PiLedgerError::Decode(e.to_string()))
+    }
+}
+
 /// Trait defining the Pi Network interface
 pub trait PiNetwork {
     fn new() -> Self;
     fn get_pi_data(&self) -> Vec<u8>;
-    fn process_pi_transaction(&mut self, transaction: Vec<u8>) -> Result<(), String>;
+
+    /// Validates a signed transfer against the ledger and applies it, returning its ID.
+    fn apply_transaction(&mut self, transaction: &SignedPiTransfer) -> Result<String, PiLedgerError>;
+
+    /// Decodes a `SignedPiTransfer` from its JSON bytes and applies it.
+    fn process_pi_transaction(&mut self, transaction: Vec<u8>) -> Result<String, PiLedgerError> {
+        let transfer = SignedPiTransfer::from_bytes(&transaction)?;
+        self.apply_transaction(&transfer)
+    }
+}
+
+/// An account's state in the ledger.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PiAccountState {
+    pub balance: u64,
+    /// Number of transfers the account has sent.
+    pub nonce: u64,
 }
 
 /// Implementation of the Pi Network
+///
+/// Keeps account balances and nonces. A transfer applies only if it is for this chain, signed
+/// by the sender's key, carries the sender's next nonce and at least the minimum fee, and the
+/// sender can cover the amount plus fee. Recipients are created on their first incoming transfer.
 pub struct PiNetworkImpl {
+    chain_id: String,
+    accounts: HashMap<String, PiAccountState>,
+    fee_pool: u64,
     pi_data: Vec<u8>,
 }
 
 impl PiNetworkImpl {
-    /// Creates a new instance of PiNetworkImpl
+    /// Creates a new instance of PiNetworkImpl for the test network
     pub fn new() -> Self {
-        Self { pi_data: vec![] }
+        Self::with_chain_id(DEFAULT_CHAIN_ID)
+    }
+
+    /// Creates an empty ledger that accepts transfers for `chain_id`.
+    pub fn with_chain_id(chain_id: &str) -> Self {
+        Self { chain_id: chain_id.to_string(), accounts: HashMap::new(), fee_pool: 0, pi_data: vec![] }
+    }
+
+    /// Credits an account outside of any transfer, as at genesis.
+    pub fn fund_account(&mut self, address: &str, amount: u64) -> Result<(), PiLedgerError> {
+        let account = self.accounts.entry(address.to_string()).or_default();
+        account.balance = account.balance.checked_add(amount)
+            .ok_or_else(|| PiLedgerError::Invalid(format!("balance of {} overflows", address)))?;
+        Ok(())
+    }
+
+    pub fn account(&self, address: &str) -> Option<&PiAccountState> {
+        self.accounts.get(address)
+    }
+
+    pub fn balance(&self, address: &str) -> u64 {
+        self.accounts.get(address).map_or(0, |account| account.balance)
+    }
+
+    /// Fees collected from applied transfers.
+    pub fn fee_pool(&self) -> u64 {
+        self.fee_pool
     }
 
     /// Adds data to the Pi Network
@@ -23,25 +177,70 @@ impl PiNetworkImpl {
 }
 
 impl PiNetwork for PiNetworkImpl {
-    /// Retrieves the current Pi data
+    fn new() -> Self {
+        PiNetworkImpl::new()
+    }
+
+    /// Retrieves the encoded transfers applied so far
     fn get_pi_data(&self) -> Vec<u8> {
         self.pi_data.clone()
     }
 
-    /// Processes a Pi transaction
-    fn process_pi_transaction(&mut self, transaction: Vec<u8>) -> Result<(), String> {
-        // Validate the transaction data
-        if transaction.is_empty() {
-            return Err("Transaction data cannot be empty.".to_string());
+    fn apply_transaction(&mut self, transaction: &SignedPiTransfer) -> Result<String, PiLedgerError> {
+        let transfer = &transaction.transfer;
+        if transfer.chain_id != self.chain_id {
+            return Err(PiLedgerError::WrongChain { expected: self.chain_id.clone(), found: transfer.chain_id.clone() });
+        }
+        let sender = public_key(&transfer.from)?;
+        public_key(&transfer.to)?;
+        if transfer.amount == 0 {
+            return Err(PiLedgerError::Invalid("amount must be positive".to_string()));
+        }
+        if transfer.from == transfer.to {
+            return Err(PiLedgerError::Invalid("sender and recipient are the same".to_string()));
+        }
+        let signed = Signature::from_bytes(&transaction.signature)
+            .map(|signature| sender.verify(&transfer.signing_bytes(), &signature).is_ok())
+            .unwrap_or(false);
+        if !signed {
+            return Err(PiLedgerError::BadSignature(transfer.from.clone()));
         }
 
-        // Process the Pi transaction (placeholder for actual logic)
-        println!("Processing Pi transaction: {:?}", transaction);
-        
-        // Here you could add logic to update the state of the Pi Network
-        self.add_pi_data(transaction); // Example of adding transaction data to the network
+        let account = self.accounts.get(&transfer.from).ok_or_else(|| PiLedgerError::AccountNotFound(transfer.from.clone()))?;
+        let expected_nonce = account.nonce.checked_add(1)
+            .ok_or_else(|| PiLedgerError::Invalid(format!("nonce of {} is exhausted", transfer.from)))?;
+        if transfer.nonce != expected_nonce {
+            return Err(PiLedgerError::BadNonce { expected: expected_nonce, found: transfer.nonce });
+        }
+        if transfer.fee < MIN_FEE {
+            return Err(PiLedgerError::InsufficientFee { required: MIN_FEE, offered: transfer.fee });
+        }
+        let needed = transfer.amount.checked_add(transfer.fee)
+            .ok_or_else(|| PiLedgerError::Invalid("amount plus fee overflows".to_string()))?;
+        if account.balance < needed {
+            return Err(PiLedgerError::InsufficientBalance { account: transfer.from.clone(), needed, available: account.balance });
+        }
+        let recipient_balance = self.balance(&transfer.to).checked_add(transfer.amount)
+            .ok_or_else(|| PiLedgerError::Invalid("recipient balance overflows".to_string()))?;
+        let fee_pool = self.fee_pool.checked_add(transfer.fee)
+            .ok_or_else(|| PiLedgerError::Invalid("fee pool overflows".to_string()))?;
 
-        println!("Pi transaction processed successfully!");
-        Ok(())
-    }
+        let account = self.accounts.get_mut(&transfer.from).expect("sender account exists");
+        account.balance -= needed;
+        account.nonce = transfer.nonce;
+        self.accounts.entry(transfer.to.clone()).or_default().balance = recipient_balance;
+        self.fee_pool = fee_pool;
+        self.add_pi_data(transaction.to_bytes());
+
+        let id = transfer.id();
+        info!("Applied Pi transfer {} of {} from {} to {}", id, transfer.amount, transfer.from, transfer.to);
+        Ok(id)
     }
+}
+
+// Pi accounts use Stellar-style `G...` addresses encoding their ed25519 key
+fn public_key(address: &str) -> Result<PublicKey, PiLedgerError> {
+    let invalid = |reason: String| PiLedgerError::InvalidAddress { address: address.to_string(), reason };
+    let key = strkey::decode_account_id(address).map_err(|e| invalid(e.to_string()))?;
+    PublicKey::from_bytes(&key).map_err(|e| invalid(e.to_string()))
+}
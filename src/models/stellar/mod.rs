@@ -0,0 +1,8 @@
+// mod.rs
+
+pub mod stellar_protocol;
+pub mod strkey;
+pub mod transaction;
+pub mod xdr;
+
+pub use stellar_protocol::{LedgerError, StellarProtocol, StellarProtocolImpl};
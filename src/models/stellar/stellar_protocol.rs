@@ -1,47 +1,500 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ed25519_dalek::PublicKey;
+use log::info;
+use thiserror::Error;
+use super::transaction::{
+    AccountId, Asset, Network, Operation, OperationBody, TransactionEnvelope, AUTHORIZED_FLAG, AUTHORIZED_TO_MAINTAIN_LIABILITIES_FLAG,
+    AUTH_CLAWBACK_ENABLED_FLAG, AUTH_IMMUTABLE_FLAG, AUTH_REQUIRED_FLAG, AUTH_REVOCABLE_FLAG, BASE_FEE, TRUSTLINE_CLAWBACK_ENABLED_FLAG,
+};
+use super::xdr::{XdrDecode, XdrEncode, XdrError};
+
+/// Reserve held per ledger entry, in stroops; an account's minimum balance is two reserves plus
+/// one per trustline.
+pub const BASE_RESERVE: i64 = 5_000_000;
+
+/// Errors returned when a transaction cannot be applied to the ledger.
+#[derive(Debug, Error, PartialEq)]
+pub enum LedgerError {
+    #[error("Invalid transaction XDR: {0}")]
+    Decode(#[from] XdrError),
+
+    #[error("Account {0} does not exist")]
+    AccountNotFound(String),
+
+    #[error("Account {0} already exists")]
+    AccountExists(String),
+
+    #[error("Transaction is missing a signature from {0}")]
+    MissingSignature(String),
+
+    #[error("Bad sequence number: expected {expected}, got {found}")]
+    BadSequence { expected: i64, found: i64 },
+
+    #[error("Fee of {offered} stroops is below the minimum of {required}")]
+    InsufficientFee { required: i64, offered: i64 },
+
+    #[error("Transaction is outside its time bounds")]
+    OutsideTimeBounds,
+
+    #[error("{account} has {available} {asset} available, {needed} needed")]
+    InsufficientBalance { account: String, asset: String, needed: i64, available: i64 },
+
+    #[error("{account} has no trustline for {asset}")]
+    NoTrustline { account: String, asset: String },
+
+    #[error("Trustline of {account} for {asset} would exceed its limit")]
+    LineFull { account: String, asset: String },
+
+    #[error("Trustline of {account} for {asset} still holds a balance")]
+    TrustlineNotEmpty { account: String, asset: String },
+
+    #[error("{account} is not authorized to hold {asset}")]
+    NotAuthorized { account: String, asset: String },
+
+    #[error("Not allowed: {0}")]
+    NotAllowed(String),
+
+    #[error("Starting balance must be at least {0} stroops")]
+    LowReserve(i64),
+
+    #[error("Malformed transaction: {0}")]
+    Malformed(String),
+
+    #[error("{0} overflows")]
+    Overflow(&'static str),
+
+    #[error("{0} operations are not supported")]
+    Unsupported(&'static str),
+
+    #[error("Operation {index} failed: {error}")]
+    OperationFailed { index: usize, error: Box<LedgerError> },
+}
+
 /// Trait defining the Stellar Protocol interface
 pub trait StellarProtocol {
     fn new() -> Self;
     fn get_stellar_data(&self) -> Vec<u8>;
-    fn process_stellar_transaction(&mut self, transaction: Vec<u8>) -> Result<(), String>;
+
+    /// Validates a signed transaction against the ledger and applies it, returning its hash.
+    fn apply_transaction(&mut self, envelope: &TransactionEnvelope) -> Result<[u8; 32], LedgerError>;
+
+    /// Decodes a `TransactionEnvelope` from XDR bytes and applies it.
+    fn process_stellar_transaction(&mut self, transaction: Vec<u8>) -> Result<[u8; 32], LedgerError> {
+        let envelope = TransactionEnvelope::from_xdr(&transaction)?;
+        self.apply_transaction(&envelope)
+    }
+}
+
+/// A holding of a credit asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trustline {
+    pub balance: i64,
+    pub limit: i64,
+    /// `AUTHORIZED_FLAG` and the other trustline flags.
+    pub flags: u32,
+}
+
+impl Trustline {
+    /// Whether the holder may send and receive the asset.
+    pub fn is_authorized(&self) -> bool {
+        self.flags & AUTHORIZED_FLAG != 0
+    }
+}
+
+/// An account's state in the ledger. Balances are in stroops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerAccount {
+    pub sequence: i64,
+    pub balance: i64,
+    pub trustlines: HashMap<Asset, Trustline>,
+    /// `AUTH_REQUIRED_FLAG` and the other account flags.
+    pub flags: u32,
+}
+
+impl LedgerAccount {
+    /// The native balance the account must keep.
+    pub fn minimum_balance(&self) -> i64 {
+        (2 + self.trustlines.len() as i64) * BASE_RESERVE
+    }
 }
 
 /// Implementation of the Stellar Protocol
+///
+/// Maintains account state and applies transactions the way a validator would: the source
+/// account must exist and sign, the sequence number must follow the account's, the fee must
+/// cover every operation, and balances may not drop below the reserve. As on the network, a
+/// transaction whose operations fail still pays its fee and uses its sequence number, but none
+/// of its operations take effect. Credit assets only move over authorized trustlines, so an
+/// issuer with `AUTH_REQUIRED_FLAG` decides who may hold its assets.
 pub struct StellarProtocolImpl {
+    network: Network,
+    accounts: HashMap<AccountId, LedgerAccount>,
+    ledger_sequence: u32,
+    close_time: Option<u64>,
+    fee_pool: i64,
     stellar_data: Vec<u8>,
 }
 
 impl StellarProtocolImpl {
-    /// Creates a new instance of StellarProtocolImpl
+    /// Creates a new instance of StellarProtocolImpl for the test network
     pub fn new() -> Self {
-        Self { stellar_data: vec![] }
+        Self::with_network(Network::testnet())
+    }
+
+    /// Creates an empty ledger for `network`, whose passphrase signatures are checked against.
+    pub fn with_network(network: Network) -> Self {
+        Self {
+            network,
+            accounts: HashMap::new(),
+            ledger_sequence: 1,
+            close_time: None,
+            fee_pool: 0,
+            stellar_data: vec![],
+        }
+    }
+
+    /// Creates an account with a native balance outside of any transaction, as at genesis.
+    pub fn fund_account(&mut self, account_id: AccountId, balance: i64) -> Result<(), LedgerError> {
+        if self.accounts.contains_key(&account_id) {
+            return Err(LedgerError::AccountExists(account_id.to_strkey()));
+        }
+        if balance < 0 {
+            return Err(LedgerError::Malformed("starting balance is negative".to_string()));
+        }
+        let sequence = (self.ledger_sequence as i64) << 32;
+        self.accounts.insert(account_id, LedgerAccount { sequence, balance, trustlines: HashMap::new(), flags: 0 });
+        Ok(())
+    }
+
+    /// Fixes the close time checked against time bounds; the system clock is used otherwise.
+    pub fn set_close_time(&mut self, close_time: u64) {
+        self.close_time = Some(close_time);
+    }
+
+    pub fn account(&self, account_id: &AccountId) -> Option<&LedgerAccount> {
+        self.accounts.get(account_id)
+    }
+
+    /// Balance of `asset` held by an account, or zero without an account or trustline.
+    pub fn balance(&self, account_id: &AccountId, asset: &Asset) -> i64 {
+        match (self.accounts.get(account_id), asset) {
+            (None, _) => 0,
+            (Some(account), Asset::Native) => account.balance,
+            (Some(account), _) => account.trustlines.get(asset).map_or(0, |line| line.balance),
+        }
+    }
+
+    /// Fees collected from applied transactions.
+    pub fn fee_pool(&self) -> i64 {
+        self.fee_pool
+    }
+
+    /// Sequence number of the last closed ledger; each applied transaction closes one.
+    pub fn ledger_sequence(&self) -> u32 {
+        self.ledger_sequence
     }
 
     /// Adds data to the Stellar Protocol
     pub fn add_stellar_data(&mut self, data: Vec<u8>) {
         self.stellar_data.extend(data);
     }
+
+    fn now(&self) -> u64 {
+        self.close_time.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+    }
+
+    fn check_signed(&self, envelope: &TransactionEnvelope, account_id: &AccountId) -> Result<(), LedgerError> {
+        let signed = PublicKey::from_bytes(&account_id.0)
+            .map(|public_key| envelope.is_signed_by(&self.network, &public_key))
+            .unwrap_or(false);
+        if signed { Ok(()) } else { Err(LedgerError::MissingSignature(account_id.to_strkey())) }
+    }
 }
 
 impl StellarProtocol for StellarProtocolImpl {
-    /// Retrieves the current Stellar data
+    fn new() -> Self {
+        StellarProtocolImpl::new()
+    }
+
+    /// Retrieves the XDR of every transaction applied so far
     fn get_stellar_data(&self) -> Vec<u8> {
         self.stellar_data.clone()
     }
 
-    /// Processes a Stellar transaction
-    fn process_stellar_transaction(&mut self, transaction: Vec<u8>) -> Result<(), String> {
-        // Validate the transaction data
-        if transaction.is_empty() {
-            return Err("Transaction data cannot be empty.".to_string());
+    fn apply_transaction(&mut self, envelope: &TransactionEnvelope) -> Result<[u8; 32], LedgerError> {
+        let tx = &envelope.tx;
+        let source_id = tx.source_account.account_id();
+        let source = self.accounts.get(&source_id).ok_or_else(|| LedgerError::AccountNotFound(source_id.to_strkey()))?;
+
+        if tx.operations.is_empty() {
+            return Err(LedgerError::Malformed("no operations".to_string()));
+        }
+        if let Some(bounds) = &tx.time_bounds {
+            let now = self.now();
+            if now < bounds.min_time || (bounds.max_time != 0 && now > bounds.max_time) {
+                return Err(LedgerError::OutsideTimeBounds);
+            }
+        }
+        let fee = BASE_FEE as i64 * tx.operations.len() as i64;
+        if (tx.fee as i64) < fee {
+            return Err(LedgerError::InsufficientFee { required: fee, offered: tx.fee as i64 });
+        }
+        let expected_sequence = source.sequence.checked_add(1).ok_or(LedgerError::Overflow("Sequence number"))?;
+        if tx.seq_num != expected_sequence {
+            return Err(LedgerError::BadSequence { expected: expected_sequence, found: tx.seq_num });
+        }
+        if source.balance - fee < source.minimum_balance() {
+            return Err(LedgerError::InsufficientBalance {
+                account: source_id.to_strkey(),
+                asset: "XLM".to_string(),
+                needed: fee,
+                available: source.balance - source.minimum_balance(),
+            });
+        }
+        self.check_signed(envelope, &source_id)?;
+        for operation in &tx.operations {
+            if let Some(op_source) = &operation.source_account {
+                self.check_signed(envelope, &op_source.account_id())?;
+            }
         }
 
-        // Process the Stellar transaction (placeholder for actual logic)
-        println!("Processing Stellar transaction: {:?}", transaction);
-        
-        // Here you could add logic to update the state of the Stellar Protocol
-        self.add_stellar_data(transaction); // Example of adding transaction data to the protocol
+        let fee_pool = self.fee_pool.checked_add(fee).ok_or(LedgerError::Overflow("Fee pool"))?;
+        let ledger_sequence = self.ledger_sequence.checked_add(1).ok_or(LedgerError::Overflow("Ledger sequence"))?;
 
-        println!("Stellar transaction processed successfully!");
-        Ok(())
+        // The fee and sequence number are spent even if an operation fails
+        let source = self.accounts.get_mut(&source_id).expect("source account exists");
+        source.balance -= fee;
+        source.sequence = tx.seq_num;
+        self.fee_pool = fee_pool;
+        self.ledger_sequence = ledger_sequence;
+        self.add_stellar_data(envelope.to_xdr());
+
+        // Operations apply to a copy that replaces the ledger only if all of them succeed
+        let mut accounts = self.accounts.clone();
+        for (index, operation) in tx.operations.iter().enumerate() {
+            apply_operation(&mut accounts, &source_id, operation, self.ledger_sequence)
+                .map_err(|error| LedgerError::OperationFailed { index, error: Box::new(error) })?;
+        }
+        self.accounts = accounts;
+
+        let hash = tx.hash(&self.network);
+        info!("Applied Stellar transaction {} from {}", hex::encode(hash), source_id);
+        Ok(hash)
+    }
+}
+
+fn apply_operation(
+    accounts: &mut HashMap<AccountId, LedgerAccount>,
+    tx_source: &AccountId,
+    operation: &Operation,
+    ledger_sequence: u32,
+) -> Result<(), LedgerError> {
+    let source_id = operation.source_account.as_ref().map_or(*tx_source, |source| source.account_id());
+    if !accounts.contains_key(&source_id) {
+        return Err(LedgerError::AccountNotFound(source_id.to_strkey()));
+    }
+
+    match &operation.body {
+        OperationBody::CreateAccount { destination, starting_balance } => {
+            if accounts.contains_key(destination) {
+                return Err(LedgerError::AccountExists(destination.to_strkey()));
+            }
+            if *starting_balance < 2 * BASE_RESERVE {
+                return Err(LedgerError::LowReserve(2 * BASE_RESERVE));
+            }
+            debit(accounts, &source_id, &Asset::Native, *starting_balance)?;
+            let sequence = (ledger_sequence as i64) << 32;
+            accounts.insert(*destination, LedgerAccount { sequence, balance: *starting_balance, trustlines: HashMap::new(), flags: 0 });
+            Ok(())
+        }
+        OperationBody::Payment { destination, asset, amount } => {
+            let destination = destination.account_id();
+            if *amount <= 0 {
+                return Err(LedgerError::Malformed(format!("payment amount {}", amount)));
+            }
+            if !accounts.contains_key(&destination) {
+                return Err(LedgerError::AccountNotFound(destination.to_strkey()));
+            }
+            debit(accounts, &source_id, asset, *amount)?;
+            credit(accounts, &destination, asset, *amount)
+        }
+        OperationBody::ChangeTrust { line, limit } => {
+            let asset = line.code();
+            let issuer = match line.issuer() {
+                Some(issuer) if issuer != source_id && *limit >= 0 => issuer,
+                _ => return Err(LedgerError::Malformed(format!("trustline for {}", asset))),
+            };
+            let issuer_flags = accounts.get(&issuer).ok_or_else(|| LedgerError::AccountNotFound(issuer.to_strkey()))?.flags;
+            let account = accounts.get_mut(&source_id).expect("source account exists");
+            match account.trustlines.get_mut(line) {
+                Some(trustline) if *limit == 0 => {
+                    if trustline.balance != 0 {
+                        return Err(LedgerError::TrustlineNotEmpty { account: source_id.to_strkey(), asset });
+                    }
+                    account.trustlines.remove(line);
+                }
+                Some(trustline) => {
+                    if *limit < trustline.balance {
+                        return Err(LedgerError::LineFull { account: source_id.to_strkey(), asset });
+                    }
+                    trustline.limit = *limit;
+                }
+                None if *limit == 0 => return Err(LedgerError::NoTrustline { account: source_id.to_strkey(), asset }),
+                None => {
+                    // A new trustline raises the reserve the account must hold
+                    if account.balance < account.minimum_balance() + BASE_RESERVE {
+                        return Err(LedgerError::InsufficientBalance {
+                            account: source_id.to_strkey(),
+                            asset: "XLM".to_string(),
+                            needed: BASE_RESERVE,
+                            available: account.balance - account.minimum_balance(),
+                        });
+                    }
+                    // Holders of assets that need authorization wait for the issuer to grant it
+                    let mut flags = if issuer_flags & AUTH_REQUIRED_FLAG == 0 { AUTHORIZED_FLAG } else { 0 };
+                    if issuer_flags & AUTH_CLAWBACK_ENABLED_FLAG != 0 {
+                        flags |= TRUSTLINE_CLAWBACK_ENABLED_FLAG;
+                    }
+                    account.trustlines.insert(line.clone(), Trustline { balance: 0, limit: *limit, flags });
+                }
+            }
+            Ok(())
+        }
+        OperationBody::SetOptions {
+            clear_flags,
+            set_flags,
+            master_weight: None,
+            low_threshold: None,
+            med_threshold: None,
+            high_threshold: None,
+            signer: None,
+            ..
+        } => {
+            let account = accounts.get_mut(&source_id).expect("source account exists");
+            let flags = (account.flags & !clear_flags.unwrap_or(0)) | set_flags.unwrap_or(0);
+            if flags != account.flags && account.flags & AUTH_IMMUTABLE_FLAG != 0 {
+                return Err(LedgerError::NotAllowed(format!("flags of {} are immutable", source_id)));
+            }
+            if flags & AUTH_CLAWBACK_ENABLED_FLAG != 0 && flags & AUTH_REVOCABLE_FLAG == 0 {
+                return Err(LedgerError::NotAllowed("clawback requires revocable authorization".to_string()));
+            }
+            account.flags = flags;
+            Ok(())
+        }
+        OperationBody::SetTrustLineFlags { trustor, asset, clear_flags, set_flags } => {
+            if asset.issuer() != Some(source_id) {
+                return Err(LedgerError::Malformed(format!("{} is not the issuer of {}", source_id, asset.code())));
+            }
+            let revocable = accounts[&source_id].flags & AUTH_REVOCABLE_FLAG != 0;
+            let trustline = trustline_mut(accounts, trustor, asset)?;
+            let flags = (trustline.flags & !clear_flags) | set_flags;
+            let authorization = AUTHORIZED_FLAG | AUTHORIZED_TO_MAINTAIN_LIABILITIES_FLAG;
+            if trustline.flags & !flags & authorization != 0 && !revocable {
+                return Err(LedgerError::NotAllowed(format!("authorization of {} cannot be revoked", asset.code())));
+            }
+            if flags & authorization == authorization {
+                return Err(LedgerError::Malformed("both authorization flags set".to_string()));
+            }
+            trustline.flags = flags;
+            Ok(())
+        }
+        OperationBody::Clawback { asset, from, amount } => {
+            if asset.issuer() != Some(source_id) {
+                return Err(LedgerError::Malformed(format!("{} is not the issuer of {}", source_id, asset.code())));
+            }
+            let from = from.account_id();
+            let trustline = trustline_mut(accounts, &from, asset)?;
+            if trustline.flags & TRUSTLINE_CLAWBACK_ENABLED_FLAG == 0 {
+                return Err(LedgerError::NotAllowed(format!("clawback of {} from {}", asset.code(), from)));
+            }
+            if trustline.balance < *amount {
+                return Err(LedgerError::InsufficientBalance {
+                    account: from.to_strkey(),
+                    asset: asset.code(),
+                    needed: *amount,
+                    available: trustline.balance,
+                });
+            }
+            trustline.balance -= amount;
+            Ok(())
+        }
+        OperationBody::PathPaymentStrictReceive { .. } | OperationBody::PathPaymentStrictSend { .. } => {
+            Err(LedgerError::Unsupported("Path payment"))
+        }
+        OperationBody::ManageSellOffer { .. } | OperationBody::ManageBuyOffer { .. } => {
+            Err(LedgerError::Unsupported("Offer"))
+        }
+        // Only master keys sign in this ledger
+        OperationBody::SetOptions { .. } => Err(LedgerError::Unsupported("Signer and threshold")),
+        OperationBody::AccountMerge { .. } => Err(LedgerError::Unsupported("Account merge")),
+    }
+}
+
+fn trustline_mut<'a>(accounts: &'a mut HashMap<AccountId, LedgerAccount>, account_id: &AccountId, asset: &Asset) -> Result<&'a mut Trustline, LedgerError> {
+    accounts
+        .get_mut(account_id)
+        .ok_or_else(|| LedgerError::AccountNotFound(account_id.to_strkey()))?
+        .trustlines
+        .get_mut(asset)
+        .ok_or_else(|| LedgerError::NoTrustline { account: account_id.to_strkey(), asset: asset.code() })
+}
+
+// Takes `amount` of `asset` from an account; issuers create their own asset from nothing
+fn debit(accounts: &mut HashMap<AccountId, LedgerAccount>, account_id: &AccountId, asset: &Asset, amount: i64) -> Result<(), LedgerError> {
+    if asset.issuer() == Some(*account_id) {
+        return Ok(());
+    }
+    let account = accounts.get_mut(account_id).ok_or_else(|| LedgerError::AccountNotFound(account_id.to_strkey()))?;
+    let (balance, available) = match asset {
+        Asset::Native => {
+            let available = account.balance - account.minimum_balance();
+            (&mut account.balance, available)
+        }
+        _ => match account.trustlines.get_mut(asset) {
+            Some(trustline) if !trustline.is_authorized() => {
+                return Err(LedgerError::NotAuthorized { account: account_id.to_strkey(), asset: asset.code() });
+            }
+            Some(trustline) => {
+                let available = trustline.balance;
+                (&mut trustline.balance, available)
+            }
+            None => return Err(LedgerError::NoTrustline { account: account_id.to_strkey(), asset: asset.code() }),
+        },
+    };
+    if available < amount {
+        return Err(LedgerError::InsufficientBalance {
+            account: account_id.to_strkey(),
+            asset: asset.code(),
+            needed: amount,
+            available,
+        });
+    }
+    *balance -= amount;
+    Ok(())
+}
+
+// Adds `amount` of `asset` to an account; paying an issuer its own asset burns it
+fn credit(accounts: &mut HashMap<AccountId, LedgerAccount>, account_id: &AccountId, asset: &Asset, amount: i64) -> Result<(), LedgerError> {
+    if asset.issuer() == Some(*account_id) {
+        return Ok(());
+    }
+    let account = accounts.get_mut(account_id).ok_or_else(|| LedgerError::AccountNotFound(account_id.to_strkey()))?;
+    let (balance, limit) = match asset {
+        Asset::Native => (&mut account.balance, i64::MAX),
+        _ => match account.trustlines.get_mut(asset) {
+            Some(trustline) if !trustline.is_authorized() => {
+                return Err(LedgerError::NotAuthorized { account: account_id.to_strkey(), asset: asset.code() });
+            }
+            Some(trustline) => (&mut trustline.balance, trustline.limit),
+            None => return Err(LedgerError::NoTrustline { account: account_id.to_strkey(), asset: asset.code() }),
+        },
+    };
+    match balance.checked_add(amount) {
+        Some(total) if total <= limit => {
+            *balance = total;
+            Ok(())
+        }
+        _ => Err(LedgerError::LineFull { account: account_id.to_strkey(), asset: asset.code() }),
     }
 }
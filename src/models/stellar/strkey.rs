@@ -0,0 +1,234 @@
+// StrKey: the base32 text form of Stellar keys and addresses (SEP-23)
+//
+// Self-contained so that it can be shared with the stablecoin crate. Every Stellar address the
+// crates accept is decoded here: `Chain::is_valid_address` checks bridge deposit memos, Horizon
+// requests check the accounts they name, and `AccountId`/`MuxedAccount` parse the addresses that
+// `KeypairEnvelopeBuilder` turns into the keys of the transactions it signs.
+
+use std::fmt;
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const VERSION_ACCOUNT_ID: u8 = 6 << 3; // G
+const VERSION_MUXED_ACCOUNT: u8 = 12 << 3; // M
+const VERSION_SEED: u8 = 18 << 3; // S
+const VERSION_PRE_AUTH_TX: u8 = 19 << 3; // T
+const VERSION_HASH_X: u8 = 23 << 3; // X
+
+/// Errors returned when decoding a StrKey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrKeyError {
+    /// The string contains a character outside the RFC 4648 base32 alphabet.
+    InvalidCharacter(char),
+    /// The string or decoded payload has the wrong length for its key type.
+    InvalidLength(usize),
+    /// The version byte does not name a known key type.
+    UnknownVersion(u8),
+    /// The CRC16 checksum does not match.
+    InvalidChecksum,
+    /// The unused trailing bits are not zero, so the encoding is not canonical.
+    NonCanonical,
+    /// The key decoded fine but is not of the requested type.
+    UnexpectedType { expected: char, found: char },
+}
+
+impl fmt::Display for StrKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrKeyError::InvalidCharacter(c) => write!(f, "Invalid base32 character `{}`", c),
+            StrKeyError::InvalidLength(length) => write!(f, "Invalid key length {}", length),
+            StrKeyError::UnknownVersion(version) => write!(f, "Unknown version byte {}", version),
+            StrKeyError::InvalidChecksum => write!(f, "Invalid checksum"),
+            StrKeyError::NonCanonical => write!(f, "Non-canonical encoding"),
+            StrKeyError::UnexpectedType { expected, found } => {
+                write!(f, "Expected a key starting with {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StrKeyError {}
+
+/// A decoded StrKey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StrKey {
+    /// `G...`: an ed25519 public key identifying an account.
+    AccountId([u8; 32]),
+    /// `M...`: an account multiplexed with a 64-bit ID.
+    MuxedAccount { ed25519: [u8; 32], id: u64 },
+    /// `S...`: an ed25519 secret seed.
+    Seed([u8; 32]),
+    /// `T...`: the hash of a pre-authorized transaction.
+    PreAuthTx([u8; 32]),
+    /// `X...`: the SHA-256 hash of a hash-x signer's preimage.
+    HashX([u8; 32]),
+}
+
+impl StrKey {
+    /// The leading character of the encoded form.
+    pub fn prefix(&self) -> char {
+        match self {
+            StrKey::AccountId(_) => 'G',
+            StrKey::MuxedAccount { .. } => 'M',
+            StrKey::Seed(_) => 'S',
+            StrKey::PreAuthTx(_) => 'T',
+            StrKey::HashX(_) => 'X',
+        }
+    }
+
+    /// Encodes the key as version byte, payload and little-endian CRC16, in base32 without padding.
+    pub fn encode(&self) -> String {
+        let mut data = Vec::with_capacity(43);
+        match self {
+            StrKey::AccountId(key) => {
+                data.push(VERSION_ACCOUNT_ID);
+                data.extend_from_slice(key);
+            }
+            StrKey::MuxedAccount { ed25519, id } => {
+                data.push(VERSION_MUXED_ACCOUNT);
+                data.extend_from_slice(ed25519);
+                data.extend_from_slice(&id.to_be_bytes());
+            }
+            StrKey::Seed(key) => {
+                data.push(VERSION_SEED);
+                data.extend_from_slice(key);
+            }
+            StrKey::PreAuthTx(hash) => {
+                data.push(VERSION_PRE_AUTH_TX);
+                data.extend_from_slice(hash);
+            }
+            StrKey::HashX(hash) => {
+                data.push(VERSION_HASH_X);
+                data.extend_from_slice(hash);
+            }
+        }
+        let checksum = crc16_xmodem(&data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+        base32_encode(&data)
+    }
+
+    /// Decodes any StrKey, verifying its alphabet, length, version and checksum.
+    pub fn decode(encoded: &str) -> Result<Self, StrKeyError> {
+        let data = base32_decode(encoded)?;
+        if data.len() < 3 {
+            return Err(StrKeyError::InvalidLength(encoded.len()));
+        }
+        let (body, checksum) = data.split_at(data.len() - 2);
+        if crc16_xmodem(body).to_le_bytes() != checksum {
+            return Err(StrKeyError::InvalidChecksum);
+        }
+
+        let (version, payload) = (body[0], &body[1..]);
+        let key = |payload: &[u8]| -> Result<[u8; 32], StrKeyError> {
+            payload.try_into().map_err(|_| StrKeyError::InvalidLength(encoded.len()))
+        };
+        match version {
+            VERSION_ACCOUNT_ID => Ok(StrKey::AccountId(key(payload)?)),
+            VERSION_SEED => Ok(StrKey::Seed(key(payload)?)),
+            VERSION_PRE_AUTH_TX => Ok(StrKey::PreAuthTx(key(payload)?)),
+            VERSION_HASH_X => Ok(StrKey::HashX(key(payload)?)),
+            VERSION_MUXED_ACCOUNT => {
+                if payload.len() != 40 {
+                    return Err(StrKeyError::InvalidLength(encoded.len()));
+                }
+                let mut id = [0u8; 8];
+                id.copy_from_slice(&payload[32..]);
+                Ok(StrKey::MuxedAccount { ed25519: key(&payload[..32])?, id: u64::from_be_bytes(id) })
+            }
+            other => Err(StrKeyError::UnknownVersion(other)),
+        }
+    }
+}
+
+impl fmt::Display for StrKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
+
+/// Encodes an account public key as a `G...` address.
+pub fn encode_account_id(key: &[u8; 32]) -> String {
+    StrKey::AccountId(*key).encode()
+}
+
+/// Decodes a `G...` address into its public key.
+pub fn decode_account_id(address: &str) -> Result<[u8; 32], StrKeyError> {
+    match StrKey::decode(address)? {
+        StrKey::AccountId(key) => Ok(key),
+        other => Err(StrKeyError::UnexpectedType { expected: 'G', found: other.prefix() }),
+    }
+}
+
+/// Decodes a `S...` secret seed.
+pub fn decode_seed(seed: &str) -> Result<[u8; 32], StrKeyError> {
+    match StrKey::decode(seed)? {
+        StrKey::Seed(key) => Ok(key),
+        other => Err(StrKeyError::UnexpectedType { expected: 'S', found: other.prefix() }),
+    }
+}
+
+/// Whether `address` is a well-formed `G...` account address.
+pub fn is_valid_account_id(address: &str) -> bool {
+    decode_account_id(address).is_ok()
+}
+
+/// Whether `address` is a well-formed `G...` or `M...` payment destination.
+pub fn is_valid_destination(address: &str) -> bool {
+    matches!(StrKey::decode(address), Ok(StrKey::AccountId(_)) | Ok(StrKey::MuxedAccount { .. }))
+}
+
+/// CRC16-XModem: polynomial 0x1021, initial value 0.
+pub fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Result<Vec<u8>, StrKeyError> {
+    // A trailing group of 5 or more bits would be a whole unused symbol
+    if (encoded.len() * 5) % 8 >= 5 {
+        return Err(StrKeyError::InvalidLength(encoded.len()));
+    }
+    let mut data = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.chars() {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(StrKeyError::InvalidCharacter(c))? as u32;
+        buffer = ((buffer << 5) | value) & 0xffff;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+        }
+    }
+    if buffer & ((1 << bits) - 1) != 0 {
+        return Err(StrKeyError::NonCanonical);
+    }
+    Ok(data)
+}
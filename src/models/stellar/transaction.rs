@@ -0,0 +1,1394 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer as _, Verifier};
+use ring::digest::{digest, SHA256};
+use thiserror::Error;
+use super::strkey::{self, StrKey, StrKeyError};
+use super::xdr::{XdrDecode, XdrEncode, XdrError, XdrReader, XdrWriter};
+
+/// Passphrase of the Stellar test network.
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+/// Passphrase of the public Stellar network.
+pub const PUBLIC_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+
+/// Minimum fee per operation, in stroops.
+pub const BASE_FEE: u32 = 100;
+/// Maximum number of operations in a transaction.
+pub const MAX_OPERATIONS: usize = 100;
+/// Maximum number of signatures on an envelope.
+pub const MAX_SIGNATURES: usize = 20;
+/// Maximum length of a text memo, in bytes.
+pub const MAX_MEMO_TEXT: usize = 28;
+/// Maximum number of intermediate assets in a path payment.
+pub const MAX_PATH_LENGTH: usize = 5;
+/// Maximum length of an account's home domain, in bytes.
+pub const MAX_HOME_DOMAIN: usize = 32;
+/// Largest signer weight or account threshold.
+pub const MAX_WEIGHT: u32 = 255;
+
+/// Account flag: holders need the issuer's authorization to hold its assets.
+pub const AUTH_REQUIRED_FLAG: u32 = 0x1;
+/// Account flag: the issuer may revoke a holder's authorization.
+pub const AUTH_REVOCABLE_FLAG: u32 = 0x2;
+/// Account flag: the account's flags can never change again.
+pub const AUTH_IMMUTABLE_FLAG: u32 = 0x4;
+/// Account flag: new trustlines to the issuer's assets allow clawback.
+pub const AUTH_CLAWBACK_ENABLED_FLAG: u32 = 0x8;
+const ACCOUNT_FLAGS: u32 = 0xf;
+
+/// Trustline flag: the holder may send and receive the asset.
+pub const AUTHORIZED_FLAG: u32 = 0x1;
+/// Trustline flag: the holder may keep its offers but not send or receive the asset.
+pub const AUTHORIZED_TO_MAINTAIN_LIABILITIES_FLAG: u32 = 0x2;
+/// Trustline flag: the issuer may claw the asset back from the holder.
+pub const TRUSTLINE_CLAWBACK_ENABLED_FLAG: u32 = 0x4;
+const TRUSTLINE_FLAGS: u32 = 0x7;
+
+const ENVELOPE_TYPE_TX: i32 = 2;
+const SIGNER_KEY_TYPE_ED25519: i32 = 0;
+const SIGNER_KEY_TYPE_PRE_AUTH_TX: i32 = 1;
+const SIGNER_KEY_TYPE_HASH_X: i32 = 2;
+const ENVELOPE_TYPE_TX_FEE_BUMP: i32 = 5;
+const KEY_TYPE_ED25519: i32 = 0;
+const KEY_TYPE_MUXED_ED25519: i32 = 0x100;
+
+/// Errors returned when building or signing a transaction.
+#[derive(Debug, Error, PartialEq)]
+pub enum TransactionError {
+    #[error("A transaction needs at least one operation")]
+    NoOperations,
+
+    #[error("{0} operations exceed the maximum of 100")]
+    TooManyOperations(usize),
+
+    #[error("Text memo of {0} bytes exceeds the maximum of 28")]
+    MemoTooLong(usize),
+
+    #[error("Invalid asset code `{0}`")]
+    InvalidAssetCode(String),
+
+    #[error("Amount must be positive, got {0}")]
+    InvalidAmount(i64),
+
+    #[error("Price must have a positive numerator and denominator")]
+    InvalidPrice,
+
+    #[error("Path of {0} assets exceeds the maximum of 5")]
+    PathTooLong(usize),
+
+    #[error("Invalid flags: {0}")]
+    InvalidFlags(String),
+
+    #[error("Home domain of {0} bytes exceeds the maximum of 32")]
+    HomeDomainTooLong(usize),
+
+    #[error("Weight or threshold {0} exceeds the maximum of 255")]
+    InvalidWeight(u32),
+
+    #[error("Time bounds end before they start")]
+    InvalidTimeBounds,
+
+    #[error("Total fee overflows")]
+    FeeOverflow,
+
+    #[error("Fee bump offers {offered} stroops per operation, at least {required} needed")]
+    FeeBumpTooLow { offered: u32, required: u32 },
+
+    #[error("An envelope can carry at most 20 signatures")]
+    TooManySignatures,
+}
+
+/// A Stellar network, identified by its passphrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    passphrase: String,
+}
+
+impl Network {
+    pub fn new(passphrase: &str) -> Self {
+        Self { passphrase: passphrase.to_string() }
+    }
+
+    pub fn testnet() -> Self {
+        Self::new(TESTNET_PASSPHRASE)
+    }
+
+    pub fn public() -> Self {
+        Self::new(PUBLIC_PASSPHRASE)
+    }
+
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    /// The network ID: SHA-256 of the passphrase.
+    pub fn network_id(&self) -> [u8; 32] {
+        sha256(self.passphrase.as_bytes())
+    }
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest(&SHA256, data).as_ref());
+    hash
+}
+
+fn invalid(type_name: &'static str, value: i32) -> XdrError {
+    XdrError::InvalidDiscriminant { type_name, value }
+}
+
+/// An ed25519 account ID (`PublicKey` in the XDR schema).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountId(pub [u8; 32]);
+
+impl AccountId {
+    /// Parses a `G...` address.
+    pub fn from_strkey(address: &str) -> Result<Self, StrKeyError> {
+        strkey::decode_account_id(address).map(AccountId)
+    }
+
+    /// The `G...` address of the account.
+    pub fn to_strkey(&self) -> String {
+        strkey::encode_account_id(&self.0)
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_strkey())
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = StrKeyError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        Self::from_strkey(address)
+    }
+}
+
+impl From<&PublicKey> for AccountId {
+    fn from(public_key: &PublicKey) -> Self {
+        AccountId(public_key.to_bytes())
+    }
+}
+
+impl XdrEncode for AccountId {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_i32(KEY_TYPE_ED25519);
+        writer.write_fixed_opaque(&self.0);
+    }
+}
+
+impl XdrDecode for AccountId {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            KEY_TYPE_ED25519 => Ok(AccountId(reader.read_fixed_opaque()?)),
+            other => Err(invalid("PublicKey", other)),
+        }
+    }
+}
+
+/// A transaction or operation source, optionally multiplexed with a 64-bit ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MuxedAccount {
+    Ed25519([u8; 32]),
+    MuxedEd25519 { id: u64, ed25519: [u8; 32] },
+}
+
+impl MuxedAccount {
+    /// Parses a `G...` or `M...` address.
+    pub fn from_strkey(address: &str) -> Result<Self, StrKeyError> {
+        match StrKey::decode(address)? {
+            StrKey::AccountId(key) => Ok(MuxedAccount::Ed25519(key)),
+            StrKey::MuxedAccount { ed25519, id } => Ok(MuxedAccount::MuxedEd25519 { id, ed25519 }),
+            other => Err(StrKeyError::UnexpectedType { expected: 'G', found: other.prefix() }),
+        }
+    }
+
+    /// The `G...` or `M...` address.
+    pub fn to_strkey(&self) -> String {
+        match self {
+            MuxedAccount::Ed25519(key) => StrKey::AccountId(*key).encode(),
+            MuxedAccount::MuxedEd25519 { id, ed25519 } => StrKey::MuxedAccount { ed25519: *ed25519, id: *id }.encode(),
+        }
+    }
+
+    /// The underlying account, without the multiplexing ID.
+    pub fn account_id(&self) -> AccountId {
+        match self {
+            MuxedAccount::Ed25519(key) | MuxedAccount::MuxedEd25519 { ed25519: key, .. } => AccountId(*key),
+        }
+    }
+}
+
+impl fmt::Display for MuxedAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_strkey())
+    }
+}
+
+impl FromStr for MuxedAccount {
+    type Err = StrKeyError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        Self::from_strkey(address)
+    }
+}
+
+impl From<AccountId> for MuxedAccount {
+    fn from(account: AccountId) -> Self {
+        MuxedAccount::Ed25519(account.0)
+    }
+}
+
+impl XdrEncode for MuxedAccount {
+    fn encode(&self, writer: &mut XdrWriter) {
+        match self {
+            MuxedAccount::Ed25519(key) => {
+                writer.write_i32(KEY_TYPE_ED25519);
+                writer.write_fixed_opaque(key);
+            }
+            MuxedAccount::MuxedEd25519 { id, ed25519 } => {
+                writer.write_i32(KEY_TYPE_MUXED_ED25519);
+                writer.write_u64(*id);
+                writer.write_fixed_opaque(ed25519);
+            }
+        }
+    }
+}
+
+impl XdrDecode for MuxedAccount {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            KEY_TYPE_ED25519 => Ok(MuxedAccount::Ed25519(reader.read_fixed_opaque()?)),
+            KEY_TYPE_MUXED_ED25519 => Ok(MuxedAccount::MuxedEd25519 {
+                id: reader.read_u64()?,
+                ed25519: reader.read_fixed_opaque()?,
+            }),
+            other => Err(invalid("MuxedAccount", other)),
+        }
+    }
+}
+
+/// An asset: native lumens or a credit identified by code and issuer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Asset {
+    Native,
+    CreditAlphanum4 { code: [u8; 4], issuer: AccountId },
+    CreditAlphanum12 { code: [u8; 12], issuer: AccountId },
+}
+
+impl Asset {
+    /// Creates a credit asset, choosing the 4 or 12 character form from the code length.
+    pub fn credit(code: &str, issuer: AccountId) -> Result<Self, TransactionError> {
+        let bytes = code.as_bytes();
+        if bytes.is_empty() || bytes.len() > 12 || !bytes.iter().all(u8::is_ascii_alphanumeric) {
+            return Err(TransactionError::InvalidAssetCode(code.to_string()));
+        }
+        if bytes.len() <= 4 {
+            let mut padded = [0u8; 4];
+            padded[..bytes.len()].copy_from_slice(bytes);
+            Ok(Asset::CreditAlphanum4 { code: padded, issuer })
+        } else {
+            let mut padded = [0u8; 12];
+            padded[..bytes.len()].copy_from_slice(bytes);
+            Ok(Asset::CreditAlphanum12 { code: padded, issuer })
+        }
+    }
+
+    /// The asset code, or "XLM" for the native asset.
+    pub fn code(&self) -> String {
+        let bytes: &[u8] = match self {
+            Asset::Native => return "XLM".to_string(),
+            Asset::CreditAlphanum4 { code, .. } => code,
+            Asset::CreditAlphanum12 { code, .. } => code,
+        };
+        bytes.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect()
+    }
+
+    pub fn issuer(&self) -> Option<AccountId> {
+        match self {
+            Asset::Native => None,
+            Asset::CreditAlphanum4 { issuer, .. } | Asset::CreditAlphanum12 { issuer, .. } => Some(*issuer),
+        }
+    }
+}
+
+impl XdrEncode for Asset {
+    fn encode(&self, writer: &mut XdrWriter) {
+        match self {
+            Asset::Native => writer.write_i32(0),
+            Asset::CreditAlphanum4 { code, issuer } => {
+                writer.write_i32(1);
+                writer.write_fixed_opaque(code);
+                issuer.encode(writer);
+            }
+            Asset::CreditAlphanum12 { code, issuer } => {
+                writer.write_i32(2);
+                writer.write_fixed_opaque(code);
+                issuer.encode(writer);
+            }
+        }
+    }
+}
+
+impl XdrDecode for Asset {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            0 => Ok(Asset::Native),
+            1 => Ok(Asset::CreditAlphanum4 { code: reader.read_fixed_opaque()?, issuer: AccountId::decode(reader)? }),
+            2 => Ok(Asset::CreditAlphanum12 { code: reader.read_fixed_opaque()?, issuer: AccountId::decode(reader)? }),
+            other => Err(invalid("Asset", other)),
+        }
+    }
+}
+
+/// A price as a fraction `n / d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Price {
+    pub n: i32,
+    pub d: i32,
+}
+
+impl Price {
+    pub fn new(n: i32, d: i32) -> Self {
+        Self { n, d }
+    }
+}
+
+impl XdrEncode for Price {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_i32(self.n);
+        writer.write_i32(self.d);
+    }
+}
+
+impl XdrDecode for Price {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        Ok(Price { n: reader.read_i32()?, d: reader.read_i32()? })
+    }
+}
+
+/// A transaction memo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Memo {
+    None,
+    Text(String),
+    Id(u64),
+    Hash([u8; 32]),
+    Return([u8; 32]),
+}
+
+impl XdrEncode for Memo {
+    fn encode(&self, writer: &mut XdrWriter) {
+        match self {
+            Memo::None => writer.write_i32(0),
+            Memo::Text(text) => {
+                writer.write_i32(1);
+                writer.write_var_opaque(text.as_bytes());
+            }
+            Memo::Id(id) => {
+                writer.write_i32(2);
+                writer.write_u64(*id);
+            }
+            Memo::Hash(hash) => {
+                writer.write_i32(3);
+                writer.write_fixed_opaque(hash);
+            }
+            Memo::Return(hash) => {
+                writer.write_i32(4);
+                writer.write_fixed_opaque(hash);
+            }
+        }
+    }
+}
+
+impl XdrDecode for Memo {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            0 => Ok(Memo::None),
+            1 => Ok(Memo::Text(reader.read_string(MAX_MEMO_TEXT as u32)?)),
+            2 => Ok(Memo::Id(reader.read_u64()?)),
+            3 => Ok(Memo::Hash(reader.read_fixed_opaque()?)),
+            4 => Ok(Memo::Return(reader.read_fixed_opaque()?)),
+            other => Err(invalid("Memo", other)),
+        }
+    }
+}
+
+/// Validity window of a transaction in UNIX seconds; a `max_time` of zero means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBounds {
+    pub min_time: u64,
+    pub max_time: u64,
+}
+
+impl XdrEncode for TimeBounds {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_u64(self.min_time);
+        writer.write_u64(self.max_time);
+    }
+}
+
+impl XdrDecode for TimeBounds {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        Ok(TimeBounds { min_time: reader.read_u64()?, max_time: reader.read_u64()? })
+    }
+}
+
+/// A key that can sign for an account besides its master key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignerKey {
+    /// An ed25519 public key.
+    Ed25519([u8; 32]),
+    /// The hash of one transaction that is authorized without signatures.
+    PreAuthTx([u8; 32]),
+    /// The SHA-256 hash of a preimage; revealing the preimage counts as a signature.
+    HashX([u8; 32]),
+}
+
+impl SignerKey {
+    /// Parses a `G...`, `T...` or `X...` key.
+    pub fn from_strkey(key: &str) -> Result<Self, StrKeyError> {
+        match StrKey::decode(key)? {
+            StrKey::AccountId(key) => Ok(SignerKey::Ed25519(key)),
+            StrKey::PreAuthTx(hash) => Ok(SignerKey::PreAuthTx(hash)),
+            StrKey::HashX(hash) => Ok(SignerKey::HashX(hash)),
+            other => Err(StrKeyError::UnexpectedType { expected: 'G', found: other.prefix() }),
+        }
+    }
+
+    /// The `G...`, `T...` or `X...` form, as listed by Horizon.
+    pub fn to_strkey(&self) -> String {
+        match self {
+            SignerKey::Ed25519(key) => StrKey::AccountId(*key).encode(),
+            SignerKey::PreAuthTx(hash) => StrKey::PreAuthTx(*hash).encode(),
+            SignerKey::HashX(hash) => StrKey::HashX(*hash).encode(),
+        }
+    }
+}
+
+impl fmt::Display for SignerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_strkey())
+    }
+}
+
+impl From<AccountId> for SignerKey {
+    fn from(account: AccountId) -> Self {
+        SignerKey::Ed25519(account.0)
+    }
+}
+
+impl XdrEncode for SignerKey {
+    fn encode(&self, writer: &mut XdrWriter) {
+        let (kind, key) = match self {
+            SignerKey::Ed25519(key) => (SIGNER_KEY_TYPE_ED25519, key),
+            SignerKey::PreAuthTx(hash) => (SIGNER_KEY_TYPE_PRE_AUTH_TX, hash),
+            SignerKey::HashX(hash) => (SIGNER_KEY_TYPE_HASH_X, hash),
+        };
+        writer.write_i32(kind);
+        writer.write_fixed_opaque(key);
+    }
+}
+
+impl XdrDecode for SignerKey {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            SIGNER_KEY_TYPE_ED25519 => Ok(SignerKey::Ed25519(reader.read_fixed_opaque()?)),
+            SIGNER_KEY_TYPE_PRE_AUTH_TX => Ok(SignerKey::PreAuthTx(reader.read_fixed_opaque()?)),
+            SIGNER_KEY_TYPE_HASH_X => Ok(SignerKey::HashX(reader.read_fixed_opaque()?)),
+            other => Err(invalid("SignerKey", other)),
+        }
+    }
+}
+
+/// A signer of an account and its weight; a weight of zero removes the signer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signer {
+    pub key: SignerKey,
+    pub weight: u32,
+}
+
+impl XdrEncode for Signer {
+    fn encode(&self, writer: &mut XdrWriter) {
+        self.key.encode(writer);
+        writer.write_u32(self.weight);
+    }
+}
+
+impl XdrDecode for Signer {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        Ok(Signer { key: SignerKey::decode(reader)?, weight: reader.read_u32()? })
+    }
+}
+
+/// The operation-specific part of an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationBody {
+    CreateAccount {
+        destination: AccountId,
+        starting_balance: i64,
+    },
+    Payment {
+        destination: MuxedAccount,
+        asset: Asset,
+        amount: i64,
+    },
+    PathPaymentStrictReceive {
+        send_asset: Asset,
+        send_max: i64,
+        destination: MuxedAccount,
+        dest_asset: Asset,
+        dest_amount: i64,
+        path: Vec<Asset>,
+    },
+    ManageSellOffer {
+        selling: Asset,
+        buying: Asset,
+        amount: i64,
+        price: Price,
+        offer_id: i64,
+    },
+    ChangeTrust {
+        line: Asset,
+        limit: i64,
+    },
+    ManageBuyOffer {
+        selling: Asset,
+        buying: Asset,
+        buy_amount: i64,
+        price: Price,
+        offer_id: i64,
+    },
+    PathPaymentStrictSend {
+        send_asset: Asset,
+        send_amount: i64,
+        destination: MuxedAccount,
+        dest_asset: Asset,
+        dest_min: i64,
+        path: Vec<Asset>,
+    },
+    /// Settings left at `None` are unchanged. The inflation destination is not modelled and is
+    /// always left unchanged.
+    SetOptions {
+        clear_flags: Option<u32>,
+        set_flags: Option<u32>,
+        master_weight: Option<u32>,
+        low_threshold: Option<u32>,
+        med_threshold: Option<u32>,
+        high_threshold: Option<u32>,
+        home_domain: Option<String>,
+        signer: Option<Signer>,
+    },
+    /// Sends the source account's remaining lumens to `destination` and deletes the account.
+    AccountMerge {
+        destination: MuxedAccount,
+    },
+    Clawback {
+        asset: Asset,
+        from: MuxedAccount,
+        amount: i64,
+    },
+    SetTrustLineFlags {
+        trustor: AccountId,
+        asset: Asset,
+        clear_flags: u32,
+        set_flags: u32,
+    },
+}
+
+/// An operation, optionally with its own source account.
+///
+/// Amounts are in stroops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub source_account: Option<MuxedAccount>,
+    pub body: OperationBody,
+}
+
+impl Operation {
+    fn new(body: OperationBody) -> Self {
+        Self { source_account: None, body }
+    }
+
+    /// Sets the account the operation acts on, instead of the transaction source.
+    pub fn with_source(mut self, source: MuxedAccount) -> Self {
+        self.source_account = Some(source);
+        self
+    }
+
+    pub fn create_account(destination: AccountId, starting_balance: i64) -> Self {
+        Self::new(OperationBody::CreateAccount { destination, starting_balance })
+    }
+
+    pub fn payment(destination: MuxedAccount, asset: Asset, amount: i64) -> Self {
+        Self::new(OperationBody::Payment { destination, asset, amount })
+    }
+
+    /// Pays exactly `dest_amount`, spending at most `send_max`.
+    pub fn path_payment_strict_receive(
+        send_asset: Asset,
+        send_max: i64,
+        destination: MuxedAccount,
+        dest_asset: Asset,
+        dest_amount: i64,
+        path: Vec<Asset>,
+    ) -> Self {
+        Self::new(OperationBody::PathPaymentStrictReceive { send_asset, send_max, destination, dest_asset, dest_amount, path })
+    }
+
+    /// Spends exactly `send_amount`, delivering at least `dest_min`.
+    pub fn path_payment_strict_send(
+        send_asset: Asset,
+        send_amount: i64,
+        destination: MuxedAccount,
+        dest_asset: Asset,
+        dest_min: i64,
+        path: Vec<Asset>,
+    ) -> Self {
+        Self::new(OperationBody::PathPaymentStrictSend { send_asset, send_amount, destination, dest_asset, dest_min, path })
+    }
+
+    /// Creates (offer ID 0), updates or, with a zero amount, deletes a sell offer.
+    pub fn manage_sell_offer(selling: Asset, buying: Asset, amount: i64, price: Price, offer_id: i64) -> Self {
+        Self::new(OperationBody::ManageSellOffer { selling, buying, amount, price, offer_id })
+    }
+
+    /// Creates (offer ID 0), updates or, with a zero amount, deletes a buy offer.
+    pub fn manage_buy_offer(selling: Asset, buying: Asset, buy_amount: i64, price: Price, offer_id: i64) -> Self {
+        Self::new(OperationBody::ManageBuyOffer { selling, buying, buy_amount, price, offer_id })
+    }
+
+    /// Creates or updates a trustline; a limit of zero removes it.
+    pub fn change_trust(line: Asset, limit: i64) -> Self {
+        Self::new(OperationBody::ChangeTrust { line, limit })
+    }
+
+    /// Changes the source account's flags (`AUTH_*_FLAG`) and home domain; `None` leaves a
+    /// setting unchanged.
+    pub fn set_options(clear_flags: Option<u32>, set_flags: Option<u32>, home_domain: Option<String>) -> Self {
+        Self::new(OperationBody::SetOptions {
+            clear_flags,
+            set_flags,
+            master_weight: None,
+            low_threshold: None,
+            med_threshold: None,
+            high_threshold: None,
+            home_domain,
+            signer: None,
+        })
+    }
+
+    /// Adds, reweighs or, with a weight of zero, removes a signer of the source account.
+    pub fn set_signer(signer: Signer) -> Self {
+        Self::new(OperationBody::SetOptions {
+            clear_flags: None,
+            set_flags: None,
+            master_weight: None,
+            low_threshold: None,
+            med_threshold: None,
+            high_threshold: None,
+            home_domain: None,
+            signer: Some(signer),
+        })
+    }
+
+    /// Sets the weight of the source account's master key and the signature weight its low,
+    /// medium and high threshold operations need.
+    pub fn set_thresholds(master_weight: u32, low: u32, med: u32, high: u32) -> Self {
+        Self::new(OperationBody::SetOptions {
+            clear_flags: None,
+            set_flags: None,
+            master_weight: Some(master_weight),
+            low_threshold: Some(low),
+            med_threshold: Some(med),
+            high_threshold: Some(high),
+            home_domain: None,
+            signer: None,
+        })
+    }
+
+    /// Merges the source account into `destination`.
+    pub fn account_merge(destination: MuxedAccount) -> Self {
+        Self::new(OperationBody::AccountMerge { destination })
+    }
+
+    /// Burns `amount` of an asset issued by the source account from a holder's trustline.
+    pub fn clawback(asset: Asset, from: MuxedAccount, amount: i64) -> Self {
+        Self::new(OperationBody::Clawback { asset, from, amount })
+    }
+
+    /// Changes the flags of a holder's trustline to an asset issued by the source account,
+    /// e.g. to authorize or revoke it.
+    pub fn set_trust_line_flags(trustor: AccountId, asset: Asset, clear_flags: u32, set_flags: u32) -> Self {
+        Self::new(OperationBody::SetTrustLineFlags { trustor, asset, clear_flags, set_flags })
+    }
+
+    fn validate(&self) -> Result<(), TransactionError> {
+        fn positive(amount: i64) -> Result<(), TransactionError> {
+            if amount > 0 { Ok(()) } else { Err(TransactionError::InvalidAmount(amount)) }
+        }
+        fn non_negative(amount: i64) -> Result<(), TransactionError> {
+            if amount >= 0 { Ok(()) } else { Err(TransactionError::InvalidAmount(amount)) }
+        }
+        fn valid_path(path: &[Asset]) -> Result<(), TransactionError> {
+            if path.len() > MAX_PATH_LENGTH { Err(TransactionError::PathTooLong(path.len())) } else { Ok(()) }
+        }
+        fn valid_price(price: &Price) -> Result<(), TransactionError> {
+            if price.n > 0 && price.d > 0 { Ok(()) } else { Err(TransactionError::InvalidPrice) }
+        }
+        fn credit(asset: &Asset) -> Result<(), TransactionError> {
+            if *asset == Asset::Native { Err(TransactionError::InvalidAssetCode("XLM".to_string())) } else { Ok(()) }
+        }
+        fn valid_flags(clear: u32, set: u32, known: u32) -> Result<(), TransactionError> {
+            if (clear | set) & !known != 0 {
+                return Err(TransactionError::InvalidFlags(format!("unknown flags {:#x}", (clear | set) & !known)));
+            }
+            if clear & set != 0 {
+                return Err(TransactionError::InvalidFlags(format!("flags {:#x} both set and cleared", clear & set)));
+            }
+            Ok(())
+        }
+
+        match &self.body {
+            OperationBody::CreateAccount { starting_balance, .. } => positive(*starting_balance),
+            OperationBody::Payment { amount, .. } => positive(*amount),
+            OperationBody::PathPaymentStrictReceive { send_max, dest_amount, path, .. } => {
+                positive(*send_max)?;
+                positive(*dest_amount)?;
+                valid_path(path)
+            }
+            OperationBody::PathPaymentStrictSend { send_amount, dest_min, path, .. } => {
+                positive(*send_amount)?;
+                positive(*dest_min)?;
+                valid_path(path)
+            }
+            OperationBody::ManageSellOffer { amount, price, .. } => {
+                non_negative(*amount)?;
+                valid_price(price)
+            }
+            OperationBody::ManageBuyOffer { buy_amount, price, .. } => {
+                non_negative(*buy_amount)?;
+                valid_price(price)
+            }
+            OperationBody::ChangeTrust { line, limit } => {
+                credit(line)?;
+                non_negative(*limit)
+            }
+            OperationBody::SetOptions {
+                clear_flags,
+                set_flags,
+                master_weight,
+                low_threshold,
+                med_threshold,
+                high_threshold,
+                home_domain,
+                signer,
+            } => {
+                valid_flags(clear_flags.unwrap_or(0), set_flags.unwrap_or(0), ACCOUNT_FLAGS)?;
+                let weights = [*master_weight, *low_threshold, *med_threshold, *high_threshold, signer.map(|s| s.weight)];
+                if let Some(weight) = weights.into_iter().flatten().find(|weight| *weight > MAX_WEIGHT) {
+                    return Err(TransactionError::InvalidWeight(weight));
+                }
+                match home_domain {
+                    Some(domain) if domain.len() > MAX_HOME_DOMAIN => Err(TransactionError::HomeDomainTooLong(domain.len())),
+                    _ => Ok(()),
+                }
+            }
+            OperationBody::AccountMerge { .. } => Ok(()),
+            OperationBody::Clawback { asset, amount, .. } => {
+                credit(asset)?;
+                positive(*amount)
+            }
+            OperationBody::SetTrustLineFlags { asset, clear_flags, set_flags, .. } => {
+                credit(asset)?;
+                valid_flags(*clear_flags, *set_flags, TRUSTLINE_FLAGS)?;
+                // Clawback can only be enabled through the issuer's account flag
+                if set_flags & TRUSTLINE_CLAWBACK_ENABLED_FLAG != 0 {
+                    return Err(TransactionError::InvalidFlags("clawback cannot be enabled on a trustline".to_string()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Optional uint32 fields of SetOptionsOp
+fn write_optional_u32(writer: &mut XdrWriter, value: Option<u32>) {
+    writer.write_bool(value.is_some());
+    if let Some(value) = value {
+        writer.write_u32(value);
+    }
+}
+
+fn read_optional_u32(reader: &mut XdrReader) -> Result<Option<u32>, XdrError> {
+    if reader.read_bool()? { Ok(Some(reader.read_u32()?)) } else { Ok(None) }
+}
+
+impl XdrEncode for Operation {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_optional(self.source_account.as_ref());
+        match &self.body {
+            OperationBody::CreateAccount { destination, starting_balance } => {
+                writer.write_i32(0);
+                destination.encode(writer);
+                writer.write_i64(*starting_balance);
+            }
+            OperationBody::Payment { destination, asset, amount } => {
+                writer.write_i32(1);
+                destination.encode(writer);
+                asset.encode(writer);
+                writer.write_i64(*amount);
+            }
+            OperationBody::PathPaymentStrictReceive { send_asset, send_max, destination, dest_asset, dest_amount, path } => {
+                writer.write_i32(2);
+                send_asset.encode(writer);
+                writer.write_i64(*send_max);
+                destination.encode(writer);
+                dest_asset.encode(writer);
+                writer.write_i64(*dest_amount);
+                writer.write_array(path);
+            }
+            OperationBody::ManageSellOffer { selling, buying, amount, price, offer_id } => {
+                writer.write_i32(3);
+                selling.encode(writer);
+                buying.encode(writer);
+                writer.write_i64(*amount);
+                price.encode(writer);
+                writer.write_i64(*offer_id);
+            }
+            OperationBody::ChangeTrust { line, limit } => {
+                writer.write_i32(6);
+                line.encode(writer);
+                writer.write_i64(*limit);
+            }
+            OperationBody::ManageBuyOffer { selling, buying, buy_amount, price, offer_id } => {
+                writer.write_i32(12);
+                selling.encode(writer);
+                buying.encode(writer);
+                writer.write_i64(*buy_amount);
+                price.encode(writer);
+                writer.write_i64(*offer_id);
+            }
+            OperationBody::PathPaymentStrictSend { send_asset, send_amount, destination, dest_asset, dest_min, path } => {
+                writer.write_i32(13);
+                send_asset.encode(writer);
+                writer.write_i64(*send_amount);
+                destination.encode(writer);
+                dest_asset.encode(writer);
+                writer.write_i64(*dest_min);
+                writer.write_array(path);
+            }
+            OperationBody::SetOptions {
+                clear_flags,
+                set_flags,
+                master_weight,
+                low_threshold,
+                med_threshold,
+                high_threshold,
+                home_domain,
+                signer,
+            } => {
+                writer.write_i32(5);
+                writer.write_bool(false); // inflation destination
+                write_optional_u32(writer, *clear_flags);
+                write_optional_u32(writer, *set_flags);
+                write_optional_u32(writer, *master_weight);
+                write_optional_u32(writer, *low_threshold);
+                write_optional_u32(writer, *med_threshold);
+                write_optional_u32(writer, *high_threshold);
+                writer.write_bool(home_domain.is_some());
+                if let Some(domain) = home_domain {
+                    writer.write_var_opaque(domain.as_bytes());
+                }
+                writer.write_optional(signer.as_ref());
+            }
+            OperationBody::AccountMerge { destination } => {
+                writer.write_i32(8);
+                destination.encode(writer);
+            }
+            OperationBody::Clawback { asset, from, amount } => {
+                writer.write_i32(19);
+                asset.encode(writer);
+                from.encode(writer);
+                writer.write_i64(*amount);
+            }
+            OperationBody::SetTrustLineFlags { trustor, asset, clear_flags, set_flags } => {
+                writer.write_i32(21);
+                trustor.encode(writer);
+                asset.encode(writer);
+                writer.write_u32(*clear_flags);
+                writer.write_u32(*set_flags);
+            }
+        }
+    }
+}
+
+impl XdrDecode for Operation {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        let source_account = reader.read_optional()?;
+        let body = match reader.read_i32()? {
+            0 => OperationBody::CreateAccount {
+                destination: AccountId::decode(reader)?,
+                starting_balance: reader.read_i64()?,
+            },
+            1 => OperationBody::Payment {
+                destination: MuxedAccount::decode(reader)?,
+                asset: Asset::decode(reader)?,
+                amount: reader.read_i64()?,
+            },
+            2 => OperationBody::PathPaymentStrictReceive {
+                send_asset: Asset::decode(reader)?,
+                send_max: reader.read_i64()?,
+                destination: MuxedAccount::decode(reader)?,
+                dest_asset: Asset::decode(reader)?,
+                dest_amount: reader.read_i64()?,
+                path: reader.read_array(MAX_PATH_LENGTH as u32)?,
+            },
+            3 => OperationBody::ManageSellOffer {
+                selling: Asset::decode(reader)?,
+                buying: Asset::decode(reader)?,
+                amount: reader.read_i64()?,
+                price: Price::decode(reader)?,
+                offer_id: reader.read_i64()?,
+            },
+            6 => OperationBody::ChangeTrust {
+                line: Asset::decode(reader)?,
+                limit: reader.read_i64()?,
+            },
+            12 => OperationBody::ManageBuyOffer {
+                selling: Asset::decode(reader)?,
+                buying: Asset::decode(reader)?,
+                buy_amount: reader.read_i64()?,
+                price: Price::decode(reader)?,
+                offer_id: reader.read_i64()?,
+            },
+            13 => OperationBody::PathPaymentStrictSend {
+                send_asset: Asset::decode(reader)?,
+                send_amount: reader.read_i64()?,
+                destination: MuxedAccount::decode(reader)?,
+                dest_asset: Asset::decode(reader)?,
+                dest_min: reader.read_i64()?,
+                path: reader.read_array(MAX_PATH_LENGTH as u32)?,
+            },
+            5 => {
+                if reader.read_optional::<AccountId>()?.is_some() {
+                    return Err(XdrError::InvalidValue("SetOptions inflation destination is not supported".to_string()));
+                }
+                OperationBody::SetOptions {
+                    clear_flags: read_optional_u32(reader)?,
+                    set_flags: read_optional_u32(reader)?,
+                    master_weight: read_optional_u32(reader)?,
+                    low_threshold: read_optional_u32(reader)?,
+                    med_threshold: read_optional_u32(reader)?,
+                    high_threshold: read_optional_u32(reader)?,
+                    home_domain: if reader.read_bool()? { Some(reader.read_string(MAX_HOME_DOMAIN as u32)?) } else { None },
+                    signer: reader.read_optional()?,
+                }
+            }
+            8 => OperationBody::AccountMerge { destination: MuxedAccount::decode(reader)? },
+            19 => OperationBody::Clawback {
+                asset: Asset::decode(reader)?,
+                from: MuxedAccount::decode(reader)?,
+                amount: reader.read_i64()?,
+            },
+            21 => OperationBody::SetTrustLineFlags {
+                trustor: AccountId::decode(reader)?,
+                asset: Asset::decode(reader)?,
+                clear_flags: reader.read_u32()?,
+                set_flags: reader.read_u32()?,
+            },
+            other => return Err(invalid("OperationType", other)),
+        };
+        Ok(Operation { source_account, body })
+    }
+}
+
+/// A Stellar transaction (the v1 `Transaction` XDR structure).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub source_account: MuxedAccount,
+    /// Total fee in stroops, i.e. the base fee times the number of operations.
+    pub fee: u32,
+    pub seq_num: i64,
+    pub time_bounds: Option<TimeBounds>,
+    pub memo: Memo,
+    pub operations: Vec<Operation>,
+}
+
+impl Transaction {
+    /// The bytes that are hashed and signed: network ID, envelope type and the transaction.
+    pub fn signature_base(&self, network: &Network) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        writer.write_fixed_opaque(&network.network_id());
+        writer.write_i32(ENVELOPE_TYPE_TX);
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// The transaction hash on the given network, as shown by Horizon.
+    pub fn hash(&self, network: &Network) -> [u8; 32] {
+        sha256(&self.signature_base(network))
+    }
+
+    /// Wraps the transaction in an envelope signed by `keypair`.
+    pub fn sign(self, network: &Network, keypair: &Keypair) -> TransactionEnvelope {
+        let mut envelope = TransactionEnvelope { tx: self, signatures: Vec::new() };
+        envelope.signatures.push(DecoratedSignature::new(&envelope.tx.hash(network), keypair));
+        envelope
+    }
+}
+
+impl XdrEncode for Transaction {
+    fn encode(&self, writer: &mut XdrWriter) {
+        self.source_account.encode(writer);
+        writer.write_u32(self.fee);
+        writer.write_i64(self.seq_num);
+        match &self.time_bounds {
+            None => writer.write_i32(0),
+            Some(bounds) => {
+                writer.write_i32(1);
+                bounds.encode(writer);
+            }
+        }
+        self.memo.encode(writer);
+        writer.write_array(&self.operations);
+        // Extension point, always version 0
+        writer.write_i32(0);
+    }
+}
+
+impl XdrDecode for Transaction {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        let source_account = MuxedAccount::decode(reader)?;
+        let fee = reader.read_u32()?;
+        let seq_num = reader.read_i64()?;
+        let time_bounds = match reader.read_i32()? {
+            0 => None,
+            1 => Some(TimeBounds::decode(reader)?),
+            other => return Err(invalid("PreconditionType", other)),
+        };
+        let memo = Memo::decode(reader)?;
+        let operations = reader.read_array(MAX_OPERATIONS as u32)?;
+        match reader.read_i32()? {
+            0 => {}
+            other => return Err(invalid("Transaction.ext", other)),
+        }
+        Ok(Transaction { source_account, fee, seq_num, time_bounds, memo, operations })
+    }
+}
+
+/// A signature with a hint identifying the signing key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoratedSignature {
+    /// Last four bytes of the signer's public key.
+    pub hint: [u8; 4],
+    pub signature: Vec<u8>,
+}
+
+impl DecoratedSignature {
+    fn new(hash: &[u8; 32], keypair: &Keypair) -> Self {
+        let public_key = keypair.public.to_bytes();
+        let mut hint = [0u8; 4];
+        hint.copy_from_slice(&public_key[28..]);
+        Self { hint, signature: keypair.sign(hash).to_bytes().to_vec() }
+    }
+
+    /// A hash-x signature: the preimage itself, hinted by the last four bytes of its hash.
+    pub fn hash_x(preimage: &[u8]) -> Self {
+        let hash = sha256(preimage);
+        let mut hint = [0u8; 4];
+        hint.copy_from_slice(&hash[28..]);
+        Self { hint, signature: preimage.to_vec() }
+    }
+}
+
+impl XdrEncode for DecoratedSignature {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_fixed_opaque(&self.hint);
+        writer.write_var_opaque(&self.signature);
+    }
+}
+
+impl XdrDecode for DecoratedSignature {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        Ok(DecoratedSignature { hint: reader.read_fixed_opaque()?, signature: reader.read_var_opaque(64)? })
+    }
+}
+
+/// A signed transaction, ready to submit (`ENVELOPE_TYPE_TX`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionEnvelope {
+    pub tx: Transaction,
+    pub signatures: Vec<DecoratedSignature>,
+}
+
+impl TransactionEnvelope {
+    /// Adds another signature, e.g. for multi-signature accounts.
+    pub fn sign(&mut self, network: &Network, keypair: &Keypair) -> Result<(), TransactionError> {
+        if self.signatures.len() >= MAX_SIGNATURES {
+            return Err(TransactionError::TooManySignatures);
+        }
+        self.signatures.push(DecoratedSignature::new(&self.tx.hash(network), keypair));
+        Ok(())
+    }
+
+    /// Adds a hash-x signature revealing `preimage`, for accounts with a `SignerKey::HashX` signer.
+    pub fn sign_hash_x(&mut self, preimage: &[u8]) -> Result<(), TransactionError> {
+        if self.signatures.len() >= MAX_SIGNATURES {
+            return Err(TransactionError::TooManySignatures);
+        }
+        self.signatures.push(DecoratedSignature::hash_x(preimage));
+        Ok(())
+    }
+
+    /// The revealed preimage of a hash-x signer with hash `hash`, if the envelope carries one.
+    pub fn hash_x_preimage(&self, hash: &[u8; 32]) -> Option<&[u8]> {
+        self.signatures
+            .iter()
+            .filter(|s| s.hint[..] == hash[28..])
+            .map(|s| s.signature.as_slice())
+            .find(|preimage| sha256(preimage) == *hash)
+    }
+
+    /// Whether the envelope carries a valid signature by `public_key` on `network`.
+    pub fn is_signed_by(&self, network: &Network, public_key: &PublicKey) -> bool {
+        let hash = self.tx.hash(network);
+        let key = public_key.to_bytes();
+        self.signatures
+            .iter()
+            .filter(|s| s.hint[..] == key[28..])
+            .filter_map(|s| Signature::from_bytes(&s.signature).ok())
+            .any(|signature| public_key.verify(&hash, &signature).is_ok())
+    }
+}
+
+impl XdrEncode for TransactionEnvelope {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_i32(ENVELOPE_TYPE_TX);
+        self.tx.encode(writer);
+        writer.write_array(&self.signatures);
+    }
+}
+
+impl XdrDecode for TransactionEnvelope {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            ENVELOPE_TYPE_TX => Ok(TransactionEnvelope {
+                tx: Transaction::decode(reader)?,
+                signatures: reader.read_array(MAX_SIGNATURES as u32)?,
+            }),
+            other => Err(invalid("EnvelopeType", other)),
+        }
+    }
+}
+
+/// A transaction paying a higher fee for an already signed inner transaction, so that it is
+/// included sooner during surge pricing. The inner transaction's source need not sign again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBumpTransaction {
+    /// Account paying the fee.
+    pub fee_source: MuxedAccount,
+    /// Total fee in stroops, covering the inner operations plus one for the bump itself.
+    pub fee: i64,
+    pub inner: TransactionEnvelope,
+}
+
+impl FeeBumpTransaction {
+    /// Wraps `inner` so that `fee_source` pays `base_fee` per operation. The bump counts as an
+    /// extra operation, and its rate may not be lower than the inner transaction's.
+    pub fn new(fee_source: impl Into<MuxedAccount>, base_fee: u32, inner: TransactionEnvelope) -> Result<Self, TransactionError> {
+        let operations = inner.tx.operations.len().max(1) as u32;
+        let required = inner.tx.fee.div_ceil(operations).max(BASE_FEE);
+        if base_fee < required {
+            return Err(TransactionError::FeeBumpTooLow { offered: base_fee, required });
+        }
+        let fee = i64::from(base_fee) * i64::from(operations + 1);
+        Ok(Self { fee_source: fee_source.into(), fee, inner })
+    }
+
+    /// The bytes that are hashed and signed by the fee source.
+    pub fn signature_base(&self, network: &Network) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        writer.write_fixed_opaque(&network.network_id());
+        writer.write_i32(ENVELOPE_TYPE_TX_FEE_BUMP);
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// The fee bump's own hash, which Horizon reports for the submission.
+    pub fn hash(&self, network: &Network) -> [u8; 32] {
+        sha256(&self.signature_base(network))
+    }
+
+    /// Wraps the fee bump in an envelope signed by the fee source's `keypair`.
+    pub fn sign(self, network: &Network, keypair: &Keypair) -> FeeBumpTransactionEnvelope {
+        let signature = DecoratedSignature::new(&self.hash(network), keypair);
+        FeeBumpTransactionEnvelope { tx: self, signatures: vec![signature] }
+    }
+}
+
+impl XdrEncode for FeeBumpTransaction {
+    fn encode(&self, writer: &mut XdrWriter) {
+        self.fee_source.encode(writer);
+        writer.write_i64(self.fee);
+        // The inner transaction is a union that only allows ENVELOPE_TYPE_TX
+        self.inner.encode(writer);
+        writer.write_i32(0);
+    }
+}
+
+impl XdrDecode for FeeBumpTransaction {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        let fee_source = MuxedAccount::decode(reader)?;
+        let fee = reader.read_i64()?;
+        let inner = TransactionEnvelope::decode(reader)?;
+        match reader.read_i32()? {
+            0 => {}
+            other => return Err(invalid("FeeBumpTransaction.ext", other)),
+        }
+        Ok(FeeBumpTransaction { fee_source, fee, inner })
+    }
+}
+
+/// A signed fee bump, ready to submit (`ENVELOPE_TYPE_TX_FEE_BUMP`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBumpTransactionEnvelope {
+    pub tx: FeeBumpTransaction,
+    pub signatures: Vec<DecoratedSignature>,
+}
+
+impl FeeBumpTransactionEnvelope {
+    /// Whether the envelope carries a valid signature by `public_key` on `network`.
+    pub fn is_signed_by(&self, network: &Network, public_key: &PublicKey) -> bool {
+        let hash = self.tx.hash(network);
+        let key = public_key.to_bytes();
+        self.signatures
+            .iter()
+            .filter(|s| s.hint[..] == key[28..])
+            .filter_map(|s| Signature::from_bytes(&s.signature).ok())
+            .any(|signature| public_key.verify(&hash, &signature).is_ok())
+    }
+}
+
+impl XdrEncode for FeeBumpTransactionEnvelope {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.write_i32(ENVELOPE_TYPE_TX_FEE_BUMP);
+        self.tx.encode(writer);
+        writer.write_array(&self.signatures);
+    }
+}
+
+impl XdrDecode for FeeBumpTransactionEnvelope {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError> {
+        match reader.read_i32()? {
+            ENVELOPE_TYPE_TX_FEE_BUMP => Ok(FeeBumpTransactionEnvelope {
+                tx: FeeBumpTransaction::decode(reader)?,
+                signatures: reader.read_array(MAX_SIGNATURES as u32)?,
+            }),
+            other => Err(invalid("EnvelopeType", other)),
+        }
+    }
+}
+
+/// Builds a validated transaction.
+///
+/// ```ignore
+/// let tx = TransactionBuilder::new(source, account.sequence + 1)
+///     .base_fee(200)
+///     .memo(Memo::Text("invoice 42".to_string()))
+///     .timeout(Duration::from_secs(300))
+///     .add_operation(Operation::payment(destination, Asset::Native, 10_000_000))
+///     .build()?;
+/// let envelope = tx.sign(&Network::testnet(), &keypair);
+/// ```
+pub struct TransactionBuilder {
+    source_account: MuxedAccount,
+    seq_num: i64,
+    base_fee: u32,
+    memo: Memo,
+    time_bounds: Option<TimeBounds>,
+    operations: Vec<Operation>,
+}
+
+impl TransactionBuilder {
+    /// Starts a transaction from `source_account` with the sequence number to use, which is
+    /// the account's current sequence plus one.
+    pub fn new(source_account: impl Into<MuxedAccount>, seq_num: i64) -> Self {
+        Self {
+            source_account: source_account.into(),
+            seq_num,
+            base_fee: BASE_FEE,
+            memo: Memo::None,
+            time_bounds: None,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Sets the fee per operation in stroops.
+    pub fn base_fee(mut self, base_fee: u32) -> Self {
+        self.base_fee = base_fee;
+        self
+    }
+
+    pub fn memo(mut self, memo: Memo) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    pub fn time_bounds(mut self, time_bounds: TimeBounds) -> Self {
+        self.time_bounds = Some(time_bounds);
+        self
+    }
+
+    /// Makes the transaction expire `timeout` from now. A timeout too large to represent
+    /// saturates to the latest possible expiry.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let max_time = now.checked_add(timeout).map_or(u64::MAX, |expiry| expiry.as_secs());
+        self.time_bounds(TimeBounds { min_time: 0, max_time })
+    }
+
+    pub fn add_operation(mut self, operation: Operation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    /// Validates the operations, memo and time bounds and computes the total fee.
+    pub fn build(self) -> Result<Transaction, TransactionError> {
+        if self.operations.is_empty() {
+            return Err(TransactionError::NoOperations);
+        }
+        if self.operations.len() > MAX_OPERATIONS {
+            return Err(TransactionError::TooManyOperations(self.operations.len()));
+        }
+        if let Memo::Text(text) = &self.memo {
+            if text.len() > MAX_MEMO_TEXT {
+                return Err(TransactionError::MemoTooLong(text.len()));
+            }
+        }
+        if let Some(bounds) = &self.time_bounds {
+            if bounds.max_time != 0 && bounds.max_time < bounds.min_time {
+                return Err(TransactionError::InvalidTimeBounds);
+            }
+        }
+        for operation in &self.operations {
+            operation.validate()?;
+        }
+        let fee = self.base_fee
+            .checked_mul(self.operations.len() as u32)
+            .ok_or(TransactionError::FeeOverflow)?;
+
+        Ok(Transaction {
+            source_account: self.source_account,
+            fee,
+            seq_num: self.seq_num,
+            time_bounds: self.time_bounds,
+            memo: self.memo,
+            operations: self.operations,
+        })
+    }
+}
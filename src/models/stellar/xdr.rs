@@ -0,0 +1,242 @@
+// XDR (RFC 4506) encoding primitives used by the Stellar transaction model
+
+use thiserror::Error;
+
+/// Errors returned when decoding XDR.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum XdrError {
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("Invalid discriminant {value} for {type_name}")]
+    InvalidDiscriminant { type_name: &'static str, value: i32 },
+
+    #[error("Length {length} exceeds the maximum of {max}")]
+    LengthExceeded { length: u32, max: u32 },
+
+    #[error("Non-zero padding")]
+    InvalidPadding,
+
+    #[error("Invalid boolean {0}")]
+    InvalidBool(u32),
+
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+
+    #[error("Invalid base64: {0}")]
+    InvalidBase64(String),
+
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+}
+
+/// A value that can be written as XDR.
+pub trait XdrEncode {
+    fn encode(&self, writer: &mut XdrWriter);
+
+    /// Encodes the value into a new buffer.
+    fn to_xdr(&self) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// Encodes the value as base64, the form Horizon accepts and returns.
+    fn to_xdr_base64(&self) -> String {
+        base64::encode(self.to_xdr())
+    }
+}
+
+/// A value that can be read from XDR.
+pub trait XdrDecode: Sized {
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrError>;
+
+    /// Decodes a value that must span the whole input.
+    fn from_xdr(bytes: &[u8]) -> Result<Self, XdrError> {
+        let mut reader = XdrReader::new(bytes);
+        let value = Self::decode(&mut reader)?;
+        match reader.remaining() {
+            0 => Ok(value),
+            n => Err(XdrError::TrailingBytes(n)),
+        }
+    }
+
+    /// Decodes a base64-encoded value.
+    fn from_xdr_base64(encoded: &str) -> Result<Self, XdrError> {
+        let bytes = base64::decode(encoded.trim()).map_err(|e| XdrError::InvalidBase64(e.to_string()))?;
+        Self::from_xdr(&bytes)
+    }
+}
+
+fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+/// Big-endian XDR writer.
+#[derive(Debug, Default)]
+pub struct XdrWriter {
+    buffer: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u32(value as u32);
+    }
+
+    /// Writes fixed-length opaque data, padded to a multiple of four bytes.
+    pub fn write_fixed_opaque(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+        self.buffer.extend(std::iter::repeat(0).take(padding(data.len())));
+    }
+
+    /// Writes variable-length opaque data or a string: length, bytes, padding.
+    pub fn write_var_opaque(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.write_fixed_opaque(data);
+    }
+
+    /// Writes a variable-length array: length followed by each element.
+    pub fn write_array<T: XdrEncode>(&mut self, items: &[T]) {
+        self.write_u32(items.len() as u32);
+        for item in items {
+            item.encode(self);
+        }
+    }
+
+    /// Writes an optional value: a boolean followed by the value if present.
+    pub fn write_optional<T: XdrEncode>(&mut self, value: Option<&T>) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            value.encode(self);
+        }
+    }
+}
+
+/// Big-endian XDR reader over a byte slice.
+pub struct XdrReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Number of bytes not yet read.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], XdrError> {
+        if self.remaining() < length {
+            return Err(XdrError::UnexpectedEof);
+        }
+        let slice = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(slice)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], XdrError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, XdrError> {
+        Ok(u32::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, XdrError> {
+        Ok(i32::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, XdrError> {
+        Ok(u64::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, XdrError> {
+        Ok(i64::from_be_bytes(self.take_array()?))
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, XdrError> {
+        match self.read_u32()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(XdrError::InvalidBool(other)),
+        }
+    }
+
+    /// Reads fixed-length opaque data and checks that its padding is zero.
+    pub fn read_fixed_opaque<const N: usize>(&mut self) -> Result<[u8; N], XdrError> {
+        let data = self.take_array::<N>()?;
+        self.read_padding(N)?;
+        Ok(data)
+    }
+
+    /// Reads variable-length opaque data of at most `max` bytes.
+    pub fn read_var_opaque(&mut self, max: u32) -> Result<Vec<u8>, XdrError> {
+        let length = self.read_u32()?;
+        if length > max {
+            return Err(XdrError::LengthExceeded { length, max });
+        }
+        let data = self.take(length as usize)?.to_vec();
+        self.read_padding(length as usize)?;
+        Ok(data)
+    }
+
+    /// Reads a string of at most `max` bytes.
+    pub fn read_string(&mut self, max: u32) -> Result<String, XdrError> {
+        let bytes = self.read_var_opaque(max)?;
+        String::from_utf8(bytes).map_err(|e| XdrError::InvalidValue(e.to_string()))
+    }
+
+    /// Reads a variable-length array of at most `max` elements.
+    pub fn read_array<T: XdrDecode>(&mut self, max: u32) -> Result<Vec<T>, XdrError> {
+        let length = self.read_u32()?;
+        if length > max {
+            return Err(XdrError::LengthExceeded { length, max });
+        }
+        (0..length).map(|_| T::decode(self)).collect()
+    }
+
+    /// Reads an optional value.
+    pub fn read_optional<T: XdrDecode>(&mut self) -> Result<Option<T>, XdrError> {
+        if self.read_bool()? {
+            Ok(Some(T::decode(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_padding(&mut self, length: usize) -> Result<(), XdrError> {
+        if self.take(padding(length))?.iter().any(|&b| b != 0) {
+            return Err(XdrError::InvalidPadding);
+        }
+        Ok(())
+    }
+}
@@ -1,57 +1,357 @@
-use aes_gcm::{Aes128Gcm, Key, Nonce, aead::{Aead, NewAead}};
-use rand::Rng;
-use std::error::Error;
+use std::num::NonZeroU32;
+use argon2::{Argon2, Params};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+/// First bytes of every envelope written by this module.
+pub const MAGIC: &[u8; 4] = b"PSEN";
+/// Version of the envelope format.
+pub const ENVELOPE_VERSION: u8 = 1;
+/// Length of the random salt stored with password-derived keys, in bytes.
+pub const SALT_LEN: usize = 16;
+
+// Largest key derivation costs accepted from an envelope, so a forged header cannot make
+// decryption run for minutes or exhaust memory. Each Argon2 pass touches all of its memory,
+// so it gets far fewer iterations than PBKDF2.
+const MAX_ARGON2_MEMORY_KIB: u32 = 256 * 1024;
+const MAX_ARGON2_ITERATIONS: u32 = 10;
+const MAX_ARGON2_PARALLELISM: u32 = 16;
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+const MAX_SALT_LEN: usize = 64;
+const TAG_LEN: usize = 16;
+
+/// Errors returned when deriving keys, encrypting or decrypting.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EncryptionError {
+    #[error("Key must be 16 bytes for AES-128 or 32 bytes for AES-256, got {0}")]
+    InvalidKeyLength(usize),
+
+    #[error("Invalid key derivation parameters: {0}")]
+    InvalidParameters(String),
+
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+
+    #[error("Data is not an encrypted envelope")]
+    NotAnEnvelope,
+
+    #[error("Unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported {kind} {id}")]
+    Unsupported { kind: &'static str, id: u8 },
+
+    #[error("Envelope is truncated")]
+    Truncated,
+
+    #[error("Envelope was encrypted with a {expected}, not a {found}")]
+    WrongSecret { expected: &'static str, found: &'static str },
+
+    #[error("Encryption failed")]
+    Encryption,
+
+    /// The key or password is wrong, or the envelope was modified.
+    #[error("Decryption failed")]
+    Decryption,
+}
+
+/// How a key is derived from a password. Costs are stored in the envelope, so they can be
+/// raised for new data without breaking old data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// Argon2id, memory-hard and the preferred choice.
+    Argon2id { memory_kib: u32, iterations: u32, parallelism: u32 },
+    /// PBKDF2 with HMAC-SHA256, for environments that require it.
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl Kdf {
+    /// Argon2id with 19 MiB of memory and two passes, the OWASP minimum.
+    pub fn argon2id() -> Self {
+        Kdf::Argon2id { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with 600,000 iterations, the OWASP minimum.
+    pub fn pbkdf2() -> Self {
+        Kdf::Pbkdf2Sha256 { iterations: 600_000 }
+    }
+
+    fn id(&self) -> u8 {
+        match self {
+            Kdf::Pbkdf2Sha256 { .. } => 1,
+            Kdf::Argon2id { .. } => 2,
+        }
+    }
+
+    /// Derives a 256-bit key from `password` and `salt`.
+    pub fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<[u8; 32], EncryptionError> {
+        self.validate()?;
+        let mut key = [0u8; 32];
+        match *self {
+            Kdf::Argon2id { memory_kib, iterations, parallelism } => {
+                let params = Params::new(memory_kib, iterations, parallelism, Some(key.len()))
+                    .map_err(|e| EncryptionError::InvalidParameters(e.to_string()))?;
+                Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key)
+                    .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+            }
+            Kdf::Pbkdf2Sha256 { iterations } => {
+                let iterations = NonZeroU32::new(iterations).expect("validated");
+                pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, password, &mut key);
+            }
+        }
+        Ok(key)
+    }
+
+    fn validate(&self) -> Result<(), EncryptionError> {
+        let (iterations, max_iterations) = match *self {
+            Kdf::Argon2id { memory_kib, iterations, parallelism } => {
+                if memory_kib > MAX_ARGON2_MEMORY_KIB || parallelism == 0 || parallelism > MAX_ARGON2_PARALLELISM {
+                    return Err(EncryptionError::InvalidParameters(format!("{} KiB of memory over {} lanes", memory_kib, parallelism)));
+                }
+                (iterations, MAX_ARGON2_ITERATIONS)
+            }
+            Kdf::Pbkdf2Sha256 { iterations } => (iterations, MAX_PBKDF2_ITERATIONS),
+        };
+        if iterations == 0 || iterations > max_iterations {
+            return Err(EncryptionError::InvalidParameters(format!("{} iterations", iterations)));
+        }
+        Ok(())
+    }
+}
 
 /// Encrypts the given data using AES-GCM with the provided key.
 ///
 /// # Arguments
 ///
 /// * `data` - The data to encrypt.
-/// * `key` - The encryption key (must be 16 bytes for AES-128).
+/// * `key` - The encryption key: 16 bytes for AES-128 or 32 bytes for AES-256.
 ///
 /// # Returns
 ///
-/// A vector containing the nonce followed by the encrypted data.
-pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    if key.len() != 16 {
-        return Err("Key must be 16 bytes long for AES-128.".into());
-    }
-
-    let cipher = Aes128Gcm::new(Key::from_slice(key));
-    let mut nonce = [0u8; 12]; // AES-GCM nonce size is 12 bytes
-    rand::thread_rng().fill(&mut nonce); // Generate a random nonce
-    let nonce = Nonce::from_slice(&nonce); // Create a Nonce from the random bytes
+/// An envelope naming the cipher, followed by the nonce and the encrypted data.
+pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let cipher = cipher_for(key.len())?;
+    seal(Header { cipher, kdf: None }, key, data)
+}
 
-    let encrypted_data = cipher.encrypt(nonce, data)
-        .map_err(|_| "Encryption failed.".to_string())?;
+/// Decrypts an envelope written by `encrypt` using the provided key.
+///
+/// Data without the envelope's magic is read as the earlier format, the nonce followed by the
+/// encrypted data under a 16-byte AES-128-GCM key, so files written before envelopes were
+/// introduced can still be opened.
+///
+/// # Arguments
+///
+/// * `data` - The envelope to decrypt.
+/// * `key` - The key it was encrypted with.
+///
+/// # Returns
+///
+/// The decrypted data, or an error if the envelope is truncated, was modified or was
+/// encrypted with a different key.
+pub fn decrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    if !data.starts_with(MAGIC) && key.len() == 16 {
+        return open_legacy(key, data);
+    }
+    let (header, header_len) = Header::parse(data)?;
+    if header.kdf.is_some() {
+        return Err(EncryptionError::WrongSecret { expected: "password", found: "key" });
+    }
+    if cipher_for(key.len())? != header.cipher {
+        return Err(EncryptionError::Decryption);
+    }
+    open(&header, header_len, key, data)
+}
 
-    let mut result = Vec::new();
-    result.extend_from_slice(&nonce);
-    result.extend_from_slice(&encrypted_data);
-    Ok(result)
+/// Encrypts the given data using AES-256-GCM with a key derived from a password.
+///
+/// # Arguments
+///
+/// * `data` - The data to encrypt.
+/// * `password` - The password to derive the key from.
+/// * `kdf` - The key derivation function and its costs.
+///
+/// # Returns
+///
+/// An envelope naming the cipher and key derivation, with its parameters and a random salt,
+/// followed by the nonce and the encrypted data.
+pub fn encrypt_with_password(data: &[u8], password: &[u8], kdf: Kdf) -> Result<Vec<u8>, EncryptionError> {
+    let mut salt = vec![0u8; SALT_LEN];
+    SystemRandom::new().fill(&mut salt).map_err(|_| EncryptionError::Encryption)?;
+    let key = kdf.derive_key(password, &salt)?;
+    seal(Header { cipher: Cipher::Aes256Gcm, kdf: Some((kdf, salt)) }, &key, data)
 }
 
-/// Decrypts the given data using AES-GCM with the provided key.
+/// Decrypts an envelope written by `encrypt_with_password`.
 ///
 /// # Arguments
 ///
-/// * `data` - The data to decrypt (must include the nonce).
-/// * `key` - The decryption key (must be 16 bytes for AES-128).
+/// * `data` - The envelope to decrypt.
+/// * `password` - The password it was encrypted with.
 ///
 /// # Returns
 ///
-/// The decrypted data.
-pub fn decrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    if key.len() != 16 {
-        return Err("Key must be 16 bytes long for AES-128.".into());
+/// The decrypted data, or an error if the envelope is truncated, was modified or was
+/// encrypted with a different password.
+pub fn decrypt_with_password(data: &[u8], password: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let (header, header_len) = Header::parse(data)?;
+    let Some((kdf, salt)) = &header.kdf else {
+        return Err(EncryptionError::WrongSecret { expected: "key", found: "password" });
+    };
+    let key = kdf.derive_key(password, salt)?;
+    open(&header, header_len, &key, data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cipher {
+    Aes128Gcm,
+    Aes256Gcm,
+}
+
+impl Cipher {
+    fn id(self) -> u8 {
+        match self {
+            Cipher::Aes128Gcm => 1,
+            Cipher::Aes256Gcm => 2,
+        }
     }
 
-    let cipher = Aes128Gcm::new(Key::from_slice(key));
-    let nonce = Nonce::from_slice(&data[..12]); // Extract the nonce from the data
-    let encrypted_data = &data[12..]; // The rest is the encrypted data
+    fn aead(self) -> &'static ring::aead::Algorithm {
+        match self {
+            Cipher::Aes128Gcm => &AES_128_GCM,
+            Cipher::Aes256Gcm => &AES_256_GCM,
+        }
+    }
+}
 
-    let decrypted_data = cipher.decrypt(nonce, encrypted_data)
-        .map_err(|_| "Decryption failed.".to_string())?;
+fn cipher_for(key_len: usize) -> Result<Cipher, EncryptionError> {
+    match key_len {
+        16 => Ok(Cipher::Aes128Gcm),
+        32 => Ok(Cipher::Aes256Gcm),
+        other => Err(EncryptionError::InvalidKeyLength(other)),
+    }
+}
+
+// Envelope layout, all integers big-endian:
+//   magic "PSEN" | version | cipher ID | KDF ID (0 for a raw key)
+//   [KDF parameters as u32s | salt length u8 | salt]
+//   nonce | ciphertext and tag
+// Everything before the nonce is authenticated along with the ciphertext.
+struct Header {
+    cipher: Cipher,
+    kdf: Option<(Kdf, Vec<u8>)>,
+}
+
+impl Header {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[ENVELOPE_VERSION, self.cipher.id(), self.kdf.as_ref().map_or(0, |(kdf, _)| kdf.id())]);
+        if let Some((kdf, salt)) = &self.kdf {
+            let params = match *kdf {
+                Kdf::Pbkdf2Sha256 { iterations } => vec![iterations],
+                Kdf::Argon2id { memory_kib, iterations, parallelism } => vec![memory_kib, iterations, parallelism],
+            };
+            for param in params {
+                bytes.extend_from_slice(&param.to_be_bytes());
+            }
+            bytes.push(salt.len() as u8);
+            bytes.extend_from_slice(salt);
+        }
+        bytes
+    }
 
-    Ok(decrypted_data)
+    // Returns the header and its length in bytes
+    fn parse(data: &[u8]) -> Result<(Self, usize), EncryptionError> {
+        let mut reader = Reader { data, position: 0 };
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(EncryptionError::NotAnEnvelope);
         }
+        reader.position = MAGIC.len();
+        let version = reader.u8()?;
+        if version != ENVELOPE_VERSION {
+            return Err(EncryptionError::UnsupportedVersion(version));
+        }
+        let cipher = match reader.u8()? {
+            1 => Cipher::Aes128Gcm,
+            2 => Cipher::Aes256Gcm,
+            id => return Err(EncryptionError::Unsupported { kind: "cipher", id }),
+        };
+        let kdf = match reader.u8()? {
+            0 => None,
+            1 => Some(Kdf::Pbkdf2Sha256 { iterations: reader.u32()? }),
+            2 => Some(Kdf::Argon2id { memory_kib: reader.u32()?, iterations: reader.u32()?, parallelism: reader.u32()? }),
+            id => return Err(EncryptionError::Unsupported { kind: "key derivation", id }),
+        };
+        let kdf = match kdf {
+            Some(kdf) => {
+                let salt_len = reader.u8()? as usize;
+                if salt_len > MAX_SALT_LEN {
+                    return Err(EncryptionError::InvalidParameters(format!("{}-byte salt", salt_len)));
+                }
+                Some((kdf, reader.take(salt_len)?.to_vec()))
+            }
+            None => None,
+        };
+        Ok((Header { cipher, kdf }, reader.position))
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], EncryptionError> {
+        let bytes = self.data.get(self.position..self.position + length).ok_or(EncryptionError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, EncryptionError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, EncryptionError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+fn seal(header: Header, key: &[u8], data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| EncryptionError::Encryption)?;
+    let mut envelope = header.encode();
+    let key = LessSafeKey::new(UnboundKey::new(header.cipher.aead(), key).map_err(|_| EncryptionError::InvalidKeyLength(key.len()))?);
+
+    let mut sealed = data.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(&envelope), &mut sealed)
+        .map_err(|_| EncryptionError::Encryption)?;
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&sealed);
+    Ok(envelope)
+}
+
+fn open(header: &Header, header_len: usize, key: &[u8], data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    if data.len() < header_len + NONCE_LEN + TAG_LEN {
+        return Err(EncryptionError::Truncated);
+    }
+    let (authenticated, rest) = data.split_at(header_len);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| EncryptionError::Truncated)?;
+    let key = LessSafeKey::new(UnboundKey::new(header.cipher.aead(), key).map_err(|_| EncryptionError::InvalidKeyLength(key.len()))?);
+
+    let mut plaintext = sealed.to_vec();
+    let length = key.open_in_place(nonce, Aad::from(authenticated), &mut plaintext).map_err(|_| EncryptionError::Decryption)?.len();
+    plaintext.truncate(length);
+    Ok(plaintext)
+}
+
+// The format written before envelopes: nonce | ciphertext and tag, with no authenticated header
+fn open_legacy(key: &[u8], data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let header = Header { cipher: Cipher::Aes128Gcm, kdf: None };
+    open(&header, 0, key, data)
+}
@@ -73,4 +73,114 @@ mod tests {
         let result = decrypt(&encrypted_data, &key2);
         assert!(result.is_err(), "Decryption should fail with an invalid key.");
     }
+
+    // Low costs keep the tests fast; real callers use Kdf::argon2id() or Kdf::pbkdf2()
+    const FAST_ARGON2: Kdf = Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 };
+    const FAST_PBKDF2: Kdf = Kdf::Pbkdf2Sha256 { iterations: 1_000 };
+
+    #[test]
+    fn test_encryption_with_aes_128_key() {
+        let key: [u8; 16] = rand::thread_rng().gen();
+        let encrypted_data = encrypt(b"Hello, world!", &key).expect("Encryption failed");
+        assert_eq!(decrypt(&encrypted_data, &key).unwrap(), b"Hello, world!");
+
+        assert_eq!(encrypt(b"Hello, world!", &[0u8; 24]), Err(EncryptionError::InvalidKeyLength(24)));
+    }
+
+    #[test]
+    fn test_password_encryption() {
+        for kdf in [FAST_ARGON2, FAST_PBKDF2] {
+            let encrypted_data = encrypt_with_password(b"Hello, world!", b"correct horse", kdf).expect("Encryption failed");
+            assert_eq!(decrypt_with_password(&encrypted_data, b"correct horse").unwrap(), b"Hello, world!");
+            assert_eq!(decrypt_with_password(&encrypted_data, b"wrong horse"), Err(EncryptionError::Decryption));
+        }
+    }
+
+    #[test]
+    fn test_password_encryption_uses_a_fresh_salt() {
+        let first = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_PBKDF2).unwrap();
+        let second = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_PBKDF2).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_key_and_password_envelopes_are_not_interchangeable() {
+        let key: [u8; 32] = rand::thread_rng().gen();
+        let encrypted_data = encrypt(b"Hello, world!", &key).unwrap();
+        assert!(matches!(decrypt_with_password(&encrypted_data, b"correct horse"), Err(EncryptionError::WrongSecret { .. })));
+
+        let encrypted_data = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_PBKDF2).unwrap();
+        assert!(matches!(decrypt(&encrypted_data, &key), Err(EncryptionError::WrongSecret { .. })));
+    }
+
+    #[test]
+    fn test_truncated_data_is_rejected() {
+        let key: [u8; 32] = rand::thread_rng().gen();
+        let encrypted_data = encrypt(b"Hello, world!", &key).unwrap();
+
+        assert_eq!(decrypt(&[], &key), Err(EncryptionError::NotAnEnvelope));
+        assert_eq!(decrypt(&encrypted_data[..6], &key), Err(EncryptionError::Truncated));
+        assert_eq!(decrypt(&encrypted_data[..20], &key), Err(EncryptionError::Truncated));
+        assert_eq!(decrypt(&encrypted_data[..encrypted_data.len() - 1], &key), Err(EncryptionError::Decryption));
+
+        let encrypted_data = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_PBKDF2).unwrap();
+        assert_eq!(decrypt_with_password(&encrypted_data[..12], b"correct horse"), Err(EncryptionError::Truncated));
+    }
+
+    #[test]
+    fn test_tampered_data_is_rejected() {
+        let encrypted_data = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_PBKDF2).unwrap();
+
+        // Flipping any ciphertext or authenticated header byte fails decryption
+        let mut tampered = encrypted_data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(decrypt_with_password(&tampered, b"correct horse"), Err(EncryptionError::Decryption));
+        let mut tampered = encrypted_data.clone();
+        tampered[20] ^= 1;
+        assert_eq!(decrypt_with_password(&tampered, b"correct horse"), Err(EncryptionError::Decryption));
+
+        // Unknown versions and forged costs are refused before any key derivation
+        let mut tampered = encrypted_data.clone();
+        tampered[4] = 9;
+        assert_eq!(decrypt_with_password(&tampered, b"correct horse"), Err(EncryptionError::UnsupportedVersion(9)));
+        let mut tampered = encrypted_data;
+        tampered[7..11].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(decrypt_with_password(&tampered, b"correct horse"), Err(EncryptionError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_forged_argon2_costs_are_capped_or_authenticated() {
+        // Argon2 header: magic, version, cipher and KDF IDs, then memory, iterations and lanes
+        let encrypted_data = encrypt_with_password(b"Hello, world!", b"correct horse", FAST_ARGON2).unwrap();
+
+        // Costs PBKDF2 would accept are still far too much for Argon2
+        for (range, cost) in [(7..11, 256 * 1024 + 1), (11..15, 11), (15..19, 17)] {
+            let mut forged = encrypted_data.clone();
+            forged[range].copy_from_slice(&u32::to_be_bytes(cost));
+            assert!(matches!(decrypt_with_password(&forged, b"correct horse"), Err(EncryptionError::InvalidParameters(_))));
+        }
+
+        // In-range costs are derived with, but the header is authenticated so decryption fails
+        let mut forged = encrypted_data;
+        forged[11..15].copy_from_slice(&u32::to_be_bytes(10));
+        assert_eq!(decrypt_with_password(&forged, b"correct horse"), Err(EncryptionError::Decryption));
+    }
+
+    #[test]
+    fn test_data_from_before_envelopes_can_be_decrypted() {
+        use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM};
+
+        // The earlier format: a nonce, then AES-128-GCM ciphertext with no associated data
+        let key: [u8; 16] = rand::thread_rng().gen();
+        let nonce: [u8; 12] = rand::thread_rng().gen();
+        let mut sealed = b"Hello, world!".to_vec();
+        LessSafeKey::new(UnboundKey::new(&AES_128_GCM, &key).unwrap())
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+            .unwrap();
+        let legacy = [&nonce[..], &sealed].concat();
+
+        assert_eq!(decrypt(&legacy, &key).unwrap(), b"Hello, world!");
+        assert_eq!(decrypt(&legacy[..20], &key), Err(EncryptionError::Truncated));
+        assert_eq!(decrypt(&legacy, &rand::thread_rng().gen::<[u8; 16]>()), Err(EncryptionError::Decryption));
     }
+}
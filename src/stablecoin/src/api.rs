@@ -27,7 +27,7 @@ struct AppState {
     balances: Mutex<HashMap<String, u64>>, // User balances
 }
 
-pub async fn run_api() -> std::io::Result<()> {
+pub async fn run_api(bind_address: &str) -> std::io::Result<()> {
     let state = web::Data::new(AppState {
         balances: Mutex::new(HashMap::new()),
     });
@@ -40,7 +40,7 @@ pub async fn run_api() -> std::io::Result<()> {
             .route("/transfer", web::post().to(transfer))
             .route("/balance/{user}", web::get().to(get_balance))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(bind_address)?
     .run()
     .await
 }
@@ -2,16 +2,22 @@ use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
-    timestamp: DateTime<Utc>,
-    message: String,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
 }
 
 pub struct EventLogger {
     events: Arc<Mutex<Vec<Event>>>, // Use Arc and Mutex for thread-safe access
 }
 
+impl Default for EventLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EventLogger {
     pub fn new() -> Self {
         EventLogger {
@@ -9,6 +9,12 @@ mod multi_sig_wallet;
 mod api;
 mod events;
 mod utils;
+// Shared with the main crate so both validate Stellar addresses the same way
+#[path = "../../models/stellar/strkey.rs"]
+mod strkey;
+// Shared with the main crate so both serve the same network
+#[path = "../../cross_chain/network.rs"]
+mod network;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -16,14 +22,16 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_web=info,info");
     env_logger::init();
 
-    println!("Starting Pi Coin Stablecoin API...");
+    let network = network::load_selected(env::args())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    println!("Starting Pi Coin Stablecoin API on {} ({})...", network.bind_address, network.name);
 
     // Run the API server
-    run_api().await
+    run_api(&network.bind_address).await
 }
 
 // Enhanced run_api function to include middleware and error handling
-pub async fn run_api() -> std::io::Result<()> {
+pub async fn run_api(bind_address: &str) -> std::io::Result<()> {
     HttpServer::new(|| {
         App::new()
             .wrap(Logger::default()) // Enable logging middleware
@@ -32,7 +40,7 @@ pub async fn run_api() -> std::io::Result<()> {
             .route("/transfer", web::post().to(api::transfer))
             .route("/balance/{user}", web::get().to(api::get_balance))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(bind_address)?
     .run()
     .await
                                                     }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use crate::events::EventLogger;
+// Also compiled into the main crate, which issues Pi Coin on Stellar against this ledger
+use super::events::EventLogger;
 
 pub struct SmartContract {
     balances: HashMap<String, u64>,
@@ -8,6 +9,12 @@ pub struct SmartContract {
     event_logger: EventLogger,
 }
 
+impl Default for SmartContract {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SmartContract {
     const INITIAL_PI_VALUE: f64 = 314159.00; // Set the initial value of Pi Coin
 
@@ -1,3 +1,5 @@
+use crate::strkey;
+
 /// Validates a Stellar address.
 ///
 /// # Arguments
@@ -6,40 +8,10 @@
 ///
 /// # Returns
 ///
-/// Returns `true` if the address is valid, `false` otherwise.
+/// Returns `true` if the address is a `G...` account address with a valid
+/// StrKey encoding and checksum, `false` otherwise.
 pub fn validate_address(address: &str) -> bool {
-    // Check if the address length is exactly 56 characters
-    if address.len() != 56 {
-        return false;
-    }
-
-    // Check if the address starts with 'G'
-    if !address.starts_with("G") {
-        return false;
-    }
-
-    // Additional checks can be added here, such as checking for valid characters
-    // Stellar addresses are base32 encoded, so we can check for valid base32 characters
-    for c in address.chars() {
-        if !is_valid_base32_char(c) {
-            return false;
-        }
-    }
-
-    true
-}
-
-/// Checks if a character is a valid base32 character.
-///
-/// # Arguments
-///
-/// * `c` - A character to be checked.
-///
-/// # Returns
-///
-/// Returns `true` if the character is a valid base32 character, `false` otherwise.
-fn is_valid_base32_char(c: char) -> bool {
-    c.is_ascii_alphanumeric() && (c.is_digit(10) || matches!(c, 'A'..='Z' | 'a'..='z'))
+    strkey::decode_account_id(address).is_ok()
 }
 
 #[cfg(test)]
@@ -48,25 +20,35 @@ mod tests {
 
     #[test]
     fn test_valid_address() {
-        let valid_address = "GABCD12345678901234567890123456789012345678901234";
+        let valid_address = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN7";
         assert!(validate_address(valid_address));
     }
 
+    #[test]
+    fn test_invalid_checksum() {
+        let invalid_address = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN6"; // Last character changed
+        assert!(!validate_address(invalid_address));
+    }
+
     #[test]
     fn test_invalid_length() {
-        let invalid_address = "GABCD1234567890123456789012345678901234567890123"; // 55 characters
+        let invalid_address = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN"; // 55 characters
         assert!(!validate_address(invalid_address));
     }
 
     #[test]
     fn test_invalid_prefix() {
-        let invalid_address = "XABCD12345678901234567890123456789012345678901234"; // Does not start with 'G'
-        assert!(!validate_address(invalid_address));
+        let seed = "SAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQC5MY"; // A secret seed, not an account
+        assert!(!validate_address(seed));
     }
 
     #[test]
     fn test_invalid_character() {
-        let invalid_address = "GABCD1234567890123456789012345678901234567890123$"; // Contains invalid character '$'
-        assert!(!validate_address(invalid_address));
+        let lowercase = "gaazi4tcr3ty5ojhctjc2a4qsy6cjwjh5iajtgkin2er7lbnvkoccwn7";
+        assert!(!validate_address(lowercase));
+        let not_base32 = "GABCD12345678901234567890123456789012345678901234567890"; // 0, 1, 8 and 9 are not base32
+        assert!(!validate_address(not_base32));
+        let symbol = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN$";
+        assert!(!validate_address(symbol));
     }
 }
@@ -0,0 +1,10 @@
+// Fixtures shared by the unit tests
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+
+/// A deterministic keypair whose secret key is `seed` repeated 32 times.
+pub fn keypair(seed: u8) -> Keypair {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
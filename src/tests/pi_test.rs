@@ -1,6 +1,28 @@
 // Unit tests for the Pi Network implementation
 
-use super::models::pi::PiNetworkImpl;
+use super::models::pi::pi_network::{DEFAULT_CHAIN_ID, MIN_FEE};
+use super::models::pi::{PiLedgerError, PiNetwork, PiNetworkImpl, PiTransfer};
+use super::models::stellar::strkey;
+use super::fixtures::keypair;
+use ed25519_dalek::Keypair;
+
+const PI: u64 = 100_000_000;
+
+fn address(keypair: &Keypair) -> String {
+    strkey::encode_account_id(&keypair.public.to_bytes())
+}
+
+fn transfer(from: &Keypair, to: &Keypair, amount: u64, nonce: u64) -> PiTransfer {
+    PiTransfer {
+        chain_id: DEFAULT_CHAIN_ID.to_string(),
+        from: address(from),
+        to: address(to),
+        amount,
+        fee: MIN_FEE,
+        nonce,
+        memo: None,
+    }
+}
 
 #[test]
 fn test_get_pi_data() {
@@ -12,6 +34,88 @@ fn test_get_pi_data() {
 #[test]
 fn test_process_pi_transaction() {
     let mut pi_network = PiNetworkImpl::new();
-    let transaction = vec![1, 2, 3, 4, 5];
-    pi_network.process_pi_transaction(transaction).unwrap();
+    let (alice, bob) = (keypair(1), keypair(2));
+    pi_network.fund_account(&address(&alice), 10 * PI).unwrap();
+    assert!(matches!(pi_network.process_pi_transaction(vec![1, 2, 3, 4, 5]), Err(PiLedgerError::Decode(_))));
+
+    let signed = transfer(&alice, &bob, 3 * PI, 1).sign(&alice);
+    let id = pi_network.process_pi_transaction(signed.to_bytes()).unwrap();
+    assert_eq!(id, signed.transfer.id());
+    assert_eq!(pi_network.balance(&address(&alice)), 7 * PI - MIN_FEE);
+    assert_eq!(pi_network.balance(&address(&bob)), 3 * PI);
+    assert_eq!(pi_network.account(&address(&alice)).unwrap().nonce, 1);
+    assert_eq!(pi_network.fee_pool(), MIN_FEE);
+    assert_eq!(pi_network.get_pi_data(), signed.to_bytes());
+
+    // The recipient can spend what it received
+    pi_network.apply_transaction(&transfer(&bob, &alice, PI, 1).sign(&bob)).unwrap();
+    assert_eq!(pi_network.balance(&address(&bob)), 2 * PI - MIN_FEE);
+}
+
+#[test]
+fn test_rejects_invalid_transfers() {
+    let mut pi_network = PiNetworkImpl::new();
+    let (alice, bob) = (keypair(1), keypair(2));
+    pi_network.fund_account(&address(&alice), PI).unwrap();
+
+    let forged = transfer(&alice, &bob, 1, 1).sign(&bob);
+    assert_eq!(pi_network.apply_transaction(&forged), Err(PiLedgerError::BadSignature(address(&alice))));
+
+    let mut tampered = transfer(&alice, &bob, 1, 1).sign(&alice);
+    tampered.transfer.amount = PI / 2;
+    assert!(matches!(pi_network.apply_transaction(&tampered), Err(PiLedgerError::BadSignature(_))));
+
+    let skipped = transfer(&alice, &bob, 1, 2).sign(&alice);
+    assert_eq!(pi_network.apply_transaction(&skipped), Err(PiLedgerError::BadNonce { expected: 1, found: 2 }));
+
+    let overdrawn = transfer(&alice, &bob, PI, 1).sign(&alice);
+    assert_eq!(
+        pi_network.apply_transaction(&overdrawn),
+        Err(PiLedgerError::InsufficientBalance { account: address(&alice), needed: PI + MIN_FEE, available: PI })
+    );
+
+    let mut cheap = transfer(&alice, &bob, 1, 1);
+    cheap.fee = MIN_FEE - 1;
+    assert!(matches!(pi_network.apply_transaction(&cheap.sign(&alice)), Err(PiLedgerError::InsufficientFee { .. })));
+
+    let mut other_chain = transfer(&alice, &bob, 1, 1);
+    other_chain.chain_id = "pi-mainnet-1".to_string();
+    assert!(matches!(pi_network.apply_transaction(&other_chain.sign(&alice)), Err(PiLedgerError::WrongChain { .. })));
+
+    let mut bad_recipient = transfer(&alice, &bob, 1, 1);
+    bad_recipient.to = "pi-bob".to_string();
+    assert!(matches!(pi_network.apply_transaction(&bad_recipient.sign(&alice)), Err(PiLedgerError::InvalidAddress { .. })));
+
+    let unfunded = transfer(&bob, &alice, 1, 1).sign(&bob);
+    assert_eq!(pi_network.apply_transaction(&unfunded), Err(PiLedgerError::AccountNotFound(address(&bob))));
+
+    assert_eq!(pi_network.balance(&address(&alice)), PI);
+    assert!(pi_network.get_pi_data().is_empty());
+}
+
+#[test]
+fn test_replayed_transfer_is_rejected() {
+    let mut pi_network = PiNetworkImpl::new();
+    let (alice, bob) = (keypair(1), keypair(2));
+    pi_network.fund_account(&address(&alice), PI).unwrap();
+
+    let signed = transfer(&alice, &bob, 1_000, 1).sign(&alice);
+    pi_network.process_pi_transaction(signed.to_bytes()).unwrap();
+    let replay = pi_network.process_pi_transaction(signed.to_bytes());
+    assert_eq!(replay, Err(PiLedgerError::BadNonce { expected: 2, found: 1 }));
+    assert_eq!(pi_network.balance(&address(&bob)), 1_000);
+}
+
+#[test]
+fn test_balance_overflow_is_rejected() {
+    let mut pi_network = PiNetworkImpl::new();
+    let (alice, bob) = (keypair(1), keypair(2));
+    pi_network.fund_account(&address(&alice), PI).unwrap();
+    pi_network.fund_account(&address(&bob), u64::MAX).unwrap();
+    assert!(matches!(pi_network.fund_account(&address(&bob), 1), Err(PiLedgerError::Invalid(_))));
+
+    let overflowing = transfer(&alice, &bob, 1, 1).sign(&alice);
+    assert!(matches!(pi_network.apply_transaction(&overflowing), Err(PiLedgerError::Invalid(_))));
+    assert_eq!(pi_network.balance(&address(&alice)), PI);
+    assert_eq!(pi_network.balance(&address(&bob)), u64::MAX);
 }
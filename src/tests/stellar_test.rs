@@ -1,6 +1,37 @@
 // Unit tests for the Stellar protocol implementation
 
-use super::models::stellar::StellarProtocolImpl;
+use super::models::stellar::stellar_protocol::BASE_RESERVE;
+use super::models::stellar::transaction::{
+    AccountId, Asset, MuxedAccount, Network, Operation, TimeBounds, TransactionBuilder, TransactionEnvelope, AUTHORIZED_FLAG,
+    AUTH_CLAWBACK_ENABLED_FLAG, AUTH_IMMUTABLE_FLAG, AUTH_REQUIRED_FLAG, AUTH_REVOCABLE_FLAG, TRUSTLINE_CLAWBACK_ENABLED_FLAG,
+};
+use super::models::stellar::xdr::XdrEncode;
+use super::models::stellar::{LedgerError, StellarProtocol, StellarProtocolImpl};
+use super::fixtures::keypair;
+use ed25519_dalek::Keypair;
+
+const XLM: i64 = 10_000_000;
+
+fn account(keypair: &Keypair) -> AccountId {
+    AccountId::from(&keypair.public)
+}
+
+fn envelope(source: &Keypair, seq_num: i64, operations: Vec<Operation>) -> TransactionEnvelope {
+    let builder = operations.into_iter().fold(TransactionBuilder::new(account(source), seq_num), |b, op| b.add_operation(op));
+    builder.build().unwrap().sign(&Network::testnet(), source)
+}
+
+fn next_seq(keypair: &Keypair, ledger: &StellarProtocolImpl) -> i64 {
+    ledger.account(&account(keypair)).unwrap().sequence + 1
+}
+
+// A ledger where keypair 1 holds 100 XLM; returns it with the account's next sequence number
+fn funded() -> (StellarProtocolImpl, i64) {
+    let mut ledger = StellarProtocolImpl::new();
+    ledger.fund_account(account(&keypair(1)), 100 * XLM).unwrap();
+    let next = next_seq(&keypair(1), &ledger);
+    (ledger, next)
+}
 
 #[test]
 fn test_get_stellar_data() {
@@ -11,7 +42,183 @@ fn test_get_stellar_data() {
 
 #[test]
 fn test_process_stellar_transaction() {
-    let mut stellar_protocol = StellarProtocolImpl::new();
-    let transaction = vec![1, 2, 3, 4, 5];
-    stellar_protocol.process_stellar_transaction(transaction).unwrap();
+    let (mut ledger, seq) = funded();
+    let alice = account(&keypair(1));
+    let bob = account(&keypair(2));
+    assert!(matches!(ledger.process_stellar_transaction(vec![1, 2, 3, 4, 5]), Err(LedgerError::Decode(_))));
+
+    let create = envelope(&keypair(1), seq, vec![Operation::create_account(bob, 10 * XLM)]);
+    let hash = ledger.process_stellar_transaction(create.to_xdr()).unwrap();
+    assert_eq!(hash, create.tx.hash(&Network::testnet()));
+    assert_eq!(ledger.balance(&alice, &Asset::Native), 90 * XLM - 100);
+    assert_eq!(ledger.balance(&bob, &Asset::Native), 10 * XLM);
+    assert_eq!(ledger.fee_pool(), 100);
+    assert_eq!(ledger.get_stellar_data(), create.to_xdr());
+
+    let pay = envelope(&keypair(1), seq + 1, vec![Operation::payment(MuxedAccount::from(bob), Asset::Native, 5 * XLM)]);
+    ledger.apply_transaction(&pay).unwrap();
+    assert_eq!(ledger.balance(&bob, &Asset::Native), 15 * XLM);
+    assert_eq!(ledger.account(&alice).unwrap().sequence, seq + 1);
+}
+
+#[test]
+fn test_rejects_invalid_transactions() {
+    let (mut ledger, seq) = funded();
+    let bob = MuxedAccount::from(account(&keypair(2)));
+    let pay = || vec![Operation::payment(bob, Asset::Native, XLM)];
+
+    let unknown = envelope(&keypair(3), 1, pay());
+    assert!(matches!(ledger.apply_transaction(&unknown), Err(LedgerError::AccountNotFound(_))));
+
+    let replayed = envelope(&keypair(1), seq + 1, pay());
+    assert_eq!(ledger.apply_transaction(&replayed), Err(LedgerError::BadSequence { expected: seq, found: seq + 1 }));
+
+    // Signed by someone other than the source account
+    let mut forged = envelope(&keypair(1), seq, pay());
+    forged.signatures.clear();
+    forged.sign(&Network::testnet(), &keypair(2)).unwrap();
+    assert!(matches!(ledger.apply_transaction(&forged), Err(LedgerError::MissingSignature(_))));
+
+    let mut underpaid = envelope(&keypair(1), seq, pay());
+    underpaid.tx.fee = 99;
+    assert_eq!(ledger.apply_transaction(&underpaid), Err(LedgerError::InsufficientFee { required: 100, offered: 99 }));
+
+    ledger.set_close_time(2_000);
+    let expired = TransactionBuilder::new(account(&keypair(1)), seq)
+        .time_bounds(TimeBounds { min_time: 0, max_time: 1_000 })
+        .add_operation(Operation::payment(bob, Asset::Native, XLM))
+        .build()
+        .unwrap()
+        .sign(&Network::testnet(), &keypair(1));
+    assert_eq!(ledger.apply_transaction(&expired), Err(LedgerError::OutsideTimeBounds));
+
+    // None of the rejected transactions touched the ledger
+    assert_eq!(ledger.balance(&account(&keypair(1)), &Asset::Native), 100 * XLM);
+    assert_eq!(ledger.account(&account(&keypair(1))).unwrap().sequence, seq - 1);
+    assert!(ledger.get_stellar_data().is_empty());
+}
+
+#[test]
+fn test_failed_operations_still_pay_the_fee() {
+    let (mut ledger, seq) = funded();
+    let alice = account(&keypair(1));
+    let bob = account(&keypair(2));
+
+    // The whole transaction is rolled back when its second operation overdraws
+    let overdraw = envelope(&keypair(1), seq, vec![
+        Operation::create_account(bob, 10 * XLM),
+        Operation::payment(MuxedAccount::from(bob), Asset::Native, 90 * XLM),
+    ]);
+    let error = ledger.apply_transaction(&overdraw).unwrap_err();
+    assert!(matches!(error, LedgerError::OperationFailed { index: 1, .. }));
+    assert!(ledger.account(&bob).is_none());
+    assert_eq!(ledger.balance(&alice, &Asset::Native), 100 * XLM - 200);
+    assert_eq!(ledger.account(&alice).unwrap().sequence, seq);
+
+    let low = envelope(&keypair(1), seq + 1, vec![Operation::create_account(bob, BASE_RESERVE)]);
+    let error = ledger.apply_transaction(&low).unwrap_err();
+    assert_eq!(error, LedgerError::OperationFailed { index: 0, error: Box::new(LedgerError::LowReserve(2 * BASE_RESERVE)) });
+}
+
+#[test]
+fn test_issued_asset_trustlines() {
+    let mut ledger = StellarProtocolImpl::new();
+    let (issuer, holder) = (keypair(1), keypair(2));
+    ledger.fund_account(account(&issuer), 100 * XLM).unwrap();
+    ledger.fund_account(account(&holder), 100 * XLM).unwrap();
+    let usd = Asset::credit("USD", account(&issuer)).unwrap();
+    let to_holder = MuxedAccount::from(account(&holder));
+    let to_issuer = MuxedAccount::from(account(&issuer));
+
+    let early = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::payment(to_holder, usd.clone(), 50)]);
+    let no_trustline = LedgerError::NoTrustline { account: account(&holder).to_strkey(), asset: "USD".to_string() };
+    assert_eq!(ledger.apply_transaction(&early), Err(LedgerError::OperationFailed { index: 0, error: Box::new(no_trustline) }));
+
+    let trust = envelope(&holder, next_seq(&holder, &ledger), vec![Operation::change_trust(usd.clone(), 100)]);
+    ledger.apply_transaction(&trust).unwrap();
+    assert_eq!(ledger.account(&account(&holder)).unwrap().minimum_balance(), 3 * BASE_RESERVE);
+
+    // Issuing creates the asset; the holder's limit caps it
+    let issue = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::payment(to_holder, usd.clone(), 80)]);
+    ledger.apply_transaction(&issue).unwrap();
+    assert_eq!(ledger.balance(&account(&holder), &usd), 80);
+    let over = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::payment(to_holder, usd.clone(), 21)]);
+    assert!(ledger.apply_transaction(&over).is_err());
+
+    let remove = envelope(&holder, next_seq(&holder, &ledger), vec![Operation::change_trust(usd.clone(), 0)]);
+    assert!(ledger.apply_transaction(&remove).is_err());
+
+    // Paying the issuer burns the asset, after which the trustline can go
+    let redeem = envelope(&holder, next_seq(&holder, &ledger), vec![
+        Operation::payment(to_issuer, usd.clone(), 80),
+        Operation::change_trust(usd.clone(), 0),
+    ]);
+    ledger.apply_transaction(&redeem).unwrap();
+    assert_eq!(ledger.balance(&account(&holder), &usd), 0);
+    assert!(ledger.account(&account(&holder)).unwrap().trustlines.is_empty());
+
+    let path = envelope(&holder, next_seq(&holder, &ledger), vec![Operation::path_payment_strict_send(Asset::Native, 1, to_issuer, Asset::Native, 1, vec![])]);
+    let error = ledger.apply_transaction(&path).unwrap_err();
+    assert_eq!(error, LedgerError::OperationFailed { index: 0, error: Box::new(LedgerError::Unsupported("Path payment")) });
+}
+
+#[test]
+fn test_authorization_and_clawback() {
+    let mut ledger = StellarProtocolImpl::new();
+    let (issuer, holder) = (keypair(1), keypair(2));
+    ledger.fund_account(account(&issuer), 100 * XLM).unwrap();
+    ledger.fund_account(account(&holder), 100 * XLM).unwrap();
+    let pi = Asset::credit("PI", account(&issuer)).unwrap();
+    let to_holder = MuxedAccount::from(account(&holder));
+    let failed = |index, error| Err(LedgerError::OperationFailed { index, error: Box::new(error) });
+
+    let clawback_only = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::set_options(None, Some(AUTH_CLAWBACK_ENABLED_FLAG), None)]);
+    let not_revocable = LedgerError::NotAllowed("clawback requires revocable authorization".to_string());
+    assert_eq!(ledger.apply_transaction(&clawback_only), failed(0, not_revocable));
+    let flags = AUTH_REQUIRED_FLAG | AUTH_REVOCABLE_FLAG | AUTH_CLAWBACK_ENABLED_FLAG;
+    let setup = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::set_options(None, Some(flags), Some("pi.example".to_string()))]);
+    ledger.apply_transaction(&setup).unwrap();
+    assert_eq!(ledger.account(&account(&issuer)).unwrap().flags, flags);
+
+    // New trustlines wait for authorization and inherit clawback
+    let trust = envelope(&holder, next_seq(&holder, &ledger), vec![Operation::change_trust(pi.clone(), i64::MAX)]);
+    ledger.apply_transaction(&trust).unwrap();
+    let trustline = &ledger.account(&account(&holder)).unwrap().trustlines[&pi];
+    assert_eq!(trustline.flags, TRUSTLINE_CLAWBACK_ENABLED_FLAG);
+    let early = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::payment(to_holder, pi.clone(), 100)]);
+    let unauthorized = || LedgerError::NotAuthorized { account: account(&holder).to_strkey(), asset: "PI".to_string() };
+    assert_eq!(ledger.apply_transaction(&early), failed(0, unauthorized()));
+
+    let authorize = Operation::set_trust_line_flags(account(&holder), pi.clone(), 0, AUTHORIZED_FLAG);
+    let not_issuer = envelope(&holder, next_seq(&holder, &ledger), vec![authorize.clone()]);
+    assert!(ledger.apply_transaction(&not_issuer).is_err());
+    let mint = envelope(&issuer, next_seq(&issuer, &ledger), vec![authorize, Operation::payment(to_holder, pi.clone(), 100)]);
+    ledger.apply_transaction(&mint).unwrap();
+    assert_eq!(ledger.balance(&account(&holder), &pi), 100);
+
+    // A revoked holder can neither send nor receive, but the issuer can still claw back
+    let revoke = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::set_trust_line_flags(account(&holder), pi.clone(), AUTHORIZED_FLAG, 0)]);
+    ledger.apply_transaction(&revoke).unwrap();
+    let send = envelope(&holder, next_seq(&holder, &ledger), vec![Operation::payment(MuxedAccount::from(account(&issuer)), pi.clone(), 10)]);
+    assert_eq!(ledger.apply_transaction(&send), failed(0, unauthorized()));
+    let clawback = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::clawback(pi.clone(), to_holder, 40)]);
+    ledger.apply_transaction(&clawback).unwrap();
+    assert_eq!(ledger.balance(&account(&holder), &pi), 60);
+    let too_much = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::clawback(pi.clone(), to_holder, 61)]);
+    assert!(matches!(ledger.apply_transaction(&too_much), Err(LedgerError::OperationFailed { error, .. }) if matches!(*error, LedgerError::InsufficientBalance { .. })));
+
+    // Once immutable, the flags stay
+    let lock = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::set_options(None, Some(AUTH_IMMUTABLE_FLAG), None)]);
+    ledger.apply_transaction(&lock).unwrap();
+    let unlock = envelope(&issuer, next_seq(&issuer, &ledger), vec![Operation::set_options(Some(AUTH_REQUIRED_FLAG), None, None)]);
+    assert!(matches!(ledger.apply_transaction(&unlock), Err(LedgerError::OperationFailed { error, .. }) if matches!(*error, LedgerError::NotAllowed(_))));
+}
+
+#[test]
+fn test_fund_account_rejects_existing_and_negative_balances() {
+    let (mut ledger, _) = funded();
+    let alice = account(&keypair(1));
+    assert_eq!(ledger.fund_account(alice, XLM), Err(LedgerError::AccountExists(alice.to_strkey())));
+    assert!(matches!(ledger.fund_account(account(&keypair(2)), -1), Err(LedgerError::Malformed(_))));
+    assert_eq!(ledger.balance(&alice, &Asset::Native), 100 * XLM);
 }
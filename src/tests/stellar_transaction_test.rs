@@ -0,0 +1,270 @@
+// Unit tests for Stellar transaction building, XDR encoding and signing
+
+use super::models::stellar::transaction::{
+    AccountId, Asset, FeeBumpTransaction, FeeBumpTransactionEnvelope, Memo, MuxedAccount, Network, Operation,
+    Price, Signer, SignerKey, TimeBounds, TransactionBuilder, TransactionEnvelope, TransactionError, Transaction, AUTHORIZED_FLAG, AUTH_IMMUTABLE_FLAG,
+    AUTH_REQUIRED_FLAG, AUTH_REVOCABLE_FLAG, TRUSTLINE_CLAWBACK_ENABLED_FLAG,
+};
+use super::models::stellar::xdr::{XdrDecode, XdrEncode, XdrError};
+use super::fixtures::keypair;
+use ed25519_dalek::Keypair;
+
+fn account(keypair: &Keypair) -> AccountId {
+    AccountId::from(&keypair.public)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_network_id() {
+    assert_eq!(
+        hex(&Network::testnet().network_id()),
+        "cee0302d59844d32bdca915c8203dd44b33fbb7edc19051ea37abedf28ecd472"
+    );
+    assert_ne!(Network::testnet().network_id(), Network::public().network_id());
+}
+
+#[test]
+fn test_payment_encoding() {
+    let tx = TransactionBuilder::new(AccountId([1; 32]), 1)
+        .add_operation(Operation::payment(MuxedAccount::Ed25519([2; 32]), Asset::Native, 100_000_000))
+        .build()
+        .unwrap();
+
+    let expected = [
+        "00000000".to_string(), "01".repeat(32), // source account
+        "00000064".to_string(),                  // fee: 100 stroops for one operation
+        "0000000000000001".to_string(),          // sequence number
+        "00000000".to_string(),                  // no preconditions
+        "00000000".to_string(),                  // no memo
+        "00000001".to_string(),                  // one operation
+        "00000000".to_string(),                  // no operation source
+        "00000001".to_string(),                  // payment
+        "00000000".to_string(), "02".repeat(32), // destination
+        "00000000".to_string(),                  // native asset
+        "0000000005f5e100".to_string(),          // 10 XLM in stroops
+        "00000000".to_string(),                  // extension
+    ]
+    .concat();
+    assert_eq!(hex(&tx.to_xdr()), expected);
+}
+
+#[test]
+fn test_round_trip_all_operations() {
+    let issuer = account(&keypair(9));
+    let usd = Asset::credit("USD", issuer).unwrap();
+    let long = Asset::credit("PISTELLAR", issuer).unwrap();
+    let destination = MuxedAccount::MuxedEd25519 { id: 42, ed25519: [3; 32] };
+
+    let tx = TransactionBuilder::new(account(&keypair(1)), 123_456_789)
+        .base_fee(250)
+        .memo(Memo::Text("bridge deposit".to_string()))
+        .time_bounds(TimeBounds { min_time: 10, max_time: 1_000 })
+        .add_operation(Operation::create_account(AccountId([4; 32]), 20_000_000))
+        .add_operation(Operation::payment(destination, usd.clone(), 5).with_source(MuxedAccount::Ed25519([5; 32])))
+        .add_operation(Operation::path_payment_strict_receive(Asset::Native, 100, destination, usd.clone(), 50, vec![long.clone()]))
+        .add_operation(Operation::path_payment_strict_send(usd.clone(), 50, destination, Asset::Native, 1, vec![]))
+        .add_operation(Operation::manage_sell_offer(usd.clone(), Asset::Native, 1_000, Price::new(3, 2), 0))
+        .add_operation(Operation::manage_buy_offer(Asset::Native, long.clone(), 0, Price::new(1, 7), 99))
+        .add_operation(Operation::change_trust(long, i64::MAX))
+        .add_operation(Operation::set_options(Some(AUTH_IMMUTABLE_FLAG), Some(AUTH_REQUIRED_FLAG | AUTH_REVOCABLE_FLAG), Some("pi.example".to_string())))
+        .add_operation(Operation::set_trust_line_flags(AccountId([4; 32]), usd.clone(), AUTHORIZED_FLAG, 0))
+        .add_operation(Operation::clawback(usd.clone(), destination, 5))
+        .add_operation(Operation::set_signer(Signer { key: SignerKey::HashX([6; 32]), weight: 1 }))
+        .add_operation(Operation::set_signer(Signer { key: SignerKey::PreAuthTx([7; 32]), weight: 2 }))
+        .add_operation(Operation::set_thresholds(0, 2, 2, 2))
+        .add_operation(Operation::account_merge(destination))
+        .build()
+        .unwrap();
+    assert_eq!(tx.fee, 250 * 14);
+
+    let decoded = Transaction::from_xdr(&tx.to_xdr()).unwrap();
+    assert_eq!(decoded, tx);
+
+    for memo in [Memo::None, Memo::Id(7), Memo::Hash([8; 32]), Memo::Return([9; 32])] {
+        let mut tx = tx.clone();
+        tx.memo = memo;
+        assert_eq!(Transaction::from_xdr_base64(&tx.to_xdr_base64()).unwrap(), tx);
+    }
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let network = Network::testnet();
+    let source = keypair(1);
+    let cosigner = keypair(2);
+    let tx = TransactionBuilder::new(account(&source), 2)
+        .add_operation(Operation::payment(MuxedAccount::Ed25519([7; 32]), Asset::Native, 1))
+        .build()
+        .unwrap();
+
+    let mut envelope = tx.sign(&network, &source);
+    assert!(envelope.is_signed_by(&network, &source.public));
+    assert!(!envelope.is_signed_by(&network, &cosigner.public));
+    assert!(!envelope.is_signed_by(&Network::public(), &source.public));
+
+    envelope.sign(&network, &cosigner).unwrap();
+    assert_eq!(envelope.signatures.len(), 2);
+    assert_eq!(envelope.signatures[1].hint, cosigner.public.to_bytes()[28..]);
+
+    let decoded = TransactionEnvelope::from_xdr_base64(&envelope.to_xdr_base64()).unwrap();
+    assert_eq!(decoded, envelope);
+    assert!(decoded.is_signed_by(&network, &cosigner.public));
+}
+
+#[test]
+fn test_hash_x_signatures_and_signer_keys() {
+    let preimage = [5u8; 32];
+    let hash: [u8; 32] = ring::digest::digest(&ring::digest::SHA256, &preimage).as_ref().try_into().unwrap();
+    let mut envelope = TransactionBuilder::new(AccountId([1; 32]), 2)
+        .add_operation(Operation::account_merge(MuxedAccount::Ed25519([7; 32])))
+        .build()
+        .unwrap()
+        .sign(&Network::testnet(), &keypair(1));
+    assert_eq!(envelope.hash_x_preimage(&hash), None);
+
+    envelope.sign_hash_x(&preimage).unwrap();
+    assert_eq!(envelope.signatures[1].hint, hash[28..]);
+    let decoded = TransactionEnvelope::from_xdr_base64(&envelope.to_xdr_base64()).unwrap();
+    assert_eq!(decoded.hash_x_preimage(&hash), Some(&preimage[..]));
+
+    for key in [SignerKey::Ed25519([1; 32]), SignerKey::PreAuthTx([2; 32]), SignerKey::HashX(hash)] {
+        assert_eq!(SignerKey::from_strkey(&key.to_strkey()).unwrap(), key);
+    }
+    assert!(SignerKey::HashX(hash).to_strkey().starts_with('X'));
+    assert!(SignerKey::from_strkey("MABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAAAAAAAAAAAA7FA2").is_err());
+}
+
+#[test]
+fn test_fee_bump() {
+    let network = Network::testnet();
+    let source = keypair(1);
+    let sponsor = keypair(3);
+    let inner = TransactionBuilder::new(account(&source), 2)
+        .base_fee(200)
+        .add_operation(Operation::payment(MuxedAccount::Ed25519([7; 32]), Asset::Native, 1))
+        .build()
+        .unwrap()
+        .sign(&network, &source);
+
+    assert_eq!(
+        FeeBumpTransaction::new(account(&sponsor), 199, inner.clone()),
+        Err(TransactionError::FeeBumpTooLow { offered: 199, required: 200 })
+    );
+    let bump = FeeBumpTransaction::new(account(&sponsor), 2_000, inner.clone()).unwrap();
+    // The bump itself counts as an operation
+    assert_eq!(bump.fee, 4_000);
+    assert_ne!(bump.hash(&network), inner.tx.hash(&network));
+
+    let envelope = bump.sign(&network, &sponsor);
+    assert!(envelope.is_signed_by(&network, &sponsor.public));
+    assert!(!envelope.is_signed_by(&network, &source.public));
+    assert!(envelope.tx.inner.is_signed_by(&network, &source.public));
+
+    let encoded = envelope.to_xdr();
+    assert_eq!(encoded[..4], [0, 0, 0, 5]);
+    assert_eq!(FeeBumpTransactionEnvelope::from_xdr(&encoded).unwrap(), envelope);
+    assert!(TransactionEnvelope::from_xdr(&encoded).is_err());
+}
+
+#[test]
+fn test_builder_validation() {
+    let source = AccountId([1; 32]);
+    let dest = MuxedAccount::Ed25519([2; 32]);
+    let build = |operation: Operation| TransactionBuilder::new(source, 1).add_operation(operation).build();
+
+    assert_eq!(TransactionBuilder::new(source, 1).build(), Err(TransactionError::NoOperations));
+    assert_eq!(build(Operation::payment(dest, Asset::Native, 0)), Err(TransactionError::InvalidAmount(0)));
+    assert_eq!(build(Operation::create_account(AccountId([2; 32]), -1)), Err(TransactionError::InvalidAmount(-1)));
+    assert_eq!(
+        build(Operation::path_payment_strict_send(Asset::Native, 1, dest, Asset::Native, 1, vec![Asset::Native; 6])),
+        Err(TransactionError::PathTooLong(6))
+    );
+    assert_eq!(
+        build(Operation::manage_sell_offer(Asset::Native, Asset::Native, 1, Price::new(1, 0), 0)),
+        Err(TransactionError::InvalidPrice)
+    );
+    assert_eq!(build(Operation::change_trust(Asset::Native, 1)), Err(TransactionError::InvalidAssetCode("XLM".to_string())));
+    assert!(matches!(build(Operation::set_options(None, Some(0x10), None)), Err(TransactionError::InvalidFlags(_))));
+    assert!(matches!(
+        build(Operation::set_options(Some(AUTH_REQUIRED_FLAG), Some(AUTH_REQUIRED_FLAG), None)),
+        Err(TransactionError::InvalidFlags(_))
+    ));
+    assert_eq!(build(Operation::set_options(None, None, Some("x".repeat(33)))), Err(TransactionError::HomeDomainTooLong(33)));
+    assert_eq!(build(Operation::set_thresholds(0, 1, 256, 1)), Err(TransactionError::InvalidWeight(256)));
+    assert_eq!(
+        build(Operation::set_signer(Signer { key: SignerKey::HashX([1; 32]), weight: 300 })),
+        Err(TransactionError::InvalidWeight(300))
+    );
+    let usd = Asset::credit("USD", source).unwrap();
+    assert!(matches!(
+        build(Operation::set_trust_line_flags(AccountId([2; 32]), usd.clone(), 0, TRUSTLINE_CLAWBACK_ENABLED_FLAG)),
+        Err(TransactionError::InvalidFlags(_))
+    ));
+    assert_eq!(build(Operation::clawback(usd, dest, 0)), Err(TransactionError::InvalidAmount(0)));
+
+    let long_memo = TransactionBuilder::new(source, 1)
+        .memo(Memo::Text("x".repeat(29)))
+        .add_operation(Operation::payment(dest, Asset::Native, 1))
+        .build();
+    assert_eq!(long_memo, Err(TransactionError::MemoTooLong(29)));
+
+    let bad_bounds = TransactionBuilder::new(source, 1)
+        .time_bounds(TimeBounds { min_time: 10, max_time: 5 })
+        .add_operation(Operation::payment(dest, Asset::Native, 1))
+        .build();
+    assert_eq!(bad_bounds, Err(TransactionError::InvalidTimeBounds));
+
+    // A timeout past the end of time saturates instead of panicking
+    let forever = TransactionBuilder::new(source, 1)
+        .timeout(std::time::Duration::MAX)
+        .add_operation(Operation::payment(dest, Asset::Native, 1))
+        .build()
+        .unwrap();
+    assert_eq!(forever.time_bounds, Some(TimeBounds { min_time: 0, max_time: u64::MAX }));
+
+    let mut builder = TransactionBuilder::new(source, 1);
+    for _ in 0..101 {
+        builder = builder.add_operation(Operation::payment(dest, Asset::Native, 1));
+    }
+    assert_eq!(builder.build(), Err(TransactionError::TooManyOperations(101)));
+}
+
+#[test]
+fn test_asset_codes() {
+    let issuer = AccountId([1; 32]);
+    assert!(matches!(Asset::credit("PI", issuer).unwrap(), Asset::CreditAlphanum4 { .. }));
+    assert!(matches!(Asset::credit("PIBRIDGE", issuer).unwrap(), Asset::CreditAlphanum12 { .. }));
+    assert_eq!(Asset::credit("PIBRIDGE", issuer).unwrap().code(), "PIBRIDGE");
+    assert_eq!(Asset::credit("", issuer), Err(TransactionError::InvalidAssetCode(String::new())));
+    assert_eq!(Asset::credit("TOOLONGASSETCODE", issuer), Err(TransactionError::InvalidAssetCode("TOOLONGASSETCODE".to_string())));
+    assert_eq!(Asset::credit("US-D", issuer), Err(TransactionError::InvalidAssetCode("US-D".to_string())));
+}
+
+#[test]
+fn test_decode_errors() {
+    let tx = TransactionBuilder::new(AccountId([1; 32]), 1)
+        .add_operation(Operation::payment(MuxedAccount::Ed25519([2; 32]), Asset::Native, 1))
+        .build()
+        .unwrap();
+    let bytes = tx.to_xdr();
+
+    assert_eq!(Transaction::from_xdr(&bytes[..bytes.len() - 1]), Err(XdrError::UnexpectedEof));
+
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(&[0; 4]);
+    assert_eq!(Transaction::from_xdr(&trailing), Err(XdrError::TrailingBytes(4)));
+
+    // Operation type 99 does not exist
+    let mut unknown = bytes.clone();
+    let op_type = 36 + 4 + 8 + 4 + 4 + 4 + 4;
+    unknown[op_type..op_type + 4].copy_from_slice(&99i32.to_be_bytes());
+    assert_eq!(
+        Transaction::from_xdr(&unknown),
+        Err(XdrError::InvalidDiscriminant { type_name: "OperationType", value: 99 })
+    );
+
+    assert!(matches!(TransactionEnvelope::from_xdr_base64("not base64!"), Err(XdrError::InvalidBase64(_))));
+}
@@ -0,0 +1,133 @@
+// Unit tests for StrKey encoding of Stellar addresses and keys
+
+use super::cross_chain::stellar_integration::{EnvelopeBuilder, StellarAsset, StellarPaymentRequest};
+use super::cross_chain::stellar_signer::KeypairEnvelopeBuilder;
+use super::models::stellar::strkey::{self, StrKey, StrKeyError};
+use super::models::stellar::transaction::{AccountId, MuxedAccount, Network, OperationBody, TransactionEnvelope};
+use super::models::stellar::xdr::XdrDecode;
+
+const ACCOUNT_1: &str = "GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7H";
+const ACCOUNT_2: &str = "GABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEJXA";
+const SEED_1: &str = "SAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQC5MY";
+
+#[test]
+fn test_encode_known_keys() {
+    assert_eq!(strkey::encode_account_id(&[0; 32]), "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF");
+    assert_eq!(strkey::encode_account_id(&[1; 32]), ACCOUNT_1);
+    assert_eq!(StrKey::Seed([1; 32]).encode(), SEED_1);
+    assert_eq!(
+        StrKey::PreAuthTx([2; 32]).encode(),
+        "TABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAFUFR"
+    );
+    assert_eq!(StrKey::HashX([3; 32]).encode(), "XABQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQH7YK");
+    assert_eq!(
+        StrKey::MuxedAccount { ed25519: [0; 32], id: 1234 }.encode(),
+        "MAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAE2KA2I"
+    );
+}
+
+#[test]
+fn test_decode_round_trip() {
+    for key in [
+        StrKey::AccountId([5; 32]),
+        StrKey::MuxedAccount { ed25519: [1; 32], id: 0 },
+        StrKey::MuxedAccount { ed25519: [2; 32], id: u64::MAX },
+        StrKey::Seed([7; 32]),
+        StrKey::PreAuthTx([8; 32]),
+        StrKey::HashX([9; 32]),
+    ] {
+        let encoded = key.encode();
+        assert!(encoded.starts_with(key.prefix()));
+        assert_eq!(StrKey::decode(&encoded), Ok(key));
+    }
+}
+
+#[test]
+fn test_sep23_muxed_vector() {
+    let decoded = StrKey::decode("MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVAAAAAAAAAAAAAJLK").unwrap();
+    assert!(matches!(decoded, StrKey::MuxedAccount { id: 9223372036854775808, .. }));
+}
+
+#[test]
+fn test_decode_errors() {
+    // Last character changed
+    assert_eq!(StrKey::decode("GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQDZ7G"), Err(StrKeyError::InvalidChecksum));
+    assert_eq!(StrKey::decode(&ACCOUNT_1.to_lowercase()), Err(StrKeyError::InvalidCharacter('g')));
+    assert_eq!(StrKey::decode(&ACCOUNT_1.replace('Z', "1")), Err(StrKeyError::InvalidCharacter('1')));
+    assert_eq!(StrKey::decode(&ACCOUNT_1[..54]), Err(StrKeyError::InvalidLength(54)));
+    // A 33-byte account key with a valid checksum
+    assert_eq!(
+        StrKey::decode("GAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAPIPI"),
+        Err(StrKeyError::InvalidLength(58))
+    );
+    assert!(StrKey::decode("").is_err());
+
+    // Same checksum-valid payload but with non-zero unused bits in the final symbol
+    assert_eq!(
+        StrKey::decode("MAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAAAAAAAAAAAABAXJ"),
+        Err(StrKeyError::NonCanonical)
+    );
+
+    assert_eq!(
+        strkey::decode_account_id(SEED_1),
+        Err(StrKeyError::UnexpectedType { expected: 'G', found: 'S' })
+    );
+    assert_eq!(
+        strkey::decode_seed(ACCOUNT_1),
+        Err(StrKeyError::UnexpectedType { expected: 'S', found: 'G' })
+    );
+}
+
+#[test]
+fn test_address_predicates() {
+    let muxed = "MAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAAAAAAAAAAAABAXI";
+    assert!(strkey::is_valid_account_id(ACCOUNT_1));
+    assert!(!strkey::is_valid_account_id(muxed));
+    assert!(strkey::is_valid_destination(muxed));
+    assert!(!strkey::is_valid_destination(SEED_1));
+    assert!(!strkey::is_valid_destination("GABCD12345678901234567890123456789012345678901234"));
+}
+
+#[test]
+fn test_account_types_parse_strkeys() {
+    let account: AccountId = ACCOUNT_2.parse().unwrap();
+    assert_eq!(account, AccountId([2; 32]));
+    assert_eq!(account.to_string(), ACCOUNT_2);
+
+    let muxed = MuxedAccount::from_strkey("MABAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAAAAAAAAAAAA7FA2").unwrap();
+    assert_eq!(muxed, MuxedAccount::MuxedEd25519 { id: 7, ed25519: [2; 32] });
+    assert_eq!(muxed.account_id(), account);
+    assert_eq!(ACCOUNT_2.parse::<MuxedAccount>().unwrap(), MuxedAccount::Ed25519([2; 32]));
+}
+
+#[test]
+fn test_keypair_envelope_builder() {
+    let network = Network::testnet();
+    let builder = KeypairEnvelopeBuilder::from_seed(network.clone(), SEED_1).unwrap();
+    let mut payment = StellarPaymentRequest {
+        source_account: builder.account_id().to_string(),
+        destination_account: ACCOUNT_2.to_string(),
+        asset: StellarAsset::Credit { code: "PI".to_string(), issuer: builder.account_id().to_string() },
+        amount: 25_000_000,
+        memo: Some("pi-tx-1".to_string()),
+    };
+
+    let envelope = TransactionEnvelope::from_xdr_base64(&builder.build_payment(&payment, 101, 200).unwrap()).unwrap();
+    assert_eq!(envelope.tx.seq_num, 101);
+    assert_eq!(envelope.tx.fee, 200);
+    assert!(matches!(envelope.tx.operations[0].body, OperationBody::Payment { amount: 25_000_000, .. }));
+    assert!(envelope.tx.time_bounds.is_some());
+
+    // Addresses with a broken checksum or in lowercase are refused before anything is signed
+    let mut corrupted = payment.clone();
+    corrupted.destination_account = ACCOUNT_2.replace("EJXA", "EJXB");
+    assert!(builder.build_payment(&corrupted, 101, 200).unwrap_err().contains("Invalid destination account"));
+    let mut corrupted = payment.clone();
+    corrupted.asset = StellarAsset::Credit { code: "PI".to_string(), issuer: ACCOUNT_1.to_lowercase() };
+    assert!(builder.build_payment(&corrupted, 101, 200).unwrap_err().contains("Invalid asset issuer"));
+
+    // Only payments from the key's own account are signed
+    payment.source_account = ACCOUNT_2.to_string();
+    assert!(builder.build_payment(&payment, 101, 200).is_err());
+    assert!(KeypairEnvelopeBuilder::from_seed(network, ACCOUNT_1).is_err());
+}
@@ -1,23 +1,91 @@
 // Unit tests for the utility functions
 
-use super::utils::crypto::CryptoUtilsImpl;
+use super::utils::crypto::{parse_key, Algorithm, CryptoError, CryptoUtils, CryptoUtilsImpl, CIPHERTEXT_VERSION, CONFIG_FILE_VAR};
+use super::utils::json::strip_json_comments;
 use super::utils::network::NetworkUtilsImpl;
+use std::fs;
+
+fn crypto(algorithm: Algorithm) -> CryptoUtilsImpl {
+    CryptoUtilsImpl::with_key(algorithm, [7; 32])
+}
 
 #[test]
 fn test_encrypt_data() {
-    let crypto_utils = CryptoUtilsImpl::new();
+    let crypto_utils = crypto(Algorithm::Aes256Gcm);
     let data = vec![1, 2, 3, 4, 5];
-    let encrypted_data = crypto_utils.encrypt_data(data);
+    let encrypted_data = crypto_utils.encrypt_data(data.clone()).unwrap();
     assert_ne!(encrypted_data, data);
+    // Version, algorithm and nonce, then the data and its 16-byte tag
+    assert_eq!(encrypted_data[..2], [CIPHERTEXT_VERSION, 1]);
+    assert_eq!(encrypted_data.len(), 2 + 12 + data.len() + 16);
+    // A fresh nonce every time
+    assert_ne!(crypto_utils.encrypt_data(data.clone()).unwrap(), encrypted_data);
 }
 
 #[test]
 fn test_decrypt_data() {
-    let crypto_utils = CryptoUtilsImpl::new();
-    let data = vec![1, 2, 3, 4, 5];
-    let encrypted_data = crypto_utils.encrypt_data(data);
-    let decrypted_data = crypto_utils.decrypt_data(encrypted_data);
-    assert_eq!(decrypted_data, data);
+    for algorithm in [Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305] {
+        let crypto_utils = crypto(algorithm);
+        let data = vec![1, 2, 3, 4, 5];
+        let encrypted_data = crypto_utils.encrypt_data(data.clone()).unwrap();
+        let decrypted_data = crypto_utils.decrypt_data(encrypted_data).unwrap();
+        assert_eq!(decrypted_data, data);
+    }
+}
+
+#[test]
+fn test_decryption_is_authenticated() {
+    let crypto_utils = crypto(Algorithm::ChaCha20Poly1305);
+    let encrypted = crypto_utils.encrypt_with_aad(b"secret".to_vec(), b"user-42").unwrap();
+    assert_eq!(crypto_utils.decrypt_with_aad(encrypted.clone(), b"user-42").unwrap(), b"secret");
+    assert_eq!(crypto_utils.decrypt_with_aad(encrypted.clone(), b"user-43"), Err(CryptoError::Decryption));
+    assert_eq!(crypto_utils.decrypt_data(encrypted.clone()), Err(CryptoError::Decryption));
+
+    let mut tampered = encrypted.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(crypto_utils.decrypt_with_aad(tampered, b"user-42"), Err(CryptoError::Decryption));
+    let other_key = CryptoUtilsImpl::with_key(Algorithm::ChaCha20Poly1305, [8; 32]);
+    assert_eq!(other_key.decrypt_with_aad(encrypted.clone(), b"user-42"), Err(CryptoError::Decryption));
+
+    let mut future = encrypted.clone();
+    future[0] = 2;
+    assert_eq!(crypto_utils.decrypt_with_aad(future, b"user-42"), Err(CryptoError::UnsupportedVersion(2)));
+    assert!(matches!(crypto_utils.decrypt_data(encrypted[..20].to_vec()), Err(CryptoError::Malformed(_))));
+}
+
+#[test]
+fn test_algorithm_rotation() {
+    // Data sealed before switching algorithms stays readable with the same key
+    let old = crypto(Algorithm::Aes256Gcm).encrypt_data(b"ledger".to_vec()).unwrap();
+    let rotated = crypto(Algorithm::ChaCha20Poly1305);
+    assert_eq!(rotated.decrypt_data(old).unwrap(), b"ledger");
+    assert_eq!(rotated.encrypt_data(b"ledger".to_vec()).unwrap()[1], Algorithm::ChaCha20Poly1305.id());
+}
+
+#[test]
+fn test_keys_from_config_and_env() {
+    let hex_key = "07".repeat(32);
+    assert_eq!(parse_key(&hex_key).unwrap(), [7; 32]);
+    assert_eq!(parse_key(&base64::encode([7u8; 32])).unwrap(), [7; 32]);
+    assert!(matches!(parse_key("0707"), Err(CryptoError::InvalidKey(_))));
+    assert!(matches!("AES-256-CBC".parse::<Algorithm>(), Err(CryptoError::UnsupportedAlgorithm(_))));
+
+    let path = std::env::temp_dir().join("pistellar_crypto_config.json");
+    fs::write(&path, r#"{ "crypto": { "algorithm": "chacha20-poly1305", "key": "${PISTELLAR_TEST_CRYPTO_KEY}" } // comment
+    }"#).unwrap();
+    assert!(matches!(CryptoUtilsImpl::from_config(&path), Err(CryptoError::Config(_))));
+    std::env::set_var("PISTELLAR_TEST_CRYPTO_KEY", &hex_key);
+    let configured = CryptoUtilsImpl::from_config(&path).unwrap();
+    std::env::set_var(CONFIG_FILE_VAR, &path);
+    assert_eq!(CryptoUtilsImpl::new().unwrap().algorithm(), Algorithm::ChaCha20Poly1305);
+    fs::remove_file(&path).unwrap();
+    // A named file must exist; only the default falls back to the environment
+    assert!(matches!(CryptoUtilsImpl::new(), Err(CryptoError::Config(_))));
+    std::env::remove_var(CONFIG_FILE_VAR);
+    assert_eq!(configured.algorithm(), Algorithm::ChaCha20Poly1305);
+    // Same key as the literal one, so each can read the other's data
+    let encrypted = configured.encrypt_data(b"pi".to_vec()).unwrap();
+    assert_eq!(crypto(Algorithm::Aes256Gcm).decrypt_data(encrypted).unwrap(), b"pi");
 }
 
 #[test]
@@ -33,3 +101,11 @@ fn test_receive_data() {
     let data = network_utils.receive_data();
     assert_eq!(data, vec![]);
 }
+
+#[test]
+fn test_strip_json_comments_keeps_urls() {
+    let stripped = strip_json_comments("{ \"url\": \"https://a.b\", // comment\n \"q\": \"\\\"//\" }");
+    let value: serde_json::Value = serde_json::from_str(&stripped).unwrap();
+    assert_eq!(value["url"], "https://a.b");
+    assert_eq!(value["q"], "\"//");
+}
@@ -1,31 +1,268 @@
 // Cryptography utility functions
 
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use thiserror::Error;
+use crate::utils::json::strip_json_comments;
+
+/// Configuration file whose `crypto` section `CryptoUtilsImpl::new` reads unless
+/// `CONFIG_FILE_VAR` names another.
+pub const DEFAULT_CONFIG_FILE: &str = "config/config.json";
+/// Environment variable naming the configuration file.
+pub const CONFIG_FILE_VAR: &str = "PISTELLAR_CONFIG_FILE";
+/// Environment variable holding the key, as 64 hex digits or base64, when there is no config.
+pub const KEY_VAR: &str = "CRYPTO_KEY";
+/// Environment variable naming the algorithm when there is no config.
+pub const ALGORITHM_VAR: &str = "CRYPTO_ALGORITHM";
+
+/// Length of a key, in bytes; both algorithms use 256-bit keys.
+pub const KEY_LEN: usize = 32;
+/// Version of the ciphertext header written by `encrypt_data`.
+pub const CIPHERTEXT_VERSION: u8 = 1;
+
+// Version, algorithm and nonce precede the sealed data
+const HEADER_LEN: usize = 2 + NONCE_LEN;
+const TAG_LEN: usize = 16;
+
+/// Errors returned when loading keys, encrypting or decrypting.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Crypto configuration error: {0}")]
+    Config(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Unsupported algorithm `{0}`, expected AES-256-GCM or CHACHA20-POLY1305")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Unsupported ciphertext version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Malformed ciphertext: {0}")]
+    Malformed(String),
+
+    #[error("Encryption failed")]
+    Encryption,
+
+    /// The key or associated data is wrong, or the ciphertext was modified.
+    #[error("Decryption failed")]
+    Decryption,
+}
+
+/// An authenticated encryption algorithm, identified in ciphertext headers by its ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Algorithm {
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Aes256Gcm => 1,
+            Algorithm::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Algorithm::Aes256Gcm),
+            2 => Some(Algorithm::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    fn aead(self) -> &'static ring::aead::Algorithm {
+        match self {
+            Algorithm::Aes256Gcm => &AES_256_GCM,
+            Algorithm::ChaCha20Poly1305 => &CHACHA20_POLY1305,
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::Aes256Gcm => f.write_str("AES-256-GCM"),
+            Algorithm::ChaCha20Poly1305 => f.write_str("CHACHA20-POLY1305"),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = CryptoError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_uppercase().replace('_', "-").as_str() {
+            "AES-256-GCM" => Ok(Algorithm::Aes256Gcm),
+            "CHACHA20-POLY1305" => Ok(Algorithm::ChaCha20Poly1305),
+            _ => Err(CryptoError::UnsupportedAlgorithm(name.to_string())),
+        }
+    }
+}
+
 pub trait CryptoUtils {
-    fn new() -> Self;
-    fn encrypt_data(&self, data: Vec<u8>) -> Vec<u8>;
-    fn decrypt_data(&self, data: Vec<u8>) -> Vec<u8>;
+    /// Loads the algorithm and key from the configuration or the environment.
+    fn new() -> Result<Self, CryptoError>
+    where
+        Self: Sized;
+
+    /// Encrypts and authenticates `data` together with `associated_data`, which is not
+    /// encrypted but must be passed again to decrypt.
+    fn encrypt_with_aad(&self, data: Vec<u8>, associated_data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    /// Decrypts data from `encrypt_with_aad`, checking it and `associated_data` are unmodified.
+    fn decrypt_with_aad(&self, data: Vec<u8>, associated_data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    fn encrypt_data(&self, data: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+        self.encrypt_with_aad(data, &[])
+    }
+
+    fn decrypt_data(&self, data: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+        self.decrypt_with_aad(data, &[])
+    }
 }
 
+/// Authenticated encryption with AES-256-GCM or ChaCha20-Poly1305.
+///
+/// Ciphertexts start with a header of the format version, the algorithm ID and a random
+/// nonce, followed by the sealed data and its tag. The header is authenticated along with the
+/// associated data. Decryption picks the algorithm from the header, so data written before
+/// the configured algorithm changed can still be read with the same key.
 pub struct CryptoUtilsImpl {
-    key: Vec<u8>,
+    algorithm: Algorithm,
+    key: [u8; KEY_LEN],
+    rng: SystemRandom,
 }
 
 impl CryptoUtilsImpl {
-    pub fn new() -> Self {
-        Self { key: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16] }
+    /// Encrypts new data with `algorithm` under `key`.
+    pub fn with_key(algorithm: Algorithm, key: [u8; KEY_LEN]) -> Self {
+        Self { algorithm, key, rng: SystemRandom::new() }
+    }
+
+    /// Reads the `crypto` section of a configuration file: `algorithm` and `key`. Either may
+    /// be a `${VAR}` reference to an environment variable; the algorithm defaults to AES-256-GCM.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self, CryptoError> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).map_err(|e| CryptoError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let json: Value = serde_json::from_str(&strip_json_comments(&data))
+            .map_err(|e| CryptoError::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+        let section = &json["crypto"];
+        let algorithm = match section["algorithm"].as_str() {
+            Some(name) => resolve(name)?.parse()?,
+            None => Algorithm::Aes256Gcm,
+        };
+        let key = section["key"]
+            .as_str()
+            .ok_or_else(|| CryptoError::Config(format!("{} has no crypto.key", path.display())))?;
+        Ok(Self::with_key(algorithm, parse_key(&resolve(key)?)?))
+    }
+
+    /// Reads the key from `KEY_VAR` and the algorithm from `ALGORITHM_VAR`, defaulting to
+    /// AES-256-GCM.
+    pub fn from_env() -> Result<Self, CryptoError> {
+        let key = env::var(KEY_VAR).map_err(|_| CryptoError::Config(format!("{} is not set", KEY_VAR)))?;
+        let algorithm = match env::var(ALGORITHM_VAR) {
+            Ok(name) => name.parse()?,
+            Err(_) => Algorithm::Aes256Gcm,
+        };
+        Ok(Self::with_key(algorithm, parse_key(&key)?))
+    }
+
+    /// Generates a random key, e.g. to provision `KEY_VAR`.
+    pub fn generate_key() -> Result<[u8; KEY_LEN], CryptoError> {
+        let mut key = [0u8; KEY_LEN];
+        SystemRandom::new().fill(&mut key).map_err(|_| CryptoError::Encryption)?;
+        Ok(key)
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    fn sealing_key(&self, algorithm: Algorithm) -> LessSafeKey {
+        // Only fails for a key of the wrong length, which the type rules out
+        LessSafeKey::new(UnboundKey::new(algorithm.aead(), &self.key).expect("256-bit key"))
     }
 }
 
 impl CryptoUtils for CryptoUtilsImpl {
-    fn encrypt_data(&self, data: Vec<u8>) -> Vec<u8> {
-        // Encrypt the data using the key
-        println!("Data encrypted!");
-        data
+    /// Reads the file named by `PISTELLAR_CONFIG_FILE`, or `config/config.json` relative to
+    /// the working directory. The environment is used when the default file does not exist.
+    fn new() -> Result<Self, CryptoError> {
+        match env::var(CONFIG_FILE_VAR) {
+            Ok(path) => Self::from_config(path),
+            Err(_) if !Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_env(),
+            Err(_) => Self::from_config(DEFAULT_CONFIG_FILE),
+        }
     }
 
-    fn decrypt_data(&self, data: Vec<u8>) -> Vec<u8> {
-        // Decrypt the data using the key
-        println!("Data decrypted!");
-        data
+    fn encrypt_with_aad(&self, mut data: Vec<u8>, associated_data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| CryptoError::Encryption)?;
+        let mut header = Vec::with_capacity(HEADER_LEN + data.len() + TAG_LEN);
+        header.extend_from_slice(&[CIPHERTEXT_VERSION, self.algorithm.id()]);
+        header.extend_from_slice(&nonce);
+
+        self.sealing_key(self.algorithm)
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(authenticated(&header, associated_data)), &mut data)
+            .map_err(|_| CryptoError::Encryption)?;
+        header.extend_from_slice(&data);
+        Ok(header)
     }
+
+    fn decrypt_with_aad(&self, data: Vec<u8>, associated_data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if data.len() < HEADER_LEN + TAG_LEN {
+            return Err(CryptoError::Malformed(format!("{} bytes is shorter than a header and tag", data.len())));
+        }
+        if data[0] != CIPHERTEXT_VERSION {
+            return Err(CryptoError::UnsupportedVersion(data[0]));
+        }
+        let algorithm = Algorithm::from_id(data[1]).ok_or_else(|| CryptoError::UnsupportedAlgorithm(format!("ID {}", data[1])))?;
+        let (header, sealed) = data.split_at(HEADER_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&header[2..]).map_err(|_| CryptoError::Malformed("nonce".to_string()))?;
+
+        let mut plaintext = sealed.to_vec();
+        let length = self
+            .sealing_key(algorithm)
+            .open_in_place(nonce, Aad::from(authenticated(header, associated_data)), &mut plaintext)
+            .map_err(|_| CryptoError::Decryption)?
+            .len();
+        plaintext.truncate(length);
+        Ok(plaintext)
+    }
+}
+
+// The header is authenticated with the caller's associated data, so it cannot be swapped
+fn authenticated(header: &[u8], associated_data: &[u8]) -> Vec<u8> {
+    [header, associated_data].concat()
+}
+
+// Replaces a `${VAR}` reference with the variable's value
+fn resolve(value: &str) -> Result<String, CryptoError> {
+    match value.strip_prefix("${").and_then(|rest| rest.strip_suffix('}')) {
+        Some(var) => env::var(var).map_err(|_| CryptoError::Config(format!("{} is not set", var))),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Parses a 256-bit key written as 64 hex digits or as base64.
+pub fn parse_key(encoded: &str) -> Result<[u8; KEY_LEN], CryptoError> {
+    let encoded = encoded.trim();
+    let bytes = if encoded.len() == 2 * KEY_LEN && encoded.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex::decode(encoded).map_err(|e| CryptoError::InvalidKey(e.to_string()))?
+    } else {
+        base64::decode(encoded).map_err(|_| CryptoError::InvalidKey("expected 64 hex digits or base64".to_string()))?
+    };
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| CryptoError::InvalidKey(format!("{} bytes, expected {}", bytes.len(), KEY_LEN)))
 }
@@ -0,0 +1,98 @@
+// JSON helpers shared by the configuration loaders and the file-backed stores
+
+use std::borrow::Borrow;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::security::encryption;
+
+/// Removes `//` line comments that appear outside of string literals.
+pub fn strip_json_comments(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let (mut in_string, mut escaped) = (false, false);
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '/' && chars.peek() == Some(&'/') {
+            while chars.peek().map_or(false, |&next| next != '\n') {
+                chars.next();
+            }
+        } else {
+            in_string = c == '"';
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// Keeps one value as a JSON document on disk, optionally encrypted with AES-GCM.
+///
+/// Saves write a temporary file and then rename it over the document, so a crash never leaves a
+/// partial file behind. The stores of the bridge, the swap coordinator, governance and the
+/// Horizon streamer are built on it.
+pub struct JsonFileStore<T> {
+    path: PathBuf,
+    // What the document holds, e.g. "swap store", for error messages
+    name: &'static str,
+    key: Option<Vec<u8>>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> JsonFileStore<T> {
+    /// Creates a store for the document at `path`, in plaintext
+    pub fn new<P: AsRef<Path>>(name: &'static str, path: P) -> Self {
+        Self { path: path.as_ref().to_path_buf(), name, key: None, value: PhantomData }
+    }
+
+    /// Encrypts the document under `key` (16 or 32 bytes)
+    pub fn encrypted(mut self, key: &[u8]) -> Self {
+        self.key = Some(key.to_vec());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the value, or `None` if the document does not exist yet
+    pub fn load(&self) -> Result<Option<T>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let mut data = fs::read(&self.path)
+            .map_err(|e| format!("Failed to read {} {}: {}", self.name, self.path.display(), e))?;
+        if let Some(key) = &self.key {
+            data = encryption::decrypt(&data, key)
+                .map_err(|e| format!("Failed to decrypt {} {}: {}", self.name, self.path.display(), e))?;
+        }
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {} {}: {}", self.name, self.path.display(), e))
+    }
+
+    /// Replaces the document with `value`, which may be a borrowed form of `T` such as a slice
+    pub fn save<V: Serialize + ?Sized>(&self, value: &V) -> Result<(), String>
+    where
+        T: Borrow<V>,
+    {
+        let mut data = serde_json::to_vec_pretty(value)
+            .map_err(|e| format!("Failed to serialize {}: {}", self.name, e))?;
+        if let Some(key) = &self.key {
+            data = encryption::encrypt(&data, key).map_err(|e| format!("Failed to encrypt {}: {}", self.name, e))?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, data)
+            .map_err(|e| format!("Failed to write {} {}: {}", self.name, tmp_path.display(), e))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to replace {} {}: {}", self.name, self.path.display(), e))
+    }
+}
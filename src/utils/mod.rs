@@ -0,0 +1,7 @@
+// mod.rs
+
+#[path = "crypto/crypto.rs"]
+pub mod crypto;
+pub mod json;
+#[path = "network/network.rs"]
+pub mod network;